@@ -924,6 +924,7 @@ impl UpdateCache for Event {
             | Event::ShardConnected(_)
             | Event::ShardConnecting(_)
             | Event::ShardDisconnected(_)
+            | Event::ShardFatal(_)
             | Event::ShardIdentifying(_)
             | Event::ShardPayload(_)
             | Event::ShardReconnecting(_)
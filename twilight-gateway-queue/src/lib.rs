@@ -23,9 +23,16 @@
 mod day_limiter;
 #[cfg(feature = "twilight-http")]
 mod large_bot_queue;
+#[cfg(feature = "remote")]
+pub mod remote;
+mod session_limit_queue;
 
 #[cfg(feature = "twilight-http")]
 pub use large_bot_queue::LargeBotQueue;
+pub use session_limit_queue::{
+    SessionLimitQueue, SessionLimitQueueBuilder, SessionLimitQueueError,
+    SessionLimitQueueErrorType, SessionStartLimitFuture, SessionStartLimitSource,
+};
 
 use std::{
     fmt::Debug,
@@ -0,0 +1,477 @@
+//! Queue shared by shards across processes through a queue server.
+//!
+//! A [`QueueServer`] releases requests to initialize sessions to the
+//! [`RemoteQueue`]s of all processes, one per [`max_concurrency`] bucket at a
+//! time.
+//!
+//! # Protocol
+//!
+//! Requests are made over a new TCP connection each. The client sends a line
+//! of the form `IDENTIFY <shard id> <shard total>` and the server responds
+//! with a line of `READY` once the shard may initialize its session. Clients
+//! closing the connection before then give up their place in the queue.
+//!
+//! [`max_concurrency`]: https://discord.com/developers/docs/topics/gateway#session-start-limit-object
+
+use super::Queue;
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    future::Future,
+    io::Error as IoError,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        oneshot::{self, Sender},
+    },
+    time::{sleep, timeout},
+};
+
+/// Maximum length of a request line, in bytes.
+const MAX_REQUEST_LEN: u64 = 64;
+
+/// Time a client has to send its request line before being disconnected.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Response to a request once the shard may initialize its session.
+const READY: &str = "READY";
+
+/// Command of a request to initialize a session.
+const IDENTIFY: &str = "IDENTIFY";
+
+/// Starting a queue server failed.
+#[derive(Debug)]
+pub struct QueueServerError {
+    kind: QueueServerErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl QueueServerError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &QueueServerErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(self) -> (QueueServerErrorType, Option<Box<dyn Error + Send + Sync>>) {
+        (self.kind, self.source)
+    }
+}
+
+impl Display for QueueServerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            QueueServerErrorType::Binding { address } => {
+                f.write_str("binding to ")?;
+                Display::fmt(address, f)?;
+
+                f.write_str(" failed")
+            }
+            QueueServerErrorType::ConcurrencyZero => {
+                f.write_str("maximum concurrency must be at least 1")
+            }
+        }
+    }
+}
+
+impl Error for QueueServerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// Type of [`QueueServerError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum QueueServerErrorType {
+    /// Binding to the address failed.
+    Binding {
+        /// Address that was bound to.
+        address: SocketAddr,
+    },
+    /// Provided maximum concurrency is 0.
+    ConcurrencyZero,
+}
+
+/// Server releasing requests to initialize sessions to [`RemoteQueue`]s.
+///
+/// Each of the [`max_concurrency`] buckets releases one request per interval,
+/// with shards being assigned to bucket `shard_id % max_concurrency`.
+///
+/// # Examples
+///
+/// Run a queue server for a bot with a maximum concurrency of 16:
+///
+/// ```no_run
+/// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use twilight_gateway_queue::remote::QueueServer;
+///
+/// let server = QueueServer::bind(([0, 0, 0, 0], 8080).into(), 16).await?;
+/// server.run().await;
+/// # Ok(()) }
+/// ```
+///
+/// [`max_concurrency`]: https://discord.com/developers/docs/topics/gateway#session-start-limit-object
+#[derive(Debug)]
+pub struct QueueServer {
+    /// Interval between releases of a bucket.
+    interval: Duration,
+    /// Listener accepting requests.
+    listener: TcpListener,
+    /// Number of buckets.
+    max_concurrency: u64,
+}
+
+impl QueueServer {
+    /// Default interval between releases of a bucket.
+    const INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Bind a new server to an address, releasing requests in the given
+    /// number of buckets.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`QueueServerErrorType::Binding`] error type if binding to
+    /// the address failed.
+    ///
+    /// Returns a [`QueueServerErrorType::ConcurrencyZero`] error type if the
+    /// maximum concurrency is 0.
+    pub async fn bind(address: SocketAddr, max_concurrency: u64) -> Result<Self, QueueServerError> {
+        if max_concurrency == 0 {
+            return Err(QueueServerError {
+                kind: QueueServerErrorType::ConcurrencyZero,
+                source: None,
+            });
+        }
+
+        let listener = TcpListener::bind(address)
+            .await
+            .map_err(|source| QueueServerError {
+                kind: QueueServerErrorType::Binding { address },
+                source: Some(Box::new(source)),
+            })?;
+
+        Ok(Self {
+            interval: Self::INTERVAL,
+            listener,
+            max_concurrency,
+        })
+    }
+
+    /// Set the interval between releases of a bucket.
+    ///
+    /// Defaults to 5 seconds, as required by Discord.
+    #[must_use = "has no effect if the server is not run"]
+    pub const fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+
+        self
+    }
+
+    /// Address the server is bound to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address could not be retrieved from the
+    /// operating system.
+    pub fn local_addr(&self) -> Result<SocketAddr, IoError> {
+        self.listener.local_addr()
+    }
+
+    /// Run the server, accepting requests until the future is dropped.
+    pub async fn run(self) {
+        let buckets = (0..self.max_concurrency)
+            .map(|_| {
+                let (tx, rx) = unbounded_channel();
+                tokio::spawn(waiter(rx, self.interval));
+
+                tx
+            })
+            .collect::<Arc<[_]>>();
+
+        loop {
+            match self.listener.accept().await {
+                Ok((stream, address)) => {
+                    tracing::debug!(%address, "accepted queue connection");
+
+                    tokio::spawn(handle(stream, Arc::clone(&buckets)));
+                }
+                Err(source) => tracing::warn!("accepting queue connection failed: {source:?}"),
+            }
+        }
+    }
+}
+
+/// Handle a request of a connection, responding once it is released.
+async fn handle(mut stream: TcpStream, buckets: Arc<[UnboundedSender<Sender<()>>]>) {
+    let mut line = String::new();
+    let mut reader = BufReader::new(&mut stream).take(MAX_REQUEST_LEN);
+
+    match timeout(REQUEST_TIMEOUT, reader.read_line(&mut line)).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(source)) => {
+            tracing::debug!("reading queue request failed: {source:?}");
+
+            return;
+        }
+        Err(_) => {
+            tracing::debug!("reading queue request timed out");
+
+            return;
+        }
+    }
+
+    let shard = if let Some(shard) = parse_request(&line) {
+        shard
+    } else {
+        tracing::debug!(request = line.trim_end(), "invalid queue request");
+
+        return;
+    };
+
+    #[allow(clippy::cast_possible_truncation)]
+    let bucket = (shard[0] % buckets.len() as u64) as usize;
+    let (tx, rx) = oneshot::channel();
+
+    if buckets[bucket].send(tx).is_err() {
+        return;
+    }
+
+    // Clients don't send anything after their request, so any read completing
+    // means that it disconnected (or misbehaved) and `rx` can be dropped.
+    let mut buf = [0; 1];
+
+    tokio::select! {
+        res = rx => {
+            if res.is_err() {
+                return;
+            }
+        }
+        _ = stream.read(&mut buf) => {
+            tracing::debug!("shard {}/{} disconnected while queued", shard[0], shard[1]);
+
+            return;
+        }
+    }
+
+    tracing::debug!("releasing shard {}/{}", shard[0], shard[1]);
+
+    if let Err(source) = stream.write_all(format!("{READY}\n").as_bytes()).await {
+        tracing::debug!("responding to queue request failed: {source:?}");
+    }
+}
+
+/// Parse a request line into the requesting shard's ID and total.
+fn parse_request(line: &str) -> Option<[u64; 2]> {
+    let mut parts = line.split_whitespace();
+
+    if parts.next()? != IDENTIFY {
+        return None;
+    }
+
+    let id = parts.next()?.parse().ok()?;
+    let total = parts.next()?.parse().ok()?;
+
+    (parts.next().is_none() && id < total).then_some([id, total])
+}
+
+/// Release the requests of a bucket, one per interval.
+async fn waiter(mut rx: UnboundedReceiver<Sender<()>>, interval: Duration) {
+    while let Some(req) = rx.recv().await {
+        // Requests of clients that disconnected while queued don't use up the
+        // interval.
+        if req.send(()).is_ok() {
+            sleep(interval).await;
+        }
+    }
+}
+
+/// [`Queue`] requesting to initialize sessions from a [`QueueServer`].
+///
+/// Requests are retried while the server is unreachable.
+///
+/// # Examples
+///
+/// Wait for shard 3 of 16 to be allowed to initialize its session:
+///
+/// ```no_run
+/// # #[tokio::main] async fn main() {
+/// use twilight_gateway_queue::{remote::RemoteQueue, Queue};
+///
+/// let queue = RemoteQueue::new(([10, 0, 0, 1], 8080).into());
+/// queue.request([3, 16]).await;
+/// # }
+/// ```
+///
+/// The queue is usually given to the gateway's `ClusterBuilder::queue` or
+/// `ShardBuilder::queue` instead.
+#[derive(Clone, Debug)]
+pub struct RemoteQueue {
+    /// Address of the server.
+    address: SocketAddr,
+}
+
+impl RemoteQueue {
+    /// Delay before retrying a failed request.
+    const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+    /// Create a new queue making requests to the server at an address.
+    pub const fn new(address: SocketAddr) -> Self {
+        Self { address }
+    }
+
+    /// Address of the server.
+    pub const fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Make a request to the server, waiting for its response.
+    async fn try_request(&self, [id, total]: [u64; 2]) -> Result<(), IoError> {
+        let mut stream = TcpStream::connect(self.address).await?;
+        stream
+            .write_all(format!("{IDENTIFY} {id} {total}\n").as_bytes())
+            .await?;
+
+        let mut line = String::new();
+        BufReader::new(stream)
+            .take(MAX_REQUEST_LEN)
+            .read_line(&mut line)
+            .await?;
+
+        if line.trim_end() == READY {
+            Ok(())
+        } else {
+            Err(IoError::new(
+                std::io::ErrorKind::InvalidData,
+                "server closed the connection without releasing the request",
+            ))
+        }
+    }
+}
+
+impl Queue for RemoteQueue {
+    fn request(&'_ self, shard_id: [u64; 2]) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(async move {
+            tracing::info!(
+                "shard {}/{} waiting for allowance",
+                shard_id[0],
+                shard_id[1]
+            );
+
+            while let Err(source) = self.try_request(shard_id).await {
+                tracing::warn!(
+                    address = %self.address,
+                    "requesting allowance failed, retrying: {source:?}",
+                );
+
+                sleep(Self::RETRY_DELAY).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QueueServer, QueueServerError, QueueServerErrorType, RemoteQueue};
+    use crate::Queue;
+    use static_assertions::{assert_fields, assert_impl_all};
+    use std::{
+        error::Error,
+        fmt::Debug,
+        time::{Duration, Instant},
+    };
+    use tokio::{io::AsyncWriteExt, net::TcpStream, time::sleep};
+
+    assert_impl_all!(QueueServer: Debug, Send, Sync);
+    assert_impl_all!(QueueServerErrorType: Debug, Send, Sync);
+    assert_fields!(QueueServerErrorType::Binding: address);
+    assert_impl_all!(QueueServerError: Error, Send, Sync);
+    assert_impl_all!(RemoteQueue: Clone, Debug, Queue, Send, Sync);
+
+    #[test]
+    fn parse_request() {
+        assert_eq!(Some([3, 4]), super::parse_request("IDENTIFY 3 4\n"));
+        assert_eq!(None, super::parse_request("IDENTIFY 4 4\n"));
+        assert_eq!(None, super::parse_request("IDENTIFY 3 4 5\n"));
+        assert_eq!(None, super::parse_request("RESUME 3 4\n"));
+    }
+
+    #[tokio::test]
+    async fn releases_buckets() -> Result<(), Box<dyn Error>> {
+        const INTERVAL: Duration = Duration::from_millis(200);
+
+        let server = QueueServer::bind(([127, 0, 0, 1], 0).into(), 2)
+            .await?
+            .interval(INTERVAL);
+        let queue = RemoteQueue::new(server.local_addr()?);
+        tokio::spawn(server.run());
+
+        // Shards 0 and 1 are in different buckets.
+        let started = Instant::now();
+        tokio::join!(queue.request([0, 4]), queue.request([1, 4]));
+        assert!(started.elapsed() < INTERVAL);
+
+        // Shard 2 is in the same bucket as shard 0.
+        queue.request([2, 4]).await;
+        assert!(started.elapsed() >= INTERVAL);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn disconnected_while_queued() -> Result<(), Box<dyn Error>> {
+        const INTERVAL: Duration = Duration::from_millis(300);
+
+        let server = QueueServer::bind(([127, 0, 0, 1], 0).into(), 1)
+            .await?
+            .interval(INTERVAL);
+        let address = server.local_addr()?;
+        let queue = RemoteQueue::new(address);
+        tokio::spawn(server.run());
+
+        let started = Instant::now();
+        queue.request([0, 4]).await;
+
+        // Shard 1 is queued behind shard 0 and disconnects before its release.
+        let mut stream = TcpStream::connect(address).await?;
+        stream.write_all(b"IDENTIFY 1 4\n").await?;
+        sleep(INTERVAL / 4).await;
+        drop(stream);
+        sleep(INTERVAL / 4).await;
+
+        // Shard 2 is released in its place, without waiting another interval.
+        queue.request([2, 4]).await;
+        assert!(started.elapsed() >= INTERVAL);
+        assert!(started.elapsed() < INTERVAL * 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn concurrency_zero() {
+        assert!(matches!(
+            QueueServer::bind(([127, 0, 0, 1], 0).into(), 0)
+                .await
+                .unwrap_err()
+                .kind(),
+            QueueServerErrorType::ConcurrencyZero
+        ));
+    }
+}
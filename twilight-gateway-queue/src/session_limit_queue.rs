@@ -0,0 +1,502 @@
+use super::Queue;
+use std::{
+    error::Error,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, Weak},
+    time::Duration,
+};
+use tokio::{
+    sync::Mutex as AsyncMutex,
+    time::{self, Instant},
+};
+use twilight_model::gateway::SessionStartLimit;
+
+/// Period after which the daily session start limit resets if the reset
+/// passes before it has been refreshed.
+const DAY: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// Interval between two sessions initialized in the same bucket.
+const IDENTIFY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Interval between two refreshes of the session start limit.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 10);
+
+/// Future resolving to the current session start limit.
+pub type SessionStartLimitFuture<'a> = Pin<
+    Box<dyn Future<Output = Result<SessionStartLimit, Box<dyn Error + Send + Sync>>> + Send + 'a>,
+>;
+
+/// Source of the current session start limit, used to refresh a
+/// [`SessionLimitQueue`].
+///
+/// This is implemented for the HTTP [`Client`], retrieving the limit from the
+/// bot's gateway information.
+///
+/// [`Client`]: twilight_http::Client
+pub trait SessionStartLimitSource: Debug + Send + Sync {
+    /// Retrieve the current session start limit.
+    fn fetch(&self) -> SessionStartLimitFuture<'_>;
+}
+
+impl<T: SessionStartLimitSource + ?Sized> SessionStartLimitSource for Arc<T> {
+    fn fetch(&self) -> SessionStartLimitFuture<'_> {
+        (**self).fetch()
+    }
+}
+
+#[cfg(feature = "twilight-http")]
+impl SessionStartLimitSource for twilight_http::Client {
+    fn fetch(&self) -> SessionStartLimitFuture<'_> {
+        Box::pin(async move {
+            let info = self.gateway().authed().await?.model().await?;
+
+            Ok(info.session_start_limit)
+        })
+    }
+}
+
+/// Requesting to initialize a session failed.
+#[derive(Debug)]
+pub struct SessionLimitQueueError {
+    kind: SessionLimitQueueErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl SessionLimitQueueError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &SessionLimitQueueErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(
+        self,
+    ) -> (
+        SessionLimitQueueErrorType,
+        Option<Box<dyn Error + Send + Sync>>,
+    ) {
+        (self.kind, self.source)
+    }
+}
+
+impl Display for SessionLimitQueueError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            SessionLimitQueueErrorType::Exhausted { reset_after } => {
+                f.write_str("session start limit is exhausted, resetting in ")?;
+
+                Debug::fmt(reset_after, f)
+            }
+        }
+    }
+}
+
+impl Error for SessionLimitQueueError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// Type of [`SessionLimitQueueError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SessionLimitQueueErrorType {
+    /// No sessions may be initialized until the session start limit resets.
+    Exhausted {
+        /// Time until the session start limit resets.
+        reset_after: Duration,
+    },
+}
+
+/// Session start limit along with when it resets.
+#[derive(Debug)]
+struct Limit {
+    /// Most recent session start limit, with `remaining` kept up to date.
+    limit: SessionStartLimit,
+    /// When the remaining sessions reset back to the total.
+    reset_at: Instant,
+}
+
+impl Limit {
+    /// Create a new limit from a session start limit retrieved now.
+    fn new(limit: SessionStartLimit) -> Self {
+        let reset_at = Instant::now() + Duration::from_millis(limit.reset_after);
+
+        Self { limit, reset_at }
+    }
+
+    /// Reset the remaining sessions if the reset has passed.
+    fn update(&mut self, now: Instant) {
+        if now >= self.reset_at {
+            self.limit.remaining = self.limit.total;
+            self.reset_at = now + DAY;
+        }
+    }
+
+    /// Reserve one of the remaining sessions, returning the time until the
+    /// reset if there are none.
+    fn reserve(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        self.update(now);
+
+        if self.limit.remaining == 0 {
+            return Err(self.reset_at - now);
+        }
+
+        self.limit.remaining -= 1;
+
+        Ok(())
+    }
+}
+
+/// Queue honoring the [session start limit] of the bot's gateway information.
+///
+/// Shards are released one per [`max_concurrency`] bucket every 5 seconds,
+/// and only as long as sessions remain in the daily limit. Once the limit is
+/// exhausted [`Queue::request`] waits until it resets, while
+/// [`try_request`] fails.
+///
+/// The limit may be periodically refreshed from a [`SessionStartLimitSource`],
+/// such as the HTTP [`Client`], to account for sessions initialized elsewhere.
+/// Sessions are counted as soon as they are requested, even if the request is
+/// dropped before it is released.
+///
+/// Like the [`LocalQueue`], this queue must not be shared across processes.
+///
+/// # Examples
+///
+/// Create a queue from the bot's gateway information, refreshing it every 10
+/// minutes:
+///
+/// ```no_run
+/// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::{env, sync::Arc};
+/// use twilight_gateway_queue::SessionLimitQueue;
+/// use twilight_http::Client;
+///
+/// let client = Arc::new(Client::new(env::var("DISCORD_TOKEN")?));
+/// let info = client.gateway().authed().await?.model().await?;
+///
+/// let queue = SessionLimitQueue::builder(info.session_start_limit)
+///     .source(client)
+///     .build();
+///
+/// println!("{} sessions remaining", queue.state().remaining);
+/// # Ok(()) }
+/// ```
+///
+/// [`Client`]: twilight_http::Client
+/// [`LocalQueue`]: super::LocalQueue
+/// [`max_concurrency`]: SessionStartLimit::max_concurrency
+/// [`try_request`]: Self::try_request
+/// [session start limit]: https://discord.com/developers/docs/topics/gateway#session-start-limit-object
+#[derive(Debug)]
+pub struct SessionLimitQueue {
+    /// Time each bucket last released a shard, in order of release.
+    buckets: Vec<AsyncMutex<Option<Instant>>>,
+    /// Interval between two sessions initialized in the same bucket.
+    identify_interval: Duration,
+    /// Session start limit shared with the refreshing task.
+    limit: Arc<Mutex<Limit>>,
+}
+
+impl SessionLimitQueue {
+    /// Create a new builder to configure and construct a queue.
+    pub const fn builder(limit: SessionStartLimit) -> SessionLimitQueueBuilder {
+        SessionLimitQueueBuilder::new(limit)
+    }
+
+    /// Create a new queue with the default configuration.
+    ///
+    /// Refer to [`SessionLimitQueueBuilder`] for the defaults.
+    pub fn new(limit: SessionStartLimit) -> Self {
+        Self::builder(limit).build()
+    }
+
+    /// Current state of the session start limit.
+    ///
+    /// `reset_after` is the number of milliseconds remaining until the
+    /// reset.
+    pub fn state(&self) -> SessionStartLimit {
+        let now = Instant::now();
+        let mut limit = self.lock_limit();
+        limit.update(now);
+
+        let reset_after = limit.reset_at.saturating_duration_since(now);
+
+        SessionStartLimit {
+            reset_after: reset_after.as_millis().try_into().unwrap_or(u64::MAX),
+            ..limit.limit.clone()
+        }
+    }
+
+    /// Request to initialize a session, failing if the session start limit is
+    /// exhausted instead of waiting for it to reset.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of type [`Exhausted`] if no sessions remain until the
+    /// limit resets.
+    ///
+    /// [`Exhausted`]: SessionLimitQueueErrorType::Exhausted
+    pub async fn try_request(&self, shard_id: [u64; 2]) -> Result<(), SessionLimitQueueError> {
+        let reserved = self.lock_limit().reserve();
+
+        if let Err(reset_after) = reserved {
+            return Err(SessionLimitQueueError {
+                kind: SessionLimitQueueErrorType::Exhausted { reset_after },
+                source: None,
+            });
+        }
+
+        self.wait_for_bucket(shard_id).await;
+
+        Ok(())
+    }
+
+    /// Lock the session start limit.
+    fn lock_limit(&self) -> MutexGuard<'_, Limit> {
+        self.limit.lock().expect("limit poisoned")
+    }
+
+    /// Wait for the turn of a shard in its bucket.
+    async fn wait_for_bucket(&self, [id, total]: [u64; 2]) {
+        #[allow(clippy::cast_possible_truncation)]
+        let bucket = &self.buckets[(id % self.buckets.len() as u64) as usize];
+        let mut last_release = bucket.lock().await;
+
+        tracing::info!("shard {id}/{total} waiting for allowance");
+
+        if let Some(last_release) = *last_release {
+            time::sleep_until(last_release + self.identify_interval).await;
+        }
+
+        *last_release = Some(Instant::now());
+    }
+}
+
+impl Queue for SessionLimitQueue {
+    /// Request to be able to identify with the gateway, waiting for the
+    /// session start limit to reset if it is exhausted.
+    fn request(&'_ self, shard_id: [u64; 2]) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(async move {
+            loop {
+                let reserved = self.lock_limit().reserve();
+
+                match reserved {
+                    Ok(()) => break,
+                    Err(reset_after) => {
+                        tracing::warn!(
+                            "session start limit exhausted, waiting {reset_after:.2?} for reset"
+                        );
+
+                        time::sleep(reset_after).await;
+                    }
+                }
+            }
+
+            self.wait_for_bucket(shard_id).await;
+        })
+    }
+}
+
+/// Builder to configure and construct a [`SessionLimitQueue`].
+#[derive(Debug)]
+#[must_use = "has no effect if not built"]
+pub struct SessionLimitQueueBuilder {
+    /// Interval between two sessions initialized in the same bucket.
+    identify_interval: Duration,
+    /// Initial session start limit.
+    limit: SessionStartLimit,
+    /// Interval between two refreshes of the session start limit.
+    refresh_interval: Duration,
+    /// Source to refresh the session start limit from.
+    source: Option<Box<dyn SessionStartLimitSource>>,
+}
+
+impl SessionLimitQueueBuilder {
+    /// Create a new builder from the bot's session start limit.
+    pub const fn new(limit: SessionStartLimit) -> Self {
+        Self {
+            identify_interval: IDENTIFY_INTERVAL,
+            limit,
+            refresh_interval: REFRESH_INTERVAL,
+            source: None,
+        }
+    }
+
+    /// Consume the builder, constructing a queue.
+    ///
+    /// The number of buckets is set by the initial limit's
+    /// `max_concurrency`, and isn't changed by refreshes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a source is set and this is not called within a Tokio
+    /// runtime.
+    pub fn build(self) -> SessionLimitQueue {
+        let buckets = (0..self.limit.max_concurrency.max(1))
+            .map(|_| AsyncMutex::new(None))
+            .collect();
+        let limit = Arc::new(Mutex::new(Limit::new(self.limit)));
+
+        if let Some(source) = self.source {
+            tokio::spawn(refresh(
+                Arc::downgrade(&limit),
+                source,
+                self.refresh_interval,
+            ));
+        }
+
+        SessionLimitQueue {
+            buckets,
+            identify_interval: self.identify_interval,
+            limit,
+        }
+    }
+
+    /// Set the interval between two sessions initialized in the same bucket.
+    ///
+    /// Defaults to 5 seconds.
+    pub const fn identify_interval(mut self, identify_interval: Duration) -> Self {
+        self.identify_interval = identify_interval;
+
+        self
+    }
+
+    /// Set the interval between two refreshes of the session start limit.
+    ///
+    /// Has no effect without a [source]. Defaults to 10 minutes.
+    ///
+    /// [source]: Self::source
+    pub const fn refresh_interval(mut self, refresh_interval: Duration) -> Self {
+        self.refresh_interval = refresh_interval;
+
+        self
+    }
+
+    /// Set the source to periodically refresh the session start limit from.
+    ///
+    /// Defaults to no source, only resetting the remaining sessions once the
+    /// reset has passed.
+    pub fn source(mut self, source: impl SessionStartLimitSource + 'static) -> Self {
+        self.source = Some(Box::new(source));
+
+        self
+    }
+}
+
+/// Periodically refresh the session start limit until the queue is dropped.
+async fn refresh(
+    limit: Weak<Mutex<Limit>>,
+    source: Box<dyn SessionStartLimitSource>,
+    interval: Duration,
+) {
+    loop {
+        time::sleep(interval).await;
+
+        if limit.strong_count() == 0 {
+            return;
+        }
+
+        match source.fetch().await {
+            Ok(fetched) => {
+                tracing::debug!(
+                    "{}/{} sessions remaining before next reset in {}ms",
+                    fetched.remaining,
+                    fetched.total,
+                    fetched.reset_after
+                );
+
+                match limit.upgrade() {
+                    Some(limit) => *limit.lock().expect("limit poisoned") = Limit::new(fetched),
+                    None => return,
+                }
+            }
+            Err(source) => {
+                tracing::warn!("refreshing the session start limit failed: {source}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Queue, SessionLimitQueue, SessionLimitQueueBuilder, SessionLimitQueueErrorType,
+        SessionStartLimitFuture, SessionStartLimitSource,
+    };
+    use static_assertions::{assert_impl_all, assert_obj_safe};
+    use std::{fmt::Debug, time::Duration};
+    use tokio::time;
+    use twilight_model::gateway::SessionStartLimit;
+
+    assert_impl_all!(SessionLimitQueue: Debug, Queue, Send, Sync);
+    assert_impl_all!(SessionLimitQueueBuilder: Debug, Send, Sync);
+    assert_obj_safe!(SessionStartLimitSource);
+
+    const fn limit(remaining: u64, reset_after: u64) -> SessionStartLimit {
+        SessionStartLimit {
+            max_concurrency: 2,
+            remaining,
+            reset_after,
+            total: 10,
+        }
+    }
+
+    #[derive(Debug)]
+    struct Source;
+
+    impl SessionStartLimitSource for Source {
+        fn fetch(&self) -> SessionStartLimitFuture<'_> {
+            Box::pin(async { Ok(limit(7, 60_000)) })
+        }
+    }
+
+    #[tokio::test]
+    async fn exhausted() {
+        let queue = SessionLimitQueue::new(limit(1, 60_000));
+        queue.try_request([0, 1]).await.unwrap();
+        assert_eq!(0, queue.state().remaining);
+
+        let error = queue.try_request([1, 2]).await.unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            SessionLimitQueueErrorType::Exhausted { reset_after }
+            if *reset_after <= Duration::from_secs(60)
+        ));
+    }
+
+    #[tokio::test]
+    async fn waits_for_reset() {
+        let queue = SessionLimitQueue::new(limit(0, 50));
+
+        time::timeout(Duration::from_secs(1), queue.request([0, 1]))
+            .await
+            .unwrap();
+        assert_eq!(9, queue.state().remaining);
+    }
+
+    #[tokio::test]
+    async fn refreshes() {
+        let queue = SessionLimitQueue::builder(limit(1, 60_000))
+            .refresh_interval(Duration::from_millis(10))
+            .source(Source)
+            .build();
+
+        time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(7, queue.state().remaining);
+    }
+}
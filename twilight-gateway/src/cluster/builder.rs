@@ -1,12 +1,19 @@
-use super::{Cluster, ClusterStartError, ClusterStartErrorType, Config, Events, ShardScheme};
+use super::{
+    Cluster, ClusterStartError, ClusterStartErrorType, Config, Events, LazyEvents, ShardScheme,
+};
 use crate::{
-    shard::{ResumeSession, ShardBuilder},
+    shard::{
+        reconnect::ReconnectStrategy, recorder::Recorder, session_store::SessionStore,
+        transport::GatewayTransport, Compression, Config as ShardConfig, Encoding, GuildFilter,
+        OverflowPolicy, ResumeSession, ShardBuilder, ZombiePolicy,
+    },
     EventTypeFlags,
 };
 use std::{
     collections::HashMap,
     fmt::{Debug, Formatter, Result as FmtResult},
     sync::Arc,
+    time::Duration,
 };
 use twilight_gateway_queue::{LocalQueue, Queue};
 use twilight_http::Client;
@@ -49,6 +56,8 @@ use crate::shard::tls::TlsContainer;
 pub struct ClusterBuilder {
     http: Arc<Client>,
     queue: Arc<dyn Queue>,
+    reshard_guild_timeout: Duration,
+    reshard_timeout: Duration,
     resume_sessions: HashMap<u64, ResumeSession>,
     shard: ShardBuilder,
     shard_presence:
@@ -62,6 +71,8 @@ impl ClusterBuilder {
         Self {
             http: Arc::new(Client::new(token.clone())),
             queue: Arc::new(LocalQueue::new()),
+            reshard_guild_timeout: Duration::from_secs(5),
+            reshard_timeout: Duration::from_secs(600),
             resume_sessions: HashMap::new(),
             shard: ShardBuilder::new(token, intents),
             shard_presence: None,
@@ -77,7 +88,32 @@ impl ClusterBuilder {
     /// there was an HTTP error retrieving the number of recommended shards.
     ///
     /// [`ClusterStartErrorType::AutoSharding`]: super::ClusterStartErrorType::AutoSharding
-    pub async fn build(mut self) -> Result<(Cluster, Events), ClusterStartError> {
+    pub async fn build(self) -> Result<(Cluster, Events), ClusterStartError> {
+        let (config, shard_config) = self.into_configs().await?;
+
+        Ok(Cluster::new_with_config(config, shard_config))
+    }
+
+    /// Consume the builder and create the cluster, with shards whose dispatch
+    /// events are not deserialized until requested.
+    ///
+    /// Refer to [`ShardBuilder::build_lazy`] for more information.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ClusterStartErrorType::AutoSharding`] error type if
+    /// there was an HTTP error retrieving the number of recommended shards.
+    ///
+    /// [`ClusterStartErrorType::AutoSharding`]: super::ClusterStartErrorType::AutoSharding
+    /// [`ShardBuilder::build_lazy`]: crate::shard::ShardBuilder::build_lazy
+    pub async fn build_lazy(self) -> Result<(Cluster, LazyEvents), ClusterStartError> {
+        let (config, shard_config) = self.into_configs().await?;
+
+        Ok(Cluster::new_with_config_lazy(config, shard_config))
+    }
+
+    /// Consume the builder, creating the cluster and shard configurations.
+    async fn into_configs(mut self) -> Result<(Config, ShardConfig), ClusterStartError> {
         if self.shard_scheme.is_none() {
             self.shard_scheme = Some(Self::recommended_shards(&self.http).await?);
         }
@@ -108,12 +144,14 @@ impl ClusterBuilder {
 
         let config = Config {
             queue: self.queue,
+            reshard_guild_timeout: self.reshard_guild_timeout,
+            reshard_timeout: self.reshard_timeout,
             resume_sessions: self.resume_sessions,
             shard_presence: self.shard_presence,
             shard_scheme: self.shard_scheme.expect("always set"),
         };
 
-        Ok(Cluster::new_with_config(config, &shard_config))
+        Ok((config, shard_config))
     }
 
     /// Retrieves the recommended shard count as a [`ShardScheme::Range`].
@@ -140,6 +178,48 @@ impl ClusterBuilder {
         })
     }
 
+    /// Set the capacity of each shard's buffer of events that have not yet
+    /// been received from the [`Events`] stream.
+    ///
+    /// Refer to the shard's [`ShardBuilder::event_buffer_capacity`] for more
+    /// information.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the provided capacity is 0.
+    #[track_caller]
+    pub fn event_buffer_capacity(mut self, event_buffer_capacity: usize) -> Self {
+        self.shard = self.shard.event_buffer_capacity(event_buffer_capacity);
+
+        self
+    }
+
+    /// Set the compression of payloads received from the gateway.
+    ///
+    /// Refer to [`ShardBuilder::compression`] for the default value and more
+    /// information.
+    ///
+    /// [`ShardBuilder::compression`]: crate::shard::ShardBuilder::compression
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.shard = self.shard.compression(compression);
+
+        self
+    }
+
+    /// Set the encoding of payloads sent to and received from the gateway.
+    ///
+    /// Refer to [`ShardBuilder::encoding`] for the default value and more
+    /// information.
+    ///
+    /// [`ShardBuilder::encoding`]: crate::shard::ShardBuilder::encoding
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.shard = self.shard.encoding(encoding);
+
+        self
+    }
+
     /// Set the event types to process.
     ///
     /// This is an optimization technique; all events not included in the
@@ -164,6 +244,18 @@ impl ClusterBuilder {
         self
     }
 
+    /// Set the filter of the guilds whose dispatch events are emitted.
+    ///
+    /// Refer to the shard's [`ShardBuilder::guild_filter`] for more
+    /// information.
+    ///
+    /// [`ShardBuilder::guild_filter`]: crate::shard::ShardBuilder::guild_filter
+    pub fn guild_filter(mut self, guild_filter: GuildFilter) -> Self {
+        self.shard = self.shard.guild_filter(guild_filter);
+
+        self
+    }
+
     /// Set the `twilight_http` Client used by the cluster.
     ///
     /// This is needed so that the cluster can retrieve gateway
@@ -219,6 +311,17 @@ impl ClusterBuilder {
         self
     }
 
+    /// Set the policy for handling events when a shard's event buffer is full.
+    ///
+    /// Refer to the shard's [`ShardBuilder::overflow_policy`] for more
+    /// information.
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.shard = self.shard.overflow_policy(overflow_policy);
+
+        self
+    }
+
     /// Set the presence to use when identifying with the gateway.
     ///
     /// Refer to the shard's [`ShardBuilder::presence`] for more information.
@@ -309,6 +412,63 @@ impl ClusterBuilder {
         self
     }
 
+    /// Set the strategy deciding how long shards wait before reconnecting, and
+    /// when they give up.
+    ///
+    /// A single strategy is shared by all shards, which is why it's given the
+    /// shard's ID. Refer to the shard's [`ShardBuilder::reconnect_strategy`]
+    /// for the default value and more information.
+    ///
+    /// [`ShardBuilder::reconnect_strategy`]: crate::shard::ShardBuilder::reconnect_strategy
+    pub fn reconnect_strategy(mut self, reconnect_strategy: Arc<dyn ReconnectStrategy>) -> Self {
+        self.shard = self.shard.reconnect_strategy(reconnect_strategy);
+
+        self
+    }
+
+    /// Set the recorder of the payloads the shards send and receive.
+    ///
+    /// Records include the ID of the shard, so a single recorder may be
+    /// shared by all shards. Refer to the shard's [`ShardBuilder::recorder`]
+    /// for more information.
+    ///
+    /// [`ShardBuilder::recorder`]: crate::shard::ShardBuilder::recorder
+    pub fn recorder(mut self, recorder: Arc<dyn Recorder>) -> Self {
+        self.shard = self.shard.recorder(recorder);
+
+        self
+    }
+
+    /// Set how long a shard of a new generation waits for its next guild to
+    /// load when resharding, before considering its remaining guilds
+    /// unavailable.
+    ///
+    /// Guilds that are unavailable, such as during an outage, are never
+    /// loaded. Refer to [`Cluster::reshard`] for more information.
+    ///
+    /// Defaults to 5 seconds.
+    ///
+    /// [`Cluster::reshard`]: super::Cluster::reshard
+    pub const fn reshard_guild_timeout(mut self, reshard_guild_timeout: Duration) -> Self {
+        self.reshard_guild_timeout = reshard_guild_timeout;
+
+        self
+    }
+
+    /// Set how long a new generation of shards may take to be ready and load
+    /// their guilds when resharding.
+    ///
+    /// Refer to [`Cluster::reshard`] for more information.
+    ///
+    /// Defaults to 10 minutes.
+    ///
+    /// [`Cluster::reshard`]: super::Cluster::reshard
+    pub const fn reshard_timeout(mut self, reshard_timeout: Duration) -> Self {
+        self.reshard_timeout = reshard_timeout;
+
+        self
+    }
+
     /// Set the session information to resume shards with.
     ///
     /// This requires having recovered the resume data when shutting down the
@@ -322,6 +482,41 @@ impl ClusterBuilder {
         self.resume_sessions = resume_sessions;
         self
     }
+
+    /// Set the store used by shards to persist their sessions.
+    ///
+    /// Sessions provided via [`resume_sessions`] take precedence over stored
+    /// sessions. Refer to the shard's [`ShardBuilder::session_store`] for
+    /// more information.
+    ///
+    /// [`resume_sessions`]: Self::resume_sessions
+    pub fn session_store(mut self, session_store: Arc<dyn SessionStore>) -> Self {
+        self.shard = self.shard.session_store(session_store);
+
+        self
+    }
+
+    /// Set the transport used by shards to connect to the gateway.
+    ///
+    /// Refer to the shard's [`ShardBuilder::transport`] for more information.
+    pub fn transport(mut self, transport: Arc<dyn GatewayTransport>) -> Self {
+        self.shard = self.shard.transport(transport);
+
+        self
+    }
+
+    /// Set the policy for detecting and handling zombie connections.
+    ///
+    /// Refer to the shard's [`ShardBuilder::zombie_policy`] for the default
+    /// value and more information.
+    ///
+    /// [`ShardBuilder::zombie_policy`]: crate::shard::ShardBuilder::zombie_policy
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn zombie_policy(mut self, zombie_policy: ZombiePolicy) -> Self {
+        self.shard = self.shard.zombie_policy(zombie_policy);
+
+        self
+    }
 }
 
 impl Debug for ClusterBuilder {
@@ -329,6 +524,8 @@ impl Debug for ClusterBuilder {
         f.debug_struct("ClusterBuilder")
             .field("http", &self.http)
             .field("queue", &self.queue)
+            .field("reshard_guild_timeout", &self.reshard_guild_timeout)
+            .field("reshard_timeout", &self.reshard_timeout)
             .field("resume_sessions", &self.resume_sessions)
             .field("shard", &self.shard)
             .field("shard_presence", &"<Fn>")
@@ -1,8 +1,12 @@
-use crate::{cluster::ShardScheme, shard::ResumeSession};
+use crate::{
+    cluster::ShardScheme,
+    shard::{Config as ShardConfig, ResumeSession},
+};
 use std::{
     collections::HashMap,
     fmt::{Debug, Formatter, Result as FmtResult},
     sync::Arc,
+    time::Duration,
 };
 use twilight_gateway_queue::Queue;
 use twilight_model::gateway::payload::outgoing::update_presence::UpdatePresencePayload;
@@ -12,6 +16,8 @@ use twilight_model::gateway::payload::outgoing::update_presence::UpdatePresenceP
 /// [`Cluster`]: crate::Cluster
 pub struct Config {
     pub(super) queue: Arc<dyn Queue>,
+    pub(super) reshard_guild_timeout: Duration,
+    pub(super) reshard_timeout: Duration,
     pub(super) resume_sessions: HashMap<u64, ResumeSession>,
     pub(super) shard_presence:
         Option<Box<dyn Fn(u64) -> Option<UpdatePresencePayload> + Send + Sync + 'static>>,
@@ -21,24 +27,74 @@ pub struct Config {
 impl Config {
     /// Return an immutable reference to the shard scheme used to start shards.
     ///
-    /// Refer to [`ClusterBuilder::shard_scheme`] for the default value.
+    /// Refer to [`ClusterBuilder::shard_scheme`] for the default value. This
+    /// is not updated when resharding; refer to [`Cluster::shard_scheme`] for
+    /// the scheme currently in use.
     ///
+    /// [`Cluster::shard_scheme`]: crate::Cluster::shard_scheme
     /// [`ClusterBuilder::shard_scheme`]: super::ClusterBuilder::shard_scheme
     pub const fn shard_scheme(&self) -> &ShardScheme {
         &self.shard_scheme
     }
 
+    /// Return how long a shard of a new generation waits for its next guild
+    /// to load when resharding, before considering its remaining guilds
+    /// unavailable.
+    ///
+    /// Refer to [`ClusterBuilder::reshard_guild_timeout`] for the default
+    /// value.
+    ///
+    /// [`ClusterBuilder::reshard_guild_timeout`]: super::ClusterBuilder::reshard_guild_timeout
+    pub const fn reshard_guild_timeout(&self) -> Duration {
+        self.reshard_guild_timeout
+    }
+
+    /// Return how long a new generation of shards may take to be ready when
+    /// resharding.
+    ///
+    /// Refer to [`ClusterBuilder::reshard_timeout`] for the default value.
+    ///
+    /// [`ClusterBuilder::reshard_timeout`]: super::ClusterBuilder::reshard_timeout
+    pub const fn reshard_timeout(&self) -> Duration {
+        self.reshard_timeout
+    }
+
     /// Return an immutable reference to the queue used for initiating shard
     /// sessions.
     pub fn queue(&self) -> &Arc<dyn Queue> {
         &self.queue
     }
+
+    /// Create the configuration of a shard managed by the cluster.
+    pub(super) fn shard_config(
+        &self,
+        shard_config: &ShardConfig,
+        shard: [u64; 2],
+        resume_session: Option<ResumeSession>,
+    ) -> ShardConfig {
+        let mut shard_config = shard_config.clone();
+        shard_config.shard = shard;
+
+        if let Some(data) = resume_session {
+            shard_config.session_id = Some(data.session_id.into_boxed_str());
+            shard_config.sequence = Some(data.sequence);
+            shard_config.resume_url = data.resume_url.map(String::into_boxed_str);
+        }
+
+        if let Some(shard_presence) = &self.shard_presence {
+            shard_config.presence = shard_presence(shard[0]);
+        }
+
+        shard_config
+    }
 }
 
 impl Debug for Config {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         f.debug_struct("Config")
             .field("queue", &self.queue)
+            .field("reshard_guild_timeout", &self.reshard_guild_timeout)
+            .field("reshard_timeout", &self.reshard_timeout)
             .field("resume_sessions", &self.resume_sessions)
             .field("shard_presence", &"<Fn>")
             .field("shard_scheme", &self.shard_scheme)
@@ -12,12 +12,17 @@
 //! [`EventType`]: twilight_model::gateway::event::EventType
 //! [`ClusterBuilder::event_types`]: crate::cluster::ClusterBuilder::event_types
 
-use crate::shard::Events as ShardEvents;
+use super::reshard::Readiness;
+use crate::shard::{Config as ShardConfig, LazyEvent, Shard};
 use futures_util::stream::{SelectAll, Stream};
 use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    mem,
     pin::Pin,
     task::{Context, Poll},
 };
+use tokio::sync::mpsc::UnboundedReceiver;
 use twilight_model::gateway::event::Event;
 
 /// Stream of events from a [`Cluster`].
@@ -36,13 +41,18 @@ use twilight_model::gateway::event::Event;
 /// [`Events`]: crate::shard::Events
 #[derive(Debug)]
 pub struct Events {
-    stream: SelectAll<ShardEventsWithId>,
+    stream: ClusterStream<crate::shard::Events>,
 }
 
 impl Events {
     /// Create a new stream of shards' events.
-    pub(super) const fn new(stream: SelectAll<ShardEventsWithId>) -> Self {
-        Self { stream }
+    pub(super) fn new(
+        stream: SelectAll<ShardEventsWithId<crate::shard::Events>>,
+        updates: UnboundedReceiver<StreamUpdate>,
+    ) -> Self {
+        Self {
+            stream: ClusterStream::new(stream, updates),
+        }
     }
 }
 
@@ -54,26 +64,268 @@ impl Stream for Events {
     }
 }
 
-/// Poll a shard's [`Events`] stream, mapping the result to the shard's ID.
+/// Stream of lazily deserialized events from a [`Cluster`].
 ///
-/// [`Events`]: crate::shard::Events
+/// Created by [`ClusterBuilder::build_lazy`]. Refer to the shard's
+/// [`LazyEvents`] stream for more information.
+///
+/// This implements [`futures_util::stream::Stream`].
+///
+/// [`Cluster`]: super::Cluster
+/// [`ClusterBuilder::build_lazy`]: super::ClusterBuilder::build_lazy
+/// [`LazyEvents`]: crate::shard::LazyEvents
+#[derive(Debug)]
+pub struct LazyEvents {
+    stream: ClusterStream<crate::shard::LazyEvents>,
+}
+
+impl LazyEvents {
+    /// Create a new stream of shards' lazy events.
+    pub(super) fn new(
+        stream: SelectAll<ShardEventsWithId<crate::shard::LazyEvents>>,
+        updates: UnboundedReceiver<StreamUpdate>,
+    ) -> Self {
+        Self {
+            stream: ClusterStream::new(stream, updates),
+        }
+    }
+}
+
+impl Stream for LazyEvents {
+    type Item = (u64, LazyEvent);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.stream).poll_next(cx)
+    }
+}
+
+/// Event streams of shards added to a cluster.
+#[derive(Debug)]
+pub enum ShardStreams {
+    /// Streams of shards emitting events.
+    Events(Vec<ShardEventsWithId<crate::shard::Events>>),
+    /// Streams of shards emitting lazy events.
+    Lazy(Vec<ShardEventsWithId<crate::shard::LazyEvents>>),
+}
+
+/// Update to the shards whose event streams a cluster's stream polls.
+#[derive(Debug)]
+pub enum StreamUpdate {
+    /// Poll the streams of shards that have been added.
+    Add(ShardStreams),
+    /// Stop polling the stream of a shard that has been removed.
+    Remove(u64),
+    /// Replace all streams with those of a new generation of shards.
+    Swap(ShardStreams),
+}
+
+/// Stream of a shard's events that a cluster can create shards with.
+pub trait ShardStream: Stream + Unpin + Sized {
+    /// Create a shard along with its stream of events.
+    fn new_shard(config: ShardConfig) -> (Shard, Self);
+
+    /// Readiness of the shard signalled by an event, if any.
+    fn readiness(item: &Self::Item) -> Option<Readiness>;
+
+    /// Wrap the streams of shards.
+    fn into_streams(streams: Vec<ShardEventsWithId<Self>>) -> ShardStreams;
+
+    /// Unwrap the streams of shards, if they are of this type.
+    fn from_streams(streams: ShardStreams) -> Option<Vec<ShardEventsWithId<Self>>>;
+}
+
+impl ShardStream for crate::shard::Events {
+    fn new_shard(config: ShardConfig) -> (Shard, Self) {
+        Shard::new_with_config(config)
+    }
+
+    fn readiness(item: &Self::Item) -> Option<Readiness> {
+        Readiness::from_event(item)
+    }
+
+    fn into_streams(streams: Vec<ShardEventsWithId<Self>>) -> ShardStreams {
+        ShardStreams::Events(streams)
+    }
+
+    fn from_streams(streams: ShardStreams) -> Option<Vec<ShardEventsWithId<Self>>> {
+        match streams {
+            ShardStreams::Events(streams) => Some(streams),
+            ShardStreams::Lazy(_) => None,
+        }
+    }
+}
+
+impl ShardStream for crate::shard::LazyEvents {
+    fn new_shard(config: ShardConfig) -> (Shard, Self) {
+        Shard::new_with_config_lazy(config)
+    }
+
+    fn readiness(item: &Self::Item) -> Option<Readiness> {
+        match item {
+            LazyEvent::Event(event) => Readiness::from_event(event),
+            LazyEvent::Raw(raw) => Readiness::from_raw(raw),
+        }
+    }
+
+    fn into_streams(streams: Vec<ShardEventsWithId<Self>>) -> ShardStreams {
+        ShardStreams::Lazy(streams)
+    }
+
+    fn from_streams(streams: ShardStreams) -> Option<Vec<ShardEventsWithId<Self>>> {
+        match streams {
+            ShardStreams::Events(_) => None,
+            ShardStreams::Lazy(streams) => Some(streams),
+        }
+    }
+}
+
+/// Poll the event streams of a cluster's shards, adding and removing streams
+/// as the cluster's shards change.
 #[derive(Debug)]
-pub struct ShardEventsWithId {
+struct ClusterStream<S: Stream>
+where
+    S::Item: Debug,
+{
+    /// Whether all shards have been removed, in which case the stream waits
+    /// for shards to be added rather than ending.
+    awaiting_shards: bool,
+    /// Streams of removed shards, polled until they have no more events
+    /// ready.
+    retiring: SelectAll<ShardEventsWithId<S>>,
+    stream: SelectAll<ShardEventsWithId<S>>,
+    updates: UnboundedReceiver<StreamUpdate>,
+}
+
+impl<S: ShardStream> ClusterStream<S>
+where
+    S::Item: Debug + Unpin,
+{
+    fn new(
+        stream: SelectAll<ShardEventsWithId<S>>,
+        updates: UnboundedReceiver<StreamUpdate>,
+    ) -> Self {
+        Self {
+            awaiting_shards: false,
+            retiring: SelectAll::new(),
+            stream,
+            updates,
+        }
+    }
+
+    /// Apply an update to the polled streams.
+    fn update(&mut self, update: StreamUpdate) {
+        match update {
+            StreamUpdate::Add(streams) => {
+                if let Some(streams) = S::from_streams(streams) {
+                    self.stream.extend(streams);
+                }
+            }
+            StreamUpdate::Remove(id) => {
+                let (removed, streams) = mem::take(&mut self.stream)
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|stream| stream.id() == id);
+
+                self.retiring.extend(removed);
+                self.stream = streams.into_iter().collect();
+                self.awaiting_shards = self.stream.is_empty();
+            }
+            StreamUpdate::Swap(streams) => {
+                if let Some(streams) = S::from_streams(streams) {
+                    let retiring = mem::replace(&mut self.stream, streams.into_iter().collect());
+                    self.retiring.extend(retiring);
+                }
+            }
+        }
+
+        if !self.stream.is_empty() {
+            self.awaiting_shards = false;
+        }
+    }
+}
+
+impl<S: ShardStream> Stream for ClusterStream<S>
+where
+    S::Item: Debug + Unpin,
+{
+    type Item = (u64, S::Item);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let cluster_dropped = loop {
+            match self.updates.poll_recv(cx) {
+                Poll::Ready(Some(update)) => self.update(update),
+                Poll::Ready(None) => break true,
+                Poll::Pending => break false,
+            }
+        };
+
+        if !self.retiring.is_empty() {
+            if let Poll::Ready(Some(item)) = Pin::new(&mut self.retiring).poll_next(cx) {
+                return Poll::Ready(Some(item));
+            }
+
+            self.retiring.clear();
+        }
+
+        if self.awaiting_shards && !cluster_dropped {
+            return Poll::Pending;
+        }
+
+        Pin::new(&mut self.stream).poll_next(cx)
+    }
+}
+
+/// Poll a shard's event stream, mapping the result to the shard's ID.
+#[derive(Debug)]
+pub struct ShardEventsWithId<S: Stream> {
+    /// Events received before the stream was added to a cluster's stream.
+    buffered: VecDeque<S::Item>,
     id: u64,
-    stream: ShardEvents,
+    stream: S,
 }
 
-impl ShardEventsWithId {
+impl<S: Stream> ShardEventsWithId<S> {
     /// Create a new stream with shard's ID and event stream.
-    pub(super) const fn new(id: u64, stream: ShardEvents) -> Self {
-        Self { id, stream }
+    pub(super) fn new(id: u64, stream: S) -> Self {
+        Self {
+            buffered: VecDeque::new(),
+            id,
+            stream,
+        }
+    }
+
+    /// ID of the shard.
+    pub(super) const fn id(&self) -> u64 {
+        self.id
     }
 }
 
-impl Stream for ShardEventsWithId {
-    type Item = (u64, Event);
+impl<S: Stream + Unpin> ShardEventsWithId<S> {
+    /// Poll the shard's event stream, buffering the next event until the
+    /// stream itself is polled.
+    pub(super) fn poll_buffer(&mut self, cx: &mut Context<'_>) -> Poll<Option<&S::Item>> {
+        match Pin::new(&mut self.stream).poll_next(cx) {
+            Poll::Ready(Some(event)) => {
+                self.buffered.push_back(event);
+
+                Poll::Ready(self.buffered.back())
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<S: Stream + Unpin> Stream for ShardEventsWithId<S>
+where
+    S::Item: Unpin,
+{
+    type Item = (u64, S::Item);
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(event) = self.buffered.pop_front() {
+            return Poll::Ready(Some((self.id, event)));
+        }
+
         match Pin::new(&mut self.stream).poll_next(cx) {
             Poll::Ready(Some(event)) => Poll::Ready(Some((self.id, event))),
             Poll::Ready(None) => Poll::Ready(None),
@@ -84,10 +336,11 @@ impl Stream for ShardEventsWithId {
 
 #[cfg(test)]
 mod tests {
-    use super::Events;
+    use super::{Events, LazyEvents};
     use futures_util::stream::Stream;
     use static_assertions::assert_impl_all;
     use std::fmt::Debug;
 
     assert_impl_all!(Events: Debug, Send, Stream, Sync);
+    assert_impl_all!(LazyEvents: Debug, Send, Stream, Sync);
 }
@@ -1,17 +1,34 @@
-use super::{ClusterBuilder, Config, Events};
+use super::{
+    event::{ShardEventsWithId, ShardStream, ShardStreams, StreamUpdate},
+    reshard, ClusterBuilder, ClusterReshardError, Config, Events, LazyEvents, PresenceSchedule,
+    Reshard, ShardScheme,
+};
 use crate::{
-    cluster::event::ShardEventsWithId,
     shard::{
-        raw_message::Message, Command, Config as ShardConfig, Information, ResumeSession, Shard,
+        raw_message::Message, Command, Config as ShardConfig, GuildMembers, Information,
+        ResumeSession, Shard,
     },
     Intents,
 };
-use futures_util::{future, stream::SelectAll};
+use futures_util::{
+    future,
+    stream::{SelectAll, Stream},
+};
 use std::{
-    collections::{hash_map::Values, HashMap},
+    collections::HashMap,
     error::Error,
     fmt::{Display, Formatter, Result as FmtResult},
     iter::FusedIterator,
+    mem,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::Duration,
+    vec::IntoIter,
+};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::time;
+use twilight_model::{
+    gateway::payload::outgoing::{update_presence::UpdatePresencePayload, RequestGuildMembers},
+    id::{marker::GuildMarker, Id},
 };
 
 /// Sending a command to a shard failed.
@@ -49,6 +66,17 @@ impl ClusterCommandError {
 impl Display for ClusterCommandError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         match &self.kind {
+            ClusterCommandErrorType::GuildNotHosted { guild_id, id } => {
+                f.write_str("shard ")?;
+                Display::fmt(id, f)?;
+                f.write_str(" of guild ")?;
+                Display::fmt(guild_id, f)?;
+
+                f.write_str(" is not managed by the cluster")
+            }
+            ClusterCommandErrorType::RequestingMembers => {
+                f.write_str("requesting the guild's members failed")
+            }
             ClusterCommandErrorType::Sending => {
                 f.write_str("sending the message over the websocket failed")
             }
@@ -74,6 +102,19 @@ impl Error for ClusterCommandError {
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum ClusterCommandErrorType {
+    /// Shard of the provided guild is not managed by the cluster.
+    GuildNotHosted {
+        /// Provided guild ID.
+        guild_id: Id<GuildMarker>,
+        /// ID of the guild's shard.
+        id: u64,
+    },
+    /// Requesting a guild's members failed.
+    ///
+    /// The source error is a [`RequestGuildMembersError`].
+    ///
+    /// [`RequestGuildMembersError`]: crate::shard::RequestGuildMembersError
+    RequestingMembers,
     /// The shard exists, but sending the provided value failed.
     Sending,
     /// Provided shard ID does not exist.
@@ -146,6 +187,117 @@ pub enum ClusterSendErrorType {
     },
 }
 
+/// Adding, removing, or restarting a shard of a cluster failed.
+#[derive(Debug)]
+pub struct ClusterShardError {
+    kind: ClusterShardErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl ClusterShardError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &ClusterShardErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(self) -> (ClusterShardErrorType, Option<Box<dyn Error + Send + Sync>>) {
+        (self.kind, self.source)
+    }
+}
+
+impl Display for ClusterShardError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            ClusterShardErrorType::GuildNotHosted { guild_id, id } => {
+                f.write_str("shard ")?;
+                Display::fmt(id, f)?;
+                f.write_str(" of guild ")?;
+                Display::fmt(guild_id, f)?;
+
+                f.write_str(" is not managed by the cluster")
+            }
+            ClusterShardErrorType::IdTooLarge { id, total } => {
+                f.write_str("shard ID ")?;
+                Display::fmt(id, f)?;
+                f.write_str(" is not less than the total number of shards (")?;
+                Display::fmt(total, f)?;
+
+                f.write_str(")")
+            }
+            ClusterShardErrorType::ShardExists { id } => {
+                f.write_str("shard ")?;
+                Display::fmt(id, f)?;
+
+                f.write_str(" already exists")
+            }
+            ClusterShardErrorType::ShardNonexistent { id } => {
+                f.write_str("shard ")?;
+                Display::fmt(id, f)?;
+
+                f.write_str(" does not exist")
+            }
+            ClusterShardErrorType::Starting { id } => {
+                f.write_str("starting shard ")?;
+                Display::fmt(id, f)?;
+
+                f.write_str(" failed")
+            }
+        }
+    }
+}
+
+impl Error for ClusterShardError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// Type of [`ClusterShardError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ClusterShardErrorType {
+    /// Shard of the provided guild is not managed by the cluster.
+    GuildNotHosted {
+        /// Provided guild ID.
+        guild_id: Id<GuildMarker>,
+        /// ID of the guild's shard.
+        id: u64,
+    },
+    /// Provided shard ID is not less than the total number of shards.
+    IdTooLarge {
+        /// Provided shard ID.
+        id: u64,
+        /// Total number of shards used by the bot.
+        total: u64,
+    },
+    /// Provided shard ID is already managed by the cluster.
+    ShardExists {
+        /// Provided shard ID.
+        id: u64,
+    },
+    /// Provided shard ID does not exist.
+    ShardNonexistent {
+        /// Provided shard ID.
+        id: u64,
+    },
+    /// Starting the shard failed.
+    Starting {
+        /// Provided shard ID.
+        id: u64,
+    },
+}
+
 /// Starting a cluster failed.
 #[derive(Debug)]
 pub struct ClusterStartError {
@@ -210,7 +362,8 @@ pub enum ClusterStartErrorType {
 /// # Using a cluster in multiple tasks
 ///
 /// To use a cluster instance in multiple tasks, consider wrapping it in an
-/// [`std::sync::Arc`] or [`std::rc::Rc`].
+/// [`std::sync::Arc`] or [`std::rc::Rc`]. Shards can be added, removed, and
+/// restarted, and the cluster resharded, through a shared reference.
 ///
 /// # Examples
 ///
@@ -218,7 +371,15 @@ pub enum ClusterStartErrorType {
 #[derive(Debug)]
 pub struct Cluster {
     config: Config,
-    shards: HashMap<u64, Shard>,
+    /// Whether the shards' events are lazily deserialized.
+    lazy: bool,
+    shard_config: ShardConfig,
+    /// Shards managed by the cluster.
+    ///
+    /// The lock is never held across an await point.
+    shards: RwLock<ManagedShards>,
+    /// Sender of updates to the shards polled by the cluster's event stream.
+    updates: UnboundedSender<StreamUpdate>,
 }
 
 impl Cluster {
@@ -270,14 +431,35 @@ impl Cluster {
         Self::builder(token, intents).build().await
     }
 
-    pub(super) fn new_with_config(
+    pub(super) fn new_with_config(config: Config, shard_config: ShardConfig) -> (Self, Events) {
+        let (updates_tx, updates_rx) = mpsc::unbounded_channel();
+        let (this, streams) = Self::new_with_shards(config, shard_config, updates_tx, false);
+
+        (this, Events::new(streams, updates_rx))
+    }
+
+    pub(super) fn new_with_config_lazy(
+        config: Config,
+        shard_config: ShardConfig,
+    ) -> (Self, LazyEvents) {
+        let (updates_tx, updates_rx) = mpsc::unbounded_channel();
+        let (this, streams) = Self::new_with_shards(config, shard_config, updates_tx, true);
+
+        (this, LazyEvents::new(streams, updates_rx))
+    }
+
+    fn new_with_shards<S: ShardStream>(
         mut config: Config,
-        shard_config: &ShardConfig,
-    ) -> (Self, Events) {
-        #[derive(Default)]
-        struct ShardFold {
-            shards: HashMap<u64, Shard>,
-            streams: Vec<ShardEventsWithId>,
+        shard_config: ShardConfig,
+        updates: UnboundedSender<StreamUpdate>,
+        lazy: bool,
+    ) -> (Self, SelectAll<ShardEventsWithId<S>>)
+    where
+        S::Item: Unpin,
+    {
+        struct ShardFold<S: Stream> {
+            shards: HashMap<u64, Arc<Shard>>,
+            streams: Vec<ShardEventsWithId<S>>,
         }
 
         let total = config.shard_scheme().total();
@@ -288,36 +470,41 @@ impl Cluster {
             metrics::gauge!("Cluster-Shard-Count", total as f64);
         }
 
-        let ShardFold { shards, streams } =
-            config
-                .shard_scheme()
-                .iter()
-                .fold(ShardFold::default(), |mut fold, idx| {
-                    let mut shard_config = shard_config.clone();
-                    shard_config.shard = [idx, total];
-
-                    if let Some(data) = config.resume_sessions.remove(&idx) {
-                        shard_config.session_id = Some(data.session_id.into_boxed_str());
-                        shard_config.sequence = Some(data.sequence);
-                        shard_config.resume_url = data.resume_url.map(String::into_boxed_str);
-                    }
-
-                    if let Some(shard_presence) = &config.shard_presence {
-                        shard_config.presence = shard_presence(idx)
-                    }
+        let ShardFold { shards, streams } = config.shard_scheme().iter().fold(
+            ShardFold {
+                shards: HashMap::new(),
+                streams: Vec::new(),
+            },
+            |mut fold, idx| {
+                let resume_session = config.resume_sessions.remove(&idx);
+                let shard_config = config.shard_config(&shard_config, [idx, total], resume_session);
 
-                    let (shard, stream) = Shard::new_with_config(shard_config);
+                let (shard, stream) = S::new_shard(shard_config);
 
-                    fold.shards.insert(idx, shard);
-                    fold.streams.push(ShardEventsWithId::new(idx, stream));
+                fold.shards.insert(idx, Arc::new(shard));
+                fold.streams.push(ShardEventsWithId::new(idx, stream));
 
-                    fold
-                });
+                fold
+            },
+        );
 
         #[allow(clippy::from_iter_instead_of_collect)]
         let select_all = SelectAll::from_iter(streams);
 
-        (Self { config, shards }, Events::new(select_all))
+        let shards = ManagedShards {
+            scheme: config.shard_scheme().clone(),
+            shards,
+        };
+
+        let this = Self {
+            config,
+            lazy,
+            shard_config,
+            shards: RwLock::new(shards),
+            updates,
+        };
+
+        (this, select_all)
     }
 
     /// Create a builder to configure and construct a cluster.
@@ -393,12 +580,14 @@ impl Cluster {
     /// # Ok(()) }
     /// ```
     pub async fn up(&self) {
-        future::join_all(self.shards.values().map(Shard::start)).await;
+        let shards = self.shards().collect::<Vec<_>>();
+
+        future::join_all(shards.iter().map(|shard| shard.start())).await;
     }
 
     /// Bring down the cluster, stopping all of the shards that it's managing.
     pub fn down(&self) {
-        for shard in self.shards.values() {
+        for shard in self.read_shards().shards.values() {
             shard.shutdown();
         }
     }
@@ -413,22 +602,382 @@ impl Cluster {
     /// disconnection. You may also not be able to resume if you missed too many
     /// events already.
     pub fn down_resumable(&self) -> HashMap<u64, ResumeSession> {
-        self.shards
+        self.read_shards()
+            .shards
             .values()
-            .map(Shard::shutdown_resumable)
+            .map(|shard| shard.shutdown_resumable())
             .filter_map(|(id, session)| session.map(|s| (id, s)))
             .collect()
     }
 
+    /// Start a new generation of shards managed according to a new scheme,
+    /// such as with a new total number of shards.
+    ///
+    /// The new shards are started alongside the current ones and the returned
+    /// future resolves once all of them are ready and have loaded their
+    /// guilds. The cluster's event stream keeps emitting the events of the
+    /// current shards until the new generation is applied via
+    /// [`apply_reshard`]; events of the new shards received in the meantime
+    /// are buffered until then.
+    ///
+    /// Guilds are only waited on if the [`EventTypeFlags::GUILD_CREATE`] event
+    /// type is enabled. Guilds that are unavailable are only known to be so
+    /// if the [`EventTypeFlags::GUILD_DELETE`] event type is enabled too;
+    /// otherwise, and for guilds that stay unavailable without Discord
+    /// saying so, a shard's remaining guilds are considered unavailable once
+    /// none have loaded within the [`reshard_guild_timeout`]. Waiting is
+    /// bounded by the configured [`reshard_timeout`].
+    ///
+    /// # Examples
+    ///
+    /// Reshard a running cluster to use 20 shards:
+    ///
+    /// ```no_run
+    /// use std::{env, sync::Arc};
+    /// use twilight_gateway::{
+    ///     cluster::{Cluster, ShardScheme},
+    ///     Intents,
+    /// };
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let token = env::var("DISCORD_TOKEN")?;
+    /// let (cluster, _events) = Cluster::new(token, Intents::GUILDS).await?;
+    /// let cluster = Arc::new(cluster);
+    /// cluster.up().await;
+    ///
+    /// // Some time later, once the bot has grown..
+    /// let scheme = ShardScheme::try_from((0..=19, 20))?;
+    /// let reshard = cluster.reshard(scheme).await?;
+    ///
+    /// // Swap the event streams and shut down the old shards.
+    /// cluster.apply_reshard(reshard);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ClusterReshardErrorType::ReadyDisabled`] error type if the
+    /// [`EventTypeFlags::READY`] event type is not enabled.
+    ///
+    /// Returns a [`ClusterReshardErrorType::ShardClosed`] error type if the
+    /// event stream of a new shard ended before it was ready.
+    ///
+    /// Returns a [`ClusterReshardErrorType::Starting`] error type if starting
+    /// a new shard failed.
+    ///
+    /// Returns a [`ClusterReshardErrorType::TimedOut`] error type if the new
+    /// shards were not ready within the [`reshard_timeout`].
+    ///
+    /// [`apply_reshard`]: Self::apply_reshard
+    /// [`ClusterReshardErrorType::ReadyDisabled`]: super::ClusterReshardErrorType::ReadyDisabled
+    /// [`ClusterReshardErrorType::ShardClosed`]: super::ClusterReshardErrorType::ShardClosed
+    /// [`ClusterReshardErrorType::Starting`]: super::ClusterReshardErrorType::Starting
+    /// [`ClusterReshardErrorType::TimedOut`]: super::ClusterReshardErrorType::TimedOut
+    /// [`reshard_guild_timeout`]: Config::reshard_guild_timeout
+    /// [`reshard_timeout`]: Config::reshard_timeout
+    /// [`EventTypeFlags::GUILD_CREATE`]: crate::EventTypeFlags::GUILD_CREATE
+    /// [`EventTypeFlags::GUILD_DELETE`]: crate::EventTypeFlags::GUILD_DELETE
+    /// [`EventTypeFlags::READY`]: crate::EventTypeFlags::READY
+    pub async fn reshard(&self, shard_scheme: ShardScheme) -> Result<Reshard, ClusterReshardError> {
+        if self.lazy {
+            reshard::prepare::<crate::shard::LazyEvents>(
+                &self.config,
+                &self.shard_config,
+                shard_scheme,
+            )
+            .await
+        } else {
+            reshard::prepare::<crate::shard::Events>(&self.config, &self.shard_config, shard_scheme)
+                .await
+        }
+    }
+
+    /// Replace the cluster's shards with a new generation created by
+    /// [`reshard`].
+    ///
+    /// The cluster's event stream emits the events of the current shards
+    /// that are immediately available before switching over to the events of
+    /// the new shards, after which the current shards are shut down.
+    ///
+    /// The reshard must have been created by this cluster. Its shard scheme
+    /// becomes the cluster's [`shard_scheme`].
+    ///
+    /// [`reshard`]: Self::reshard
+    /// [`shard_scheme`]: Self::shard_scheme
+    pub fn apply_reshard(&self, mut reshard: Reshard) {
+        let shards = mem::take(&mut reshard.shards)
+            .into_iter()
+            .map(|(id, shard)| (id, Arc::new(shard)))
+            .collect();
+        let previous = mem::replace(
+            &mut *self.write_shards(),
+            ManagedShards {
+                scheme: reshard.shard_scheme.clone(),
+                shards,
+            },
+        );
+
+        #[cfg(feature = "metrics")]
+        #[allow(clippy::cast_precision_loss)]
+        {
+            metrics::gauge!("Cluster-Shard-Count", reshard.shard_scheme.total() as f64);
+        }
+
+        if let Some(streams) = reshard.streams.take() {
+            // The event stream may have been dropped, in which case there's
+            // nothing to swap.
+            let _res = self.updates.send(StreamUpdate::Swap(streams));
+        }
+
+        for shard in previous.shards.values() {
+            shard.shutdown();
+        }
+    }
+
+    /// Add a shard to the cluster, merging its events into the cluster's
+    /// event stream.
+    ///
+    /// The shard is started via the cluster's [`queue`], so it identifies in
+    /// turn with any other shards that are starting. A session to resume may
+    /// be provided, such as one returned by [`remove_shard`] on another
+    /// cluster, in which case it takes precedence over any session stored in
+    /// the configured session store.
+    ///
+    /// The cluster's [`shard_scheme`] is not modified.
+    ///
+    /// # Examples
+    ///
+    /// Move shard 3 from one cluster to another:
+    ///
+    /// ```no_run
+    /// use std::env;
+    /// use twilight_gateway::{
+    ///     cluster::{Cluster, ShardScheme},
+    ///     Intents,
+    /// };
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let token = env::var("DISCORD_TOKEN")?;
+    /// let (first, _events) = Cluster::builder(token.clone(), Intents::GUILDS)
+    ///     .shard_scheme(ShardScheme::try_from((0..=3, 8))?)
+    ///     .build()
+    ///     .await?;
+    /// let (second, _events) = Cluster::builder(token, Intents::GUILDS)
+    ///     .shard_scheme(ShardScheme::try_from((4..=7, 8))?)
+    ///     .build()
+    ///     .await?;
+    /// first.up().await;
+    /// second.up().await;
+    ///
+    /// let resume_session = first.remove_shard(3)?;
+    /// second.add_shard(3, resume_session).await?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ClusterShardErrorType::IdTooLarge`] error type if the
+    /// provided shard ID is not less than the total number of shards.
+    ///
+    /// Returns a [`ClusterShardErrorType::ShardExists`] error type if the
+    /// cluster already manages a shard with the provided ID, including if
+    /// one was added while the shard was starting.
+    ///
+    /// Returns a [`ClusterShardErrorType::Starting`] error type if starting the
+    /// shard failed.
+    ///
+    /// [`queue`]: Config::queue
+    /// [`remove_shard`]: Self::remove_shard
+    /// [`shard_scheme`]: Self::shard_scheme
+    pub async fn add_shard(
+        &self,
+        id: u64,
+        resume_session: Option<ResumeSession>,
+    ) -> Result<(), ClusterShardError> {
+        {
+            let shards = self.read_shards();
+            let total = shards.scheme.total();
+
+            if id >= total {
+                return Err(ClusterShardError {
+                    kind: ClusterShardErrorType::IdTooLarge { id, total },
+                    source: None,
+                });
+            }
+
+            if shards.shards.contains_key(&id) {
+                return Err(ClusterShardError {
+                    kind: ClusterShardErrorType::ShardExists { id },
+                    source: None,
+                });
+            }
+        }
+
+        self.start_shard(id, resume_session, None).await
+    }
+
+    /// Remove a shard from the cluster, unmerging its events from the
+    /// cluster's event stream.
+    ///
+    /// The shard is shut down in a resumable way, returning the information
+    /// needed to resume its session if it can be resumed. Refer to
+    /// [`Shard::shutdown_resumable`] for more information.
+    ///
+    /// Events of the shard that are immediately available are still emitted
+    /// by the event stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ClusterShardErrorType::ShardNonexistent`] error type if the
+    /// provided shard ID does not exist in the cluster.
+    pub fn remove_shard(&self, id: u64) -> Result<Option<ResumeSession>, ClusterShardError> {
+        let shard = self
+            .write_shards()
+            .shards
+            .remove(&id)
+            .ok_or(ClusterShardError {
+                kind: ClusterShardErrorType::ShardNonexistent { id },
+                source: None,
+            })?;
+
+        let (_, resume_session) = shard.shutdown_resumable();
+        // The event stream may have been dropped, in which case there's
+        // nothing to remove.
+        let _res = self.updates.send(StreamUpdate::Remove(id));
+
+        Ok(resume_session)
+    }
+
+    /// Restart a shard of the cluster.
+    ///
+    /// The shard is shut down in a resumable way and a new shard is started in
+    /// its place via the cluster's [`queue`], resuming the session if
+    /// possible.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ClusterShardErrorType::ShardNonexistent`] error type if the
+    /// provided shard ID does not exist in the cluster.
+    ///
+    /// Returns a [`ClusterShardErrorType::Starting`] error type if starting the
+    /// new shard failed, in which case the shard is no longer part of the
+    /// cluster.
+    ///
+    /// [`queue`]: Config::queue
+    pub async fn restart_shard(&self, id: u64) -> Result<(), ClusterShardError> {
+        let presence = self.shard(id).and_then(|shard| shard.presence());
+        let resume_session = self.remove_shard(id)?;
+
+        self.start_shard(id, resume_session, presence).await
+    }
+
+    /// Create and start a shard, adding it to the cluster.
+    ///
+    /// The presence, if any, overrides the configured presence of the shard.
+    /// The shard is shut down if another one with the same ID was added while
+    /// it was starting.
+    async fn start_shard(
+        &self,
+        id: u64,
+        resume_session: Option<ResumeSession>,
+        presence: Option<UpdatePresencePayload>,
+    ) -> Result<(), ClusterShardError> {
+        let shard = [id, self.read_shards().scheme.total()];
+        let mut shard_config = self
+            .config
+            .shard_config(&self.shard_config, shard, resume_session);
+
+        if presence.is_some() {
+            shard_config.presence = presence;
+        }
+
+        let (shard, streams) = if self.lazy {
+            new_shard::<crate::shard::LazyEvents>(id, shard_config)
+        } else {
+            new_shard::<crate::shard::Events>(id, shard_config)
+        };
+
+        shard.start().await.map_err(|source| ClusterShardError {
+            kind: ClusterShardErrorType::Starting { id },
+            source: Some(Box::new(source)),
+        })?;
+
+        {
+            let mut shards = self.write_shards();
+
+            if shards.shards.contains_key(&id) {
+                drop(shards);
+                shard.shutdown();
+
+                return Err(ClusterShardError {
+                    kind: ClusterShardErrorType::ShardExists { id },
+                    source: None,
+                });
+            }
+
+            shards.shards.insert(id, Arc::new(shard));
+        }
+
+        // The event stream may have been dropped, in which case there's
+        // nothing to add to.
+        let _res = self.updates.send(StreamUpdate::Add(streams));
+
+        Ok(())
+    }
+
+    /// Scheme the cluster's shards are currently managed by.
+    ///
+    /// This is the configured [`Config::shard_scheme`] until a new generation
+    /// of shards is applied via [`apply_reshard`].
+    ///
+    /// [`apply_reshard`]: Self::apply_reshard
+    pub fn shard_scheme(&self) -> ShardScheme {
+        self.read_shards().scheme.clone()
+    }
+
     /// Return a Shard by its ID.
-    pub fn shard(&self, id: u64) -> Option<&Shard> {
-        self.shards.get(&id)
+    pub fn shard(&self, id: u64) -> Option<Arc<Shard>> {
+        self.read_shards().shards.get(&id).cloned()
+    }
+
+    /// Return the shard receiving events of a guild.
+    ///
+    /// The shard's ID is calculated with the cluster's [`shard_scheme`]. Refer
+    /// to [`ShardScheme::shard_for_guild`] for more information.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ClusterShardErrorType::GuildNotHosted`] error type if the
+    /// guild's shard is not managed by the cluster.
+    ///
+    /// [`shard_scheme`]: Self::shard_scheme
+    pub fn shard_for_guild(
+        &self,
+        guild_id: Id<GuildMarker>,
+    ) -> Result<Arc<Shard>, ClusterShardError> {
+        let (id, shard) = self.guild_shard(guild_id);
+
+        shard.ok_or(ClusterShardError {
+            kind: ClusterShardErrorType::GuildNotHosted { guild_id, id },
+            source: None,
+        })
     }
 
     /// Return an iterator of all the shards.
-    pub fn shards(&self) -> Shards<'_> {
+    ///
+    /// The iterator holds the shards managed by the cluster at the time of
+    /// calling.
+    pub fn shards(&self) -> Shards {
         Shards {
-            iter: self.shards.values(),
+            iter: self
+                .read_shards()
+                .shards
+                .values()
+                .cloned()
+                .collect::<Vec<_>>()
+                .into_iter(),
         }
     }
 
@@ -459,7 +1008,8 @@ impl Cluster {
     /// # Ok(()) }
     /// ```
     pub fn info(&self) -> HashMap<u64, Information> {
-        self.shards
+        self.read_shards()
+            .shards
             .iter()
             .filter_map(|(id, shard)| shard.info().ok().map(|info| (*id, info)))
             .collect()
@@ -525,6 +1075,97 @@ impl Cluster {
             })
     }
 
+    /// Send a command to the shard receiving events of a guild.
+    ///
+    /// Refer to [`shard_for_guild`] for how the shard is determined.
+    ///
+    /// # Examples
+    ///
+    /// Request the members of a guild from its shard:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::env;
+    /// use twilight_gateway::{cluster::Cluster, Intents};
+    /// use twilight_model::{gateway::payload::outgoing::RequestGuildMembers, id::Id};
+    ///
+    /// let intents = Intents::GUILD_MEMBERS;
+    /// let token = env::var("DISCORD_TOKEN")?;
+    ///
+    /// let (cluster, _events) = Cluster::new(token, intents).await?;
+    /// cluster.up().await;
+    ///
+    /// let guild_id = Id::new(1);
+    /// let request = RequestGuildMembers::builder(guild_id).query("", None);
+    /// cluster.command_for_guild(guild_id, &request).await?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ClusterCommandErrorType::GuildNotHosted`] error type if the
+    /// guild's shard is not managed by the cluster.
+    ///
+    /// Returns a [`ClusterCommandErrorType::Sending`] error type if the shard
+    /// exists, but sending it failed.
+    ///
+    /// [`shard_for_guild`]: Self::shard_for_guild
+    pub async fn command_for_guild(
+        &self,
+        guild_id: Id<GuildMarker>,
+        value: &impl Command,
+    ) -> Result<(), ClusterCommandError> {
+        let (id, shard) = self.guild_shard(guild_id);
+        let shard = shard.ok_or(ClusterCommandError {
+            kind: ClusterCommandErrorType::GuildNotHosted { guild_id, id },
+            source: None,
+        })?;
+
+        shard
+            .command(value)
+            .await
+            .map_err(|source| ClusterCommandError {
+                kind: ClusterCommandErrorType::Sending,
+                source: Some(Box::new(source)),
+            })
+    }
+
+    /// Request the members of a guild from its shard, waiting for all of the
+    /// member chunks received in response.
+    ///
+    /// Refer to [`Shard::request_guild_members`] for more information.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ClusterCommandErrorType::GuildNotHosted`] error type if the
+    /// guild's shard is not managed by the cluster.
+    ///
+    /// Returns a [`ClusterCommandErrorType::RequestingMembers`] error type if
+    /// sending the request failed or not all member chunks were received
+    /// before the timeout elapsed.
+    pub async fn request_guild_members(
+        &self,
+        request: RequestGuildMembers,
+        timeout: Duration,
+    ) -> Result<GuildMembers, ClusterCommandError> {
+        let guild_id = request.d.guild_id;
+        let (id, shard) = self.guild_shard(guild_id);
+
+        let shard = shard.ok_or(ClusterCommandError {
+            kind: ClusterCommandErrorType::GuildNotHosted { guild_id, id },
+            source: None,
+        })?;
+
+        shard
+            .request_guild_members(request, timeout)
+            .await
+            .map_err(|source| ClusterCommandError {
+                kind: ClusterCommandErrorType::RequestingMembers,
+                source: Some(Box::new(source)),
+            })
+    }
+
     /// Send a raw websocket message.
     ///
     /// # Examples
@@ -572,25 +1213,182 @@ impl Cluster {
                 source: Some(Box::new(source)),
             })
     }
+
+    /// Update the presence of every shard.
+    ///
+    /// The presence becomes the current presence of every shard, which new
+    /// sessions identify with. Refer to [`Shard::set_presence`] for more
+    /// information.
+    ///
+    /// # Examples
+    ///
+    /// Set the current user's presence to be playing "twilight":
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::env;
+    /// use twilight_gateway::{cluster::Cluster, Intents};
+    /// use twilight_model::gateway::{
+    ///     payload::outgoing::update_presence::UpdatePresencePayload,
+    ///     presence::{Activity, ActivityType, MinimalActivity, Status},
+    /// };
+    ///
+    /// let token = env::var("DISCORD_TOKEN")?;
+    /// let (cluster, _events) = Cluster::new(token, Intents::GUILDS).await?;
+    /// cluster.up().await;
+    ///
+    /// let activity = Activity::from(MinimalActivity {
+    ///     kind: ActivityType::Playing,
+    ///     name: "twilight".to_owned(),
+    ///     url: None,
+    /// });
+    /// let presence = UpdatePresencePayload::new(vec![activity], false, None, Status::Online)?;
+    ///
+    /// cluster.set_presence(&presence).await?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ClusterCommandErrorType::Sending`] error type if sending
+    /// the presence over a shard failed. The presence is still sent over the
+    /// other shards, and is set as the current presence of every shard.
+    pub async fn set_presence(
+        &self,
+        presence: &UpdatePresencePayload,
+    ) -> Result<(), ClusterCommandError> {
+        let shards = self.shards().collect::<Vec<_>>();
+        let results = future::join_all(
+            shards
+                .iter()
+                .map(|shard| shard.set_presence(presence.clone())),
+        )
+        .await;
+
+        results
+            .into_iter()
+            .collect::<Result<(), _>>()
+            .map_err(|source| ClusterCommandError {
+                kind: ClusterCommandErrorType::Sending,
+                source: Some(Box::new(source)),
+            })
+    }
+
+    /// Rotate through the presences of a schedule, updating the presence of
+    /// every shard at the schedule's interval.
+    ///
+    /// The returned future never completes; drop it to stop the rotation.
+    /// Failures to update the presence of a shard are logged, with the shard
+    /// applying the presence once it identifies a new session.
+    ///
+    /// # Examples
+    ///
+    /// Alternate between two activities every minute:
+    ///
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::{env, sync::Arc, time::Duration};
+    /// use twilight_gateway::{
+    ///     cluster::{Cluster, PresenceSchedule},
+    ///     Intents,
+    /// };
+    /// use twilight_model::gateway::{
+    ///     payload::outgoing::update_presence::UpdatePresencePayload,
+    ///     presence::{Activity, ActivityType, MinimalActivity, Status},
+    /// };
+    ///
+    /// let token = env::var("DISCORD_TOKEN")?;
+    /// let (cluster, _events) = Cluster::new(token, Intents::GUILDS).await?;
+    /// let cluster = Arc::new(cluster);
+    /// cluster.up().await;
+    ///
+    /// let presences = ["twilight", "with shards"]
+    ///     .into_iter()
+    ///     .map(|name| {
+    ///         let activity = Activity::from(MinimalActivity {
+    ///             kind: ActivityType::Playing,
+    ///             name: name.to_owned(),
+    ///             url: None,
+    ///         });
+    ///
+    ///         UpdatePresencePayload::new(vec![activity], false, None, Status::Online)
+    ///     })
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// let schedule = PresenceSchedule::new(Duration::from_secs(60), presences)?;
+    ///
+    /// tokio::spawn(async move { cluster.rotate_presence(&schedule).await });
+    /// # Ok(()) }
+    /// ```
+    pub async fn rotate_presence(&self, schedule: &PresenceSchedule) {
+        let mut interval = time::interval(schedule.interval());
+
+        for presence in schedule.presences().iter().cycle() {
+            interval.tick().await;
+
+            if let Err(source) = self.set_presence(presence).await {
+                tracing::debug!("failed to rotate presence: {source}");
+            }
+        }
+    }
+
+    /// ID of the shard receiving events of a guild according to the current
+    /// shard scheme, along with the shard if it is managed by the cluster.
+    fn guild_shard(&self, guild_id: Id<GuildMarker>) -> (u64, Option<Arc<Shard>>) {
+        let shards = self.read_shards();
+        let id = shards.scheme.shard_for_guild(guild_id);
+
+        (id, shards.shards.get(&id).cloned())
+    }
+
+    /// Acquire a read lock on the shards.
+    fn read_shards(&self) -> RwLockReadGuard<'_, ManagedShards> {
+        self.shards.read().expect("shards poisoned")
+    }
+
+    /// Acquire a write lock on the shards.
+    fn write_shards(&self) -> RwLockWriteGuard<'_, ManagedShards> {
+        self.shards.write().expect("shards poisoned")
+    }
+}
+
+/// Shards managed by a cluster, along with the scheme they are managed by.
+#[derive(Debug)]
+struct ManagedShards {
+    /// Scheme the shards are managed by, replaced when resharding.
+    scheme: ShardScheme,
+    /// Shards by their ID.
+    shards: HashMap<u64, Arc<Shard>>,
+}
+
+/// Create a shard along with its event stream.
+fn new_shard<S: ShardStream>(id: u64, config: ShardConfig) -> (Shard, ShardStreams) {
+    let (shard, stream) = S::new_shard(config);
+
+    (
+        shard,
+        S::into_streams(Vec::from([ShardEventsWithId::new(id, stream)])),
+    )
 }
 
 /// Iterator over a [`Cluster`]'s managed [shards][`Shard`].
 ///
 /// This is returned by [`Cluster::shards`].
-pub struct Shards<'a> {
-    iter: Values<'a, u64, Shard>,
+pub struct Shards {
+    iter: IntoIter<Arc<Shard>>,
 }
 
-impl ExactSizeIterator for Shards<'_> {
+impl ExactSizeIterator for Shards {
     fn len(&self) -> usize {
         self.iter.len()
     }
 }
 
-impl FusedIterator for Shards<'_> {}
+impl FusedIterator for Shards {}
 
-impl<'a> Iterator for Shards<'a> {
-    type Item = &'a Shard;
+impl Iterator for Shards {
+    type Item = Arc<Shard>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.iter.next()
@@ -601,17 +1399,26 @@ impl<'a> Iterator for Shards<'a> {
 mod tests {
     use super::{
         Cluster, ClusterCommandError, ClusterCommandErrorType, ClusterSendError,
-        ClusterSendErrorType, ClusterStartError, ClusterStartErrorType,
+        ClusterSendErrorType, ClusterShardError, ClusterShardErrorType, ClusterStartError,
+        ClusterStartErrorType,
     };
     use static_assertions::{assert_fields, assert_impl_all};
     use std::{error::Error, fmt::Debug};
 
     assert_impl_all!(ClusterCommandErrorType: Debug, Send, Sync);
+    assert_fields!(ClusterCommandErrorType::GuildNotHosted: guild_id, id);
     assert_fields!(ClusterCommandErrorType::ShardNonexistent: id);
     assert_impl_all!(ClusterCommandError: Error, Send, Sync);
     assert_impl_all!(ClusterSendErrorType: Debug, Send, Sync);
     assert_fields!(ClusterSendErrorType::ShardNonexistent: id);
     assert_impl_all!(ClusterSendError: Error, Send, Sync);
+    assert_impl_all!(ClusterShardErrorType: Debug, Send, Sync);
+    assert_fields!(ClusterShardErrorType::GuildNotHosted: guild_id, id);
+    assert_fields!(ClusterShardErrorType::IdTooLarge: id, total);
+    assert_fields!(ClusterShardErrorType::ShardExists: id);
+    assert_fields!(ClusterShardErrorType::ShardNonexistent: id);
+    assert_fields!(ClusterShardErrorType::Starting: id);
+    assert_impl_all!(ClusterShardError: Error, Send, Sync);
     assert_impl_all!(ClusterStartErrorType: Debug, Send, Sync);
     assert_impl_all!(ClusterStartError: Error, Send, Sync);
     assert_impl_all!(Cluster: Debug, Send, Sync);
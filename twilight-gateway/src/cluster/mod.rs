@@ -91,14 +91,18 @@ mod builder;
 mod config;
 mod event;
 mod r#impl;
+mod presence;
+mod reshard;
 
 pub use self::{
     builder::ClusterBuilder,
     config::Config,
-    event::Events,
+    event::{Events, LazyEvents},
+    presence::{PresenceSchedule, PresenceScheduleError, PresenceScheduleErrorType},
     r#impl::{
-        Cluster, ClusterCommandError, ClusterCommandErrorType, ClusterStartError,
-        ClusterStartErrorType, Shards,
+        Cluster, ClusterCommandError, ClusterCommandErrorType, ClusterShardError,
+        ClusterShardErrorType, ClusterStartError, ClusterStartErrorType, Shards,
     },
+    reshard::{ClusterReshardError, ClusterReshardErrorType, Reshard},
     scheme::{ShardScheme, ShardSchemeRangeError, ShardSchemeRangeErrorType},
 };
@@ -0,0 +1,180 @@
+//! Schedules of presences rotated through by a cluster.
+
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    time::Duration,
+};
+use twilight_model::gateway::payload::outgoing::update_presence::UpdatePresencePayload;
+
+/// Creating a presence schedule failed.
+#[derive(Debug)]
+pub struct PresenceScheduleError {
+    kind: PresenceScheduleErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl PresenceScheduleError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &PresenceScheduleErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(
+        self,
+    ) -> (
+        PresenceScheduleErrorType,
+        Option<Box<dyn Error + Send + Sync>>,
+    ) {
+        (self.kind, self.source)
+    }
+}
+
+impl Display for PresenceScheduleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            PresenceScheduleErrorType::Empty => f.write_str("schedule has no presences"),
+            PresenceScheduleErrorType::IntervalTooShort { interval } => {
+                f.write_str("interval of ")?;
+                Display::fmt(&interval.as_secs_f64(), f)?;
+                f.write_str("s is shorter than the minimum of ")?;
+                Display::fmt(&PresenceSchedule::MIN_INTERVAL.as_secs(), f)?;
+
+                f.write_str("s")
+            }
+        }
+    }
+}
+
+impl Error for PresenceScheduleError {}
+
+/// Type of [`PresenceScheduleError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PresenceScheduleErrorType {
+    /// Schedule has no presences.
+    Empty,
+    /// Interval is shorter than [`PresenceSchedule::MIN_INTERVAL`].
+    IntervalTooShort {
+        /// Provided interval.
+        interval: Duration,
+    },
+}
+
+/// Presences to rotate through at an interval.
+///
+/// Refer to [`Cluster::rotate_presence`] for rotating through the schedule.
+///
+/// [`Cluster::rotate_presence`]: super::Cluster::rotate_presence
+#[derive(Clone, Debug)]
+pub struct PresenceSchedule {
+    /// Interval between presences.
+    interval: Duration,
+    /// Presences in the order they are set.
+    presences: Vec<UpdatePresencePayload>,
+}
+
+impl PresenceSchedule {
+    /// Minimum interval between presences.
+    ///
+    /// Presence updates share the gateway ratelimit with other commands, so
+    /// rotating too quickly takes up room needed for other commands.
+    pub const MIN_INTERVAL: Duration = Duration::from_secs(15);
+
+    /// Create a new schedule rotating through presences at an interval.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of type [`Empty`] if no presences were provided.
+    ///
+    /// Returns an error of type [`IntervalTooShort`] if the interval is
+    /// shorter than [`MIN_INTERVAL`].
+    ///
+    /// [`Empty`]: PresenceScheduleErrorType::Empty
+    /// [`IntervalTooShort`]: PresenceScheduleErrorType::IntervalTooShort
+    /// [`MIN_INTERVAL`]: Self::MIN_INTERVAL
+    pub fn new(
+        interval: Duration,
+        presences: Vec<UpdatePresencePayload>,
+    ) -> Result<Self, PresenceScheduleError> {
+        if presences.is_empty() {
+            return Err(PresenceScheduleError {
+                kind: PresenceScheduleErrorType::Empty,
+                source: None,
+            });
+        }
+
+        if interval < Self::MIN_INTERVAL {
+            return Err(PresenceScheduleError {
+                kind: PresenceScheduleErrorType::IntervalTooShort { interval },
+                source: None,
+            });
+        }
+
+        Ok(Self {
+            interval,
+            presences,
+        })
+    }
+
+    /// Interval between presences.
+    pub const fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Immutable reference to the presences in the order they are set.
+    pub fn presences(&self) -> &[UpdatePresencePayload] {
+        &self.presences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PresenceSchedule, PresenceScheduleError, PresenceScheduleErrorType};
+    use static_assertions::assert_impl_all;
+    use std::{error::Error, fmt::Debug, time::Duration};
+    use twilight_model::gateway::{
+        payload::outgoing::update_presence::UpdatePresencePayload,
+        presence::{Activity, ActivityType, MinimalActivity, Status},
+    };
+
+    assert_impl_all!(PresenceSchedule: Clone, Debug, Send, Sync);
+    assert_impl_all!(PresenceScheduleError: Error, Send, Sync);
+
+    #[test]
+    fn new() {
+        let activity = Activity::from(MinimalActivity {
+            kind: ActivityType::Playing,
+            name: "twilight".to_owned(),
+            url: None,
+        });
+        let presence =
+            UpdatePresencePayload::new(vec![activity], false, None, Status::Online).unwrap();
+
+        assert!(matches!(
+            PresenceSchedule::new(Duration::from_secs(60), Vec::new())
+                .unwrap_err()
+                .kind(),
+            PresenceScheduleErrorType::Empty
+        ));
+        assert!(matches!(
+            PresenceSchedule::new(Duration::from_secs(1), vec![presence.clone()])
+                .unwrap_err()
+                .kind(),
+            PresenceScheduleErrorType::IntervalTooShort { interval } if interval.as_secs() == 1
+        ));
+
+        let schedule = PresenceSchedule::new(Duration::from_secs(60), vec![presence]).unwrap();
+        assert_eq!(Duration::from_secs(60), schedule.interval());
+        assert_eq!(1, schedule.presences().len());
+    }
+}
@@ -0,0 +1,412 @@
+use super::{
+    event::{ShardEventsWithId, ShardStream, ShardStreams},
+    Config, ShardScheme,
+};
+use crate::{
+    shard::{Config as ShardConfig, RawEvent, Shard},
+    EventTypeFlags,
+};
+use futures_util::future;
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    future::Future,
+    task::Poll,
+};
+use tokio::time::{self, Instant};
+use twilight_model::{
+    gateway::event::Event,
+    id::{marker::GuildMarker, Id},
+};
+
+/// Resharding a cluster failed.
+#[derive(Debug)]
+pub struct ClusterReshardError {
+    kind: ClusterReshardErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl ClusterReshardError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &ClusterReshardErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(
+        self,
+    ) -> (
+        ClusterReshardErrorType,
+        Option<Box<dyn Error + Send + Sync>>,
+    ) {
+        (self.kind, self.source)
+    }
+}
+
+impl Display for ClusterReshardError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            ClusterReshardErrorType::ReadyDisabled => {
+                f.write_str("the READY event type is required to reshard but is not enabled")
+            }
+            ClusterReshardErrorType::ShardClosed { id } => {
+                f.write_str("shard ")?;
+                Display::fmt(id, f)?;
+
+                f.write_str(" of the new generation closed before it was ready")
+            }
+            ClusterReshardErrorType::Starting { id } => {
+                f.write_str("starting shard ")?;
+                Display::fmt(id, f)?;
+
+                f.write_str(" of the new generation failed")
+            }
+            ClusterReshardErrorType::TimedOut => {
+                f.write_str("the new generation was not ready before the timeout")
+            }
+        }
+    }
+}
+
+impl Error for ClusterReshardError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// Type of [`ClusterReshardError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ClusterReshardErrorType {
+    /// The [`EventTypeFlags::READY`] event type is not enabled, so whether
+    /// the new shards are ready can't be determined.
+    ReadyDisabled,
+    /// A shard's event stream ended before the shard was ready.
+    ShardClosed {
+        /// ID of the shard.
+        id: u64,
+    },
+    /// Starting a shard failed.
+    Starting {
+        /// ID of the shard.
+        id: u64,
+    },
+    /// The new shards were not ready within the configured
+    /// [`reshard_timeout`].
+    ///
+    /// [`reshard_timeout`]: super::Config::reshard_timeout
+    TimedOut,
+}
+
+/// New generation of a cluster's shards that is ready to replace the current
+/// generation.
+///
+/// Created by [`Cluster::reshard`] and applied via
+/// [`Cluster::apply_reshard`]. The shards of the new generation are shut down
+/// if this is dropped without being applied.
+///
+/// [`Cluster::apply_reshard`]: super::Cluster::apply_reshard
+/// [`Cluster::reshard`]: super::Cluster::reshard
+#[derive(Debug)]
+#[must_use = "the new shards are shut down if not applied"]
+pub struct Reshard {
+    pub(super) streams: Option<ShardStreams>,
+    pub(super) shard_scheme: ShardScheme,
+    pub(super) shards: HashMap<u64, Shard>,
+}
+
+impl Reshard {
+    /// Immutable reference to the shard scheme of the new generation.
+    pub const fn shard_scheme(&self) -> &ShardScheme {
+        &self.shard_scheme
+    }
+}
+
+impl Drop for Reshard {
+    fn drop(&mut self) {
+        for shard in self.shards.values() {
+            shard.shutdown();
+        }
+    }
+}
+
+/// Progress towards a shard being ready signalled by an event.
+#[derive(Debug)]
+pub enum Readiness {
+    /// Shard's session is ready, and the guilds will be loaded.
+    Ready {
+        /// IDs of the guilds that will be loaded.
+        guilds: Vec<Id<GuildMarker>>,
+    },
+    /// Guild has been loaded, or is unavailable.
+    GuildLoaded(Id<GuildMarker>),
+}
+
+impl Readiness {
+    /// Readiness signalled by an event.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::Ready(ready) => Some(Self::Ready {
+                guilds: ready.guilds.iter().map(|guild| guild.id).collect(),
+            }),
+            Event::GuildCreate(guild) => Some(Self::GuildLoaded(guild.id)),
+            Event::GuildDelete(guild) => Some(Self::GuildLoaded(guild.id)),
+            Event::UnavailableGuild(guild) => Some(Self::GuildLoaded(guild.id)),
+            _ => None,
+        }
+    }
+
+    /// Readiness signalled by an event that has not been deserialized.
+    ///
+    /// Only the ID of guild events is deserialized.
+    pub fn from_raw(raw: &RawEvent) -> Option<Self> {
+        #[derive(Deserialize)]
+        struct Guild {
+            id: Id<GuildMarker>,
+        }
+
+        match raw.event_type() {
+            Some("GUILD_CREATE" | "GUILD_DELETE") => raw
+                .deserialize::<Guild>()
+                .ok()
+                .map(|guild| Self::GuildLoaded(guild.id)),
+            _ => None,
+        }
+    }
+}
+
+/// Start a new generation of shards, waiting until all of them are ready and
+/// have loaded their guilds.
+///
+/// Events received while waiting are buffered in the generation's streams.
+pub(super) async fn prepare<S: ShardStream>(
+    config: &Config,
+    shard_config: &ShardConfig,
+    shard_scheme: ShardScheme,
+) -> Result<Reshard, ClusterReshardError> {
+    let event_types = shard_config.event_types();
+
+    if !event_types.contains(EventTypeFlags::READY) {
+        return Err(ClusterReshardError {
+            kind: ClusterReshardErrorType::ReadyDisabled,
+            source: None,
+        });
+    }
+
+    let total = shard_scheme.total();
+    let mut reshard = Reshard {
+        streams: None,
+        shard_scheme,
+        shards: HashMap::new(),
+    };
+    let mut streams = Vec::new();
+
+    for id in reshard.shard_scheme.iter() {
+        let mut shard_config = config.shard_config(shard_config, [id, total], None);
+        // Stored sessions belong to shards of the previous total.
+        shard_config.session_store_resume = false;
+
+        let (shard, stream) = S::new_shard(shard_config);
+
+        reshard.shards.insert(id, shard);
+        streams.push(ShardEventsWithId::new(id, stream));
+    }
+
+    future::try_join_all(reshard.shards.iter().map(|(id, shard)| async move {
+        shard.start().await.map_err(|source| ClusterReshardError {
+            kind: ClusterReshardErrorType::Starting { id: *id },
+            source: Some(Box::new(source)),
+        })
+    }))
+    .await?;
+
+    // Guilds can only be waited on if their events are emitted, which those
+    // filtered out by the guild filter never are.
+    let wait_for_guilds = event_types.contains(EventTypeFlags::GUILD_CREATE);
+    let guild_filter = shard_config.guild_filter();
+    let awaits_guild =
+        |guild_id| wait_for_guilds && guild_filter.map_or(true, |filter| filter.allows(guild_id));
+    // Shards that are not yet ready, mapped to the guilds they have yet to
+    // load once they are.
+    let mut pending = streams
+        .iter()
+        .map(|stream| (stream.id(), None))
+        .collect::<HashMap<u64, Option<HashSet<Id<GuildMarker>>>>>();
+    // When the pending shards last became ready or loaded a guild.
+    let mut progressed = HashMap::new();
+    let guild_timeout = config.reshard_guild_timeout();
+    let mut guild_deadline = Box::pin(time::sleep(guild_timeout));
+
+    let ready = future::poll_fn(|cx| {
+        for stream in &mut streams {
+            let id = stream.id();
+
+            loop {
+                match stream.poll_buffer(cx) {
+                    Poll::Ready(Some(item)) => {
+                        if let Some(readiness) = S::readiness(item) {
+                            progressed.insert(id, Instant::now());
+                            update_pending(&mut pending, id, readiness, &awaits_guild);
+                        }
+                    }
+                    Poll::Ready(None) if pending.contains_key(&id) => {
+                        return Poll::Ready(Err(ClusterReshardError {
+                            kind: ClusterReshardErrorType::ShardClosed { id },
+                            source: None,
+                        }));
+                    }
+                    Poll::Ready(None) | Poll::Pending => break,
+                }
+            }
+        }
+
+        // Unavailable guilds are never loaded, and Discord doesn't always
+        // say which guilds are unavailable.
+        let now = Instant::now();
+        pending.retain(|id, guilds| match (guilds, progressed.get(id)) {
+            (Some(guilds), Some(progressed)) if *progressed + guild_timeout <= now => {
+                tracing::debug!(
+                    shard_id = id,
+                    remaining = guilds.len(),
+                    "considering guilds that didn't load unavailable",
+                );
+
+                false
+            }
+            _ => true,
+        });
+
+        if pending.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+
+        let next_deadline = pending
+            .iter()
+            .filter(|(_, guilds)| guilds.is_some())
+            .filter_map(|(id, _)| progressed.get(id))
+            .min();
+
+        if let Some(progressed) = next_deadline {
+            guild_deadline.as_mut().reset(*progressed + guild_timeout);
+
+            // The deadline may have passed since the pending shards were
+            // checked.
+            if guild_deadline.as_mut().poll(cx).is_ready() {
+                cx.waker().wake_by_ref();
+            }
+        }
+
+        Poll::Pending
+    });
+
+    time::timeout(config.reshard_timeout(), ready)
+        .await
+        .map_err(|source| ClusterReshardError {
+            kind: ClusterReshardErrorType::TimedOut,
+            source: Some(Box::new(source)),
+        })??;
+
+    reshard.streams = Some(S::into_streams(streams));
+
+    Ok(reshard)
+}
+
+/// Update the pending shards with the readiness of one of them, removing the
+/// shard once it is ready and has loaded the guilds it awaits.
+fn update_pending(
+    pending: &mut HashMap<u64, Option<HashSet<Id<GuildMarker>>>>,
+    id: u64,
+    readiness: Readiness,
+    awaits_guild: &dyn Fn(Id<GuildMarker>) -> bool,
+) {
+    let guilds = match pending.get_mut(&id) {
+        Some(guilds) => guilds,
+        None => return,
+    };
+
+    match (readiness, guilds) {
+        (Readiness::Ready { guilds: ready }, guilds) => {
+            *guilds = Some(
+                ready
+                    .into_iter()
+                    .filter(|guild_id| awaits_guild(*guild_id))
+                    .collect(),
+            );
+        }
+        (Readiness::GuildLoaded(guild_id), Some(guilds)) => {
+            guilds.remove(&guild_id);
+        }
+        (Readiness::GuildLoaded(_), None) => {}
+    }
+
+    if matches!(pending.get(&id), Some(Some(guilds)) if guilds.is_empty()) {
+        pending.remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{update_pending, ClusterReshardError, ClusterReshardErrorType, Readiness, Reshard};
+    use static_assertions::{assert_fields, assert_impl_all};
+    use std::{
+        collections::{HashMap, HashSet},
+        error::Error,
+        fmt::Debug,
+    };
+    use twilight_model::id::Id;
+
+    assert_impl_all!(ClusterReshardErrorType: Debug, Send, Sync);
+    assert_fields!(ClusterReshardErrorType::ShardClosed: id);
+    assert_fields!(ClusterReshardErrorType::Starting: id);
+    assert_impl_all!(ClusterReshardError: Error, Send, Sync);
+    assert_impl_all!(Reshard: Debug, Send, Sync);
+
+    #[test]
+    fn pending_guilds() {
+        let mut pending = HashMap::from([(0, None), (1, None)]);
+
+        update_pending(&mut pending, 0, Readiness::GuildLoaded(Id::new(1)), &|_| {
+            true
+        });
+        assert_eq!(Some(&None), pending.get(&0));
+
+        let guilds = Vec::from([Id::new(1), Id::new(2)]);
+        update_pending(&mut pending, 0, Readiness::Ready { guilds }, &|_| true);
+        update_pending(&mut pending, 0, Readiness::GuildLoaded(Id::new(1)), &|_| {
+            true
+        });
+        assert_eq!(Some(&Some(HashSet::from([Id::new(2)]))), pending.get(&0));
+
+        update_pending(&mut pending, 0, Readiness::GuildLoaded(Id::new(2)), &|_| {
+            true
+        });
+        assert!(!pending.contains_key(&0));
+
+        let guilds = Vec::from([Id::new(3)]);
+        update_pending(&mut pending, 1, Readiness::Ready { guilds }, &|_| false);
+        assert!(pending.is_empty());
+
+        // Guilds filtered out are not waited on.
+        let mut pending = HashMap::from([(0, None)]);
+        let guilds = Vec::from([Id::new(1), Id::new(2)]);
+        update_pending(&mut pending, 0, Readiness::Ready { guilds }, &|guild_id| {
+            guild_id.get() == 1
+        });
+        update_pending(&mut pending, 0, Readiness::GuildLoaded(Id::new(1)), &|_| {
+            true
+        });
+        assert!(pending.is_empty());
+    }
+}
@@ -6,6 +6,7 @@ use std::{
     iter::StepBy,
     ops::{Bound, RangeBounds, RangeInclusive},
 };
+use twilight_model::id::{marker::GuildMarker, Id};
 
 /// Starting a cluster failed.
 #[derive(Debug)]
@@ -233,6 +234,30 @@ impl ShardScheme {
         }
     }
 
+    /// ID of the shard receiving events of a guild.
+    ///
+    /// This is calculated with the formula `(guild_id >> 22) % total`, where
+    /// `total` is the [total number of shards].
+    ///
+    /// The shard is not necessarily managed by a cluster using this scheme.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use twilight_gateway::cluster::ShardScheme;
+    /// use twilight_model::id::Id;
+    ///
+    /// let scheme = ShardScheme::try_from((0..=4, 10))?;
+    /// assert_eq!(6, scheme.shard_for_guild(Id::new(175_928_847_299_117_063)));
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [total number of shards]: Self::total
+    pub const fn shard_for_guild(&self, guild_id: Id<GuildMarker>) -> u64 {
+        (guild_id.get() >> 22) % self.total()
+    }
+
     /// Maximum shard ID across all clusters.
     pub const fn to(&self) -> u64 {
         match *self {
@@ -332,6 +357,7 @@ mod tests {
     use super::{ShardScheme, ShardSchemeIter, ShardSchemeRangeError, ShardSchemeRangeErrorType};
     use static_assertions::{assert_fields, assert_impl_all};
     use std::{error::Error, fmt::Debug, hash::Hash};
+    use twilight_model::id::Id;
 
     assert_impl_all!(ShardSchemeIter: Clone, Debug, Send, Sync);
     assert_fields!(ShardSchemeRangeErrorType::IdTooLarge: end, start, total);
@@ -384,6 +410,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn scheme_shard_for_guild() {
+        let scheme = ShardScheme::Range {
+            from: 0,
+            to: 0,
+            total: 1,
+        };
+        assert_eq!(0, scheme.shard_for_guild(Id::new(175_928_847_299_117_063)));
+
+        let scheme = ShardScheme::Bucket {
+            bucket_id: 0,
+            concurrency: 16,
+            total: 160,
+        };
+        assert_eq!(36, scheme.shard_for_guild(Id::new(175_928_847_299_117_063)));
+    }
+
     #[test]
     fn scheme_total() {
         assert_eq!(
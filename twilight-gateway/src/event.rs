@@ -1,5 +1,5 @@
 use bitflags::bitflags;
-use twilight_model::gateway::event::EventType;
+use twilight_model::gateway::{event::EventType, Intents};
 
 bitflags! {
     /// Bitflags representing all of the possible types of events.
@@ -133,6 +133,8 @@ bitflags! {
         const SHARD_CONNECTING = 1 << 34;
         /// Shard has disconnected from the gateway.
         const SHARD_DISCONNECTED = 1 << 35;
+        /// Shard has stopped after a fatal close code.
+        const SHARD_FATAL = 1 << 76;
         /// Shard is identifying to create a session with the gateway.
         const SHARD_IDENTIFYING = 1 << 36;
         /// Incoming message has been received from the gateway.
@@ -265,8 +267,8 @@ bitflags! {
         /// [`Intents::GUILD_MESSAGES`]: crate::Intents::GUILD_MESSAGES
         const GUILD_MESSAGES = Self::MESSAGE_CREATE.bits()
             | Self::MESSAGE_DELETE.bits()
-            | Self::MESSAGE_DELETE.bits()
-            | Self::MESSAGE_DELETE_BULK.bits();
+            | Self::MESSAGE_DELETE_BULK.bits()
+            | Self::MESSAGE_UPDATE.bits();
 
         /// All [`EventTypeFlags`] in [`Intents::GUILD_MESSAGE_REACTIONS`].
         ///
@@ -308,6 +310,159 @@ bitflags! {
     }
 }
 
+/// Event types received under each intent.
+///
+/// Guild intents come before their direct message counterparts, so that
+/// [`EventTypeFlags::required_intents`] prefers them.
+const INTENT_EVENT_TYPES: [(Intents, EventTypeFlags); 20] = [
+    (Intents::GUILDS, EventTypeFlags::GUILDS),
+    (Intents::GUILDS, EventTypeFlags::UNAVAILABLE_GUILD),
+    (Intents::GUILD_MEMBERS, EventTypeFlags::GUILD_MEMBERS),
+    (Intents::GUILD_MODERATION, EventTypeFlags::GUILD_MODERATION),
+    (
+        Intents::GUILD_EMOJIS_AND_STICKERS,
+        EventTypeFlags::GUILD_EMOJIS_AND_STICKERS,
+    ),
+    (
+        Intents::GUILD_INTEGRATIONS,
+        EventTypeFlags::GUILD_INTEGRATIONS,
+    ),
+    (Intents::GUILD_WEBHOOKS, EventTypeFlags::GUILD_WEBHOOKS),
+    (Intents::GUILD_INVITES, EventTypeFlags::GUILD_INVITES),
+    (
+        Intents::GUILD_VOICE_STATES,
+        EventTypeFlags::GUILD_VOICE_STATES,
+    ),
+    (Intents::GUILD_PRESENCES, EventTypeFlags::GUILD_PRESENCES),
+    (Intents::GUILD_MESSAGES, EventTypeFlags::GUILD_MESSAGES),
+    (
+        Intents::GUILD_MESSAGE_REACTIONS,
+        EventTypeFlags::GUILD_MESSAGE_REACTIONS,
+    ),
+    (
+        Intents::GUILD_MESSAGE_TYPING,
+        EventTypeFlags::GUILD_MESSAGE_TYPING,
+    ),
+    (
+        Intents::GUILD_SCHEDULED_EVENTS,
+        EventTypeFlags::GUILD_SCHEDULED_EVENTS,
+    ),
+    (
+        Intents::AUTO_MODERATION_CONFIGURATION,
+        EventTypeFlags::AUTO_MODERATION_CONFIGURATION,
+    ),
+    (
+        Intents::AUTO_MODERATION_EXECUTION,
+        EventTypeFlags::AUTO_MODERATION_EXECUTION,
+    ),
+    (Intents::DIRECT_MESSAGES, EventTypeFlags::DIRECT_MESSAGES),
+    (
+        Intents::DIRECT_MESSAGES,
+        EventTypeFlags::CHANNEL_PINS_UPDATE,
+    ),
+    (
+        Intents::DIRECT_MESSAGE_REACTIONS,
+        EventTypeFlags::DIRECT_MESSAGE_REACTIONS,
+    ),
+    (
+        Intents::DIRECT_MESSAGE_TYPING,
+        EventTypeFlags::DIRECT_MESSAGE_TYPING,
+    ),
+];
+
+impl EventTypeFlags {
+    /// Event types that can be received under a set of intents.
+    ///
+    /// Event types that don't require an intent, such as [`READY`] and the
+    /// shard's own events, are always included.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use twilight_gateway::{EventTypeFlags, Intents};
+    ///
+    /// let event_types = EventTypeFlags::from_intents(Intents::GUILD_VOICE_STATES);
+    ///
+    /// assert!(event_types.contains(EventTypeFlags::READY | EventTypeFlags::VOICE_STATE_UPDATE));
+    /// assert!(!event_types.contains(EventTypeFlags::MESSAGE_CREATE));
+    /// ```
+    ///
+    /// [`READY`]: Self::READY
+    pub fn from_intents(intents: Intents) -> Self {
+        INTENT_EVENT_TYPES.iter().fold(
+            Self::ungated(),
+            |event_types, (intent, intent_event_types)| {
+                if intents.contains(*intent) {
+                    event_types | *intent_event_types
+                } else {
+                    event_types
+                }
+            },
+        )
+    }
+
+    /// Minimal intents required to receive the event types.
+    ///
+    /// Event types received under either a guild or a direct message intent,
+    /// such as [`MESSAGE_CREATE`], only require the guild intent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use twilight_gateway::{EventTypeFlags, Intents};
+    ///
+    /// let event_types = EventTypeFlags::MESSAGE_CREATE | EventTypeFlags::MEMBER_ADD;
+    ///
+    /// assert_eq!(
+    ///     Intents::GUILD_MEMBERS | Intents::GUILD_MESSAGES,
+    ///     event_types.required_intents()
+    /// );
+    /// ```
+    ///
+    /// [`MESSAGE_CREATE`]: Self::MESSAGE_CREATE
+    pub fn required_intents(self) -> Intents {
+        let mut remaining = self - Self::ungated();
+        let mut intents = Intents::empty();
+
+        for (intent, intent_event_types) in INTENT_EVENT_TYPES {
+            if remaining.intersects(intent_event_types) {
+                intents |= intent;
+                remaining -= intent_event_types;
+            }
+        }
+
+        intents
+    }
+
+    /// Event types that can never be received under a set of intents.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use twilight_gateway::{EventTypeFlags, Intents};
+    ///
+    /// let event_types = EventTypeFlags::READY | EventTypeFlags::MESSAGE_CREATE;
+    ///
+    /// assert_eq!(
+    ///     EventTypeFlags::MESSAGE_CREATE,
+    ///     event_types.unreceivable(Intents::GUILDS)
+    /// );
+    /// ```
+    #[must_use]
+    pub fn unreceivable(self, intents: Intents) -> Self {
+        self - Self::from_intents(intents)
+    }
+
+    /// Event types that don't require an intent.
+    fn ungated() -> Self {
+        INTENT_EVENT_TYPES
+            .iter()
+            .fold(Self::all(), |event_types, (_, intent_event_types)| {
+                event_types - *intent_event_types
+            })
+    }
+}
+
 impl From<EventType> for EventTypeFlags {
     fn from(event_type: EventType) -> Self {
         match event_type {
@@ -368,6 +523,7 @@ impl From<EventType> for EventTypeFlags {
             EventType::ShardConnected => Self::SHARD_CONNECTED,
             EventType::ShardConnecting => Self::SHARD_CONNECTING,
             EventType::ShardDisconnected => Self::SHARD_DISCONNECTED,
+            EventType::ShardFatal => Self::SHARD_FATAL,
             EventType::ShardIdentifying => Self::SHARD_IDENTIFYING,
             EventType::ShardReconnecting => Self::SHARD_RECONNECTING,
             EventType::ShardPayload => Self::SHARD_PAYLOAD,
@@ -422,9 +578,10 @@ impl Default for EventTypeFlags {
 
 #[cfg(test)]
 mod tests {
-    use super::{EventType, EventTypeFlags};
+    use super::{EventType, EventTypeFlags, INTENT_EVENT_TYPES};
     use static_assertions::assert_impl_all;
     use std::{fmt::Debug, hash::Hash};
+    use twilight_model::gateway::Intents;
 
     assert_impl_all!(
         EventTypeFlags: Copy,
@@ -438,4 +595,57 @@ mod tests {
         Sync,
         TryFrom<(u8, Option<&'static str>)>
     );
+    #[test]
+    fn from_intents() {
+        let event_types = EventTypeFlags::from_intents(Intents::empty());
+        assert!(event_types.contains(
+            EventTypeFlags::READY | EventTypeFlags::INTERACTION_CREATE | EventTypeFlags::RESUMED
+        ));
+        assert!(!event_types.intersects(EventTypeFlags::GUILDS | EventTypeFlags::MESSAGE_CREATE));
+
+        assert_eq!(
+            EventTypeFlags::all(),
+            EventTypeFlags::from_intents(Intents::all())
+        );
+    }
+
+    #[test]
+    fn required_intents() {
+        assert_eq!(
+            Intents::empty(),
+            (EventTypeFlags::READY | EventTypeFlags::INTERACTION_CREATE).required_intents()
+        );
+        assert_eq!(
+            Intents::GUILDS,
+            (EventTypeFlags::CHANNEL_PINS_UPDATE | EventTypeFlags::UNAVAILABLE_GUILD)
+                .required_intents()
+        );
+        assert_eq!(
+            Intents::GUILD_MESSAGES | Intents::GUILD_MESSAGE_TYPING,
+            (EventTypeFlags::MESSAGE_UPDATE | EventTypeFlags::TYPING_START).required_intents()
+        );
+
+        for (intent, event_types) in INTENT_EVENT_TYPES {
+            let required = event_types.required_intents();
+
+            assert!(
+                EventTypeFlags::from_intents(required).contains(event_types),
+                "{intent:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn unreceivable() {
+        let event_types =
+            EventTypeFlags::READY | EventTypeFlags::MEMBER_ADD | EventTypeFlags::MESSAGE_CREATE;
+
+        assert_eq!(
+            EventTypeFlags::MEMBER_ADD,
+            event_types.unreceivable(Intents::DIRECT_MESSAGES)
+        );
+        assert!(event_types
+            .unreceivable(event_types.required_intents())
+            .is_empty());
+    }
 }
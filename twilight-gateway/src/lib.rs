@@ -20,12 +20,16 @@
 )]
 
 pub mod cluster;
+#[cfg(feature = "test-support")]
+pub mod mock;
 pub mod shard;
 
 mod event;
 
 pub use self::event::EventTypeFlags;
-pub use twilight_model::gateway::Intents;
+// Close codes are carried by `Event::ShardFatal`, which lives in
+// `twilight-model`, so the model's enum is re-exported rather than duplicated.
+pub use twilight_model::gateway::{CloseCode, Intents};
 
 #[doc(no_inline)]
 pub use self::{
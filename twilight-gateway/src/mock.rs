@@ -0,0 +1,837 @@
+//! In-process mock of Discord's gateway for testing shards and clusters.
+//!
+//! [`MockGateway`] is a local websocket server speaking the gateway protocol.
+//! Each connection made by a shard is handed to the test as a
+//! [`MockConnection`], which is then scripted to send hellos, ready and
+//! resumed events, heartbeat acknowledgements, invalid sessions, reconnect
+//! requests, and dispatch events, or to close the connection with a close
+//! code. Payloads are encoded in ETF and compressed, either individually or as
+//! a zlib stream, if the shard requests it.
+//!
+//! Point a shard at the mock via [`ShardBuilder::gateway_url`].
+//!
+//! Requires the `test-support` feature.
+//!
+//! # Examples
+//!
+//! Identify a shard with the mock gateway and wait for it to be ready:
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use twilight_gateway::{mock::MockGateway, Event, Intents, Shard};
+//! use twilight_model::gateway::OpCode;
+//!
+//! # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut gateway = MockGateway::bind().await?;
+//!
+//! let (shard, mut events) = Shard::builder("token".to_owned(), Intents::empty())
+//!     .gateway_url(gateway.url().to_owned())
+//!     .build();
+//! shard.start().await?;
+//!
+//! let mut connection = gateway.next_connection().await?;
+//! let payload = connection.handshake().await?;
+//! assert_eq!(OpCode::Identify, payload.op());
+//!
+//! while let Some(event) = events.next().await {
+//!     if matches!(event, Event::Ready(_)) {
+//!         break;
+//!     }
+//! }
+//! # Ok(()) }
+//! ```
+//!
+//! [`ShardBuilder::gateway_url`]: crate::shard::ShardBuilder::gateway_url
+
+use crate::{
+    shard::{etf, Encoding},
+    API_VERSION,
+};
+use futures_util::{sink::SinkExt, stream::StreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    net::Ipv4Addr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, UnboundedReceiver},
+    task::JoinHandle,
+};
+use tokio_tungstenite::{
+    tungstenite::{
+        handshake::server::{ErrorResponse, Request, Response},
+        protocol::{frame::coding::CloseCode, CloseFrame},
+        Message,
+    },
+    WebSocketStream,
+};
+use twilight_model::{
+    gateway::{
+        payload::{incoming::Ready, outgoing::identify::IdentifyInfo},
+        OpCode,
+    },
+    id::Id,
+    oauth::{ApplicationFlags, PartialApplication},
+    user::CurrentUser,
+};
+
+#[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+use flate2::{Compress, Compression, FlushCompress};
+
+/// Operating the mock gateway failed.
+#[derive(Debug)]
+pub struct MockGatewayError {
+    kind: MockGatewayErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl MockGatewayError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &MockGatewayErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(self) -> (MockGatewayErrorType, Option<Box<dyn Error + Send + Sync>>) {
+        (self.kind, self.source)
+    }
+}
+
+impl Display for MockGatewayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            MockGatewayErrorType::Binding => f.write_str("failed to bind the mock gateway"),
+            MockGatewayErrorType::Closed => f.write_str("connection has been closed"),
+            MockGatewayErrorType::Compressing => f.write_str("failed to compress payload"),
+            MockGatewayErrorType::Deserializing => f.write_str("failed to deserialize payload"),
+            MockGatewayErrorType::Receiving => f.write_str("failed to receive message"),
+            MockGatewayErrorType::Sending => f.write_str("failed to send message"),
+            MockGatewayErrorType::Serializing => f.write_str("failed to serialize payload"),
+            MockGatewayErrorType::UnexpectedPayload { op } => {
+                f.write_str("received unexpected payload with opcode ")?;
+
+                Debug::fmt(op, f)
+            }
+        }
+    }
+}
+
+impl Error for MockGatewayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// Type of [`MockGatewayError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MockGatewayErrorType {
+    /// Binding the server to a local port failed.
+    Binding,
+    /// Connection or server has been closed.
+    Closed,
+    /// Compressing an outgoing payload failed.
+    Compressing,
+    /// Deserializing an incoming payload failed.
+    Deserializing,
+    /// Receiving a message from the shard failed.
+    Receiving,
+    /// Sending a message to the shard failed.
+    Sending,
+    /// Serializing an outgoing payload failed.
+    Serializing,
+    /// Shard sent a payload that wasn't expected.
+    UnexpectedPayload {
+        /// Opcode of the payload.
+        op: OpCode,
+    },
+}
+
+/// Local websocket server speaking the gateway protocol.
+///
+/// The server is stopped when dropped.
+#[derive(Debug)]
+pub struct MockGateway {
+    connections: UnboundedReceiver<MockConnection>,
+    handle: JoinHandle<()>,
+    url: String,
+}
+
+impl MockGateway {
+    /// Bind a new mock gateway to a free local port.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockGatewayErrorType::Binding`] error type if binding to a
+    /// local port failed.
+    pub async fn bind() -> Result<Self, MockGatewayError> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .map_err(|source| MockGatewayError {
+                kind: MockGatewayErrorType::Binding,
+                source: Some(Box::new(source)),
+            })?;
+        let address = listener.local_addr().map_err(|source| MockGatewayError {
+            kind: MockGatewayErrorType::Binding,
+            source: Some(Box::new(source)),
+        })?;
+        let url = format!("ws://{address}");
+
+        let (tx, connections) = mpsc::unbounded_channel();
+        let sequence = Arc::new(AtomicU64::new(0));
+        let resume_url = url.clone();
+
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut query = String::new();
+                #[allow(clippy::result_large_err)]
+                let callback = |request: &Request, response: Response| {
+                    query.push_str(request.uri().query().unwrap_or_default());
+
+                    Ok::<_, ErrorResponse>(response)
+                };
+
+                let stream = match tokio_tungstenite::accept_hdr_async(stream, callback).await {
+                    Ok(stream) => stream,
+                    Err(source) => {
+                        tracing::warn!("mock gateway handshake failed: {source}");
+
+                        continue;
+                    }
+                };
+
+                let connection =
+                    MockConnection::new(stream, query, resume_url.clone(), Arc::clone(&sequence));
+
+                if tx.send(connection).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            connections,
+            handle,
+            url,
+        })
+    }
+
+    /// URL of the mock gateway, to provide to [`ShardBuilder::gateway_url`].
+    ///
+    /// [`ShardBuilder::gateway_url`]: crate::shard::ShardBuilder::gateway_url
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Wait for the next connection made to the mock gateway.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockGatewayErrorType::Closed`] error type if the server has
+    /// stopped accepting connections.
+    pub async fn next_connection(&mut self) -> Result<MockConnection, MockGatewayError> {
+        self.connections.recv().await.ok_or(MockGatewayError {
+            kind: MockGatewayErrorType::Closed,
+            source: None,
+        })
+    }
+}
+
+impl Drop for MockGateway {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Payload sent by a shard to the [`MockGateway`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MockPayload {
+    bytes: Vec<u8>,
+    encoding: Encoding,
+    op: OpCode,
+}
+
+impl MockPayload {
+    /// Immutable reference to the bytes of the payload, in the
+    /// [encoding] requested by the shard.
+    ///
+    /// [encoding]: MockConnection::encoding
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Opcode of the payload.
+    pub const fn op(&self) -> OpCode {
+        self.op
+    }
+
+    /// Deserialize the data of the payload, its `d` field.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockGatewayErrorType::Deserializing`] error type if the
+    /// data could not be deserialized into the type.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, MockGatewayError> {
+        #[derive(Deserialize)]
+        struct Data<T> {
+            d: T,
+        }
+
+        from_slice::<Data<T>>(self.encoding, &self.bytes).map(|data| data.d)
+    }
+}
+
+/// Payload sent by the mock gateway to a shard.
+#[derive(Serialize)]
+struct OutgoingPayload<'a, T> {
+    d: T,
+    op: OpCode,
+    s: Option<u64>,
+    t: Option<&'a str>,
+}
+
+/// Connection made by a shard to the [`MockGateway`].
+///
+/// Heartbeats sent by the shard are acknowledged while [receiving] payloads.
+/// Refer to [`ack_heartbeats`] to disable this.
+///
+/// [`ack_heartbeats`]: Self::ack_heartbeats
+/// [receiving]: Self::receive
+pub struct MockConnection {
+    ack_heartbeats: bool,
+    #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+    compress: Option<Compress>,
+    /// Whether payloads are individually compressed, as requested by the
+    /// shard when identifying.
+    #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+    compress_payloads: bool,
+    encoding: Encoding,
+    query: String,
+    resume_url: String,
+    sequence: Arc<AtomicU64>,
+    stream: WebSocketStream<TcpStream>,
+}
+
+impl MockConnection {
+    /// Default heartbeat interval sent by [`handshake`], in milliseconds.
+    ///
+    /// [`handshake`]: Self::handshake
+    pub const HEARTBEAT_INTERVAL: u64 = 41_250;
+
+    /// Session ID sent in READY payloads by [`handshake`].
+    ///
+    /// [`handshake`]: Self::handshake
+    pub const SESSION_ID: &'static str = "mock-session";
+
+    #[cfg_attr(
+        not(any(feature = "zlib-stock", feature = "zlib-simd")),
+        allow(clippy::missing_const_for_fn)
+    )]
+    fn new(
+        stream: WebSocketStream<TcpStream>,
+        query: String,
+        resume_url: String,
+        sequence: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            ack_heartbeats: true,
+            #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+            compress: query
+                .contains("compress=zlib-stream")
+                .then(|| Compress::new(Compression::default(), true)),
+            #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+            compress_payloads: false,
+            encoding: if query.contains("encoding=etf") {
+                Encoding::Etf
+            } else {
+                Encoding::Json
+            },
+            query,
+            resume_url,
+            sequence,
+            stream,
+        }
+    }
+
+    /// Set whether heartbeats are acknowledged while receiving payloads.
+    ///
+    /// Disabling this allows testing how shards handle zombied connections.
+    ///
+    /// Defaults to enabled.
+    pub fn ack_heartbeats(&mut self, ack_heartbeats: bool) {
+        self.ack_heartbeats = ack_heartbeats;
+    }
+
+    /// Whether payloads sent to the shard are compressed, either individually
+    /// or with zlib-stream.
+    pub const fn is_compressed(&self) -> bool {
+        #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+        {
+            self.compress.is_some() || self.compress_payloads
+        }
+
+        #[cfg(not(any(feature = "zlib-stock", feature = "zlib-simd")))]
+        false
+    }
+
+    /// Encoding of payloads requested by the shard.
+    ///
+    /// Payloads [received] from the shard are deserialized from this
+    /// encoding.
+    ///
+    /// [received]: Self::receive
+    pub const fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Query string of the URL the shard connected with.
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Perform the opening handshake of a session.
+    ///
+    /// Sends HELLO and waits for the shard to identify or resume, then replies
+    /// with READY or RESUMED, returning the payload sent by the shard.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockGatewayErrorType::UnexpectedPayload`] error type if the
+    /// shard sent a payload other than an IDENTIFY or RESUME.
+    ///
+    /// Returns the errors of [`hello`], [`receive`], [`ready`], and
+    /// [`resumed`].
+    ///
+    /// [`hello`]: Self::hello
+    /// [`ready`]: Self::ready
+    /// [`receive`]: Self::receive
+    /// [`resumed`]: Self::resumed
+    pub async fn handshake(&mut self) -> Result<MockPayload, MockGatewayError> {
+        self.hello(Self::HEARTBEAT_INTERVAL).await?;
+
+        let payload = self.receive().await?;
+
+        match payload.op() {
+            OpCode::Identify => {
+                let identify = payload.deserialize::<IdentifyInfo>()?;
+
+                #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+                {
+                    self.compress_payloads = identify.compress;
+                }
+
+                self.ready(Self::SESSION_ID, identify.shard).await?;
+            }
+            OpCode::Resume => self.resumed().await?,
+            op => {
+                return Err(MockGatewayError {
+                    kind: MockGatewayErrorType::UnexpectedPayload { op },
+                    source: None,
+                })
+            }
+        }
+
+        Ok(payload)
+    }
+
+    /// Receive the next payload sent by the shard.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockGatewayErrorType::Closed`] error type if the shard
+    /// closed the connection.
+    ///
+    /// Returns a [`MockGatewayErrorType::Deserializing`] error type if the
+    /// payload isn't valid.
+    ///
+    /// Returns a [`MockGatewayErrorType::Receiving`] error type if receiving
+    /// a message failed.
+    pub async fn receive(&mut self) -> Result<MockPayload, MockGatewayError> {
+        #[derive(Deserialize)]
+        struct Op {
+            op: OpCode,
+        }
+
+        loop {
+            let bytes = match self.stream.next().await {
+                Some(Ok(Message::Binary(bytes))) => bytes,
+                Some(Ok(Message::Text(text))) => text.into_bytes(),
+                Some(Ok(Message::Close(_))) | None => {
+                    return Err(MockGatewayError {
+                        kind: MockGatewayErrorType::Closed,
+                        source: None,
+                    })
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(source)) => {
+                    return Err(MockGatewayError {
+                        kind: MockGatewayErrorType::Receiving,
+                        source: Some(Box::new(source)),
+                    })
+                }
+            };
+
+            let Op { op } = from_slice(self.encoding, &bytes)?;
+
+            if op == OpCode::Heartbeat && self.ack_heartbeats {
+                self.heartbeat_ack().await?;
+
+                continue;
+            }
+
+            return Ok(MockPayload {
+                bytes,
+                encoding: self.encoding,
+                op,
+            });
+        }
+    }
+
+    /// Close the connection with a close code and reason.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockGatewayErrorType::Sending`] error type if sending the
+    /// close frame failed.
+    pub async fn close(&mut self, code: u16, reason: &str) -> Result<(), MockGatewayError> {
+        let frame = CloseFrame {
+            code: CloseCode::from(code),
+            reason: reason.to_owned().into(),
+        };
+
+        self.stream
+            .close(Some(frame))
+            .await
+            .map_err(|source| MockGatewayError {
+                kind: MockGatewayErrorType::Sending,
+                source: Some(Box::new(source)),
+            })
+    }
+
+    /// Send a dispatch event with a sequence number.
+    ///
+    /// Sequence numbers are shared between all connections to the mock
+    /// gateway, so that they continue across resumes.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`send`].
+    ///
+    /// [`send`]: Self::send
+    pub async fn dispatch(
+        &mut self,
+        event_type: &str,
+        data: impl Serialize,
+    ) -> Result<(), MockGatewayError> {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed) + 1;
+
+        self.send(OpCode::Event, Some(sequence), Some(event_type), data)
+            .await
+    }
+
+    /// Send a heartbeat acknowledgement.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`send`].
+    ///
+    /// [`send`]: Self::send
+    pub async fn heartbeat_ack(&mut self) -> Result<(), MockGatewayError> {
+        self.send(OpCode::HeartbeatAck, None, None, ()).await
+    }
+
+    /// Send HELLO with a heartbeat interval in milliseconds.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`send`].
+    ///
+    /// [`send`]: Self::send
+    pub async fn hello(&mut self, heartbeat_interval: u64) -> Result<(), MockGatewayError> {
+        #[derive(Serialize)]
+        struct Hello {
+            heartbeat_interval: u64,
+        }
+
+        self.send(OpCode::Hello, None, None, Hello { heartbeat_interval })
+            .await
+    }
+
+    /// Send an invalid session payload, optionally allowing the session to be
+    /// resumed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`send`].
+    ///
+    /// [`send`]: Self::send
+    pub async fn invalid_session(&mut self, resumable: bool) -> Result<(), MockGatewayError> {
+        self.send(OpCode::InvalidSession, None, None, resumable)
+            .await
+    }
+
+    /// Send a READY dispatch event for a session.
+    ///
+    /// The resume URL of the session is the mock gateway's URL.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`send`].
+    ///
+    /// [`send`]: Self::send
+    pub async fn ready(
+        &mut self,
+        session_id: &str,
+        shard: Option<[u64; 2]>,
+    ) -> Result<(), MockGatewayError> {
+        let ready = Ready {
+            application: PartialApplication {
+                flags: ApplicationFlags::empty(),
+                id: Id::new(1),
+            },
+            guilds: Vec::new(),
+            resume_gateway_url: self.resume_url.clone(),
+            session_id: session_id.to_owned(),
+            shard,
+            user: CurrentUser {
+                accent_color: None,
+                avatar: None,
+                banner: None,
+                bot: true,
+                discriminator: 1,
+                email: None,
+                flags: None,
+                id: Id::new(1),
+                locale: None,
+                mfa_enabled: false,
+                name: "mock".to_owned(),
+                premium_type: None,
+                public_flags: None,
+                verified: None,
+            },
+            version: API_VERSION.into(),
+        };
+
+        self.dispatch("READY", ready).await
+    }
+
+    /// Send RECONNECT, requesting the shard to reconnect and resume.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`send`].
+    ///
+    /// [`send`]: Self::send
+    pub async fn reconnect(&mut self) -> Result<(), MockGatewayError> {
+        self.send(OpCode::Reconnect, None, None, ()).await
+    }
+
+    /// Send a RESUMED dispatch event.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`send`].
+    ///
+    /// [`send`]: Self::send
+    pub async fn resumed(&mut self) -> Result<(), MockGatewayError> {
+        self.dispatch("RESUMED", ()).await
+    }
+
+    /// Send a payload in the encoding requested by the shard, compressing it
+    /// if the shard requested compression.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockGatewayErrorType::Compressing`] error type if
+    /// compressing the payload failed.
+    ///
+    /// Returns a [`MockGatewayErrorType::Sending`] error type if sending the
+    /// message failed.
+    ///
+    /// Returns a [`MockGatewayErrorType::Serializing`] error type if
+    /// serializing the payload failed.
+    pub async fn send(
+        &mut self,
+        op: OpCode,
+        sequence: Option<u64>,
+        event_type: Option<&str>,
+        data: impl Serialize,
+    ) -> Result<(), MockGatewayError> {
+        let payload = OutgoingPayload {
+            d: data,
+            op,
+            s: sequence,
+            t: event_type,
+        };
+
+        let bytes = match self.encoding {
+            Encoding::Etf => etf::to_vec(&payload).map_err(|source| MockGatewayError {
+                kind: MockGatewayErrorType::Serializing,
+                source: Some(Box::new(source)),
+            })?,
+            _ => serde_json::to_vec(&payload).map_err(|source| MockGatewayError {
+                kind: MockGatewayErrorType::Serializing,
+                source: Some(Box::new(source)),
+            })?,
+        };
+
+        #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+        let bytes = match self.compress.as_mut() {
+            Some(compress) => compress_sync(compress, &bytes)?,
+            None if self.compress_payloads => {
+                compress_sync(&mut Compress::new(Compression::default(), true), &bytes)?
+            }
+            None => bytes,
+        };
+
+        // Uncompressed JSON payloads are sent as text.
+        let message = if self.is_compressed() || self.encoding != Encoding::Json {
+            Message::Binary(bytes)
+        } else {
+            Message::Text(String::from_utf8(bytes).map_err(|source| MockGatewayError {
+                kind: MockGatewayErrorType::Serializing,
+                source: Some(Box::new(source)),
+            })?)
+        };
+
+        self.stream
+            .send(message)
+            .await
+            .map_err(|source| MockGatewayError {
+                kind: MockGatewayErrorType::Sending,
+                source: Some(Box::new(source)),
+            })
+    }
+}
+
+impl Debug for MockConnection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("MockConnection")
+            .field("ack_heartbeats", &self.ack_heartbeats)
+            .field("compressed", &self.is_compressed())
+            .field("encoding", &self.encoding)
+            .field("query", &self.query)
+            .field("resume_url", &self.resume_url)
+            .field("sequence", &self.sequence)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Deserialize a payload sent by a shard in an encoding.
+fn from_slice<T: DeserializeOwned>(
+    encoding: Encoding,
+    bytes: &[u8],
+) -> Result<T, MockGatewayError> {
+    let value: Result<T, Box<dyn Error + Send + Sync>> = match encoding {
+        Encoding::Etf => etf::from_slice(bytes).map_err(Into::into),
+        _ => serde_json::from_slice(bytes).map_err(Into::into),
+    };
+
+    value.map_err(|source| MockGatewayError {
+        kind: MockGatewayErrorType::Deserializing,
+        source: Some(source),
+    })
+}
+
+/// Compress bytes as the next message of a zlib stream, ending it with a sync
+/// flush.
+#[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+fn compress_sync(compress: &mut Compress, input: &[u8]) -> Result<Vec<u8>, MockGatewayError> {
+    let mut output = Vec::with_capacity(input.len() + 64);
+    let mut offset = 0;
+
+    loop {
+        let before = compress.total_in();
+        compress
+            .compress_vec(&input[offset..], &mut output, FlushCompress::Sync)
+            .map_err(|source| MockGatewayError {
+                kind: MockGatewayErrorType::Compressing,
+                source: Some(Box::new(source)),
+            })?;
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            offset += (compress.total_in() - before) as usize;
+        }
+
+        // The flush is complete once all input has been consumed and the
+        // output wasn't limited by the buffer's capacity.
+        if offset == input.len() && output.len() < output.capacity() {
+            return Ok(output);
+        }
+
+        output.reserve(1024);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MockConnection, MockGateway, MockGatewayError, MockGatewayErrorType, MockPayload};
+    use crate::shard::Encoding;
+    use static_assertions::{assert_fields, assert_impl_all};
+    use std::{error::Error, fmt::Debug};
+    use twilight_model::gateway::{payload::outgoing::Heartbeat, OpCode};
+
+    assert_fields!(MockGatewayErrorType::UnexpectedPayload: op);
+    assert_impl_all!(MockConnection: Debug, Send, Sync);
+    assert_impl_all!(MockGateway: Debug, Send, Sync);
+    assert_impl_all!(MockGatewayError: Error, Send, Sync);
+    assert_impl_all!(MockGatewayErrorType: Debug, Send, Sync);
+    assert_impl_all!(MockPayload: Clone, Debug, Eq, PartialEq, Send, Sync);
+
+    #[test]
+    fn payload_deserialize() {
+        let payload = MockPayload {
+            bytes: br#"{"op":1,"d":5}"#.to_vec(),
+            encoding: Encoding::Json,
+            op: OpCode::Heartbeat,
+        };
+
+        assert_eq!(5, payload.deserialize::<u64>().unwrap());
+        assert!(payload.deserialize::<String>().is_err());
+
+        let payload = MockPayload {
+            bytes: Encoding::Etf.to_vec(&Heartbeat::new(5)).unwrap(),
+            encoding: Encoding::Etf,
+            op: OpCode::Heartbeat,
+        };
+
+        assert_eq!(5, payload.deserialize::<u64>().unwrap());
+        assert!(payload.deserialize::<String>().is_err());
+    }
+
+    #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+    #[test]
+    fn compress_sync() {
+        use flate2::{Compress, Compression, Decompress, FlushDecompress};
+
+        let mut compress = Compress::new(Compression::default(), true);
+        let mut decompress = Decompress::new(true);
+
+        for input in [&b"first payload"[..], &[b'a'; 4_096][..]] {
+            let compressed = super::compress_sync(&mut compress, input).unwrap();
+            assert!(compressed.ends_with(&[0x00, 0x00, 0xff, 0xff]));
+
+            let mut output = Vec::with_capacity(input.len() * 2);
+            decompress
+                .decompress_vec(&compressed, &mut output, FlushDecompress::Sync)
+                .unwrap();
+            assert_eq!(input, output);
+        }
+    }
+}
@@ -1,4 +1,11 @@
-use super::{Config, Events, Shard};
+use super::{
+    reconnect::{ExponentialBackoff, ReconnectStrategy},
+    recorder::Recorder,
+    session_store::SessionStore,
+    transport::GatewayTransport,
+    Compression, Config, Encoding, Events, GuildFilter, LazyEvents, OverflowPolicy, Shard,
+    ZombiePolicy,
+};
 use crate::EventTypeFlags;
 use std::{
     borrow::Cow,
@@ -98,16 +105,26 @@ pub enum ShardIdErrorType {
 #[derive(Debug)]
 #[must_use = "has no effect if not built"]
 pub struct ShardBuilder {
+    compression: Compression,
+    encoding: Encoding,
+    event_buffer_capacity: Option<usize>,
     event_types: EventTypeFlags,
     pub(crate) gateway_url: Option<String>,
+    guild_filter: Option<GuildFilter>,
     identify_properties: Option<IdentifyProperties>,
     intents: Intents,
     large_threshold: u64,
+    overflow_policy: OverflowPolicy,
     presence: Option<UpdatePresencePayload>,
     queue: Arc<dyn Queue>,
     ratelimit_payloads: bool,
+    reconnect_strategy: Arc<dyn ReconnectStrategy>,
+    recorder: Option<Arc<dyn Recorder>>,
+    session_store: Option<Arc<dyn SessionStore>>,
     shard: [u64; 2],
     token: Box<str>,
+    transport: Option<Arc<dyn GatewayTransport>>,
+    zombie_policy: ZombiePolicy,
 }
 
 impl ShardBuilder {
@@ -120,34 +137,63 @@ impl ShardBuilder {
         }
 
         Self {
+            compression: Compression::default(),
+            encoding: Encoding::default(),
+            event_buffer_capacity: None,
             event_types: EventTypeFlags::default(),
             gateway_url: None,
+            guild_filter: None,
             identify_properties: None,
             intents,
             large_threshold: 50,
+            overflow_policy: OverflowPolicy::default(),
             presence: None,
             queue: Arc::new(LocalQueue::new()),
             ratelimit_payloads: true,
+            reconnect_strategy: Arc::new(ExponentialBackoff::default()),
+            recorder: None,
+            session_store: None,
             shard: [0, 1],
             token: token.into_boxed_str(),
+            transport: None,
+            zombie_policy: ZombiePolicy::default(),
         }
     }
 
     pub(crate) fn into_config(self) -> Config {
+        let unreceivable = self.unreceivable_event_types();
+
+        if self.event_types != EventTypeFlags::default() && !unreceivable.is_empty() {
+            tracing::warn!(
+                event_types = ?unreceivable,
+                intents = ?self.intents,
+                "event types can never be received under the intents",
+            );
+        }
+
         Config {
+            compression: self.compression,
+            encoding: self.encoding,
+            event_buffer_capacity: self.event_buffer_capacity,
             event_types: self.event_types,
             gateway_url: match self.gateway_url {
                 Some(s) => Cow::Owned(s),
                 None => Cow::Borrowed(crate::URL),
             },
+            guild_filter: self.guild_filter,
             identify_properties: self.identify_properties,
             intents: self.intents,
             large_threshold: self.large_threshold,
+            overflow_policy: self.overflow_policy,
             presence: self.presence,
             queue: self.queue,
             resume_url: None,
             ratelimit_payloads: self.ratelimit_payloads,
+            reconnect_strategy: self.reconnect_strategy,
+            recorder: self.recorder,
             session_id: None,
+            session_store: self.session_store,
+            session_store_resume: true,
             sequence: None,
             shard: self.shard,
             #[cfg(any(
@@ -157,6 +203,8 @@ impl ShardBuilder {
             ))]
             tls: None,
             token: self.token,
+            transport: self.transport,
+            zombie_policy: self.zombie_policy,
         }
     }
 
@@ -165,6 +213,95 @@ impl ShardBuilder {
         Shard::new_with_config(self.into_config())
     }
 
+    /// Consume the builder, constructing a shard whose dispatch events are
+    /// not deserialized until requested.
+    ///
+    /// Dispatch events are emitted as [`LazyEvent::Raw`], holding the raw
+    /// payload. Call [`RawEvent::deserialize`] or [`RawEvent::event`] on the
+    /// events you care about, leaving the rest untouched.
+    ///
+    /// [`LazyEvent::Raw`]: super::LazyEvent::Raw
+    /// [`RawEvent::deserialize`]: super::RawEvent::deserialize
+    /// [`RawEvent::event`]: super::RawEvent::event
+    pub fn build_lazy(self) -> (Shard, LazyEvents) {
+        Shard::new_with_config_lazy(self.into_config())
+    }
+
+    /// Set the compression of payloads received from the gateway.
+    ///
+    /// Compressed payloads require the `zlib-stock` or `zlib-simd` feature;
+    /// [`Shard::start`] returns a
+    /// [`ShardStartErrorType::CompressionUnsupported`] error type otherwise.
+    ///
+    /// Defaults to [`Compression::Stream`] if the `zlib-stock` or `zlib-simd`
+    /// feature is enabled, otherwise to [`Compression::Disabled`].
+    ///
+    /// [`ShardStartErrorType::CompressionUnsupported`]: super::ShardStartErrorType::CompressionUnsupported
+    pub const fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+
+        self
+    }
+
+    /// Set the encoding of payloads sent to and received from the gateway.
+    ///
+    /// The shard's events are the same regardless of the encoding, while
+    /// [`RawEvent`]s and [`ShardPayload`]s hold payloads in the encoding.
+    ///
+    /// Defaults to [`Encoding::Json`].
+    ///
+    /// [`RawEvent`]: super::RawEvent
+    /// [`ShardPayload`]: twilight_model::gateway::event::shard::Payload
+    pub const fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+
+        self
+    }
+
+    /// Set the capacity of the buffer holding events that have not yet been
+    /// received from the [`Events`] stream.
+    ///
+    /// When the buffer is full the [`overflow_policy`] determines whether the
+    /// shard waits for room to be made or drops events. The number of dropped
+    /// events is available via [`Information::dropped_events`].
+    ///
+    /// Default is an unbounded buffer, which grows for as long as events are
+    /// produced faster than they are consumed.
+    ///
+    /// # Examples
+    ///
+    /// Buffer at most 1000 events, dropping the oldest events when the buffer
+    /// is full:
+    ///
+    /// ```no_run
+    /// use std::env;
+    /// use twilight_gateway::{shard::OverflowPolicy, Intents, Shard};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let token = env::var("DISCORD_TOKEN")?;
+    ///
+    /// let (shard, events) = Shard::builder(token, Intents::GUILD_MESSAGES)
+    ///     .event_buffer_capacity(1000)
+    ///     .overflow_policy(OverflowPolicy::DropOldest)
+    ///     .build();
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the provided capacity is 0.
+    ///
+    /// [`Information::dropped_events`]: super::Information::dropped_events
+    /// [`overflow_policy`]: Self::overflow_policy
+    #[track_caller]
+    pub fn event_buffer_capacity(mut self, event_buffer_capacity: usize) -> Self {
+        assert!(event_buffer_capacity > 0, "event buffer capacity is 0");
+
+        self.event_buffer_capacity = Some(event_buffer_capacity);
+
+        self
+    }
+
     /// Set the event types to process.
     ///
     /// This is an optimization technique; all events not included in the
@@ -172,13 +309,44 @@ impl ShardBuilder {
     /// will be discarded. All events will still be sent if
     /// [`EventTypeFlags::SHARD_PAYLOAD`] is enabled.
     ///
+    /// Building the shard logs a warning if any of the event types can never
+    /// be received under the configured intents. Refer to
+    /// [`EventTypeFlags::required_intents`] for the intents needed to receive
+    /// them.
+    ///
     /// [`EventTypeFlags::SHARD_PAYLOAD`]: crate::EventTypeFlags::SHARD_PAYLOAD
+    /// [`EventTypeFlags::required_intents`]: crate::EventTypeFlags::required_intents
     pub const fn event_types(mut self, event_types: EventTypeFlags) -> Self {
         self.event_types = event_types;
 
         self
     }
 
+    /// Configured event types that can never be received under the
+    /// configured intents.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::env;
+    /// use twilight_gateway::{EventTypeFlags, Intents, Shard};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let token = env::var("DISCORD_TOKEN")?;
+    ///
+    /// let builder = Shard::builder(token, Intents::GUILDS)
+    ///     .event_types(EventTypeFlags::GUILD_CREATE | EventTypeFlags::MESSAGE_CREATE);
+    ///
+    /// assert_eq!(
+    ///     EventTypeFlags::MESSAGE_CREATE,
+    ///     builder.unreceivable_event_types()
+    /// );
+    /// # Ok(()) }
+    /// ```
+    pub fn unreceivable_event_types(&self) -> EventTypeFlags {
+        self.event_types.unreceivable(self.intents)
+    }
+
     /// Set the proxy URL for connecting to the gateway.
     ///
     /// Default is to use Discord's gateway URL.
@@ -189,6 +357,37 @@ impl ShardBuilder {
         self
     }
 
+    /// Set the filter of the guilds whose dispatch events are emitted.
+    ///
+    /// Dispatch events of guilds that are filtered out are dropped before
+    /// being deserialized, which saves processing time when only the events
+    /// of some guilds are of interest. Refer to [`GuildFilter`] for more
+    /// information.
+    ///
+    /// Defaults to emitting the events of all guilds.
+    ///
+    /// # Examples
+    ///
+    /// Only emit the events of a single guild:
+    ///
+    /// ```no_run
+    /// use std::env;
+    /// use twilight_gateway::{shard::GuildFilter, Intents, Shard};
+    /// use twilight_model::id::Id;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let shard = Shard::builder(env::var("DISCORD_TOKEN")?, Intents::GUILD_MESSAGES)
+    ///     .guild_filter(GuildFilter::allow([Id::new(1)]))
+    ///     .build();
+    /// # Ok(()) }
+    /// ```
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn guild_filter(mut self, guild_filter: GuildFilter) -> Self {
+        self.guild_filter = Some(guild_filter);
+
+        self
+    }
+
     /// Set the properties to identify with.
     ///
     /// This may be used if you want to set a different operating system, for
@@ -244,6 +443,19 @@ impl ShardBuilder {
         self
     }
 
+    /// Set the policy for handling events when the event buffer is full.
+    ///
+    /// This only has an effect if an [`event_buffer_capacity`] has been set.
+    ///
+    /// Default is [`OverflowPolicy::Block`].
+    ///
+    /// [`event_buffer_capacity`]: Self::event_buffer_capacity
+    pub const fn overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.overflow_policy = overflow_policy;
+
+        self
+    }
+
     /// Set the presence to use automatically when starting a new session.
     ///
     /// Default is no presence, which defaults to strictly being "online"
@@ -314,6 +526,51 @@ impl ShardBuilder {
         self
     }
 
+    /// Set the strategy deciding how long to wait before reconnecting, and
+    /// when to give up.
+    ///
+    /// Refer to the [`reconnect`] module for more information.
+    ///
+    /// Defaults to [`ExponentialBackoff::default`].
+    ///
+    /// [`reconnect`]: super::reconnect
+    pub fn reconnect_strategy(mut self, reconnect_strategy: Arc<dyn ReconnectStrategy>) -> Self {
+        self.reconnect_strategy = reconnect_strategy;
+
+        self
+    }
+
+    /// Set the recorder of the payloads the shard sends and receives.
+    ///
+    /// Recordings can be replayed to reproduce the events they produced.
+    /// Refer to the [`recorder`] module for more information.
+    ///
+    /// Defaults to no recorder.
+    ///
+    /// [`recorder`]: super::recorder
+    pub fn recorder(mut self, recorder: Arc<dyn Recorder>) -> Self {
+        self.recorder = Some(recorder);
+
+        self
+    }
+
+    /// Set the store used to persist the shard's session.
+    ///
+    /// The shard stores the details needed to resume its session whenever
+    /// they change, and resumes a stored session when started. Refer to the
+    /// [`session_store`] module for more information.
+    ///
+    /// Defaults to no store, so sessions are only resumable when provided
+    /// via [`ClusterBuilder::resume_sessions`].
+    ///
+    /// [`ClusterBuilder::resume_sessions`]: crate::cluster::ClusterBuilder::resume_sessions
+    /// [`session_store`]: super::session_store
+    pub fn session_store(mut self, session_store: Arc<dyn SessionStore>) -> Self {
+        self.session_store = Some(session_store);
+
+        self
+    }
+
     /// Set the shard ID to connect as, and the total number of shards used by
     /// the bot.
     ///
@@ -363,6 +620,36 @@ impl ShardBuilder {
 
         Ok(self)
     }
+
+    /// Set the transport used to connect to the gateway.
+    ///
+    /// Useful for connecting through a custom proxy, or for running a shard
+    /// against a mock or a replay of recorded traffic. Refer to the
+    /// [`transport`] module for more information.
+    ///
+    /// Defaults to a [`TungsteniteTransport`], connecting over a websocket.
+    ///
+    /// [`TungsteniteTransport`]: super::transport::TungsteniteTransport
+    /// [`transport`]: super::transport
+    pub fn transport(mut self, transport: Arc<dyn GatewayTransport>) -> Self {
+        self.transport = Some(transport);
+
+        self
+    }
+
+    /// Set the policy for detecting and handling zombie connections.
+    ///
+    /// A connection is a zombie when it's open but heartbeats are no longer
+    /// acknowledged, or are acknowledged too late. The policy sets how many
+    /// heartbeats may be missed, the maximum tolerated latency, and whether
+    /// to resume the session or reconnect with a new one.
+    ///
+    /// Defaults to [`ZombiePolicy::default`].
+    pub const fn zombie_policy(mut self, zombie_policy: ZombiePolicy) -> Self {
+        self.zombie_policy = zombie_policy;
+
+        self
+    }
 }
 
 impl From<(String, Intents)> for ShardBuilder {
@@ -0,0 +1,362 @@
+//! Channel carrying events from a shard's processor to its [`Events`] stream.
+//!
+//! By default the channel is unbounded. When a capacity is configured via
+//! [`ShardBuilder::event_buffer_capacity`] the channel is bounded and the
+//! configured [`OverflowPolicy`] decides what happens when it is full.
+//!
+//! [`Events`]: super::Events
+//! [`ShardBuilder::event_buffer_capacity`]: super::ShardBuilder::event_buffer_capacity
+
+use super::LazyEvent;
+use crate::EventTypeFlags;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    Notify,
+};
+
+/// Policy for handling new events when a bounded event buffer is full.
+///
+/// Refer to [`ShardBuilder::event_buffer_capacity`] for more information.
+///
+/// [`ShardBuilder::event_buffer_capacity`]: super::ShardBuilder::event_buffer_capacity
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum OverflowPolicy {
+    /// Wait until the consumer makes room in the buffer.
+    ///
+    /// The shard stops processing payloads from the gateway until the event
+    /// has been buffered. Blocking for too long will cause heartbeat
+    /// acknowledgements to go unprocessed, causing the shard to reconnect.
+    #[default]
+    Block,
+    /// Drop the oldest buffered event to make room for the new event.
+    DropOldest,
+    /// Drop the new event.
+    DropNewest,
+    /// Drop the new event if its type is contained within the event type
+    /// flags, otherwise wait until the consumer makes room in the buffer.
+    DropEventTypes(EventTypeFlags),
+}
+
+/// Create a new unbounded channel.
+pub fn unbounded() -> (Sender, Receiver) {
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    (Sender::Unbounded(tx), Receiver::Unbounded(rx))
+}
+
+/// Create a new channel bounded to a capacity, handling overflows with the
+/// provided policy.
+pub fn bounded(capacity: usize, policy: OverflowPolicy) -> (Sender, Receiver) {
+    let shared = Arc::new(Shared {
+        capacity,
+        policy,
+        receiver_closed: AtomicBool::new(false),
+        senders: AtomicUsize::new(1),
+        space: Notify::new(),
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            waker: None,
+        }),
+    });
+
+    (
+        Sender::Bounded(BoundedSender {
+            shared: Arc::clone(&shared),
+        }),
+        Receiver::Bounded(BoundedReceiver { shared }),
+    )
+}
+
+/// Sending half of an event channel.
+#[derive(Clone, Debug)]
+pub enum Sender {
+    Bounded(BoundedSender),
+    Unbounded(UnboundedSender<LazyEvent>),
+}
+
+impl Sender {
+    /// Send an event over the channel.
+    ///
+    /// Returns whether an event was dropped due to the channel being full.
+    /// Events sent after the receiver has been dropped are discarded without
+    /// being counted.
+    pub async fn send(&self, event: LazyEvent) -> bool {
+        match self {
+            Self::Bounded(tx) => tx.send(event).await,
+            Self::Unbounded(tx) => {
+                let _res = tx.send(event);
+
+                false
+            }
+        }
+    }
+}
+
+/// Receiving half of an event channel.
+#[derive(Debug)]
+pub enum Receiver {
+    Bounded(BoundedReceiver),
+    Unbounded(UnboundedReceiver<LazyEvent>),
+}
+
+impl Receiver {
+    /// Poll for the next event.
+    ///
+    /// Returns `None` once all senders have been dropped and the buffer is
+    /// empty.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<LazyEvent>> {
+        match self {
+            Self::Bounded(rx) => rx.poll_recv(cx),
+            Self::Unbounded(rx) => rx.poll_recv(cx),
+        }
+    }
+
+    /// Receive an event if one is immediately available.
+    pub fn try_recv(&mut self) -> Option<LazyEvent> {
+        match self {
+            Self::Bounded(rx) => rx.shared.pop(),
+            Self::Unbounded(rx) => rx.try_recv().ok(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    queue: VecDeque<LazyEvent>,
+    waker: Option<Waker>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    capacity: usize,
+    policy: OverflowPolicy,
+    receiver_closed: AtomicBool,
+    senders: AtomicUsize,
+    /// Notified when room is made in the queue or the receiver is dropped.
+    space: Notify,
+    state: Mutex<State>,
+}
+
+impl Shared {
+    fn pop(&self) -> Option<LazyEvent> {
+        let event = self.state.lock().expect("state poisoned").queue.pop_front();
+
+        if event.is_some() {
+            self.space.notify_one();
+        }
+
+        event
+    }
+
+    fn push(state: &mut State, event: LazyEvent) {
+        state.queue.push_back(event);
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BoundedSender {
+    shared: Arc<Shared>,
+}
+
+impl BoundedSender {
+    async fn send(&self, event: LazyEvent) -> bool {
+        loop {
+            let space = self.shared.space.notified();
+
+            {
+                let mut state = self.shared.state.lock().expect("state poisoned");
+
+                if self.shared.receiver_closed.load(Ordering::Acquire) {
+                    return false;
+                }
+
+                if state.queue.len() < self.shared.capacity {
+                    Shared::push(&mut state, event);
+
+                    return false;
+                }
+
+                match self.shared.policy {
+                    OverflowPolicy::Block => {}
+                    OverflowPolicy::DropEventTypes(event_types)
+                        if !event
+                            .kind()
+                            .map_or(false, |kind| event_types.contains(kind)) => {}
+                    OverflowPolicy::DropEventTypes(_) | OverflowPolicy::DropNewest => {
+                        return true;
+                    }
+                    OverflowPolicy::DropOldest => {
+                        state.queue.pop_front();
+                        Shared::push(&mut state, event);
+
+                        return true;
+                    }
+                }
+            }
+
+            space.await;
+        }
+    }
+}
+
+impl Clone for BoundedSender {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Drop for BoundedSender {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+
+        // Wake the receiver so that it can observe that the channel ended.
+        if let Some(waker) = self
+            .shared
+            .state
+            .lock()
+            .expect("state poisoned")
+            .waker
+            .take()
+        {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BoundedReceiver {
+    shared: Arc<Shared>,
+}
+
+impl BoundedReceiver {
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<LazyEvent>> {
+        let mut state = self.shared.state.lock().expect("state poisoned");
+
+        if let Some(event) = state.queue.pop_front() {
+            self.shared.space.notify_one();
+
+            return Poll::Ready(Some(event));
+        }
+
+        if self.shared.senders.load(Ordering::Acquire) == 0 {
+            return Poll::Ready(None);
+        }
+
+        state.waker = Some(cx.waker().clone());
+
+        Poll::Pending
+    }
+}
+
+impl Drop for BoundedReceiver {
+    fn drop(&mut self) {
+        self.shared.receiver_closed.store(true, Ordering::Release);
+        self.shared.space.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OverflowPolicy, Receiver, Sender};
+    use crate::EventTypeFlags;
+    use static_assertions::assert_impl_all;
+    use std::{fmt::Debug, time::Duration};
+    use tokio::time;
+    use twilight_model::gateway::event::{Event, EventType};
+
+    assert_impl_all!(OverflowPolicy: Clone, Copy, Debug, Default, Eq, PartialEq, Send, Sync);
+    assert_impl_all!(Receiver: Debug, Send, Sync);
+    assert_impl_all!(Sender: Clone, Debug, Send, Sync);
+
+    fn kinds(rx: &mut Receiver) -> Vec<EventType> {
+        let mut kinds = Vec::new();
+
+        while let Some(event) = rx.try_recv() {
+            kinds.push(event.into_event().unwrap().kind());
+        }
+
+        kinds
+    }
+
+    #[tokio::test]
+    async fn drop_newest() {
+        let (tx, mut rx) = super::bounded(1, OverflowPolicy::DropNewest);
+        assert!(!tx.send(Event::GatewayReconnect.into()).await);
+        assert!(tx.send(Event::GatewayHeartbeatAck.into()).await);
+
+        assert_eq!(Vec::from([EventType::GatewayReconnect]), kinds(&mut rx));
+    }
+
+    #[tokio::test]
+    async fn drop_oldest() {
+        let (tx, mut rx) = super::bounded(1, OverflowPolicy::DropOldest);
+        assert!(!tx.send(Event::GatewayReconnect.into()).await);
+        assert!(tx.send(Event::GatewayHeartbeatAck.into()).await);
+
+        assert_eq!(Vec::from([EventType::GatewayHeartbeatAck]), kinds(&mut rx));
+    }
+
+    #[tokio::test]
+    async fn drop_event_types() {
+        let policy = OverflowPolicy::DropEventTypes(EventTypeFlags::GATEWAY_HEARTBEAT_ACK);
+        let (tx, mut rx) = super::bounded(1, policy);
+        assert!(!tx.send(Event::GatewayReconnect.into()).await);
+        assert!(tx.send(Event::GatewayHeartbeatAck.into()).await);
+
+        // Event types not included in the flags wait for room in the buffer.
+        let blocked = time::timeout(
+            Duration::from_millis(50),
+            tx.send(Event::GatewayReconnect.into()),
+        );
+        assert!(blocked.await.is_err());
+
+        assert_eq!(Vec::from([EventType::GatewayReconnect]), kinds(&mut rx));
+    }
+
+    #[tokio::test]
+    async fn block() {
+        let (tx, mut rx) = super::bounded(1, OverflowPolicy::Block);
+        assert!(!tx.send(Event::GatewayReconnect.into()).await);
+
+        let sender = tokio::spawn(async move { tx.send(Event::GatewayHeartbeatAck.into()).await });
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(!sender.is_finished());
+
+        assert_eq!(Vec::from([EventType::GatewayReconnect]), kinds(&mut rx));
+        assert!(!sender.await.unwrap());
+        assert_eq!(Vec::from([EventType::GatewayHeartbeatAck]), kinds(&mut rx));
+    }
+
+    #[tokio::test]
+    async fn receiver_dropped() {
+        let (tx, rx) = super::bounded(1, OverflowPolicy::Block);
+        assert!(!tx.send(Event::GatewayReconnect.into()).await);
+        drop(rx);
+
+        // Sending must not block forever once the receiver is gone.
+        let sent = time::timeout(
+            Duration::from_secs(1),
+            tx.send(Event::GatewayReconnect.into()),
+        );
+        assert_eq!(Ok(false), sent.await);
+    }
+}
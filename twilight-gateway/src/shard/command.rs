@@ -2,26 +2,57 @@
 //!
 //! [`Shard::command`]: super::Shard::command
 
+use super::Priority;
 use twilight_model::gateway::payload::outgoing::{
-    identify::Identify, resume::Resume, Heartbeat, RequestGuildMembers, UpdatePresence,
-    UpdateVoiceState,
+    identify::Identify, resume::Resume, update_presence::UpdatePresencePayload, Heartbeat,
+    RequestGuildMembers, UpdatePresence, UpdateVoiceState,
 };
 
 mod private {
+    use super::Priority;
     use serde::Serialize;
     use twilight_model::gateway::payload::outgoing::{
-        identify::Identify, resume::Resume, Heartbeat, RequestGuildMembers, UpdatePresence,
-        UpdateVoiceState,
+        identify::Identify, resume::Resume, update_presence::UpdatePresencePayload, Heartbeat,
+        RequestGuildMembers, UpdatePresence, UpdateVoiceState,
     };
 
-    pub trait Sealed: Serialize {}
+    pub trait Sealed: Serialize {
+        /// Priority of the command waiting for the shard's ratelimiter.
+        const PRIORITY: Priority;
+
+        /// Presence set by the command, if it updates the presence.
+        fn presence(&self) -> Option<&UpdatePresencePayload> {
+            None
+        }
+    }
+
+    impl Sealed for Heartbeat {
+        const PRIORITY: Priority = Priority::High;
+    }
+
+    impl Sealed for Identify {
+        const PRIORITY: Priority = Priority::High;
+    }
+
+    impl Sealed for RequestGuildMembers {
+        const PRIORITY: Priority = Priority::Normal;
+    }
+
+    impl Sealed for Resume {
+        const PRIORITY: Priority = Priority::High;
+    }
 
-    impl Sealed for Heartbeat {}
-    impl Sealed for Identify {}
-    impl Sealed for RequestGuildMembers {}
-    impl Sealed for Resume {}
-    impl Sealed for UpdatePresence {}
-    impl Sealed for UpdateVoiceState {}
+    impl Sealed for UpdatePresence {
+        const PRIORITY: Priority = Priority::Low;
+
+        fn presence(&self) -> Option<&UpdatePresencePayload> {
+            Some(&self.d)
+        }
+    }
+
+    impl Sealed for UpdateVoiceState {
+        const PRIORITY: Priority = Priority::High;
+    }
 }
 
 /// Trait marker to denote what can be provided to [`Shard::command`].
@@ -43,6 +74,16 @@ impl Command for Resume {}
 impl Command for UpdatePresence {}
 impl Command for UpdateVoiceState {}
 
+/// Priority of a command waiting for the shard's ratelimiter.
+pub(crate) const fn priority<T: Command>(_: &T) -> Priority {
+    T::PRIORITY
+}
+
+/// Presence set by a command, if it updates the presence.
+pub(crate) fn presence<T: Command>(value: &T) -> Option<&UpdatePresencePayload> {
+    private::Sealed::presence(value)
+}
+
 #[cfg(test)]
 mod tests {
     use super::Command;
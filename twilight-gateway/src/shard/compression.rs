@@ -0,0 +1,98 @@
+//! Compression of payloads received from the gateway.
+
+/// Compression of payloads received from the gateway.
+///
+/// Compression trades processing time for bandwidth. Compressed payloads are
+/// only supported with the `zlib-stock` or `zlib-simd` feature, and starting
+/// a shard configured to receive them without either feature fails.
+///
+/// Defaults to [`Stream`] if the `zlib-stock` or `zlib-simd` feature is
+/// enabled, otherwise to [`Disabled`].
+///
+/// [`Disabled`]: Self::Disabled
+/// [`Stream`]: Self::Stream
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Payloads are not compressed.
+    Disabled,
+    /// Payloads are individually compressed with zlib.
+    ///
+    /// Discord only compresses some payloads, such as large guild create
+    /// events, and sends the rest uncompressed.
+    Payload,
+    /// Payloads are compressed as a single zlib stream spanning the
+    /// connection.
+    ///
+    /// Compresses best, at the cost of keeping the stream's state in memory.
+    Stream,
+}
+
+impl Compression {
+    /// Whether payloads are individually compressed, which is requested when
+    /// identifying.
+    pub(crate) const fn is_payload(self) -> bool {
+        matches!(self, Self::Payload)
+    }
+
+    /// Whether payloads are compressed as a zlib stream, which is requested
+    /// in the gateway URL's query string.
+    pub(crate) const fn is_stream(self) -> bool {
+        matches!(self, Self::Stream)
+    }
+
+    /// Whether the compression is supported by the enabled features.
+    pub(crate) const fn is_supported(self) -> bool {
+        matches!(self, Self::Disabled) || cfg!(any(feature = "zlib-stock", feature = "zlib-simd"))
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        if cfg!(any(feature = "zlib-stock", feature = "zlib-simd")) {
+            Self::Stream
+        } else {
+            Self::Disabled
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compression;
+    use static_assertions::assert_impl_all;
+    use std::{fmt::Debug, hash::Hash};
+
+    assert_impl_all!(
+        Compression: Clone,
+        Copy,
+        Debug,
+        Default,
+        Eq,
+        Hash,
+        PartialEq,
+        Send,
+        Sync
+    );
+
+    #[test]
+    fn default() {
+        #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+        assert_eq!(Compression::Stream, Compression::default());
+
+        #[cfg(not(any(feature = "zlib-stock", feature = "zlib-simd")))]
+        assert_eq!(Compression::Disabled, Compression::default());
+    }
+
+    #[test]
+    fn supported() {
+        assert!(Compression::Disabled.is_supported());
+
+        for compression in [Compression::Payload, Compression::Stream] {
+            assert_eq!(
+                cfg!(any(feature = "zlib-stock", feature = "zlib-simd")),
+                compression.is_supported()
+            );
+        }
+    }
+}
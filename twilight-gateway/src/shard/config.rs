@@ -1,3 +1,7 @@
+use super::{
+    reconnect::ReconnectStrategy, recorder::Recorder, session_store::SessionStore,
+    transport::GatewayTransport, Compression, Encoding, GuildFilter, OverflowPolicy, ZombiePolicy,
+};
 use crate::EventTypeFlags;
 use std::{borrow::Cow, sync::Arc};
 use twilight_gateway_queue::Queue;
@@ -21,16 +25,29 @@ use super::tls::TlsContainer;
 /// [`Shard::builder`]: super::Shard::builder
 #[derive(Clone, Debug)]
 pub struct Config {
+    pub(crate) compression: Compression,
+    pub(crate) encoding: Encoding,
+    pub(super) event_buffer_capacity: Option<usize>,
     pub(super) event_types: EventTypeFlags,
     pub(super) gateway_url: Cow<'static, str>,
+    pub(crate) guild_filter: Option<GuildFilter>,
     pub(super) identify_properties: Option<IdentifyProperties>,
     pub(super) intents: Intents,
     pub(super) large_threshold: u64,
+    pub(super) overflow_policy: OverflowPolicy,
     pub(crate) presence: Option<UpdatePresencePayload>,
     pub(super) queue: Arc<dyn Queue>,
     pub(crate) ratelimit_payloads: bool,
+    pub(crate) reconnect_strategy: Arc<dyn ReconnectStrategy>,
+    pub(crate) recorder: Option<Arc<dyn Recorder>>,
     pub(crate) resume_url: Option<Box<str>>,
     pub(crate) session_id: Option<Box<str>>,
+    /// Whether a session persisted in the session store may be resumed.
+    ///
+    /// Disabled for shards whose total differs from that of the shards which
+    /// persisted the sessions.
+    pub(crate) session_store_resume: bool,
+    pub(super) session_store: Option<Arc<dyn SessionStore>>,
     pub(crate) sequence: Option<u64>,
     pub(crate) shard: [u64; 2],
     #[cfg(any(
@@ -40,9 +57,27 @@ pub struct Config {
     ))]
     pub(crate) tls: Option<TlsContainer>,
     pub(super) token: Box<str>,
+    pub(super) transport: Option<Arc<dyn GatewayTransport>>,
+    pub(crate) zombie_policy: ZombiePolicy,
 }
 
 impl Config {
+    /// Compression of payloads received from the gateway.
+    pub const fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Encoding of payloads sent to and received from the gateway.
+    pub const fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Capacity of the event buffer, if events are buffered in a bounded
+    /// channel.
+    pub const fn event_buffer_capacity(&self) -> Option<usize> {
+        self.event_buffer_capacity
+    }
+
     /// Copy of the event type flags.
     pub const fn event_types(&self) -> EventTypeFlags {
         self.event_types
@@ -53,6 +88,12 @@ impl Config {
         &self.gateway_url
     }
 
+    /// Return an immutable reference to the filter of the guilds whose
+    /// dispatch events are emitted, if one has been configured.
+    pub const fn guild_filter(&self) -> Option<&GuildFilter> {
+        self.guild_filter.as_ref()
+    }
+
     /// Return an immutable reference to the identification properties the shard
     /// will use.
     pub const fn identify_properties(&self) -> Option<&IdentifyProperties> {
@@ -70,6 +111,15 @@ impl Config {
         self.large_threshold
     }
 
+    /// Policy used when the bounded event buffer is full.
+    ///
+    /// Only used if an [event buffer capacity] has been configured.
+    ///
+    /// [event buffer capacity]: Self::event_buffer_capacity
+    pub const fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+
     /// Return an immutable reference to the presence to set when identifying
     /// with the gateway.
     ///
@@ -84,6 +134,24 @@ impl Config {
         self.ratelimit_payloads
     }
 
+    /// Return an immutable reference to the strategy deciding how long to
+    /// wait before reconnecting.
+    pub fn reconnect_strategy(&self) -> &dyn ReconnectStrategy {
+        self.reconnect_strategy.as_ref()
+    }
+
+    /// Return an immutable reference to the recorder of the shard's payloads,
+    /// if one has been configured.
+    pub fn recorder(&self) -> Option<&dyn Recorder> {
+        self.recorder.as_deref()
+    }
+
+    /// Return an immutable reference to the store persisting the shard's
+    /// session, if one has been configured.
+    pub fn session_store(&self) -> Option<&dyn SessionStore> {
+        self.session_store.as_deref()
+    }
+
     /// The shard's ID and the total number of shards used by the bot.
     pub const fn shard(&self) -> [u64; 2] {
         self.shard
@@ -94,6 +162,17 @@ impl Config {
     pub const fn token(&self) -> &str {
         &self.token
     }
+
+    /// Return an immutable reference to the transport used to connect to the
+    /// gateway, if one other than the default has been configured.
+    pub fn transport(&self) -> Option<&dyn GatewayTransport> {
+        self.transport.as_deref()
+    }
+
+    /// Policy for detecting and handling zombie connections.
+    pub const fn zombie_policy(&self) -> ZombiePolicy {
+        self.zombie_policy
+    }
 }
 
 #[cfg(test)]
@@ -1,10 +1,16 @@
-use super::json;
+use super::{
+    channel::{self, OverflowPolicy, Receiver, Sender},
+    Encoding, LazyEvent, RawEvent,
+};
 use crate::{Event, EventTypeFlags};
 use std::{
     error::Error,
     fmt::{Debug, Display, Formatter, Result as FmtResult},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
-use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use twilight_model::gateway::event::shard::Payload;
 
 #[derive(Debug)]
@@ -63,16 +69,51 @@ pub enum EmitJsonErrorType {
 /// operations.
 #[derive(Clone, Debug)]
 pub struct Emitter {
+    dropped_events: Arc<AtomicU64>,
     event_types: EventTypeFlags,
-    tx: UnboundedSender<Event>,
+    lazy: bool,
+    tx: Sender,
 }
 
 impl Emitter {
-    /// Create a new emitter for events and bytes.
-    pub fn new(event_types: EventTypeFlags) -> (Self, UnboundedReceiver<Event>) {
-        let (tx, rx) = mpsc::unbounded_channel();
+    /// Create a new emitter for events and bytes over an unbounded channel.
+    pub fn new(event_types: EventTypeFlags) -> (Self, Receiver) {
+        let (tx, rx) = channel::unbounded();
+
+        (Self::with_sender(event_types, tx), rx)
+    }
+
+    /// Create a new emitter for events and bytes over a channel bounded to a
+    /// capacity.
+    pub fn bounded(
+        event_types: EventTypeFlags,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> (Self, Receiver) {
+        let (tx, rx) = channel::bounded(capacity, policy);
+
+        (Self::with_sender(event_types, tx), rx)
+    }
+
+    fn with_sender(event_types: EventTypeFlags, tx: Sender) -> Self {
+        Self {
+            dropped_events: Arc::new(AtomicU64::new(0)),
+            event_types,
+            lazy: false,
+            tx,
+        }
+    }
+
+    /// Set whether dispatch events are emitted without being deserialized.
+    pub const fn lazy(mut self, lazy: bool) -> Self {
+        self.lazy = lazy;
+
+        self
+    }
 
-        (Self { event_types, tx }, rx)
+    /// Counter of events dropped due to the channel being full.
+    pub fn dropped_events(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.dropped_events)
     }
 
     /// Whether the configured event types include an individual event type.
@@ -89,41 +130,46 @@ impl Emitter {
     ///
     /// [`EventTypeFlags::SHARD_PAYLOAD`]: crate::EventTypeFlags::SHARD_PAYLOAD
     #[tracing::instrument(level = "trace")]
-    pub fn bytes(&self, bytes: &[u8]) {
+    pub async fn bytes(&self, bytes: &[u8]) {
         if self.wants(EventTypeFlags::SHARD_PAYLOAD) {
-            self.send(Event::ShardPayload(Payload {
+            self.send(LazyEvent::Event(Event::ShardPayload(Payload {
                 bytes: bytes.to_vec(),
-            }))
+            })))
+            .await;
         }
     }
 
     /// Send an event to the listener if it has subscribed to its event type.
     #[tracing::instrument(level = "trace")]
-    pub fn event(&self, event: Event) {
+    pub async fn event(&self, event: Event) {
         let event_type = EventTypeFlags::from(event.kind());
 
         if self.wants(event_type) {
-            self.send(event);
+            self.send(LazyEvent::Event(event)).await;
         }
     }
 
-    /// Emit a JSON payload that hasn't been deserialized yet, but only if the
+    /// Emit a payload that hasn't been deserialized yet, but only if the
     /// listener wants the event type.
     ///
+    /// If the emitter is lazy then the payload is emitted as a [`RawEvent`]
+    /// without being deserialized.
+    ///
     /// # Errors
     ///
     /// Returns a [`EmitJsonErrorType::EventTypeUnknown`] error type if the
     /// event type is unknown.
     ///
     /// Returns a [`EmitJsonErrorType::Parsing`] error type if the combination
-    /// of the provided opcode, sequence, event type, and JSON could not be
+    /// of the provided opcode, sequence, event type, and payload could not be
     /// parsed into an event.
-    pub fn json(
+    pub async fn payload(
         &self,
         op: u8,
         seq: Option<u64>,
         event_type: Option<&str>,
-        json: &mut [u8],
+        encoding: Encoding,
+        bytes: &mut [u8],
     ) -> Result<(), EmitJsonError> {
         let flag = EventTypeFlags::try_from((op, event_type)).map_err(|(op, event_type)| {
             EmitJsonError {
@@ -135,47 +181,93 @@ impl Emitter {
             }
         })?;
 
-        if self.wants(flag) {
-            let gateway_event =
-                json::parse_gateway_event(op, seq, event_type, json).map_err(|source| {
-                    EmitJsonError {
-                        kind: EmitJsonErrorType::Parsing,
-                        source: Some(Box::new(source)),
-                    }
+        if self.wants(flag) && self.lazy {
+            let raw = RawEvent::new(
+                op,
+                seq,
+                event_type.map(ToOwned::to_owned),
+                encoding,
+                bytes.to_vec(),
+            );
+            self.send(LazyEvent::Raw(raw)).await;
+        } else if self.wants(flag) {
+            let gateway_event = encoding
+                .parse_gateway_event(op, seq, event_type, bytes)
+                .map_err(|source| EmitJsonError {
+                    kind: EmitJsonErrorType::Parsing,
+                    source: Some(Box::new(source)),
                 })?;
-            self.send(Event::from(gateway_event));
+            self.send(LazyEvent::Event(Event::from(gateway_event)))
+                .await;
         }
 
         Ok(())
     }
 
-    fn send(&self, event: Event) {
-        let _res = self.tx.send(event);
+    async fn send(&self, event: LazyEvent) {
+        if self.tx.send(event).await {
+            self.dropped_events.fetch_add(1, Ordering::Relaxed);
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Emitter;
+    use super::{Emitter, Encoding, LazyEvent, OverflowPolicy};
     use crate::{Event, EventTypeFlags};
+    use std::sync::atomic::Ordering;
 
-    #[test]
-    fn bytes_send() {
+    #[tokio::test]
+    async fn bytes_send() {
         let (emitter, mut rx) = Emitter::new(EventTypeFlags::SHARD_PAYLOAD);
-        emitter.bytes(&[1]);
+        emitter.bytes(&[1]).await;
 
-        assert!(rx.try_recv().is_ok());
-        assert!(rx.try_recv().is_err());
+        assert!(rx.try_recv().is_some());
+        assert!(rx.try_recv().is_none());
     }
 
-    #[test]
-    fn event_sends_to_rx() {
+    #[tokio::test]
+    async fn event_sends_to_rx() {
         let (emitter, mut rx) = Emitter::new(EventTypeFlags::default());
-        emitter.event(Event::GatewayReconnect);
+        emitter.event(Event::GatewayReconnect).await;
 
-        assert!(rx.try_recv().is_ok());
+        assert!(rx.try_recv().is_some());
 
         // now check that the event didn't send the event twice
-        assert!(rx.try_recv().is_err());
+        assert!(rx.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn payload_lazy() {
+        let (emitter, mut rx) = Emitter::new(EventTypeFlags::default());
+        let emitter = emitter.lazy(true);
+        let mut json = br#"{"t":"RESUMED","s":1,"op":0,"d":null}"#.to_vec();
+        emitter
+            .payload(0, Some(1), Some("RESUMED"), Encoding::Json, &mut json)
+            .await
+            .unwrap();
+
+        match rx.try_recv() {
+            Some(LazyEvent::Raw(raw)) => {
+                assert_eq!(json, raw.bytes());
+                assert_eq!(Encoding::Json, raw.encoding());
+                assert_eq!(Some("RESUMED"), raw.event_type());
+                assert_eq!(0, raw.op());
+                assert_eq!(Some(1), raw.sequence());
+            }
+            other => panic!("expected raw event, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn dropped_events_counted() {
+        let (emitter, mut rx) =
+            Emitter::bounded(EventTypeFlags::default(), 1, OverflowPolicy::DropNewest);
+        emitter.event(Event::GatewayReconnect).await;
+        emitter.event(Event::GatewayReconnect).await;
+
+        assert_eq!(1, emitter.dropped_events().load(Ordering::Relaxed));
+        assert!(rx.try_recv().is_some());
+        assert!(rx.try_recv().is_none());
     }
 }
@@ -0,0 +1,99 @@
+//! Encodings of payloads sent to and received from the gateway.
+
+use super::{
+    etf,
+    json::{self, GatewayEventParsingError},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::error::Error;
+use twilight_model::gateway::event::GatewayEvent;
+
+/// Encoding of payloads sent to and received from the gateway.
+///
+/// Events are the same regardless of the encoding, while the bytes of
+/// [`RawEvent`]s and [`ShardPayload`]s are in the encoding they were received
+/// in.
+///
+/// [`RawEvent`]: super::RawEvent
+/// [`ShardPayload`]: twilight_model::gateway::event::shard::Payload
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum Encoding {
+    /// Erlang external term format.
+    Etf,
+    /// JSON.
+    #[default]
+    Json,
+}
+
+impl Encoding {
+    /// Name of the encoding, as used in the gateway URL's query string.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Etf => "etf",
+            Self::Json => "json",
+        }
+    }
+
+    /// Deserialize a payload in the encoding.
+    pub(crate) fn deserialize<T: DeserializeOwned>(
+        self,
+        bytes: &mut [u8],
+    ) -> Result<T, Box<dyn Error + Send + Sync>> {
+        match self {
+            Self::Etf => Ok(etf::from_slice(bytes)?),
+            Self::Json => Ok(json::from_slice(bytes)?),
+        }
+    }
+
+    /// Parse a gateway event from a payload in the encoding.
+    pub(crate) fn parse_gateway_event(
+        self,
+        op: u8,
+        sequence: Option<u64>,
+        event_type: Option<&str>,
+        bytes: &mut [u8],
+    ) -> Result<GatewayEvent, GatewayEventParsingError> {
+        match self {
+            Self::Etf => etf::parse_gateway_event(op, sequence, event_type, bytes),
+            Self::Json => json::parse_gateway_event(op, sequence, event_type, bytes),
+        }
+    }
+
+    /// Serialize a payload in the encoding.
+    pub(crate) fn to_vec(
+        self,
+        value: &impl Serialize,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        match self {
+            Self::Etf => Ok(etf::to_vec(value)?),
+            Self::Json => Ok(json::to_vec(value)?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Encoding;
+    use static_assertions::assert_impl_all;
+    use std::{fmt::Debug, hash::Hash};
+
+    assert_impl_all!(
+        Encoding: Clone,
+        Copy,
+        Debug,
+        Default,
+        Eq,
+        Hash,
+        PartialEq,
+        Send,
+        Sync
+    );
+
+    #[test]
+    fn name() {
+        assert_eq!("etf", Encoding::Etf.name());
+        assert_eq!("json", Encoding::Json.name());
+        assert_eq!(Encoding::Json, Encoding::default());
+    }
+}
@@ -0,0 +1,1174 @@
+//! Encoding and decoding of payloads in the Erlang external term format.
+//!
+//! Payloads are deserialized directly from their terms by a [`Deserializer`],
+//! without being transcoded into JSON first. Snowflakes are sent as integers
+//! rather than strings, which [`Id`] deserializes from either.
+//!
+//! [`Id`]: twilight_model::id::Id
+
+use super::json::{GatewayEventParsingError, GatewayEventParsingErrorType};
+use serde::{
+    de::{
+        self, value::SeqDeserializer, DeserializeOwned, DeserializeSeed, EnumAccess, MapAccess,
+        SeqAccess, Unexpected, VariantAccess, Visitor,
+    },
+    forward_to_deserialize_any, Deserialize, Serialize,
+};
+use serde_json::Value;
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    str,
+};
+use twilight_model::gateway::event::{GatewayEvent, GatewayEventDeserializer};
+
+/// Version of the external term format.
+const VERSION: u8 = 131;
+
+/// Maximum depth that lists, tuples, and maps may be nested to.
+///
+/// Terms are deserialized recursively, so this bounds the stack used by
+/// payloads of hostile servers.
+const MAX_DEPTH: usize = 128;
+
+const ATOM_EXT: u8 = 100;
+const ATOM_UTF8_EXT: u8 = 118;
+const BINARY_EXT: u8 = 109;
+const FLOAT_EXT: u8 = 99;
+const INTEGER_EXT: u8 = 98;
+const LARGE_BIG_EXT: u8 = 111;
+const LARGE_TUPLE_EXT: u8 = 105;
+const LIST_EXT: u8 = 108;
+const MAP_EXT: u8 = 116;
+const NEW_FLOAT_EXT: u8 = 70;
+const NIL_EXT: u8 = 106;
+const SMALL_ATOM_EXT: u8 = 115;
+const SMALL_ATOM_UTF8_EXT: u8 = 119;
+const SMALL_BIG_EXT: u8 = 110;
+const SMALL_INTEGER_EXT: u8 = 97;
+const SMALL_TUPLE_EXT: u8 = 104;
+const STRING_EXT: u8 = 107;
+
+/// Encoding or decoding a payload in the external term format failed.
+#[derive(Debug)]
+pub struct EtfError {
+    kind: EtfErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl EtfError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &EtfErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(self) -> (EtfErrorType, Option<Box<dyn Error + Send + Sync>>) {
+        (self.kind, self.source)
+    }
+
+    const fn new(kind: EtfErrorType) -> Self {
+        Self { kind, source: None }
+    }
+}
+
+impl Display for EtfError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            EtfErrorType::DepthExceeded => {
+                f.write_str("terms are nested deeper than the maximum of ")?;
+
+                Display::fmt(&MAX_DEPTH, f)
+            }
+            EtfErrorType::Deserializing => f.write_str("deserializing a term into a value failed"),
+            EtfErrorType::IntegerTooLarge => {
+                f.write_str("integer does not fit in 64 bits and can't be represented")
+            }
+            EtfErrorType::KeyInvalid { tag } => {
+                f.write_str("map key with tag ")?;
+                Display::fmt(tag, f)?;
+
+                f.write_str(" can't be represented as a string")
+            }
+            EtfErrorType::PayloadIncomplete => f.write_str("payload ended unexpectedly"),
+            EtfErrorType::PayloadMalformed { tag } => {
+                f.write_str("term with tag ")?;
+                Display::fmt(tag, f)?;
+
+                f.write_str(" is malformed")
+            }
+            EtfErrorType::Serializing => f.write_str("serializing the payload into a term failed"),
+            EtfErrorType::TagUnsupported { tag } => {
+                f.write_str("term tag ")?;
+                Display::fmt(tag, f)?;
+
+                f.write_str(" is unsupported")
+            }
+            EtfErrorType::VersionInvalid { version } => {
+                f.write_str("format version ")?;
+                Display::fmt(version, f)?;
+
+                f.write_str(" is invalid")
+            }
+        }
+    }
+}
+
+impl Error for EtfError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+impl de::Error for EtfError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self {
+            kind: EtfErrorType::Deserializing,
+            source: Some(msg.to_string().into()),
+        }
+    }
+}
+
+/// Type of [`EtfError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EtfErrorType {
+    /// Lists, tuples, or maps are nested too deeply.
+    DepthExceeded,
+    /// Deserializing a term into a value failed, such as due to the term
+    /// being of the wrong type.
+    Deserializing,
+    /// Big integer does not fit in 64 bits.
+    IntegerTooLarge,
+    /// Map key is not an atom, binary, or integer.
+    KeyInvalid {
+        /// Tag of the key's term.
+        tag: u8,
+    },
+    /// Payload ended before a term was complete.
+    PayloadIncomplete,
+    /// Term is malformed, such as a binary that is not valid UTF-8 or a float
+    /// that can't be parsed.
+    PayloadMalformed {
+        /// Tag of the term.
+        tag: u8,
+    },
+    /// Serializing the payload into a term failed.
+    Serializing,
+    /// Term has a tag that is not supported, such as a compressed term.
+    TagUnsupported {
+        /// Tag of the term.
+        tag: u8,
+    },
+    /// Payload has an invalid format version.
+    VersionInvalid {
+        /// Format version of the payload.
+        version: u8,
+    },
+}
+
+/// Deserialize a value from a payload in the external term format.
+pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, EtfError> {
+    T::deserialize(&mut Deserializer::from_slice(bytes)?)
+}
+
+/// Peek at the opcode, sequence, and event type of a gateway payload in the
+/// external term format.
+///
+/// Returns `None` if the payload has no opcode.
+pub fn peek(bytes: &[u8]) -> Option<(u8, Option<u64>, Option<String>)> {
+    /// Fields of a gateway payload other than its data.
+    #[derive(Deserialize)]
+    struct Header {
+        op: u8,
+        s: Option<u64>,
+        t: Option<String>,
+    }
+
+    let header = from_slice::<Header>(bytes).ok()?;
+
+    Some((header.op, header.s, header.t))
+}
+
+/// Parse a gateway event from a payload in the external term format.
+///
+/// # Errors
+///
+/// Returns a [`GatewayEventParsingErrorType::Deserializing`] error type if the
+/// payload failed to deserialize.
+///
+/// Returns a [`GatewayEventParsingErrorType::PayloadInvalid`] error type if the
+/// payload is not in a supported version of the format.
+pub fn parse_gateway_event(
+    op: u8,
+    sequence: Option<u64>,
+    event_type: Option<&str>,
+    bytes: &[u8],
+) -> Result<GatewayEvent, GatewayEventParsingError> {
+    let mut etf_deserializer =
+        Deserializer::from_slice(bytes).map_err(|source| GatewayEventParsingError {
+            kind: GatewayEventParsingErrorType::PayloadInvalid,
+            source: Some(Box::new(source)),
+        })?;
+
+    GatewayEventDeserializer::new(op, sequence, event_type)
+        .deserialize(&mut etf_deserializer)
+        .map_err(|source| GatewayEventParsingError {
+            kind: GatewayEventParsingErrorType::Deserializing,
+            source: Some(Box::new(source)),
+        })
+}
+
+/// Serialize a value into a payload in the external term format.
+///
+/// Strings, including snowflakes, are encoded as binaries and `null` as the
+/// `nil` atom.
+pub fn to_vec(value: &impl Serialize) -> Result<Vec<u8>, EtfError> {
+    let value = serde_json::to_value(value).map_err(|source| EtfError {
+        kind: EtfErrorType::Serializing,
+        source: Some(Box::new(source)),
+    })?;
+
+    let mut bytes = Vec::from([VERSION]);
+    encode(&mut bytes, &value);
+
+    Ok(bytes)
+}
+
+/// Deserializer of values from a payload in the external term format.
+///
+/// Atoms are deserialized as strings, except for `nil`, `true`, and `false`,
+/// which are deserialized as unit and booleans. Tuples and lists, including
+/// lists of small integers sent as strings, are deserialized as sequences.
+/// Integer map keys are deserialized as strings, like the keys of JSON
+/// objects.
+pub struct Deserializer<'de> {
+    /// Remaining bytes of the payload.
+    bytes: &'de [u8],
+    /// Number of lists, tuples, and maps the current term is nested in.
+    depth: usize,
+}
+
+impl<'de> Deserializer<'de> {
+    /// Create a new deserializer of a payload, failing if it is not in a
+    /// supported version of the format.
+    pub fn from_slice(bytes: &'de [u8]) -> Result<Self, EtfError> {
+        let mut deserializer = Self { bytes, depth: 0 };
+
+        let version = deserializer.u8()?;
+
+        if version != VERSION {
+            return Err(EtfError::new(EtfErrorType::VersionInvalid { version }));
+        }
+
+        Ok(deserializer)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'de [u8], EtfError> {
+        if self.bytes.len() < len {
+            return Err(EtfError::new(EtfErrorType::PayloadIncomplete));
+        }
+
+        let (taken, remaining) = self.bytes.split_at(len);
+        self.bytes = remaining;
+
+        Ok(taken)
+    }
+
+    fn peek(&self) -> Result<u8, EtfError> {
+        self.bytes
+            .first()
+            .copied()
+            .ok_or_else(|| EtfError::new(EtfErrorType::PayloadIncomplete))
+    }
+
+    fn u8(&mut self) -> Result<u8, EtfError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<usize, EtfError> {
+        let bytes = self.take(2)?;
+
+        Ok(usize::from(u16::from_be_bytes([bytes[0], bytes[1]])))
+    }
+
+    fn u32(&mut self) -> Result<usize, EtfError> {
+        let bytes = self.take(4)?;
+
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    /// Whether the next term is the `nil` atom.
+    fn peek_nil(&self) -> bool {
+        matches!(
+            self.bytes,
+            [
+                SMALL_ATOM_EXT | SMALL_ATOM_UTF8_EXT,
+                3,
+                b'n',
+                b'i',
+                b'l',
+                ..
+            ] | [ATOM_EXT | ATOM_UTF8_EXT, 0, 3, b'n', b'i', b'l', ..]
+        )
+    }
+
+    /// Decode the integer of a term whose tag has been taken.
+    fn integer(&mut self, tag: u8) -> Result<Integer, EtfError> {
+        match tag {
+            INTEGER_EXT => {
+                let bytes = self.take(4)?;
+                let integer = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+                Ok(u64::try_from(integer)
+                    .map_or(Integer::Negative(i64::from(integer)), Integer::Positive))
+            }
+            SMALL_INTEGER_EXT => Ok(Integer::Positive(u64::from(self.u8()?))),
+            SMALL_BIG_EXT | LARGE_BIG_EXT => {
+                let len = if tag == SMALL_BIG_EXT {
+                    usize::from(self.u8()?)
+                } else {
+                    self.u32()?
+                };
+                let negative = self.u8()? != 0;
+                let digits = self.take(len)?;
+
+                if digits.iter().skip(8).any(|digit| *digit != 0) {
+                    return Err(EtfError::new(EtfErrorType::IntegerTooLarge));
+                }
+
+                let integer = digits
+                    .iter()
+                    .take(8)
+                    .rev()
+                    .fold(0_u64, |integer, digit| integer << 8 | u64::from(*digit));
+
+                if negative && integer != 0 {
+                    i64::try_from(-i128::from(integer))
+                        .map(Integer::Negative)
+                        .map_err(|_| EtfError::new(EtfErrorType::IntegerTooLarge))
+                } else {
+                    Ok(Integer::Positive(integer))
+                }
+            }
+            tag => Err(EtfError::new(EtfErrorType::TagUnsupported { tag })),
+        }
+    }
+
+    /// Enter a list, tuple, or map, failing if it is nested too deeply.
+    fn nest(&mut self) -> Result<(), EtfError> {
+        if self.depth == MAX_DEPTH {
+            return Err(EtfError::new(EtfErrorType::DepthExceeded));
+        }
+
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    /// Visit the elements of a list or tuple, or the entries of a map, which
+    /// are `len` terms long.
+    ///
+    /// Terms that the visitor doesn't visit are skipped.
+    fn nested<V: Visitor<'de>>(
+        &mut self,
+        len: usize,
+        map: bool,
+        visitor: V,
+    ) -> Result<V::Value, EtfError> {
+        self.nest()?;
+
+        let mut access = Access {
+            de: self,
+            remaining: len,
+        };
+        let value = if map {
+            visitor.visit_map(&mut access)?
+        } else {
+            visitor.visit_seq(&mut access)?
+        };
+
+        for _ in 0..access.remaining {
+            self.skip()?;
+        }
+
+        self.depth -= 1;
+
+        Ok(value)
+    }
+
+    /// Skip the next term without deserializing it.
+    ///
+    /// Nested terms are counted rather than recursed into, so skipping is not
+    /// limited in depth.
+    fn skip(&mut self) -> Result<(), EtfError> {
+        let mut remaining = 1_usize;
+
+        while remaining > 0 {
+            remaining -= 1;
+
+            let tag = self.u8()?;
+            let len = match tag {
+                ATOM_EXT | ATOM_UTF8_EXT | STRING_EXT => self.u16()?,
+                SMALL_ATOM_EXT | SMALL_ATOM_UTF8_EXT => usize::from(self.u8()?),
+                BINARY_EXT => self.u32()?,
+                FLOAT_EXT => 31,
+                NEW_FLOAT_EXT => 8,
+                INTEGER_EXT => 4,
+                SMALL_INTEGER_EXT => 1,
+                // Big integers are followed by their sign.
+                SMALL_BIG_EXT => usize::from(self.u8()?) + 1,
+                LARGE_BIG_EXT => self.u32()?.saturating_add(1),
+                NIL_EXT => 0,
+                // Proper lists end with an empty list.
+                LIST_EXT => {
+                    remaining = remaining.saturating_add(self.u32()?).saturating_add(1);
+
+                    0
+                }
+                SMALL_TUPLE_EXT => {
+                    remaining = remaining.saturating_add(usize::from(self.u8()?));
+
+                    0
+                }
+                LARGE_TUPLE_EXT => {
+                    remaining = remaining.saturating_add(self.u32()?);
+
+                    0
+                }
+                MAP_EXT => {
+                    remaining = remaining.saturating_add(self.u32()?.saturating_mul(2));
+
+                    0
+                }
+                tag => return Err(EtfError::new(EtfErrorType::TagUnsupported { tag })),
+            };
+
+            self.take(len)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = EtfError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EtfError> {
+        let tag = self.u8()?;
+
+        match tag {
+            ATOM_EXT | ATOM_UTF8_EXT | SMALL_ATOM_EXT | SMALL_ATOM_UTF8_EXT => {
+                let len = if matches!(tag, SMALL_ATOM_EXT | SMALL_ATOM_UTF8_EXT) {
+                    usize::from(self.u8()?)
+                } else {
+                    self.u16()?
+                };
+
+                match self.take(len)? {
+                    b"nil" => visitor.visit_unit(),
+                    b"true" => visitor.visit_bool(true),
+                    b"false" => visitor.visit_bool(false),
+                    atom => visitor.visit_borrowed_str(string(tag, atom)?),
+                }
+            }
+            BINARY_EXT => {
+                let len = self.u32()?;
+
+                visitor.visit_borrowed_str(string(tag, self.take(len)?)?)
+            }
+            FLOAT_EXT => {
+                let float = self.take(31)?;
+                let float = str::from_utf8(float)
+                    .ok()
+                    .and_then(|float| float.trim_end_matches('\0').parse::<f64>().ok())
+                    .ok_or_else(|| EtfError::new(EtfErrorType::PayloadMalformed { tag }))?;
+
+                visitor.visit_f64(float)
+            }
+            NEW_FLOAT_EXT => {
+                let bytes = self.take(8)?;
+                let mut float = [0; 8];
+                float.copy_from_slice(bytes);
+
+                visitor.visit_f64(f64::from_be_bytes(float))
+            }
+            INTEGER_EXT | SMALL_INTEGER_EXT | SMALL_BIG_EXT | LARGE_BIG_EXT => {
+                match self.integer(tag)? {
+                    Integer::Negative(integer) => visitor.visit_i64(integer),
+                    Integer::Positive(integer) => visitor.visit_u64(integer),
+                }
+            }
+            LIST_EXT => {
+                let len = self.u32()?;
+                let value = self.nested(len, false, visitor)?;
+
+                if self.u8()? != NIL_EXT {
+                    return Err(EtfError::new(EtfErrorType::PayloadMalformed { tag }));
+                }
+
+                Ok(value)
+            }
+            NIL_EXT => visitor.visit_seq(Access {
+                de: self,
+                remaining: 0,
+            }),
+            SMALL_TUPLE_EXT => {
+                let len = usize::from(self.u8()?);
+
+                self.nested(len, false, visitor)
+            }
+            LARGE_TUPLE_EXT => {
+                let len = self.u32()?;
+
+                self.nested(len, false, visitor)
+            }
+            MAP_EXT => {
+                let len = self.u32()?;
+
+                self.nested(len.saturating_mul(2), true, visitor)
+            }
+            // Lists of small integers are sent as strings of bytes.
+            STRING_EXT => {
+                let len = self.u16()?;
+                let bytes = self.take(len)?;
+
+                SeqDeserializer::new(bytes.iter().copied()).deserialize_any(visitor)
+            }
+            tag => Err(EtfError::new(EtfErrorType::TagUnsupported { tag })),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EtfError> {
+        if self.peek_nil() {
+            self.skip()?;
+
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, EtfError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, EtfError> {
+        // Variants with data are maps of the variant to the data, while unit
+        // variants are only the variant.
+        if self.peek()? != MAP_EXT {
+            return visitor.visit_enum(Enum {
+                de: self,
+                data: false,
+            });
+        }
+
+        self.u8()?;
+        let len = self.u32()?;
+
+        if len != 1 {
+            return Err(de::Error::invalid_length(len, &"a map of one variant"));
+        }
+
+        self.nest()?;
+        let value = visitor.visit_enum(Enum {
+            de: self,
+            data: true,
+        })?;
+        self.depth -= 1;
+
+        Ok(value)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EtfError> {
+        self.skip()?;
+
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier
+    }
+}
+
+/// Integer decoded from a term.
+enum Integer {
+    Negative(i64),
+    Positive(u64),
+}
+
+/// Access to the elements of a list or tuple, or the entries of a map.
+struct Access<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    /// Number of terms remaining, which for maps is twice the number of
+    /// entries.
+    remaining: usize,
+}
+
+impl<'de> SeqAccess<'de> for Access<'_, 'de> {
+    type Error = EtfError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, EtfError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de> MapAccess<'de> for Access<'_, 'de> {
+    type Error = EtfError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, EtfError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+
+        seed.deserialize(MapKey { de: &mut *self.de }).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, EtfError> {
+        self.remaining = self.remaining.saturating_sub(1);
+
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining / 2)
+    }
+}
+
+/// Deserializer of a map key, which must be an atom, binary, or integer.
+struct MapKey<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de> de::Deserializer<'de> for MapKey<'_, 'de> {
+    type Error = EtfError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EtfError> {
+        match self.de.peek()? {
+            ATOM_EXT | ATOM_UTF8_EXT | SMALL_ATOM_EXT | SMALL_ATOM_UTF8_EXT | BINARY_EXT => {
+                self.de.deserialize_any(visitor)
+            }
+            tag @ (INTEGER_EXT | SMALL_INTEGER_EXT | SMALL_BIG_EXT | LARGE_BIG_EXT) => {
+                self.de.u8()?;
+
+                match self.de.integer(tag)? {
+                    Integer::Negative(integer) => visitor.visit_string(integer.to_string()),
+                    Integer::Positive(integer) => visitor.visit_string(integer.to_string()),
+                }
+            }
+            tag => Err(EtfError::new(EtfErrorType::KeyInvalid { tag })),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, EtfError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct seq tuple tuple_struct map
+        struct enum identifier ignored_any
+    }
+}
+
+/// Access to the variant of an enum, and its data if it has any.
+struct Enum<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    /// Whether the variant is followed by its data.
+    data: bool,
+}
+
+impl<'de> EnumAccess<'de> for Enum<'_, 'de> {
+    type Error = EtfError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), EtfError> {
+        let variant = seed.deserialize(MapKey { de: &mut *self.de })?;
+
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Enum<'_, 'de> {
+    type Error = EtfError;
+
+    fn unit_variant(self) -> Result<(), EtfError> {
+        if self.data {
+            de::Deserialize::deserialize(self.de)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, EtfError> {
+        if self.data {
+            seed.deserialize(self.de)
+        } else {
+            Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            ))
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, EtfError> {
+        if self.data {
+            de::Deserializer::deserialize_any(self.de, visitor)
+        } else {
+            Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"tuple variant",
+            ))
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, EtfError> {
+        if self.data {
+            de::Deserializer::deserialize_any(self.de, visitor)
+        } else {
+            Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"struct variant",
+            ))
+        }
+    }
+}
+
+/// Decode a binary or atom, failing if it is not valid UTF-8.
+fn string(tag: u8, bytes: &[u8]) -> Result<&str, EtfError> {
+    str::from_utf8(bytes).map_err(|source| EtfError {
+        kind: EtfErrorType::PayloadMalformed { tag },
+        source: Some(Box::new(source)),
+    })
+}
+
+/// Encode a JSON value as a term.
+fn encode(bytes: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => atom(bytes, "nil"),
+        Value::Bool(true) => atom(bytes, "true"),
+        Value::Bool(false) => atom(bytes, "false"),
+        Value::Number(number) => {
+            if let Some(integer) = number.as_u64() {
+                integer_unsigned(bytes, integer);
+            } else if let Some(integer) = number.as_i64() {
+                integer_signed(bytes, integer);
+            } else if let Some(float) = number.as_f64() {
+                bytes.push(NEW_FLOAT_EXT);
+                bytes.extend_from_slice(&float.to_be_bytes());
+            }
+        }
+        Value::String(string) => binary(bytes, string),
+        Value::Array(array) if array.is_empty() => bytes.push(NIL_EXT),
+        Value::Array(array) => {
+            bytes.push(LIST_EXT);
+            bytes.extend_from_slice(&length(array.len()).to_be_bytes());
+
+            for value in array {
+                encode(bytes, value);
+            }
+
+            bytes.push(NIL_EXT);
+        }
+        Value::Object(map) => {
+            bytes.push(MAP_EXT);
+            bytes.extend_from_slice(&length(map.len()).to_be_bytes());
+
+            for (key, value) in map {
+                binary(bytes, key);
+                encode(bytes, value);
+            }
+        }
+    }
+}
+
+fn atom(bytes: &mut Vec<u8>, atom: &str) {
+    bytes.push(SMALL_ATOM_UTF8_EXT);
+    // Only short atoms are encoded.
+    #[allow(clippy::cast_possible_truncation)]
+    bytes.push(atom.len() as u8);
+    bytes.extend_from_slice(atom.as_bytes());
+}
+
+fn binary(bytes: &mut Vec<u8>, string: &str) {
+    bytes.push(BINARY_EXT);
+    bytes.extend_from_slice(&length(string.len()).to_be_bytes());
+    bytes.extend_from_slice(string.as_bytes());
+}
+
+fn integer_signed(bytes: &mut Vec<u8>, integer: i64) {
+    if let Ok(integer) = i32::try_from(integer) {
+        bytes.push(INTEGER_EXT);
+        bytes.extend_from_slice(&integer.to_be_bytes());
+    } else {
+        big(bytes, integer < 0, integer.unsigned_abs());
+    }
+}
+
+fn integer_unsigned(bytes: &mut Vec<u8>, integer: u64) {
+    if let Ok(integer) = u8::try_from(integer) {
+        bytes.push(SMALL_INTEGER_EXT);
+        bytes.push(integer);
+    } else if let Ok(integer) = i32::try_from(integer) {
+        bytes.push(INTEGER_EXT);
+        bytes.extend_from_slice(&integer.to_be_bytes());
+    } else {
+        big(bytes, false, integer);
+    }
+}
+
+fn big(bytes: &mut Vec<u8>, negative: bool, integer: u64) {
+    let digits = integer.to_le_bytes();
+    let len = digits
+        .iter()
+        .rposition(|digit| *digit != 0)
+        .map_or(0, |idx| idx + 1);
+
+    bytes.push(SMALL_BIG_EXT);
+    // At most 8 digits.
+    #[allow(clippy::cast_possible_truncation)]
+    bytes.push(len as u8);
+    bytes.push(u8::from(negative));
+    bytes.extend_from_slice(&digits[..len]);
+}
+
+/// Length of a collection as encoded in a term.
+///
+/// Payloads sent to the gateway are far smaller than 4 GiB.
+#[allow(clippy::cast_possible_truncation)]
+const fn length(len: usize) -> u32 {
+    len as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_slice, peek, to_vec, EtfError, EtfErrorType, MAX_DEPTH};
+    use serde::{de::IgnoredAny, Deserialize};
+    use serde_json::{json, Value};
+    use static_assertions::{assert_fields, assert_impl_all};
+    use std::{collections::HashMap, error::Error, fmt::Debug};
+    use twilight_model::{
+        gateway::{
+            event::{DispatchEvent, GatewayEvent},
+            payload::outgoing::Heartbeat,
+            presence::Status,
+        },
+        id::{marker::UserMarker, Id},
+    };
+
+    assert_impl_all!(EtfErrorType: Debug, Send, Sync);
+    assert_fields!(EtfErrorType::KeyInvalid: tag);
+    assert_fields!(EtfErrorType::PayloadMalformed: tag);
+    assert_fields!(EtfErrorType::TagUnsupported: tag);
+    assert_fields!(EtfErrorType::VersionInvalid: version);
+    assert_impl_all!(EtfError: Error, Send, Sync);
+
+    fn round_trip(value: &Value) -> Value {
+        from_slice(&to_vec(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn values() {
+        let value = json!({
+            "array": [1, "two", null],
+            "bool": true,
+            "empty": [],
+            "float": 1.5,
+            "negative": -100_000_000_000_i64,
+            "nested": { "key": false },
+            "small": 5,
+            "string": "with \"quotes\"",
+            "unsigned": u64::MAX,
+        });
+
+        assert_eq!(value, round_trip(&value));
+    }
+
+    #[test]
+    fn heartbeat() {
+        let bytes = to_vec(&Heartbeat::new(1_234)).unwrap();
+
+        assert_eq!(
+            json!({ "d": 1_234, "op": 1 }),
+            from_slice::<Value>(&bytes).unwrap()
+        );
+        assert_eq!(Some((1, None, None)), peek(&bytes));
+    }
+
+    /// Snowflakes sent as integers deserialize into IDs.
+    #[test]
+    fn snowflake_integer() {
+        #[derive(Deserialize)]
+        struct User {
+            id: Id<UserMarker>,
+        }
+
+        // Map of `id` to 400_000_000_000_000_000 as a small big integer.
+        let bytes = [
+            131, 116, 0, 0, 0, 1, 109, 0, 0, 0, 2, b'i', b'd', 110, 8, 0, 0, 0, 40, 118, 225, 21,
+            141, 5,
+        ];
+
+        let user = from_slice::<User>(&bytes).unwrap();
+        assert_eq!(Id::new(400_000_000_000_000_000), user.id);
+    }
+
+    /// Lists of small integers are sent as strings of bytes.
+    #[test]
+    fn string_ext() {
+        let bytes = [131, 107, 0, 3, 1, 2, 3];
+
+        assert_eq!(vec![1_u8, 2, 3], from_slice::<Vec<u8>>(&bytes).unwrap());
+    }
+
+    #[test]
+    fn atoms() {
+        let bytes = [
+            131, 104, 3, 115, 3, b'n', b'i', b'l', 100, 0, 4, b't', b'r', b'u', b'e', 119, 2, b'o',
+            b'k',
+        ];
+
+        assert_eq!(
+            json!([null, true, "ok"]),
+            from_slice::<Value>(&bytes).unwrap()
+        );
+        assert_eq!(
+            (None, true, "ok".to_owned()),
+            from_slice::<(Option<u8>, bool, String)>(&bytes).unwrap()
+        );
+    }
+
+    #[test]
+    fn enums() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Data {
+            Newtype(u8),
+            Unit,
+        }
+
+        let bytes = to_vec(&json!(["online", "Unit", { "Newtype": 5 }])).unwrap();
+
+        assert_eq!(
+            (Status::Online, Data::Unit, Data::Newtype(5)),
+            from_slice::<(Status, Data, Data)>(&bytes).unwrap()
+        );
+    }
+
+    /// Integer map keys deserialize as strings.
+    #[test]
+    fn integer_keys() {
+        // Map of 1 and 400_000_000_000_000_000 to `true`.
+        let bytes = [
+            131, 116, 0, 0, 0, 2, 97, 1, 115, 4, b't', b'r', b'u', b'e', 110, 8, 0, 0, 0, 40, 118,
+            225, 21, 141, 5, 115, 4, b't', b'r', b'u', b'e',
+        ];
+
+        let map = from_slice::<HashMap<String, bool>>(&bytes).unwrap();
+        assert_eq!(Some(&true), map.get("1"));
+        assert_eq!(Some(&true), map.get("400000000000000000"));
+
+        let map = from_slice::<HashMap<Id<UserMarker>, bool>>(&bytes).unwrap();
+        assert_eq!(Some(&true), map.get(&Id::new(400_000_000_000_000_000)));
+
+        assert!(matches!(
+            from_slice::<Value>(&[131, 116, 0, 0, 0, 1, 106, 97, 1])
+                .unwrap_err()
+                .kind(),
+            EtfErrorType::KeyInvalid { tag: 106 }
+        ));
+    }
+
+    /// Fields that aren't deserialized are skipped, including the remaining
+    /// elements of sequences.
+    #[test]
+    fn skipped() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Partial {
+            first: (u8,),
+            last: u8,
+        }
+
+        let bytes = to_vec(&json!({
+            "first": [1, [2, { "key": "value" }], 3],
+            "ignored": { "nested": [[[null]]], "float": 1.5 },
+            "last": 4,
+        }))
+        .unwrap();
+
+        assert_eq!(
+            Partial {
+                first: (1,),
+                last: 4,
+            },
+            from_slice(&bytes).unwrap()
+        );
+        assert!(from_slice::<IgnoredAny>(&bytes).is_ok());
+    }
+
+    #[test]
+    fn gateway_event() {
+        let bytes = to_vec(&json!({
+            "d": {
+                "channel_id": 2,
+                "guild_id": null,
+                "timestamp": 1_000,
+                "user_id": 3,
+            },
+            "op": 0,
+            "s": 5,
+            "t": "TYPING_START",
+        }))
+        .unwrap();
+
+        let (op, seq, event_type) = peek(&bytes).unwrap();
+        assert_eq!(
+            (0, Some(5), Some("TYPING_START")),
+            (op, seq, event_type.as_deref())
+        );
+
+        let event = super::parse_gateway_event(op, seq, event_type.as_deref(), &bytes).unwrap();
+        assert!(matches!(
+            event,
+            GatewayEvent::Dispatch(5, DispatchEvent::TypingStart(typing))
+                if typing.channel_id == Id::new(2) && typing.guild_id.is_none()
+        ));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            from_slice::<Value>(&[130, 97, 1]).unwrap_err().kind(),
+            EtfErrorType::VersionInvalid { version: 130 }
+        ));
+        assert!(matches!(
+            from_slice::<Value>(&[131, 109, 0, 0, 0, 5, b'a'])
+                .unwrap_err()
+                .kind(),
+            EtfErrorType::PayloadIncomplete
+        ));
+        assert!(matches!(
+            from_slice::<Value>(&[131, 80, 0, 0, 0, 1])
+                .unwrap_err()
+                .kind(),
+            EtfErrorType::TagUnsupported { tag: 80 }
+        ));
+        assert!(matches!(
+            from_slice::<String>(&[131, 97, 1]).unwrap_err().kind(),
+            EtfErrorType::Deserializing
+        ));
+        assert!(matches!(
+            from_slice::<u64>(&[131, 98, 255, 255, 255, 255])
+                .unwrap_err()
+                .kind(),
+            EtfErrorType::Deserializing
+        ));
+        assert_eq!(None, peek(&[131, 97, 1]));
+    }
+
+    #[test]
+    fn malformed() {
+        // Binary that is not valid UTF-8.
+        assert!(matches!(
+            from_slice::<Value>(&[131, 109, 0, 0, 0, 2, 0xc3, 0x28])
+                .unwrap_err()
+                .kind(),
+            EtfErrorType::PayloadMalformed { tag: 109 }
+        ));
+
+        let mut float = Vec::from([131, 99]);
+        float.extend_from_slice(&[b'x'; 31]);
+        assert!(matches!(
+            from_slice::<Value>(&float).unwrap_err().kind(),
+            EtfErrorType::PayloadMalformed { tag: 99 }
+        ));
+
+        // Improper list, whose tail is not an empty list.
+        assert!(matches!(
+            from_slice::<Value>(&[131, 108, 0, 0, 0, 1, 97, 1, 97, 2])
+                .unwrap_err()
+                .kind(),
+            EtfErrorType::PayloadMalformed { tag: 108 }
+        ));
+
+        // Big integer that does not fit in 64 bits.
+        assert!(matches!(
+            from_slice::<Value>(&[131, 110, 9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1])
+                .unwrap_err()
+                .kind(),
+            EtfErrorType::IntegerTooLarge
+        ));
+    }
+
+    #[test]
+    fn depth() {
+        let nested = |depth| {
+            let mut bytes = Vec::from([131]);
+            bytes.extend((0..depth).flat_map(|_| [104, 1]));
+            bytes.push(106);
+
+            bytes
+        };
+
+        assert!(from_slice::<Value>(&nested(MAX_DEPTH)).is_ok());
+        assert!(matches!(
+            from_slice::<Value>(&nested(MAX_DEPTH + 1))
+                .unwrap_err()
+                .kind(),
+            EtfErrorType::DepthExceeded
+        ));
+        assert!(matches!(
+            from_slice::<Value>(&nested(100_000)).unwrap_err().kind(),
+            EtfErrorType::DepthExceeded
+        ));
+
+        // Skipping terms does not recurse.
+        assert!(from_slice::<IgnoredAny>(&nested(100_000)).is_ok());
+    }
+}
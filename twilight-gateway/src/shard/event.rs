@@ -12,13 +12,13 @@
 //! [`EventType`]: ::twilight_model::gateway::event::EventType
 //! [`ShardBuilder::event_types`]: crate::shard::ShardBuilder::event_types
 
+use super::{channel::Receiver, LazyEvent};
 use crate::EventTypeFlags;
 use futures_util::stream::Stream;
 use std::{
     pin::Pin,
     task::{Context, Poll},
 };
-use tokio::sync::mpsc::UnboundedReceiver;
 use twilight_model::gateway::event::Event;
 
 /// A stream of events from a [`Shard`].
@@ -39,11 +39,11 @@ use twilight_model::gateway::event::Event;
 #[derive(Debug)]
 pub struct Events {
     event_types: EventTypeFlags,
-    rx: UnboundedReceiver<Event>,
+    rx: Receiver,
 }
 
 impl Events {
-    pub(super) const fn new(event_types: EventTypeFlags, rx: UnboundedReceiver<Event>) -> Self {
+    pub(super) const fn new(event_types: EventTypeFlags, rx: Receiver) -> Self {
         Self { event_types, rx }
     }
 
@@ -57,7 +57,15 @@ impl Stream for Events {
     type Item = Event;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        self.rx.poll_recv(cx)
+        loop {
+            return match self.rx.poll_recv(cx) {
+                Poll::Ready(Some(LazyEvent::Event(event))) => Poll::Ready(Some(event)),
+                // Raw events are only emitted by lazy shards.
+                Poll::Ready(Some(LazyEvent::Raw(_))) => continue,
+                Poll::Ready(None) => Poll::Ready(None),
+                Poll::Pending => Poll::Pending,
+            };
+        }
     }
 }
 
@@ -0,0 +1,288 @@
+//! Filtering of dispatch events by the guild they belong to.
+
+use super::{etf, Encoding};
+use serde::Deserialize;
+use std::{
+    collections::HashSet,
+    fmt::{Debug, Formatter, Result as FmtResult},
+    sync::Arc,
+};
+use twilight_model::id::{marker::GuildMarker, Id};
+
+/// Filter of the guilds whose dispatch events are emitted.
+///
+/// The ID of the guild a dispatch event belongs to is peeked at before the
+/// event is deserialized, so events of guilds that are filtered out cost
+/// little to drop. Dispatch events that don't belong to a guild, such as
+/// direct messages, are always emitted.
+///
+/// # Examples
+///
+/// Only emit the events of two guilds:
+///
+/// ```
+/// use twilight_gateway::shard::GuildFilter;
+/// use twilight_model::id::Id;
+///
+/// let filter = GuildFilter::allow([Id::new(1), Id::new(2)]);
+/// assert!(filter.allows(Id::new(1)));
+/// assert!(!filter.allows(Id::new(3)));
+/// ```
+#[derive(Clone)]
+#[must_use = "has no effect if not given to a shard builder"]
+pub struct GuildFilter(Arc<dyn Fn(Id<GuildMarker>) -> bool + Send + Sync>);
+
+impl GuildFilter {
+    /// Create a new filter emitting the events of guilds matching a
+    /// predicate.
+    pub fn new(predicate: impl Fn(Id<GuildMarker>) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(predicate))
+    }
+
+    /// Create a new filter only emitting the events of a set of guilds.
+    pub fn allow(guild_ids: impl IntoIterator<Item = Id<GuildMarker>>) -> Self {
+        let guild_ids = guild_ids.into_iter().collect::<HashSet<_>>();
+
+        Self::new(move |guild_id| guild_ids.contains(&guild_id))
+    }
+
+    /// Whether the events of a guild are emitted.
+    #[must_use = "checking a filter has no effect if left unused"]
+    pub fn allows(&self, guild_id: Id<GuildMarker>) -> bool {
+        (self.0)(guild_id)
+    }
+}
+
+impl Debug for GuildFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_tuple("GuildFilter").finish()
+    }
+}
+
+/// Peek at the ID of the guild a dispatch event belongs to without
+/// deserializing the event.
+///
+/// This is the `guild_id` field of the event's data, or its `id` field for
+/// events about the guild itself.
+pub(crate) fn peek_guild_id(
+    event_type: &str,
+    encoding: Encoding,
+    bytes: &[u8],
+) -> Option<Id<GuildMarker>> {
+    let guild = matches!(event_type, "GUILD_CREATE" | "GUILD_DELETE" | "GUILD_UPDATE");
+
+    match encoding {
+        Encoding::Etf => peek_etf(guild, bytes),
+        Encoding::Json => peek_json(guild, bytes),
+    }
+}
+
+/// Peek at the guild ID of a payload in the [`Encoding::Etf`] encoding,
+/// skipping the other fields of its data.
+fn peek_etf(guild: bool, bytes: &[u8]) -> Option<Id<GuildMarker>> {
+    #[derive(Deserialize)]
+    struct Payload<T> {
+        d: T,
+    }
+
+    #[derive(Deserialize)]
+    struct Guild {
+        id: Option<Id<GuildMarker>>,
+    }
+
+    #[derive(Deserialize)]
+    struct GuildResource {
+        guild_id: Option<Id<GuildMarker>>,
+    }
+
+    if guild {
+        etf::from_slice::<Payload<Guild>>(bytes).ok()?.d.id
+    } else {
+        etf::from_slice::<Payload<GuildResource>>(bytes)
+            .ok()?
+            .d
+            .guild_id
+    }
+}
+
+/// Peek at the guild ID of a payload in the [`Encoding::Json`] encoding by
+/// scanning for its key.
+fn peek_json(guild: bool, json: &[u8]) -> Option<Id<GuildMarker>> {
+    let key: &[u8] = if guild { b"id" } else { b"guild_id" };
+
+    let mut depth = 0_usize;
+    let mut index = 0;
+
+    while let Some(byte) = json.get(index) {
+        match byte {
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            b'"' => {
+                let start = index + 1;
+                let end = start + string_len(json.get(start..)?)?;
+                index = end;
+
+                // Fields of the event's data are nested in the payload's `d`
+                // object.
+                if depth == 2 && &json[start..end] == key {
+                    let rest = skip_whitespace(json.get(end + 1..)?);
+
+                    if let Some(rest) = rest.strip_prefix(b":") {
+                        return parse_id(skip_whitespace(rest));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        index += 1;
+    }
+
+    None
+}
+
+/// Length of a string's contents up to its closing quote.
+fn string_len(json: &[u8]) -> Option<usize> {
+    let mut escaped = false;
+
+    json.iter().position(|byte| {
+        let end = !escaped && *byte == b'"';
+        escaped = !escaped && *byte == b'\\';
+
+        end
+    })
+}
+
+/// Skip leading JSON whitespace.
+fn skip_whitespace(json: &[u8]) -> &[u8] {
+    let start = json
+        .iter()
+        .position(|byte| !matches!(byte, b' ' | b'\n' | b'\r' | b'\t'))
+        .unwrap_or(json.len());
+
+    &json[start..]
+}
+
+/// Parse an ID serialized as a string, such as `"123"`, or as an integer,
+/// such as `123`.
+fn parse_id(json: &[u8]) -> Option<Id<GuildMarker>> {
+    let digits = if let Some(json) = json.strip_prefix(b"\"") {
+        let end = json.iter().position(|byte| *byte == b'"')?;
+
+        &json[..end]
+    } else {
+        let end = json
+            .iter()
+            .position(|byte| !byte.is_ascii_digit())
+            .unwrap_or(json.len());
+
+        &json[..end]
+    };
+
+    if digits.is_empty() {
+        return None;
+    }
+
+    let id = digits.iter().try_fold(0_u64, |id, byte| {
+        let digit = char::from(*byte).to_digit(10)?;
+
+        id.checked_mul(10)?.checked_add(u64::from(digit))
+    })?;
+
+    Id::new_checked(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Encoding, GuildFilter};
+    use serde_json::json;
+    use static_assertions::assert_impl_all;
+    use std::fmt::Debug;
+    use twilight_model::id::Id;
+
+    assert_impl_all!(GuildFilter: Clone, Debug, Send, Sync);
+
+    #[test]
+    fn filter() {
+        let filter = GuildFilter::new(|guild_id| guild_id.get() % 2 == 0);
+        assert!(filter.allows(Id::new(2)));
+        assert!(!filter.allows(Id::new(3)));
+    }
+
+    #[test]
+    fn peek_guild_id() {
+        let json = br#"{"t":"MESSAGE_CREATE","s":1,"op":0,"d":{"author":{"id":"3"},"content":"\"guild_id\":\"4\"","guild_id":"2","id":"5"}}"#;
+        assert_eq!(
+            Some(Id::new(2)),
+            super::peek_guild_id("MESSAGE_CREATE", Encoding::Json, json)
+        );
+
+        let json = br#"{"t":"GUILD_CREATE","s":1,"op":0,"d":{"roles":[{"id":"6"}], "id" : "7"}}"#;
+        assert_eq!(
+            Some(Id::new(7)),
+            super::peek_guild_id("GUILD_CREATE", Encoding::Json, json)
+        );
+
+        let json = br#"{"t":"MESSAGE_CREATE","s":1,"op":0,"d":{"channel_id":"1","guild_id":null}}"#;
+        assert_eq!(
+            None,
+            super::peek_guild_id("MESSAGE_CREATE", Encoding::Json, json)
+        );
+
+        let json = br#"{"t":"GUILD_DELETE","s":1,"op":0,"d":{"id":9,"unavailable":true}}"#;
+        assert_eq!(
+            Some(Id::new(9)),
+            super::peek_guild_id("GUILD_DELETE", Encoding::Json, json)
+        );
+
+        let json = br#"{"t":"MESSAGE_CREATE","s":1,"op":0,"d":{"guild_id":-1}}"#;
+        assert_eq!(
+            None,
+            super::peek_guild_id("MESSAGE_CREATE", Encoding::Json, json)
+        );
+
+        let json = br#"{"t":"TYPING_START","s":1,"op":0,"d":{"member":{"guild_id":"8"}}}"#;
+        assert_eq!(
+            None,
+            super::peek_guild_id("TYPING_START", Encoding::Json, json)
+        );
+    }
+
+    #[test]
+    fn peek_guild_id_etf() {
+        let peek = |event_type, payload| {
+            let bytes = Encoding::Etf.to_vec(&payload).unwrap();
+
+            super::peek_guild_id(event_type, Encoding::Etf, &bytes)
+        };
+
+        assert_eq!(
+            Some(Id::new(2)),
+            peek(
+                "MESSAGE_CREATE",
+                json!({"t": "MESSAGE_CREATE", "op": 0, "d": {"author": {"id": 3}, "guild_id": 2, "id": 5}}),
+            )
+        );
+        assert_eq!(
+            Some(Id::new(7)),
+            peek(
+                "GUILD_CREATE",
+                json!({"t": "GUILD_CREATE", "op": 0, "d": {"roles": [{"id": 6}], "id": 7}}),
+            )
+        );
+        assert_eq!(
+            None,
+            peek(
+                "MESSAGE_CREATE",
+                json!({"t": "MESSAGE_CREATE", "op": 0, "d": {"channel_id": 1, "guild_id": null}}),
+            )
+        );
+        assert_eq!(
+            None,
+            peek(
+                "TYPING_START",
+                json!({"t": "TYPING_START", "op": 0, "d": {"member": {"guild_id": 8}}}),
+            )
+        );
+    }
+}
@@ -1,30 +1,39 @@
 use super::{
     builder::ShardBuilder,
-    command::Command,
+    channel::Receiver,
+    command::{self, Command},
     config::Config,
     emitter::Emitter,
     event::Events,
-    json,
+    lazy::LazyEvents,
+    members::{CancelOnDrop, GuildMembers, MemberRequests},
+    presence::CurrentPresence,
+    priority::Priority,
     processor::{ConnectingErrorType, Latency, Session, ShardProcessor},
-    raw_message::Message,
+    raw_message::{CloseFrame, Message},
     stage::Stage,
+    Compression,
 };
 use crate::Intents;
-use leaky_bucket_lite::LeakyBucket;
 use serde::{Deserialize, Serialize};
 use std::{
-    borrow::Cow,
     error::Error,
-    fmt::{Display, Formatter, Result as FmtResult},
-    sync::{atomic::Ordering, Arc, Mutex},
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
 };
 use tokio::{
     sync::{watch::Receiver as WatchReceiver, OnceCell},
     task::JoinHandle,
-    time::Instant,
+    time::{self, Duration, Instant},
 };
-use tokio_tungstenite::tungstenite::protocol::{
-    frame::coding::CloseCode, CloseFrame as TungsteniteCloseFrame,
+use twilight_model::gateway::{
+    payload::outgoing::{
+        update_presence::UpdatePresencePayload, RequestGuildMembers, UpdatePresence,
+    },
+    OpCode,
 };
 
 /// Sending a command failed.
@@ -80,7 +89,7 @@ impl Display for CommandError {
             CommandErrorType::Sending => {
                 f.write_str("sending the message over the websocket failed")
             }
-            CommandErrorType::Serializing => f.write_str("serializing the value as json failed"),
+            CommandErrorType::Serializing => f.write_str("serializing the value failed"),
             CommandErrorType::SessionInactive => Display::fmt(&SessionInactiveError, f),
         }
     }
@@ -103,7 +112,7 @@ pub enum CommandErrorType {
     /// Sending the payload over the WebSocket failed. This is indicative of a
     /// shutdown shard.
     Sending,
-    /// Serializing the payload as JSON failed.
+    /// Serializing the payload in the configured encoding failed.
     Serializing,
     /// Shard's session is inactive because the shard hasn't been started.
     SessionInactive,
@@ -186,6 +195,69 @@ pub enum SendErrorType {
     SessionInactive,
 }
 
+/// Requesting a guild's members failed.
+#[derive(Debug)]
+pub struct RequestGuildMembersError {
+    kind: RequestGuildMembersErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl RequestGuildMembersError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &RequestGuildMembersErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(
+        self,
+    ) -> (
+        RequestGuildMembersErrorType,
+        Option<Box<dyn Error + Send + Sync>>,
+    ) {
+        (self.kind, self.source)
+    }
+}
+
+impl Display for RequestGuildMembersError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            RequestGuildMembersErrorType::Command => f.write_str("sending the request failed"),
+            RequestGuildMembersErrorType::TimedOut => {
+                f.write_str("not all member chunks were received in time")
+            }
+        }
+    }
+}
+
+impl Error for RequestGuildMembersError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// Type of [`RequestGuildMembersError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RequestGuildMembersErrorType {
+    /// Sending the request failed.
+    ///
+    /// The source error is a [`CommandError`].
+    Command,
+    /// Not all member chunks were received before the timeout elapsed.
+    TimedOut,
+}
+
 /// Starting a shard and connecting to the gateway failed.
 #[derive(Debug)]
 pub struct ShardStartError {
@@ -219,6 +291,12 @@ impl Display for ShardStartError {
             ShardStartErrorType::AlreadyStarted => {
                 f.write_str("shard has already been previously started")
             }
+            ShardStartErrorType::CompressionUnsupported { compression } => {
+                f.write_str("compression ")?;
+                Debug::fmt(compression, f)?;
+
+                f.write_str(" requires the `zlib-stock` or `zlib-simd` feature")
+            }
             ShardStartErrorType::Establishing => f.write_str("establishing the connection failed"),
             ShardStartErrorType::ParsingGatewayUrl { url } => {
                 f.write_str("the gateway url `")?;
@@ -247,6 +325,12 @@ pub enum ShardStartErrorType {
     /// Shards can't be started multiple times; you need to create a new
     /// instance of the shard.
     AlreadyStarted,
+    /// Configured compression requires the `zlib-stock` or `zlib-simd`
+    /// feature, neither of which is enabled.
+    CompressionUnsupported {
+        /// Configured compression.
+        compression: Compression,
+    },
     /// Establishing a connection to the gateway failed.
     Establishing,
     /// Parsing the gateway URL provided by Discord to connect to the gateway
@@ -261,8 +345,11 @@ pub enum ShardStartErrorType {
 /// and connection stage.
 #[derive(Clone, Debug)]
 pub struct Information {
+    dropped_events: u64,
     id: u64,
     latency: Latency,
+    /// Number of commands waiting for the ratelimiter, by priority.
+    queued: [usize; Priority::ALL.len()],
     ratelimit_refill: Option<Instant>,
     ratelimit_requests: Option<u32>,
     session_id: Option<Box<str>>,
@@ -272,6 +359,15 @@ pub struct Information {
 }
 
 impl Information {
+    /// Number of events dropped because the bounded event buffer was full.
+    ///
+    /// This is always 0 if no [event buffer capacity] has been configured.
+    ///
+    /// [event buffer capacity]: super::ShardBuilder::event_buffer_capacity
+    pub const fn dropped_events(&self) -> u64 {
+        self.dropped_events
+    }
+
     /// Return the ID of the shard.
     pub const fn id(&self) -> u64 {
         self.id
@@ -285,6 +381,13 @@ impl Information {
         &self.latency
     }
 
+    /// Number of commands of a priority waiting for the ratelimiter.
+    ///
+    /// This is always 0 if payload ratelimiting has been disabled.
+    pub const fn queued(&self, priority: Priority) -> usize {
+        self.queued[priority.index()]
+    }
+
     /// When the ratelimiter will next refill the [`ratelimit_requests`].
     ///
     /// This will be `None` if payload ratelimiting has been disabled.
@@ -405,7 +508,12 @@ pub struct ResumeSession {
 #[derive(Debug)]
 pub struct Shard {
     config: Arc<Config>,
+    dropped_events: Arc<AtomicU64>,
     emitter: Mutex<Option<Emitter>>,
+    /// Requests for guild members waiting for their member chunks.
+    member_requests: Arc<MemberRequests>,
+    /// Presence most recently set, identified with by new sessions.
+    presence: Arc<CurrentPresence>,
     processor_handle: OnceCell<JoinHandle<()>>,
     session: OnceCell<WatchReceiver<Arc<Session>>>,
 }
@@ -446,19 +554,42 @@ impl Shard {
     }
 
     pub(crate) fn new_with_config(config: Config) -> (Self, Events) {
+        let (this, rx) = Self::new_with_emitter(config, false);
+        let event_types = this.config.event_types();
+
+        (this, Events::new(event_types, rx))
+    }
+
+    pub(crate) fn new_with_config_lazy(config: Config) -> (Self, LazyEvents) {
+        let (this, rx) = Self::new_with_emitter(config, true);
+        let event_types = this.config.event_types();
+
+        (this, LazyEvents::new(event_types, rx))
+    }
+
+    fn new_with_emitter(config: Config, lazy: bool) -> (Self, Receiver) {
         let config = Arc::new(config);
         let event_types = config.event_types();
 
-        let (emitter, rx) = Emitter::new(event_types);
+        let (emitter, rx) = match config.event_buffer_capacity() {
+            Some(capacity) => Emitter::bounded(event_types, capacity, config.overflow_policy()),
+            None => Emitter::new(event_types),
+        };
+        let emitter = emitter.lazy(lazy);
+
+        let presence = Arc::new(CurrentPresence::new(config.presence().cloned()));
 
         let this = Self {
             config,
+            dropped_events: emitter.dropped_events(),
             emitter: Mutex::new(Some(emitter)),
+            member_requests: Arc::new(MemberRequests::default()),
+            presence,
             processor_handle: OnceCell::new(),
             session: OnceCell::new(),
         };
 
-        (this, Events::new(event_types, rx))
+        (this, rx)
     }
 
     /// Create a builder to configure and construct a shard.
@@ -485,6 +616,9 @@ impl Shard {
     /// Returns a [`ShardStartErrorType::AlreadyStarted`] error type if the
     /// shard has already been started.
     ///
+    /// Returns a [`ShardStartErrorType::CompressionUnsupported`] error type if
+    /// the configured compression requires a feature that isn't enabled.
+    ///
     /// Returns a [`ShardStartErrorType::Establishing`] error type if
     /// establishing a connection to the gateway failed.
     ///
@@ -494,6 +628,15 @@ impl Shard {
     /// [`shutdown_resumable`]: Self::shutdown_resumable
     /// [`shutdown`]: Self::shutdown
     pub async fn start(&self) -> Result<(), ShardStartError> {
+        let compression = self.config.compression();
+
+        if !compression.is_supported() {
+            return Err(ShardStartError {
+                kind: ShardStartErrorType::CompressionUnsupported { compression },
+                source: None,
+            });
+        }
+
         let emitter = self
             .emitter
             .lock()
@@ -505,23 +648,28 @@ impl Shard {
             })?;
 
         let config = Arc::clone(&self.config);
-        let (processor, wrx) = ShardProcessor::new(config, emitter)
-            .await
-            .map_err(|source| {
-                let (kind, source) = source.into_parts();
-
-                let new_kind = match kind {
-                    ConnectingErrorType::Establishing => ShardStartErrorType::Establishing,
-                    ConnectingErrorType::ParsingUrl { url } => {
-                        ShardStartErrorType::ParsingGatewayUrl { url }
-                    }
-                };
-
-                ShardStartError {
-                    source,
-                    kind: new_kind,
+        let (processor, wrx) = ShardProcessor::new(
+            config,
+            emitter,
+            Arc::clone(&self.member_requests),
+            Arc::clone(&self.presence),
+        )
+        .await
+        .map_err(|source| {
+            let (kind, source) = source.into_parts();
+
+            let new_kind = match kind {
+                ConnectingErrorType::Establishing => ShardStartErrorType::Establishing,
+                ConnectingErrorType::ParsingUrl { url } => {
+                    ShardStartErrorType::ParsingGatewayUrl { url }
                 }
-            })?;
+            };
+
+            ShardStartError {
+                source,
+                kind: new_kind,
+            }
+        })?;
 
         let handle = tokio::spawn(async {
             processor.run().await;
@@ -548,16 +696,25 @@ impl Shard {
         let (ratelimit_requests, ratelimit_refill) = if let Some(limiter) = session.ratelimit.get()
         {
             (
-                limiter.as_ref().map(LeakyBucket::tokens),
-                limiter.as_ref().map(LeakyBucket::next_refill),
+                limiter.as_ref().map(|queue| queue.bucket().tokens()),
+                limiter.as_ref().map(|queue| queue.bucket().next_refill()),
             )
         } else {
             return Err(SessionInactiveError);
         };
+        let queued = Priority::ALL.map(|priority| {
+            session
+                .ratelimit
+                .get()
+                .and_then(Option::as_ref)
+                .map_or(0, |queue| queue.depth(priority))
+        });
 
         Ok(Information {
+            dropped_events: self.dropped_events.load(Ordering::Relaxed),
             id: self.config().shard()[0],
             latency: session.heartbeats.latency(),
+            queued,
             ratelimit_refill,
             ratelimit_requests,
             gateway_url: self.config().gateway_url().to_owned(),
@@ -569,6 +726,13 @@ impl Shard {
 
     /// Send a command over the gateway.
     ///
+    /// Commands waiting for the ratelimiter are sent in order of their
+    /// [`Priority`].
+    ///
+    /// The presence of an [`UpdatePresence`] command becomes the shard's
+    /// current [`presence`], which new sessions identify with. It is set even
+    /// if sending the command fails.
+    ///
     /// # Examples
     ///
     /// Updating the shard's presence after identifying can be done by sending
@@ -613,25 +777,146 @@ impl Shard {
     /// restarting.
     ///
     /// Returns a [`CommandErrorType::Serializing`] error type if the provided
-    /// value failed to serialize into JSON.
+    /// value failed to serialize into the configured [`Encoding`].
     ///
     /// Returns a [`CommandErrorType::SessionInactive`] error type if the shard
     /// has not been started.
     ///
+    /// [`Encoding`]: super::Encoding
     /// [`UpdatePresence`]: twilight_model::gateway::payload::outgoing::UpdatePresence
+    /// [`presence`]: Self::presence
     pub async fn command(&self, value: &impl Command) -> Result<(), CommandError> {
-        let json = json::to_vec(value).map_err(|source| CommandError {
-            source: Some(Box::new(source)),
-            kind: CommandErrorType::Serializing,
-        })?;
+        let bytes = self
+            .config
+            .encoding()
+            .to_vec(value)
+            .map_err(|source| CommandError {
+                source: Some(source),
+                kind: CommandErrorType::Serializing,
+            })?;
 
-        self.send(Message::Binary(json))
+        if let Some(presence) = command::presence(value) {
+            self.presence.set(presence.clone());
+        }
+
+        self.send_prioritized(Message::Binary(bytes), command::priority(value))
             .await
             .map_err(CommandError::from_send)
     }
 
+    /// Copy of the shard's current presence, if any.
+    ///
+    /// This is the configured [presence] until a presence update is sent over
+    /// the shard, after which it is the most recently sent one. New sessions
+    /// identify with it, so that the presence is kept when the shard has to
+    /// re-identify.
+    ///
+    /// [presence]: super::ShardBuilder::presence
+    pub fn presence(&self) -> Option<UpdatePresencePayload> {
+        self.presence.get()
+    }
+
+    /// Update the shard's presence, making it the shard's current
+    /// [`presence`].
+    ///
+    /// The update waits for the ratelimiter with a [`Low`] priority.
+    ///
+    /// # Errors
+    ///
+    /// Refer to [`command`]. The presence is set as the shard's current
+    /// presence even if sending it fails, and will be applied once the shard
+    /// identifies a new session.
+    ///
+    /// [`Low`]: Priority::Low
+    /// [`command`]: Self::command
+    /// [`presence`]: Self::presence
+    pub async fn set_presence(&self, presence: UpdatePresencePayload) -> Result<(), CommandError> {
+        let command = UpdatePresence {
+            d: presence,
+            op: OpCode::PresenceUpdate,
+        };
+
+        self.command(&command).await
+    }
+
+    /// Request the members of a guild, waiting for all of the member chunks
+    /// received in response.
+    ///
+    /// A nonce is generated to correlate the member chunks with the request,
+    /// replacing any nonce of the provided request. The member chunks are
+    /// still emitted as events.
+    ///
+    /// # Examples
+    ///
+    /// Request the members of a guild whose names start with "tw", along with
+    /// their presences:
+    ///
+    /// ```no_run
+    /// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::{env, time::Duration};
+    /// use twilight_gateway::{Intents, Shard};
+    /// use twilight_model::{gateway::payload::outgoing::RequestGuildMembers, id::Id};
+    ///
+    /// let intents = Intents::GUILD_MEMBERS | Intents::GUILD_PRESENCES;
+    /// let (shard, _events) = Shard::new(env::var("DISCORD_TOKEN")?, intents);
+    /// shard.start().await?;
+    ///
+    /// let request = RequestGuildMembers::builder(Id::new(1))
+    ///     .presences(true)
+    ///     .query("tw", None);
+    /// let members = shard
+    ///     .request_guild_members(request, Duration::from_secs(10))
+    ///     .await?;
+    ///
+    /// println!(
+    ///     "found {} members and {} presences",
+    ///     members.members.len(),
+    ///     members.presences.len(),
+    /// );
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RequestGuildMembersErrorType::Command`] error type if
+    /// sending the request failed.
+    ///
+    /// Returns a [`RequestGuildMembersErrorType::TimedOut`] error type if not
+    /// all member chunks were received before the timeout elapsed.
+    pub async fn request_guild_members(
+        &self,
+        mut request: RequestGuildMembers,
+        timeout: Duration,
+    ) -> Result<GuildMembers, RequestGuildMembersError> {
+        let (nonce, rx) = self.member_requests.register(request.d.guild_id);
+        // Cancel the request however this returns, including when this future
+        // is dropped before the member chunks are received.
+        let _guard = CancelOnDrop::new(&self.member_requests, &nonce);
+        request.d.nonce = Some(nonce.clone());
+
+        if let Err(source) = self.command(&request).await {
+            return Err(RequestGuildMembersError {
+                kind: RequestGuildMembersErrorType::Command,
+                source: Some(Box::new(source)),
+            });
+        }
+
+        if let Ok(Ok(members)) = time::timeout(timeout, rx).await {
+            return Ok(members);
+        }
+
+        // The request's sender is only dropped once the request is cancelled,
+        // so this is a timeout.
+        Err(RequestGuildMembersError {
+            kind: RequestGuildMembersErrorType::TimedOut,
+            source: None,
+        })
+    }
+
     /// Send a raw websocket message.
     ///
+    /// Messages wait for the ratelimiter with a [`Priority::Normal`] priority.
+    ///
     /// # Examples
     ///
     /// Send a ping message:
@@ -689,6 +974,16 @@ impl Shard {
     ///
     /// [`shutdown`]: Self::shutdown
     pub async fn send(&self, message: Message) -> Result<(), SendError> {
+        self.send_prioritized(message, Priority::Normal).await
+    }
+
+    /// Send a raw websocket message once the ratelimiter allows a message of
+    /// its priority to be sent.
+    async fn send_prioritized(
+        &self,
+        message: Message,
+        priority: Priority,
+    ) -> Result<(), SendError> {
         let session = self.session().map_err(|source| SendError {
             source: Some(Box::new(source)),
             kind: SendErrorType::SessionInactive,
@@ -701,7 +996,17 @@ impl Shard {
             // The value of the cell has been set, it will be Some if ratelimiting
             // is enabled, else None. We can ignore the second case.
             if let Some(ratelimiter) = maybe_ratelimiter {
-                ratelimiter.acquire_one().await;
+                #[cfg(feature = "metrics")]
+                let started = Instant::now();
+
+                ratelimiter.acquire(priority).await;
+
+                #[cfg(feature = "metrics")]
+                metrics::histogram!(
+                    "Shard-Ratelimit-Wait",
+                    started.elapsed().as_secs_f64(),
+                    "shard" => self.config.shard()[0].to_string(),
+                );
             }
         } else {
             return Err(SendError {
@@ -710,13 +1015,10 @@ impl Shard {
             });
         }
 
-        session
-            .tx
-            .send(message.into_tungstenite())
-            .map_err(|source| SendError {
-                kind: SendErrorType::Sending,
-                source: Some(Box::new(source)),
-            })
+        session.tx.send(message).map_err(|source| SendError {
+            kind: SendErrorType::Sending,
+            source: Some(Box::new(source)),
+        })
     }
 
     /// Shut down the shard.
@@ -731,10 +1033,7 @@ impl Shard {
 
         if let Ok(session) = self.session() {
             // Since we're shutting down now, we don't care if it sends or not.
-            let _res = session.close(Some(TungsteniteCloseFrame {
-                code: CloseCode::Normal,
-                reason: "".into(),
-            }));
+            let _res = session.close(Some(CloseFrame::from((1000, ""))));
             session.stop_heartbeater();
         }
     }
@@ -760,10 +1059,7 @@ impl Shard {
             return (shard_id, None);
         };
 
-        let _res = session.close(Some(TungsteniteCloseFrame {
-            code: CloseCode::Restart,
-            reason: Cow::from("Closing in a resumable way"),
-        }));
+        let _res = session.close(Some(CloseFrame::from((1012, "Closing in a resumable way"))));
 
         let session_id = session.id();
         let sequence = session.seq.load(Ordering::Relaxed);
@@ -794,21 +1090,42 @@ impl Shard {
 #[cfg(test)]
 mod tests {
     use super::{
-        CommandError, CommandErrorType, Information, ResumeSession, SendError, SendErrorType,
+        CommandError, CommandErrorType, Information, RequestGuildMembersError,
+        RequestGuildMembersErrorType, ResumeSession, SendError, SendErrorType,
         SessionInactiveError, Shard, ShardStartError, ShardStartErrorType,
     };
+    #[cfg(not(any(feature = "zlib-stock", feature = "zlib-simd")))]
+    use super::{Compression, Intents};
     use static_assertions::{assert_fields, assert_impl_all};
     use std::{error::Error, fmt::Debug};
 
     assert_impl_all!(CommandErrorType: Debug, Send, Sync);
     assert_impl_all!(CommandError: Error, Send, Sync);
     assert_impl_all!(Information: Clone, Debug, Send, Sync);
+    assert_impl_all!(RequestGuildMembersErrorType: Debug, Send, Sync);
+    assert_impl_all!(RequestGuildMembersError: Error, Send, Sync);
     assert_impl_all!(ResumeSession: Clone, Debug, Send, Sync);
     assert_impl_all!(SendErrorType: Debug, Send, Sync);
     assert_impl_all!(SendError: Error, Send, Sync);
     assert_impl_all!(SessionInactiveError: Error, Send, Sync);
+    assert_fields!(ShardStartErrorType::CompressionUnsupported: compression);
     assert_fields!(ShardStartErrorType::ParsingGatewayUrl: url);
     assert_impl_all!(ShardStartErrorType: Debug, Send, Sync);
     assert_impl_all!(ShardStartError: Error, Send, Sync);
     assert_impl_all!(Shard: Debug, Send, Sync);
+
+    #[cfg(not(any(feature = "zlib-stock", feature = "zlib-simd")))]
+    #[tokio::test]
+    async fn start_compression_unsupported() {
+        let (shard, _events) = Shard::builder("token".to_owned(), Intents::empty())
+            .compression(Compression::Stream)
+            .build();
+
+        assert!(matches!(
+            shard.start().await.unwrap_err().kind(),
+            ShardStartErrorType::CompressionUnsupported {
+                compression: Compression::Stream
+            }
+        ));
+    }
 }
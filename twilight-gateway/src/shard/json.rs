@@ -20,10 +20,10 @@ impl Display for GatewayEventParsingError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         match &self.kind {
             GatewayEventParsingErrorType::Deserializing => {
-                f.write_str("deserializing gateway event as json failed")
+                f.write_str("deserializing gateway event failed")
             }
             GatewayEventParsingErrorType::PayloadInvalid => {
-                f.write_str("payload is an invalid json structure")
+                f.write_str("payload is an invalid structure")
             }
         }
     }
@@ -41,13 +41,13 @@ impl Error for GatewayEventParsingError {
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum GatewayEventParsingErrorType {
-    /// Deserializing the GatewayEvent payload from JSON failed.
+    /// Deserializing the `GatewayEvent` payload failed.
     Deserializing,
     /// The payload received from Discord was an unrecognized or invalid
     /// structure.
     ///
-    /// The payload was either invalid JSON or did not contain the necessary
-    /// "op" key in the object.
+    /// The payload was either invalid in its encoding or did not contain the
+    /// necessary "op" key in the object.
     PayloadInvalid,
 }
 
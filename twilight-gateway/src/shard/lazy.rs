@@ -0,0 +1,523 @@
+//! Events whose deserialization is deferred to the consumer.
+//!
+//! Instead of deserializing every dispatch event the shard receives, a shard
+//! built via [`ShardBuilder::build_lazy`] emits the raw bytes of gateway
+//! payloads along with the opcode, sequence and event name peeked from the
+//! payload. The consumer may then forward the payloads or deserialize them on
+//! demand.
+//!
+//! [`ShardBuilder::build_lazy`]: super::ShardBuilder::build_lazy
+
+use super::{
+    channel::Receiver,
+    etf::{self, EtfError, EtfErrorType},
+    Encoding,
+};
+use crate::EventTypeFlags;
+use futures_util::stream::Stream;
+use serde::{
+    de::{DeserializeOwned, DeserializeSeed},
+    Deserialize,
+};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use twilight_model::gateway::event::{Event, GatewayEventDeserializer};
+
+/// Deserializing a [`RawEvent`] failed.
+#[derive(Debug)]
+pub struct RawEventDeserializeError {
+    kind: RawEventDeserializeErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl RawEventDeserializeError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &RawEventDeserializeErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(
+        self,
+    ) -> (
+        RawEventDeserializeErrorType,
+        Option<Box<dyn Error + Send + Sync>>,
+    ) {
+        (self.kind, self.source)
+    }
+}
+
+impl Display for RawEventDeserializeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            RawEventDeserializeErrorType::Deserializing => {
+                f.write_str("deserializing the raw event failed")
+            }
+            RawEventDeserializeErrorType::PayloadInvalid => {
+                f.write_str("payload is invalid in its encoding")
+            }
+        }
+    }
+}
+
+impl Error for RawEventDeserializeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// Type of [`RawEventDeserializeError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RawEventDeserializeErrorType {
+    /// Deserializing the payload into the requested type failed.
+    Deserializing,
+    /// Payload isn't valid in its encoding, such as invalid JSON.
+    PayloadInvalid,
+}
+
+/// Data field of a gateway payload.
+#[derive(Deserialize)]
+struct Data<T> {
+    d: T,
+}
+
+/// Gateway payload that has not been deserialized.
+///
+/// # Examples
+///
+/// Deserialize only the message create events of a lazy shard:
+///
+/// ```no_run
+/// use futures::StreamExt;
+/// use std::env;
+/// use twilight_gateway::{shard::LazyEvent, Intents, Shard};
+/// use twilight_model::channel::Message;
+///
+/// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let token = env::var("DISCORD_TOKEN")?;
+/// let (shard, mut events) = Shard::builder(token, Intents::GUILD_MESSAGES).build_lazy();
+/// shard.start().await?;
+///
+/// while let Some(event) = events.next().await {
+///     if let LazyEvent::Raw(raw) = event {
+///         if raw.event_type() == Some("MESSAGE_CREATE") {
+///             let message = raw.deserialize::<Message>()?;
+///             println!("received message: {}", message.content);
+///         }
+///     }
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RawEvent {
+    bytes: Vec<u8>,
+    encoding: Encoding,
+    event_type: Option<String>,
+    op: u8,
+    sequence: Option<u64>,
+}
+
+impl RawEvent {
+    pub(super) const fn new(
+        op: u8,
+        sequence: Option<u64>,
+        event_type: Option<String>,
+        encoding: Encoding,
+        bytes: Vec<u8>,
+    ) -> Self {
+        Self {
+            bytes,
+            encoding,
+            event_type,
+            op,
+            sequence,
+        }
+    }
+
+    /// Immutable reference to the bytes of the payload, in the shard's
+    /// [encoding].
+    ///
+    /// [encoding]: Self::encoding
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Encoding of the payload.
+    pub const fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Consume the event, returning the bytes of the payload.
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Name of the dispatch event, if the payload is a dispatch event.
+    pub fn event_type(&self) -> Option<&str> {
+        self.event_type.as_deref()
+    }
+
+    /// Event type flag of the payload.
+    ///
+    /// Returns `None` if the opcode and event name pair is unknown.
+    pub fn kind(&self) -> Option<EventTypeFlags> {
+        EventTypeFlags::try_from((self.op, self.event_type())).ok()
+    }
+
+    /// Opcode of the payload.
+    pub const fn op(&self) -> u8 {
+        self.op
+    }
+
+    /// Sequence of the payload, if the payload is a dispatch event.
+    pub const fn sequence(&self) -> Option<u64> {
+        self.sequence
+    }
+
+    /// Deserialize the data of the payload into a type.
+    ///
+    /// For dispatch events this is the event's model, such as
+    /// [`Message`] for `MESSAGE_CREATE` events.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RawEventDeserializeErrorType::Deserializing`] error type if
+    /// the payload's data could not be deserialized into the type.
+    ///
+    /// Returns a [`RawEventDeserializeErrorType::PayloadInvalid`] error type
+    /// if the payload is not valid in its encoding.
+    ///
+    /// [`Message`]: twilight_model::channel::Message
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, RawEventDeserializeError> {
+        if self.encoding == Encoding::Etf {
+            return etf::from_slice::<Data<T>>(&self.bytes)
+                .map(|data| data.d)
+                .map_err(etf_error);
+        }
+
+        #[cfg(not(feature = "simd-json"))]
+        let data = serde_json::from_slice::<Data<T>>(&self.bytes).map_err(json_error);
+        #[cfg(feature = "simd-json")]
+        let data =
+            {
+                let mut bytes = self.bytes.clone();
+                let mut json_deserializer = simd_json::Deserializer::from_slice(&mut bytes)
+                    .map_err(|source| RawEventDeserializeError {
+                        kind: RawEventDeserializeErrorType::PayloadInvalid,
+                        source: Some(Box::new(source)),
+                    })?;
+
+                Data::<T>::deserialize(&mut json_deserializer).map_err(|source| {
+                    RawEventDeserializeError {
+                        kind: RawEventDeserializeErrorType::Deserializing,
+                        source: Some(Box::new(source)),
+                    }
+                })
+            };
+
+        data.map(|data| data.d)
+    }
+
+    /// Deserialize the payload into an [`Event`], as a shard that isn't lazy
+    /// would have.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RawEventDeserializeErrorType::Deserializing`] error type if
+    /// the payload could not be deserialized into an event.
+    ///
+    /// Returns a [`RawEventDeserializeErrorType::PayloadInvalid`] error type
+    /// if the payload is not valid in its encoding.
+    pub fn event(&self) -> Result<Event, RawEventDeserializeError> {
+        let gateway_deserializer =
+            GatewayEventDeserializer::new(self.op, self.sequence, self.event_type());
+
+        if self.encoding == Encoding::Etf {
+            let mut etf_deserializer =
+                etf::Deserializer::from_slice(&self.bytes).map_err(etf_error)?;
+
+            return gateway_deserializer
+                .deserialize(&mut etf_deserializer)
+                .map(Event::from)
+                .map_err(etf_error);
+        }
+
+        #[cfg(not(feature = "simd-json"))]
+        let gateway_event = {
+            let mut json_deserializer = serde_json::Deserializer::from_slice(&self.bytes);
+
+            gateway_deserializer
+                .deserialize(&mut json_deserializer)
+                .map_err(json_error)
+        };
+        #[cfg(feature = "simd-json")]
+        let gateway_event =
+            {
+                let mut bytes = self.bytes.clone();
+                let mut json_deserializer = simd_json::Deserializer::from_slice(&mut bytes)
+                    .map_err(|source| RawEventDeserializeError {
+                        kind: RawEventDeserializeErrorType::PayloadInvalid,
+                        source: Some(Box::new(source)),
+                    })?;
+
+                gateway_deserializer
+                    .deserialize(&mut json_deserializer)
+                    .map_err(|source| RawEventDeserializeError {
+                        kind: RawEventDeserializeErrorType::Deserializing,
+                        source: Some(Box::new(source)),
+                    })
+            };
+
+        gateway_event.map(Event::from)
+    }
+}
+
+/// Map a [`serde_json`] error to a deserialization error, distinguishing
+/// invalid JSON from JSON of the wrong shape.
+#[cfg(not(feature = "simd-json"))]
+fn json_error(source: serde_json::Error) -> RawEventDeserializeError {
+    let kind = if source.is_syntax() || source.is_eof() {
+        RawEventDeserializeErrorType::PayloadInvalid
+    } else {
+        RawEventDeserializeErrorType::Deserializing
+    };
+
+    RawEventDeserializeError {
+        kind,
+        source: Some(Box::new(source)),
+    }
+}
+
+/// Map an [`EtfError`] to a deserialization error, distinguishing invalid
+/// terms from terms of the wrong shape.
+fn etf_error(source: EtfError) -> RawEventDeserializeError {
+    let kind = if matches!(source.kind(), EtfErrorType::Deserializing) {
+        RawEventDeserializeErrorType::Deserializing
+    } else {
+        RawEventDeserializeErrorType::PayloadInvalid
+    };
+
+    RawEventDeserializeError {
+        kind,
+        source: Some(Box::new(source)),
+    }
+}
+
+/// Event emitted by a lazy shard.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LazyEvent {
+    /// Event that has already been deserialized.
+    ///
+    /// This includes events created by the shard itself, such as
+    /// [`Event::ShardConnected`], and gateway payloads the shard must
+    /// deserialize to operate, such as [`Event::Ready`].
+    Event(Event),
+    /// Gateway payload that has not been deserialized.
+    Raw(RawEvent),
+}
+
+impl LazyEvent {
+    /// Deserialize the event into an [`Event`] if it isn't already.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RawEventDeserializeError`] if the event is a
+    /// [`LazyEvent::Raw`] and deserializing it failed.
+    pub fn into_event(self) -> Result<Event, RawEventDeserializeError> {
+        match self {
+            Self::Event(event) => Ok(event),
+            Self::Raw(raw) => raw.event(),
+        }
+    }
+
+    /// Event type flag of the event.
+    ///
+    /// Returns `None` if the event is a [`LazyEvent::Raw`] with an unknown
+    /// opcode and event name pair.
+    pub fn kind(&self) -> Option<EventTypeFlags> {
+        match self {
+            Self::Event(event) => Some(EventTypeFlags::from(event.kind())),
+            Self::Raw(raw) => raw.kind(),
+        }
+    }
+}
+
+impl From<Event> for LazyEvent {
+    fn from(event: Event) -> Self {
+        Self::Event(event)
+    }
+}
+
+/// A stream of lazy events from a [`Shard`].
+///
+/// The events of this stream are filtered by the same event types as the
+/// [`Events`] stream, which can be checked via [`LazyEvents::event_types`].
+///
+/// This implements [`futures::stream::Stream`].
+///
+/// [`Events`]: super::Events
+/// [`LazyEvents::event_types`]: Self::event_types
+/// [`Shard`]: super::Shard
+/// [`futures::stream::Stream`]: https://docs.rs/futures/*/futures/stream/trait.Stream.html
+#[derive(Debug)]
+pub struct LazyEvents {
+    event_types: EventTypeFlags,
+    rx: Receiver,
+}
+
+impl LazyEvents {
+    pub(super) const fn new(event_types: EventTypeFlags, rx: Receiver) -> Self {
+        Self { event_types, rx }
+    }
+
+    /// Returns the event types that can be passed to this stream.
+    pub const fn event_types(&self) -> EventTypeFlags {
+        self.event_types
+    }
+}
+
+impl Stream for LazyEvents {
+    type Item = LazyEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        etf, Encoding, LazyEvent, LazyEvents, RawEvent, RawEventDeserializeError,
+        RawEventDeserializeErrorType,
+    };
+    use crate::EventTypeFlags;
+    use futures_util::stream::Stream;
+    use static_assertions::assert_impl_all;
+    use std::{error::Error, fmt::Debug};
+    use twilight_model::{
+        gateway::{event::Event, payload::incoming::TypingStart},
+        id::Id,
+    };
+
+    assert_impl_all!(LazyEvent: Clone, Debug, From<Event>, PartialEq, Send, Sync);
+    assert_impl_all!(LazyEvents: Debug, Send, Stream, Sync);
+    assert_impl_all!(RawEvent: Clone, Debug, Eq, PartialEq, Send, Sync);
+    assert_impl_all!(RawEventDeserializeErrorType: Debug, Send, Sync);
+    assert_impl_all!(RawEventDeserializeError: Error, Send, Sync);
+
+    const TYPING_START: &str = r#"{"t":"TYPING_START","s":3,"op":0,"d":{"user_id":"2","timestamp":1600000000,"channel_id":"1"}}"#;
+
+    fn raw() -> RawEvent {
+        RawEvent::new(
+            0,
+            Some(3),
+            Some("TYPING_START".to_owned()),
+            Encoding::Json,
+            TYPING_START.as_bytes().to_vec(),
+        )
+    }
+
+    #[test]
+    fn deserialize() -> Result<(), Box<dyn Error>> {
+        let typing = raw().deserialize::<TypingStart>()?;
+        assert_eq!(Id::new(1), typing.channel_id);
+        assert_eq!(Id::new(2), typing.user_id);
+
+        assert!(matches!(
+            raw().deserialize::<u64>().unwrap_err().kind(),
+            RawEventDeserializeErrorType::Deserializing
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn payload_invalid() {
+        let raw = RawEvent::new(
+            0,
+            Some(3),
+            Some("TYPING_START".to_owned()),
+            Encoding::Json,
+            br#"{"op":0,"d":"#.to_vec(),
+        );
+
+        assert!(matches!(
+            raw.deserialize::<TypingStart>().unwrap_err().kind(),
+            RawEventDeserializeErrorType::PayloadInvalid
+        ));
+        assert!(matches!(
+            raw.event().unwrap_err().kind(),
+            RawEventDeserializeErrorType::PayloadInvalid
+        ));
+
+        let raw = RawEvent::new(
+            0,
+            Some(3),
+            Some("TYPING_START".to_owned()),
+            Encoding::Etf,
+            Vec::from([131, 116, 0, 0, 0, 1]),
+        );
+
+        assert!(matches!(
+            raw.deserialize::<TypingStart>().unwrap_err().kind(),
+            RawEventDeserializeErrorType::PayloadInvalid
+        ));
+        assert!(matches!(
+            raw.event().unwrap_err().kind(),
+            RawEventDeserializeErrorType::PayloadInvalid
+        ));
+    }
+
+    /// Payloads in the ETF encoding are deserialized from their terms.
+    #[test]
+    fn etf() -> Result<(), Box<dyn Error>> {
+        let json = serde_json::from_str::<serde_json::Value>(TYPING_START)?;
+        let raw = RawEvent::new(
+            0,
+            Some(3),
+            Some("TYPING_START".to_owned()),
+            Encoding::Etf,
+            etf::to_vec(&json)?,
+        );
+
+        let typing = raw.deserialize::<TypingStart>()?;
+        assert_eq!(Id::new(2), typing.user_id);
+
+        assert!(matches!(
+            raw.deserialize::<u64>().unwrap_err().kind(),
+            RawEventDeserializeErrorType::Deserializing
+        ));
+
+        let event = raw.event()?;
+        assert!(matches!(event, Event::TypingStart(typing) if typing.channel_id == Id::new(1)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn event() -> Result<(), Box<dyn Error>> {
+        let raw = raw();
+        assert_eq!(Some(EventTypeFlags::TYPING_START), raw.kind());
+
+        let event = LazyEvent::Raw(raw).into_event()?;
+        assert!(matches!(event, Event::TypingStart(typing) if typing.channel_id == Id::new(1)));
+
+        Ok(())
+    }
+}
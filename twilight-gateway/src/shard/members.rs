@@ -0,0 +1,235 @@
+//! Aggregation of the member chunks received in response to requests for a
+//! guild's members.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+use tokio::sync::oneshot::{self, Receiver, Sender};
+use twilight_model::{
+    gateway::{payload::incoming::MemberChunk, presence::Presence},
+    guild::Member,
+    id::{
+        marker::{GuildMarker, UserMarker},
+        Id,
+    },
+};
+
+/// Members of a guild gathered from all of the member chunks received in
+/// response to a request.
+///
+/// Returned by [`Shard::request_guild_members`].
+///
+/// [`Shard::request_guild_members`]: super::Shard::request_guild_members
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct GuildMembers {
+    /// ID of the guild.
+    pub guild_id: Id<GuildMarker>,
+    /// Members that were found.
+    pub members: Vec<Member>,
+    /// IDs of requested users that were not found.
+    pub not_found: Vec<Id<UserMarker>>,
+    /// Presences of the found members, if requested.
+    pub presences: Vec<Presence>,
+}
+
+/// Request for a guild's members waiting for its member chunks.
+#[derive(Debug)]
+struct PendingRequest {
+    /// Members gathered so far.
+    members: GuildMembers,
+    /// Indices of the member chunks received so far.
+    received: HashSet<u32>,
+    /// Sender of the gathered members once all chunks have been received.
+    tx: Sender<GuildMembers>,
+}
+
+/// Requests for guild members, keyed by their nonce, waiting for their member
+/// chunks.
+#[derive(Debug, Default)]
+pub struct MemberRequests {
+    /// Counter used to generate unique nonces.
+    next_nonce: AtomicU64,
+    /// Pending requests by nonce.
+    pending: Mutex<HashMap<String, PendingRequest>>,
+}
+
+impl MemberRequests {
+    /// Register a new request for the members of a guild.
+    ///
+    /// Returns the nonce to send with the request and a receiver of the
+    /// gathered members.
+    pub fn register(&self, guild_id: Id<GuildMarker>) -> (String, Receiver<GuildMembers>) {
+        let nonce = format!(
+            "twilight-{}",
+            self.next_nonce.fetch_add(1, Ordering::Relaxed)
+        );
+        let (tx, rx) = oneshot::channel();
+
+        self.pending.lock().expect("members poisoned").insert(
+            nonce.clone(),
+            PendingRequest {
+                members: GuildMembers {
+                    guild_id,
+                    members: Vec::new(),
+                    not_found: Vec::new(),
+                    presences: Vec::new(),
+                },
+                received: HashSet::new(),
+                tx,
+            },
+        );
+
+        (nonce, rx)
+    }
+
+    /// Stop waiting for a request's member chunks, such as when it timed out.
+    pub fn cancel(&self, nonce: &str) {
+        self.pending.lock().expect("members poisoned").remove(nonce);
+    }
+
+    /// Whether any requests are waiting for member chunks.
+    pub fn is_pending(&self) -> bool {
+        !self.pending.lock().expect("members poisoned").is_empty()
+    }
+
+    /// Add a member chunk to the request it is a response to, if any.
+    ///
+    /// Once all of a request's chunks have been received its gathered
+    /// members are sent.
+    pub fn chunk(&self, chunk: MemberChunk) {
+        let nonce = match chunk.nonce {
+            Some(nonce) => nonce,
+            None => return,
+        };
+
+        let mut pending = self.pending.lock().expect("members poisoned");
+
+        let request = match pending.get_mut(&nonce) {
+            Some(request) => request,
+            None => return,
+        };
+
+        // Duplicated chunks would otherwise complete the request early.
+        if !request.received.insert(chunk.chunk_index) {
+            return;
+        }
+
+        request.members.members.extend(chunk.members);
+        request.members.not_found.extend(chunk.not_found);
+        request.members.presences.extend(chunk.presences);
+
+        // Chunks may arrive in any order, so only their count matters.
+        if request.received.len() < chunk.chunk_count as usize {
+            return;
+        }
+
+        if let Some(request) = pending.remove(&nonce) {
+            // The requester may have timed out in the meantime.
+            let _res = request.tx.send(request.members);
+        }
+    }
+}
+
+/// Guard cancelling a request when dropped, such as when the future waiting
+/// for its member chunks is dropped before they are received.
+#[derive(Debug)]
+pub struct CancelOnDrop<'a> {
+    /// Nonce of the request.
+    nonce: &'a str,
+    /// Requests the request is pending in.
+    requests: &'a MemberRequests,
+}
+
+impl<'a> CancelOnDrop<'a> {
+    /// Create a new guard cancelling the request with a nonce.
+    pub const fn new(requests: &'a MemberRequests, nonce: &'a str) -> Self {
+        Self { nonce, requests }
+    }
+}
+
+impl Drop for CancelOnDrop<'_> {
+    fn drop(&mut self) {
+        // Requests whose member chunks were all received are already removed.
+        self.requests.cancel(self.nonce);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CancelOnDrop, GuildMembers, MemberRequests};
+    use static_assertions::assert_impl_all;
+    use std::fmt::Debug;
+    use twilight_model::{gateway::payload::incoming::MemberChunk, id::Id};
+
+    assert_impl_all!(GuildMembers: Clone, Debug, Eq, PartialEq, Send, Sync);
+    assert_impl_all!(MemberRequests: Debug, Default, Send, Sync);
+
+    fn chunk(nonce: &str, chunk_index: u32, user_id: u64) -> MemberChunk {
+        MemberChunk {
+            chunk_count: 2,
+            chunk_index,
+            guild_id: Id::new(1),
+            members: Vec::new(),
+            nonce: Some(nonce.to_owned()),
+            not_found: Vec::from([Id::new(user_id)]),
+            presences: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn gathers_chunks() {
+        let requests = MemberRequests::default();
+        let (nonce, mut rx) = requests.register(Id::new(1));
+        assert!(requests.is_pending());
+
+        requests.chunk(chunk("unrelated", 0, 2));
+        requests.chunk(chunk(&nonce, 1, 3));
+        assert!(rx.try_recv().is_err());
+
+        requests.chunk(chunk(&nonce, 0, 4));
+        let members = rx.try_recv().unwrap();
+        assert_eq!(Id::new(1), members.guild_id);
+        assert_eq!(Vec::from([Id::new(3), Id::new(4)]), members.not_found);
+        assert!(!requests.is_pending());
+    }
+
+    #[test]
+    fn duplicate_chunk() {
+        let requests = MemberRequests::default();
+        let (nonce, mut rx) = requests.register(Id::new(1));
+
+        requests.chunk(chunk(&nonce, 0, 2));
+        requests.chunk(chunk(&nonce, 0, 2));
+        assert!(rx.try_recv().is_err());
+
+        requests.chunk(chunk(&nonce, 1, 3));
+        let members = rx.try_recv().unwrap();
+        assert_eq!(Vec::from([Id::new(2), Id::new(3)]), members.not_found);
+    }
+
+    #[test]
+    fn cancel_on_drop() {
+        let requests = MemberRequests::default();
+        let (nonce, _rx) = requests.register(Id::new(1));
+
+        drop(CancelOnDrop::new(&requests, &nonce));
+        assert!(!requests.is_pending());
+    }
+
+    #[test]
+    fn cancel() {
+        let requests = MemberRequests::default();
+        let (nonce, _rx) = requests.register(Id::new(1));
+        let (other, _other_rx) = requests.register(Id::new(1));
+        assert_ne!(nonce, other);
+
+        requests.cancel(&nonce);
+        requests.cancel(&other);
+        assert!(!requests.is_pending());
+    }
+}
@@ -48,15 +48,28 @@
 //! [new messages]: ::twilight_model::gateway::event::Event::MessageCreate
 
 pub mod raw_message;
+pub mod reconnect;
+pub mod recorder;
+pub mod session_store;
 pub mod stage;
+pub mod transport;
 
 mod builder;
+mod channel;
 mod command;
+mod compression;
 mod config;
 mod emitter;
+mod encoding;
+pub(crate) mod etf;
 mod event;
+mod guild_filter;
 mod r#impl;
 mod json;
+mod lazy;
+mod members;
+mod presence;
+mod priority;
 mod processor;
 #[cfg(any(
     feature = "native",
@@ -64,21 +77,29 @@ mod processor;
     feature = "rustls-webpki-roots"
 ))]
 pub(crate) mod tls;
+mod zombie;
 
 pub use self::{
     builder::{ShardBuilder, ShardIdError, ShardIdErrorType},
+    channel::OverflowPolicy,
     command::Command,
+    compression::Compression,
     config::Config,
+    encoding::Encoding,
+    etf::{EtfError, EtfErrorType},
     event::Events,
+    guild_filter::GuildFilter,
+    lazy::{
+        LazyEvent, LazyEvents, RawEvent, RawEventDeserializeError, RawEventDeserializeErrorType,
+    },
+    members::GuildMembers,
+    priority::Priority,
     processor::heartbeat::Latency,
     r#impl::{
-        CommandError, CommandErrorType, Information, ResumeSession, SendError, SendErrorType,
+        CommandError, CommandErrorType, Information, RequestGuildMembersError,
+        RequestGuildMembersErrorType, ResumeSession, SendError, SendErrorType,
         SessionInactiveError, Shard, ShardStartError, ShardStartErrorType,
     },
     stage::Stage,
+    zombie::{ZombieAction, ZombiePolicy},
 };
-
-use tokio::net::TcpStream;
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
-
-type ShardStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
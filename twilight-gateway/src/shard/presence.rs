@@ -0,0 +1,37 @@
+//! Current presence of a shard, re-applied when identifying a new session.
+
+use std::sync::Mutex;
+use twilight_model::gateway::payload::outgoing::update_presence::UpdatePresencePayload;
+
+/// Presence most recently set on a shard.
+///
+/// Starts out as the configured presence and is replaced by every presence
+/// update sent over the shard, so that a new session identifies with the
+/// presence the shard had before reconnecting.
+#[derive(Debug)]
+pub struct CurrentPresence {
+    /// Current presence, if any has been set.
+    presence: Mutex<Option<UpdatePresencePayload>>,
+}
+
+impl CurrentPresence {
+    /// Create a new current presence starting out as the configured presence.
+    pub const fn new(presence: Option<UpdatePresencePayload>) -> Self {
+        Self {
+            presence: Mutex::new(presence),
+        }
+    }
+
+    /// Copy of the current presence.
+    pub fn get(&self) -> Option<UpdatePresencePayload> {
+        self.presence.lock().expect("presence poisoned").clone()
+    }
+
+    /// Replace the current presence.
+    pub fn set(&self, presence: UpdatePresencePayload) {
+        self.presence
+            .lock()
+            .expect("presence poisoned")
+            .replace(presence);
+    }
+}
@@ -0,0 +1,246 @@
+//! Prioritization of commands waiting for the shard's ratelimiter.
+
+use leaky_bucket_lite::LeakyBucket;
+use std::{collections::VecDeque, sync::Mutex};
+use tokio::sync::oneshot::{self, Receiver, Sender};
+
+/// Priority of a command waiting for the shard's ratelimiter.
+///
+/// Commands of a higher priority are sent before any waiting commands of a
+/// lower priority, while commands of the same priority are sent in order.
+/// Heartbeats sent by the shard are not ratelimited, as the ratelimiter
+/// reserves room for them.
+///
+/// The priority of a [`Command`] is determined by its type:
+///
+/// - [`Heartbeat`], [`Identify`], [`Resume`], and [`UpdateVoiceState`] are
+///   [`High`];
+/// - [`RequestGuildMembers`] and raw messages are [`Normal`];
+/// - [`UpdatePresence`] is [`Low`].
+///
+/// [`Command`]: super::Command
+/// [`Heartbeat`]: twilight_model::gateway::payload::outgoing::Heartbeat
+/// [`High`]: Self::High
+/// [`Identify`]: twilight_model::gateway::payload::outgoing::identify::Identify
+/// [`Low`]: Self::Low
+/// [`Normal`]: Self::Normal
+/// [`RequestGuildMembers`]: twilight_model::gateway::payload::outgoing::RequestGuildMembers
+/// [`Resume`]: twilight_model::gateway::payload::outgoing::resume::Resume
+/// [`UpdatePresence`]: twilight_model::gateway::payload::outgoing::UpdatePresence
+/// [`UpdateVoiceState`]: twilight_model::gateway::payload::outgoing::UpdateVoiceState
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum Priority {
+    /// Commands that must be sent promptly, such as voice state updates.
+    High,
+    /// Commands without particular urgency, such as guild member requests.
+    Normal,
+    /// Commands that may be delayed, such as presence updates.
+    Low,
+}
+
+impl Priority {
+    /// Priorities from highest to lowest.
+    pub(crate) const ALL: [Self; 3] = [Self::High, Self::Normal, Self::Low];
+
+    /// Index of the priority's queue, with higher priorities first.
+    pub(crate) const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Queue of commands waiting for the shard's ratelimiter, handing out tokens
+/// by priority.
+///
+/// Only one command at a time waits for a token from the bucket. Once it has
+/// acquired one, the turn is passed on to the next command of the highest
+/// waiting priority.
+#[derive(Debug)]
+pub struct CommandQueue {
+    /// Bucket of tokens to send commands.
+    bucket: LeakyBucket,
+    /// State of the commands waiting for a turn.
+    state: Mutex<QueueState>,
+}
+
+/// State of the commands waiting for a turn.
+#[derive(Debug, Default)]
+struct QueueState {
+    /// Whether a command currently has the turn to acquire a token.
+    acquiring: bool,
+    /// Senders notifying commands of their turn, by priority.
+    waiting: [VecDeque<Sender<()>>; Priority::ALL.len()],
+}
+
+impl CommandQueue {
+    /// Create a new queue over a bucket of tokens.
+    pub fn new(bucket: LeakyBucket) -> Self {
+        Self {
+            bucket,
+            state: Mutex::new(QueueState::default()),
+        }
+    }
+
+    /// Immutable reference to the bucket of tokens.
+    pub const fn bucket(&self) -> &LeakyBucket {
+        &self.bucket
+    }
+
+    /// Number of commands of a priority waiting for their turn.
+    pub fn depth(&self, priority: Priority) -> usize {
+        self.state.lock().expect("queue poisoned").waiting[priority.index()]
+            .iter()
+            .filter(|tx| !tx.is_closed())
+            .count()
+    }
+
+    /// Wait for the turn of a command of a priority, and then for a token.
+    pub async fn acquire(&self, priority: Priority) {
+        let rx = {
+            let mut state = self.state.lock().expect("queue poisoned");
+
+            if state.acquiring {
+                let (tx, rx) = oneshot::channel();
+                state.waiting[priority.index()].push_back(tx);
+
+                Some(rx)
+            } else {
+                state.acquiring = true;
+
+                None
+            }
+        };
+
+        let mut turn = Turn { queue: self, rx };
+
+        if let Some(rx) = turn.rx.as_mut() {
+            // The sender is only dropped when the queue is, which can't happen
+            // while it is borrowed.
+            let _res = rx.await;
+            turn.rx = None;
+        }
+
+        self.bucket.acquire_one().await;
+    }
+
+    /// Pass the turn on to the next command of the highest waiting priority.
+    fn release(&self) {
+        let mut state = self.state.lock().expect("queue poisoned");
+
+        for waiting in &mut state.waiting {
+            while let Some(tx) = waiting.pop_front() {
+                // Commands that stopped waiting have closed their receiver.
+                if tx.send(()).is_ok() {
+                    return;
+                }
+            }
+        }
+
+        state.acquiring = false;
+    }
+}
+
+/// Turn of a command to acquire a token, passed on when dropped.
+struct Turn<'a> {
+    /// Queue the turn belongs to.
+    queue: &'a CommandQueue,
+    /// Receiver of the turn, if the command is still waiting for it.
+    rx: Option<Receiver<()>>,
+}
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        match self.rx.as_mut() {
+            Some(rx) => {
+                rx.close();
+
+                // The turn may have been passed on right before the command
+                // stopped waiting for it.
+                if rx.try_recv().is_ok() {
+                    self.queue.release();
+                }
+            }
+            None => self.queue.release(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandQueue, Priority};
+    use leaky_bucket_lite::LeakyBucket;
+    use static_assertions::assert_impl_all;
+    use std::{fmt::Debug, hash::Hash, sync::Arc, time::Duration};
+    use tokio::{sync::mpsc, task, time};
+
+    assert_impl_all!(
+        Priority: Clone,
+        Copy,
+        Debug,
+        Eq,
+        Hash,
+        Ord,
+        PartialEq,
+        PartialOrd,
+        Send,
+        Sync
+    );
+    assert_impl_all!(CommandQueue: Debug, Send, Sync);
+
+    #[tokio::test]
+    async fn acquires_by_priority() {
+        let bucket = LeakyBucket::builder()
+            .max(1)
+            .tokens(0)
+            .refill_interval(Duration::from_millis(50))
+            .refill_amount(1)
+            .build();
+        let queue = Arc::new(CommandQueue::new(bucket));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        for priority in [Priority::Normal, Priority::Low, Priority::High] {
+            let queue = Arc::clone(&queue);
+            let tx = tx.clone();
+
+            tokio::spawn(async move {
+                queue.acquire(priority).await;
+                tx.send(priority).unwrap();
+            });
+
+            task::yield_now().await;
+        }
+
+        // The first command waits for a token while the others wait for
+        // their turn.
+        while queue.depth(Priority::Low) + queue.depth(Priority::High) < 2 {
+            time::sleep(Duration::from_millis(1)).await;
+        }
+
+        assert_eq!(0, queue.depth(Priority::Normal));
+        assert_eq!(Some(Priority::Normal), rx.recv().await);
+        assert_eq!(Some(Priority::High), rx.recv().await);
+        assert_eq!(Some(Priority::Low), rx.recv().await);
+    }
+
+    #[tokio::test]
+    async fn cancelled_turn_is_passed_on() {
+        let bucket = LeakyBucket::builder()
+            .max(1)
+            .tokens(0)
+            .refill_interval(Duration::from_millis(10))
+            .refill_amount(1)
+            .build();
+        let queue = CommandQueue::new(bucket);
+
+        // Stop waiting while holding the turn.
+        assert!(
+            time::timeout(Duration::from_millis(1), queue.acquire(Priority::Low))
+                .await
+                .is_err()
+        );
+
+        time::timeout(Duration::from_secs(1), queue.acquire(Priority::Low))
+            .await
+            .unwrap();
+    }
+}
@@ -1,9 +1,47 @@
-use flate2::{Decompress, DecompressError, FlushDecompress};
+use flate2::{Decompress, DecompressError, FlushDecompress, Status};
 use std::{mem, time::Instant};
 
 const ZLIB_SUFFIX: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
 const INTERNAL_BUFFER_SIZE: usize = 32 * 1024;
 
+/// First byte of a zlib header using the deflate method with a 32 KiB window.
+const ZLIB_HEADER: u8 = 0x78;
+
+/// Whether a payload is compressed with zlib.
+///
+/// Payloads that are not compressed begin with a JSON object or, if encoded
+/// in ETF, the format's version.
+pub fn is_compressed(payload: &[u8]) -> bool {
+    payload.first() == Some(&ZLIB_HEADER)
+}
+
+/// Decompress an individually compressed payload.
+///
+/// # Errors
+///
+/// Returns `flate2`'s `DecompressError` if the payload is not valid zlib.
+pub fn inflate(compressed: &[u8]) -> Result<Vec<u8>, DecompressError> {
+    let mut decompress = Decompress::new(true);
+    let mut buffer = Vec::with_capacity(INTERNAL_BUFFER_SIZE);
+
+    loop {
+        let before = decompress.total_in();
+        let offset = before.try_into().unwrap_or(compressed.len());
+        let status =
+            decompress.decompress_vec(&compressed[offset..], &mut buffer, FlushDecompress::Sync)?;
+
+        let consumed = decompress.total_in() >= compressed.len() as u64;
+        let stalled = decompress.total_in() == before && buffer.len() < buffer.capacity();
+
+        if status == Status::StreamEnd || (consumed && buffer.len() < buffer.capacity()) || stalled
+        {
+            return Ok(buffer);
+        }
+
+        buffer.reserve(INTERNAL_BUFFER_SIZE);
+    }
+}
+
 #[derive(Debug)]
 pub struct Inflater {
     decompress: Decompress,
@@ -96,7 +134,11 @@ impl Inflater {
             let saved_percentage =
                 self.decompress.total_in() as f64 / self.decompress.total_out() as f64;
             let saved_percentage_readable = saved_percentage * 100.0;
-            let saved_kib = (self.decompress.total_out() - self.decompress.total_in()) / 1_024;
+            let saved_kib = self
+                .decompress
+                .total_out()
+                .saturating_sub(self.decompress.total_in())
+                / 1_024;
 
             tracing::trace!(
                 saved_kib = saved_kib,
@@ -203,6 +245,15 @@ mod tests {
     ];
     const SHARD: [u64; 2] = [2, 5];
 
+    #[test]
+    fn inflate() -> Result<(), Box<dyn Error>> {
+        assert!(super::is_compressed(MESSAGE));
+        assert!(!super::is_compressed(OUTPUT));
+        assert_eq!(OUTPUT, super::inflate(MESSAGE)?);
+
+        Ok(())
+    }
+
     #[test]
     fn inflater() -> Result<(), Box<dyn Error>> {
         let mut inflater = Inflater::new(SHARD);
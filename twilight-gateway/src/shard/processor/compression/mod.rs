@@ -1,116 +1,128 @@
 #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
 mod inflater;
 
-use super::r#impl::ReceivingEventError;
+use super::{
+    super::{Compression, Encoding},
+    r#impl::ReceivingEventError,
+};
 
+#[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+use super::r#impl::ReceivingEventErrorType;
 #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
 use inflater::Inflater;
 
-/// Interface for working with buffers variable on the `zlib-stock` and
-/// `zlib-simd` feature flags.
+/// Buffer of received payloads, decompressing them according to the
+/// configured [`Compression`].
 #[derive(Debug)]
-pub struct Compression {
-    /// Inflater for use with compression.
-    #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
-    inner: Inflater,
-    /// Buffer for use without compression.
-    #[cfg(not(any(feature = "zlib-stock", feature = "zlib-simd")))]
+pub struct Buffer {
+    /// Compression of received payloads.
+    compression: Compression,
+    /// Encoding of received payloads.
+    encoding: Encoding,
+    /// Buffer of the received payload, decompressed if it was individually
+    /// compressed.
     inner: Vec<u8>,
+    /// Inflater of the zlib stream, if payloads are compressed as a stream.
+    #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+    inflater: Option<Inflater>,
 }
 
-impl Compression {
-    /// Create a new buffer, abstracting over an inflater if `zlib-stock` or
-    /// `zlib-simd` features are enabled or a simple `Vec` if the features are
-    /// disabled.
+impl Buffer {
+    /// Create a new buffer, abstracting over an inflater if payloads are
+    /// compressed as a zlib stream or a simple `Vec` otherwise.
     #[cfg_attr(
         not(any(feature = "zlib-stock", feature = "zlib-simd")),
         allow(clippy::missing_const_for_fn, unused_variables)
     )]
-    pub fn new(shard_id: [u64; 2]) -> Self {
+    pub fn new(shard_id: [u64; 2], compression: Compression, encoding: Encoding) -> Self {
         Self {
-            #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
-            inner: Inflater::new(shard_id),
-            #[cfg(not(any(feature = "zlib-stock", feature = "zlib-simd")))]
+            compression,
+            encoding,
             inner: Vec::new(),
+            #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+            inflater: compression.is_stream().then(|| Inflater::new(shard_id)),
         }
     }
 
     /// Mutable reference to the internal buffer slice.
     ///
-    /// When compression is enabled this will mutably reference the inflater's
-    /// buffer.
+    /// When payloads are compressed as a stream this will mutably reference
+    /// the inflater's buffer.
     ///
-    /// When compression is disabled this will mutably reference the standard
-    /// buffer.
+    /// Otherwise this will mutably reference the standard buffer.
     pub fn buffer_slice_mut(&mut self) -> &mut [u8] {
         #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
-        {
-            self.inner.buffer_mut()
+        if let Some(inflater) = self.inflater.as_mut() {
+            return inflater.buffer_mut();
         }
 
-        #[cfg(not(any(feature = "zlib-stock", feature = "zlib-simd")))]
         self.inner.as_mut_slice()
     }
 
     /// Clear the inner buffer.
     pub fn clear(&mut self) {
+        #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+        if let Some(inflater) = self.inflater.as_mut() {
+            inflater.clear();
+        }
+
         self.inner.clear();
     }
 
     /// Extend the buffer with bytes from a Binary websocket message.
     ///
-    /// If compression is disabled then this will do nothing.
+    /// If compression is disabled and the encoding is JSON then this will do
+    /// nothing.
     ///
     /// Returns whether the inner buffer was extended.
-    #[cfg_attr(
-        not(any(feature = "zlib-stock", feature = "zlib-simd")),
-        allow(clippy::unused_self, unused_variables)
-    )]
     pub fn extend_binary(&mut self, bytes: &[u8]) -> bool {
         #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
-        {
-            self.inner.extend(bytes);
+        if let Some(inflater) = self.inflater.as_mut() {
+            inflater.extend(bytes);
 
-            true
+            return true;
         }
 
-        #[cfg(not(any(feature = "zlib-stock", feature = "zlib-simd")))]
-        // Binary payloads are not received when compression is disabled.
-        false
+        // Binary payloads are not received when compression is disabled,
+        // unless they are encoded in ETF.
+        if self.compression == Compression::Disabled && self.encoding == Encoding::Json {
+            return false;
+        }
+
+        self.inner.extend_from_slice(bytes);
+
+        true
     }
 
     /// Extend the buffer with bytes from a Text websocket message.
     ///
-    /// If compression is enabled then this will do nothing.
+    /// If payloads are compressed as a stream then this will do nothing.
     ///
     /// Returns whether the inner buffer was extended.
-    #[cfg_attr(
-        any(feature = "zlib-stock", feature = "zlib-simd"),
-        allow(clippy::unused_self, unused_variables)
-    )]
     pub fn extend_text(&mut self, bytes: &[u8]) -> bool {
-        #[cfg(not(any(feature = "zlib-stock", feature = "zlib-simd")))]
-        {
-            self.inner.extend_from_slice(bytes);
-
-            true
+        // Text payloads are not received when payloads are compressed as a
+        // stream.
+        if self.compression.is_stream() {
+            return false;
         }
 
-        #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
-        // Text payloads are not received when compression is enabled.
-        false
+        self.inner.extend_from_slice(bytes);
+
+        true
     }
 
-    /// Mutable reference to the inner completed message if compression is
-    /// enabled.
+    /// Mutable reference to the inner completed message of a Binary websocket
+    /// message.
     ///
-    /// If compression is enabled and a message has completed then a mutable
-    /// slice of the buffer is returned.
+    /// If payloads are compressed as a stream and a message has completed
+    /// then a mutable slice of the buffer is returned, otherwise a successful
+    /// `None` is returned.
     ///
-    /// If compression is enabled and a message has *not* completed then a
-    /// successful `None` is returned.
+    /// If payloads are individually compressed then the message is
+    /// decompressed, unless it was sent uncompressed, and returned.
     ///
-    /// If compression is disabled then a successful `None` is returned.
+    /// If compression is disabled then a successful `None` is returned, unless
+    /// the encoding is not JSON, in which case the buffer is returned.
     ///
     /// # Errors
     ///
@@ -119,60 +131,127 @@ impl Compression {
     /// message failed.
     #[cfg_attr(
         not(any(feature = "zlib-stock", feature = "zlib-simd")),
-        allow(clippy::unused_self)
+        allow(clippy::unnecessary_wraps)
     )]
     pub fn message_mut(&mut self) -> Result<Option<&mut [u8]>, ReceivingEventError> {
-        #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
-        {
-            use super::r#impl::ReceivingEventErrorType;
-
-            self.inner.msg().map_err(|source| ReceivingEventError {
-                kind: ReceivingEventErrorType::Decompressing,
-                source: Some(Box::new(source)),
-            })
-        }
+        Ok(match self.compression {
+            #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+            Compression::Payload if inflater::is_compressed(&self.inner) => {
+                self.inner =
+                    inflater::inflate(&self.inner).map_err(|source| ReceivingEventError {
+                        kind: ReceivingEventErrorType::Decompressing,
+                        source: Some(Box::new(source)),
+                    })?;
 
-        #[cfg(not(any(feature = "zlib-stock", feature = "zlib-simd")))]
-        Ok(None)
+                Some(self.inner.as_mut_slice())
+            }
+            #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+            Compression::Stream => match self.inflater.as_mut() {
+                Some(inflater) => inflater.msg().map_err(|source| ReceivingEventError {
+                    kind: ReceivingEventErrorType::Decompressing,
+                    source: Some(Box::new(source)),
+                })?,
+                None => None,
+            },
+            _ if self.compression == Compression::Disabled && self.encoding == Encoding::Json => {
+                None
+            }
+            _ => Some(self.inner.as_mut_slice()),
+        })
     }
 
     /// Reset the buffer for a new gateway session.
     pub fn reset(&mut self) {
         #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
-        self.inner.reset();
+        if let Some(inflater) = self.inflater.as_mut() {
+            inflater.reset();
+        }
 
-        #[cfg(not(any(feature = "zlib-stock", feature = "zlib-simd")))]
-        self.clear();
+        self.inner.clear();
     }
 }
 
-/// Add a toggle to a gateway connection URL depending on whether compression is
-/// enabled.
+/// Add a toggle to a gateway connection URL depending on the compression.
 ///
-/// If compression is enabled then the `compress` query parameter is appended
-/// with a value of `zlib-stream`.
-#[cfg_attr(
-    not(any(feature = "zlib-stock", feature = "zlib-simd")),
-    allow(clippy::ptr_arg, unused_variables)
-)]
-pub fn add_url_feature(buf: &mut String) {
-    #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
-    buf.push_str("&compress=zlib-stream");
+/// If payloads are compressed as a stream then the `compress` query parameter
+/// is appended with a value of `zlib-stream`.
+pub fn add_url_feature(buf: &mut String, compression: Compression) {
+    if compression.is_stream() {
+        buf.push_str("&compress=zlib-stream");
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{Buffer, Compression, Encoding};
+
+    const JSON: &[u8] = br#"{"op":11,"d":null}"#;
+    const SHARD: [u64; 2] = [0, 1];
+
     #[test]
     fn add_url_features() {
         let mut buf = String::new();
-        super::add_url_feature(&mut buf);
-
-        #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
-        {
-            assert_eq!("&compress=zlib-stream", buf);
-        }
+        super::add_url_feature(&mut buf, Compression::Disabled);
+        assert!(buf.is_empty());
 
-        #[cfg(not(any(feature = "zlib-stock", feature = "zlib-simd")))]
+        super::add_url_feature(&mut buf, Compression::Payload);
         assert!(buf.is_empty());
+
+        super::add_url_feature(&mut buf, Compression::Stream);
+        assert_eq!("&compress=zlib-stream", buf);
+    }
+
+    #[test]
+    fn disabled() {
+        let mut buffer = Buffer::new(SHARD, Compression::Disabled, Encoding::Json);
+        assert!(!buffer.extend_binary(JSON));
+        assert!(buffer.extend_text(JSON));
+        assert_eq!(JSON, buffer.buffer_slice_mut());
+
+        buffer.clear();
+        assert!(buffer.buffer_slice_mut().is_empty());
+    }
+
+    #[test]
+    fn disabled_etf() {
+        let etf = crate::shard::etf::to_vec(&serde_json::json!({ "op": 11 })).unwrap();
+
+        let mut buffer = Buffer::new(SHARD, Compression::Disabled, Encoding::Etf);
+        assert!(buffer.extend_binary(&etf));
+        assert_eq!(
+            Some(etf.as_slice()),
+            buffer.message_mut().unwrap().as_deref()
+        );
+        assert_eq!(etf, buffer.buffer_slice_mut());
+    }
+
+    #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+    #[test]
+    fn payload() {
+        use flate2::{write::ZlibEncoder, Compression as Level};
+        use std::io::Write;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Level::default());
+        encoder.write_all(JSON).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut buffer = Buffer::new(SHARD, Compression::Payload, Encoding::Json);
+        assert!(buffer.extend_binary(&compressed));
+        assert_eq!(Some(JSON), buffer.message_mut().unwrap().as_deref());
+        assert_eq!(JSON, buffer.buffer_slice_mut());
+
+        // Payloads may also be sent uncompressed.
+        buffer.clear();
+        assert!(buffer.extend_text(JSON));
+        assert_eq!(JSON, buffer.buffer_slice_mut());
+    }
+
+    #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+    #[test]
+    fn stream() {
+        let mut buffer = Buffer::new(SHARD, Compression::Stream, Encoding::Json);
+        assert!(!buffer.extend_text(JSON));
+        assert!(buffer.extend_binary(&[0x78, 0x9c]));
+        assert_eq!(None, buffer.message_mut().unwrap());
     }
 }
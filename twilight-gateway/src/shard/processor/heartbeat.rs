@@ -1,5 +1,5 @@
 use super::{
-    super::json,
+    super::{raw_message::Message, Encoding},
     session::{SessionSendError, SessionSendErrorType},
 };
 use serde::{Deserialize, Serialize};
@@ -12,8 +12,7 @@ use std::{
     time::{Duration, Instant},
 };
 use tokio::sync::mpsc::UnboundedSender;
-use tokio_tungstenite::tungstenite::Message as TungsteniteMessage;
-use twilight_model::gateway::payload::outgoing::Heartbeat;
+use twilight_model::gateway::{event::shard::DisconnectCause, payload::outgoing::Heartbeat};
 
 /// Information about the latency of a [`Shard`]'s websocket connection.
 ///
@@ -103,18 +102,22 @@ impl Heartbeats {
         self.received().is_some()
     }
 
-    pub fn receive(&self) {
+    /// Record a heartbeat acknowledgement, returning the latency of the
+    /// acknowledged heartbeat if one was sent.
+    pub fn receive(&self) -> Option<Duration> {
         self.set_received(Instant::now());
 
         self.total_iterations.fetch_add(1, Ordering::SeqCst);
 
-        if let Some(dur) = self.sent().map(|s| s.elapsed()) {
+        let dur = self.sent().map(|s| s.elapsed());
+
+        if let Some(dur) = dur {
             let millis = if let Ok(millis) = dur.as_millis().try_into() {
                 millis
             } else {
                 tracing::error!("duration millis is more than u64: {dur:?}");
 
-                return;
+                return None;
             };
 
             self.total_time.fetch_add(millis, Ordering::SeqCst);
@@ -127,6 +130,8 @@ impl Heartbeats {
 
             recent.push_back(millis);
         }
+
+        dur
     }
 
     pub fn send(&self) {
@@ -174,24 +179,35 @@ impl Default for Heartbeats {
 }
 
 pub struct Heartbeater {
+    encoding: Encoding,
     heartbeats: Arc<Heartbeats>,
     interval: u64,
+    /// Number of heartbeats in a row that may go unacknowledged.
+    missed_acks: u32,
     seq: Arc<AtomicU64>,
-    tx: UnboundedSender<TungsteniteMessage>,
+    tx: UnboundedSender<Message>,
+    /// Sender notifying the processor that the connection is a zombie.
+    zombie: UnboundedSender<DisconnectCause>,
 }
 
 impl Heartbeater {
     pub fn new(
+        encoding: Encoding,
         heartbeats: Arc<Heartbeats>,
         interval: u64,
+        missed_acks: u32,
         seq: Arc<AtomicU64>,
-        tx: UnboundedSender<TungsteniteMessage>,
+        tx: UnboundedSender<Message>,
+        zombie: UnboundedSender<DisconnectCause>,
     ) -> Self {
         Self {
+            encoding,
             heartbeats,
             interval,
+            missed_acks,
             seq,
             tx,
+            zombie,
         }
     }
 
@@ -208,37 +224,44 @@ impl Heartbeater {
     async fn try_run(self) -> Result<(), SessionSendError> {
         let duration = Duration::from_millis(self.interval);
 
-        let mut last = true;
+        let mut missed = 0;
 
         loop {
             tokio::time::sleep(duration).await;
 
-            // Check if a heartbeat acknowledgement was received.
+            // Check if the last sent heartbeat was acknowledged.
             //
-            // If so, then check if one was received last time.
-            //
-            // - if so, then mark that we didn't get one this time
-            // - if not, then end the heartbeater because something is off
-            // (connecting closed?)
-            if self.heartbeats.last_acked() {
-                last = true;
-            } else if last {
-                last = false;
+            // If too many heartbeats in a row went unacknowledged then the
+            // connection is a zombie, so notify the processor and end the
+            // heartbeater.
+            if self.heartbeats.sent().is_none() || self.heartbeats.last_acked() {
+                missed = 0;
             } else {
-                return Ok(());
+                missed += 1;
+
+                if missed >= self.missed_acks {
+                    tracing::warn!(missed, "heartbeats not acknowledged");
+
+                    let _res = self.zombie.send(DisconnectCause::HeartbeatAckMissed);
+
+                    return Ok(());
+                }
             }
 
             let seq = self.seq.load(Ordering::Acquire);
             let heartbeat = Heartbeat::new(seq);
-            let bytes = json::to_vec(&heartbeat).map_err(|source| SessionSendError {
-                kind: SessionSendErrorType::Serializing,
-                source: Some(Box::new(source)),
-            })?;
+            let bytes = self
+                .encoding
+                .to_vec(&heartbeat)
+                .map_err(|source| SessionSendError {
+                    kind: SessionSendErrorType::Serializing,
+                    source: Some(source),
+                })?;
 
             tracing::debug!(seq, "sending heartbeat");
 
             self.tx
-                .send(TungsteniteMessage::Binary(bytes))
+                .send(Message::Binary(bytes))
                 .map_err(|source| SessionSendError {
                     kind: SessionSendErrorType::Sending,
                     source: Some(Box::new(source)),
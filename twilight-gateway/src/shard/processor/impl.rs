@@ -1,55 +1,52 @@
 use super::{
     super::{
         emitter::{EmitJsonErrorType, Emitter},
-        json::{self, GatewayEventParsingError, GatewayEventParsingErrorType},
-        Config, ShardStream, Stage,
+        etf, guild_filter,
+        json::{GatewayEventParsingError, GatewayEventParsingErrorType},
+        members::MemberRequests,
+        presence::CurrentPresence,
+        raw_message::{CloseFrame, Message},
+        recorder::{Direction, Record},
+        transport::{GatewayConnection, GatewayTransport, TungsteniteTransport},
+        Config, Encoding, ResumeSession, Stage, ZombieAction,
     },
-    compression::{self, Compression},
+    compression::{self, Buffer},
     session::{Session, SessionSendError, SessionSendErrorType},
     socket_forwarder::SocketForwarder,
 };
 use crate::{EventTypeFlags, API_VERSION};
+use futures_util::future::{self, Either};
 use serde::{Deserialize, Serialize};
 use std::{
-    borrow::Cow,
     env::consts::OS,
     error::Error,
     fmt::{Debug, Display, Formatter, Result as FmtResult},
     str,
     sync::{atomic::Ordering, Arc},
-    time::Duration,
 };
 use tokio::sync::{
-    mpsc::UnboundedReceiver,
+    mpsc::{self, UnboundedReceiver},
     watch::{channel as watch_channel, Receiver as WatchReceiver, Sender as WatchSender},
 };
-use tokio_tungstenite::tungstenite::{
-    protocol::{frame::coding::CloseCode, CloseFrame, WebSocketConfig},
-    Message,
-};
 use twilight_model::gateway::{
     event::{
-        shard::{Connected, Connecting, Disconnected, Identifying, Reconnecting, Resuming},
+        shard::{
+            Connected, Connecting, DisconnectCause, Disconnected, Fatal, Identifying, Reconnecting,
+            Resuming,
+        },
         DispatchEvent, Event, GatewayEvent, GatewayEventDeserializer,
     },
     payload::{
-        incoming::Ready,
+        incoming::{MemberChunk, Ready},
         outgoing::{
             identify::{Identify, IdentifyInfo, IdentifyProperties},
             resume::Resume,
         },
     },
-    Intents, OpCode,
+    CloseCode, Intents, OpCode,
 };
 use url::Url;
 
-#[cfg(any(
-    feature = "native",
-    feature = "rustls-native-roots",
-    feature = "rustls-webpki-roots"
-))]
-use crate::shard::tls::TlsContainer;
-
 /// Connecting to the gateway failed.
 #[derive(Debug)]
 pub struct ConnectingError {
@@ -121,7 +118,7 @@ impl Display for ProcessError {
 
                 f.write_str(") pair is unknown")
             }
-            ProcessErrorType::ParsingPayload => f.write_str("payload could not be parsed as json"),
+            ProcessErrorType::ParsingPayload => f.write_str("payload could not be parsed"),
             ProcessErrorType::PayloadNotUtf8 { .. } => {
                 f.write_str("the payload from Discord wasn't UTF-8 valid")
             }
@@ -181,6 +178,7 @@ impl ReceivingEventError {
                 | ReceivingEventErrorType::InvalidApiVersion
                 | ReceivingEventErrorType::InvalidShard { .. }
                 | ReceivingEventErrorType::ShardingRequired
+                | ReceivingEventErrorType::ClosedFatally { .. }
         )
     }
 
@@ -202,6 +200,11 @@ impl Display for ReceivingEventError {
 
                 f.write_str(" is invalid")
             }
+            ReceivingEventErrorType::ClosedFatally { code } => {
+                f.write_str("the connection was closed with the fatal close code ")?;
+
+                Display::fmt(&(*code as u16), f)
+            }
             ReceivingEventErrorType::Decompressing => {
                 f.write_str("a frame could not be decompressed")
             }
@@ -258,7 +261,17 @@ impl Error for ReceivingEventError {
 pub enum ReceivingEventErrorType {
     /// Provided authorization token is invalid.
     AuthorizationInvalid { shard_id: u64, token: String },
+    /// The connection was closed with a fatal close code not covered by
+    /// another error type.
+    ClosedFatally {
+        /// Close code the connection was closed with.
+        code: CloseCode,
+    },
     /// Decompressing a frame from Discord failed.
+    #[cfg_attr(
+        not(any(feature = "zlib-stock", feature = "zlib-simd")),
+        allow(dead_code)
+    )]
     Decompressing,
     /// The event stream has ended, this is recoverable by resuming.
     EventStreamEnded,
@@ -299,6 +312,11 @@ struct ReadyMinimal {
     d: Ready,
 }
 
+#[derive(Deserialize)]
+struct MemberChunkMinimal {
+    d: MemberChunk,
+}
+
 /// Runs in the background and processes incoming events, and then broadcasts
 /// to all listeners.
 #[derive(Debug)]
@@ -307,26 +325,55 @@ pub struct ShardProcessor {
     pub emitter: Emitter,
     pub rx: UnboundedReceiver<Message>,
     pub session: Arc<Session>,
-    compression: Compression,
+    compression: Buffer,
     gateway_endpoint: Box<str>,
     gateway_params: Box<str>,
+    /// Requests for guild members waiting for their member chunks.
+    member_requests: Arc<MemberRequests>,
+    /// Presence to identify with.
+    presence: Arc<CurrentPresence>,
+    /// Number of reconnect attempts since the session was last ready or
+    /// resumed.
+    reconnect_attempt: u32,
     resume: Option<(u64, Box<str>)>,
+    /// Whether the reconnect strategy gave up, stopping the processor.
+    stopped: bool,
+    transport: Arc<dyn GatewayTransport>,
     wtx: WatchSender<Arc<Session>>,
+    /// Receiver of notifications from the heartbeater that the connection is
+    /// a zombie.
+    zombie: UnboundedReceiver<DisconnectCause>,
 }
 
 impl ShardProcessor {
+    #[allow(clippy::too_many_lines)]
     pub async fn new(
         config: Arc<Config>,
         emitter: Emitter,
+        member_requests: Arc<MemberRequests>,
+        presence: Arc<CurrentPresence>,
     ) -> Result<(Self, WatchReceiver<Arc<Session>>), ConnectingError> {
         //if we got resume info we don't need to wait
         let shard_id = config.shard();
-        let resumable = config.sequence.is_some() && config.session_id.is_some();
-        let url = if resumable {
-            config
+        let resume_session = match (&config.session_id, config.sequence) {
+            (Some(session_id), Some(sequence)) => Some(ResumeSession {
+                resume_url: config.resume_url.as_deref().map(ToOwned::to_owned),
+                session_id: session_id.to_string(),
+                sequence,
+            }),
+            _ => match &config.session_store {
+                Some(session_store) if config.session_store_resume => {
+                    session_store.load(shard_id[0]).await
+                }
+                _ => None,
+            },
+        };
+        let resumable = resume_session.is_some();
+        let url = if let Some(resume_session) = &resume_session {
+            resume_session
                 .resume_url
-                .as_ref()
-                .map_or_else(|| config.gateway_url().to_owned(), ToString::to_string)
+                .clone()
+                .unwrap_or_else(|| config.gateway_url().to_owned())
         } else {
             tracing::debug!("shard {shard_id:?} is not resumable");
             tracing::debug!("shard {shard_id:?} queued");
@@ -347,56 +394,88 @@ impl ShardProcessor {
         // and encoding".
         //
         // <https://discord.com/developers/docs/topics/gateway#connecting-gateway-url-query-string-params>
-        params.push_str("&encoding=json");
-
-        compression::add_url_feature(&mut params);
+        params.push_str("&encoding=");
+        params.push_str(config.encoding().name());
+
+        compression::add_url_feature(&mut params, config.compression());
+
+        emitter
+            .event(Event::ShardConnecting(Connecting {
+                gateway: url.clone(),
+                shard_id: config.shard()[0],
+            }))
+            .await;
+        let transport = config.transport.clone().unwrap_or_else(|| {
+            #[cfg(not(any(
+                feature = "native",
+                feature = "rustls-native-roots",
+                feature = "rustls-webpki-roots"
+            )))]
+            let transport = TungsteniteTransport::new();
 
-        emitter.event(Event::ShardConnecting(Connecting {
-            gateway: url.clone(),
-            shard_id: config.shard()[0],
-        }));
-        let stream = Self::connect(
-            &url,
-            &params,
             #[cfg(any(
                 feature = "native",
                 feature = "rustls-native-roots",
                 feature = "rustls-webpki-roots"
             ))]
-            config.tls.as_ref(),
-        )
-        .await?;
-        let (forwarder, rx, tx) = SocketForwarder::new(stream);
-        tokio::spawn(forwarder.run());
+            let transport = TungsteniteTransport::with_tls(config.tls.clone());
 
-        let session = Arc::new(Session::new(tx, config.ratelimit_payloads));
+            Arc::new(transport)
+        });
+        let connection = Self::connect(transport.as_ref(), &url, &params).await?;
+        let (forwarder, rx, tx) = SocketForwarder::new(
+            connection,
+            shard_id[0],
+            config.encoding(),
+            config.recorder.clone(),
+        );
+        tokio::spawn(forwarder.run());
 
-        if resumable {
-            session.set_id(config.session_id.clone().unwrap());
+        let (zombie_tx, zombie) = mpsc::unbounded_channel();
+        let session = Arc::new(Session::new(
+            tx,
+            shard_id[0],
+            config.ratelimit_payloads,
+            config.encoding(),
+            zombie_tx,
+        ));
+
+        if let Some(resume_session) = resume_session {
+            session.set_id(resume_session.session_id.into_boxed_str());
             session
                 .seq
-                .store(config.sequence.unwrap(), Ordering::Relaxed)
+                .store(resume_session.sequence, Ordering::Relaxed);
+
+            if let Some(resume_url) = resume_session.resume_url {
+                session.set_resume_url(resume_url.into_boxed_str());
+            }
         }
 
         let (wtx, wrx) = watch_channel(Arc::clone(&session));
 
         let gateway_url = config.gateway_url().to_owned();
         let mut processor = Self {
-            compression: Compression::new(shard_id),
+            compression: Buffer::new(shard_id, config.compression(), config.encoding()),
             config,
             emitter,
             rx,
             session,
             gateway_endpoint: gateway_url.into_boxed_str(),
             gateway_params: params.into_boxed_str(),
+            member_requests,
+            presence,
+            reconnect_attempt: 0,
             resume: None,
+            stopped: false,
+            transport,
             wtx,
+            zombie,
         };
 
         if resumable {
             tracing::debug!("resuming shard {shard_id:?}");
 
-            processor.resume().await;
+            processor.resume("Startup").await;
         }
 
         Ok((processor, wrx))
@@ -404,21 +483,30 @@ impl ShardProcessor {
 
     pub async fn run(mut self) {
         loop {
+            if self.stopped {
+                break;
+            }
+
             if let Err(source) = self.next_payload().await {
                 tracing::warn!("{source}");
 
-                self.emit_disconnected(None, None);
-
+                // Fatal errors are from fatal close codes, whose disconnect
+                // has already been emitted.
                 if source.fatal() {
+                    self.session.set_stage(Stage::Disconnected);
+
                     break;
                 }
 
+                self.emit_disconnected(DisconnectCause::Errored, None, None)
+                    .await;
+
                 if source.reconnectable() {
-                    self.reconnect().await;
+                    self.reconnect("Receiving").await;
                 }
 
                 if source.resumable() {
-                    self.resume().await;
+                    self.resume("Receiving").await;
                 }
 
                 continue;
@@ -441,9 +529,10 @@ impl ShardProcessor {
 
                 if source.fatal() {
                     tracing::debug!("error processing event; reconnecting");
-                    self.emit_disconnected(None, None);
+                    self.emit_disconnected(DisconnectCause::Errored, None, None)
+                        .await;
 
-                    self.reconnect().await;
+                    self.reconnect("Processing").await;
                 }
             }
         }
@@ -451,45 +540,63 @@ impl ShardProcessor {
 
     #[allow(clippy::too_many_lines)]
     async fn process(&mut self) -> Result<(), ProcessError> {
+        let encoding = self.config.encoding();
+
         let (op, seq, event_type) = {
             let buffer = self.compression.buffer_slice_mut();
-            let json = str::from_utf8_mut(buffer).map_err(|source| ProcessError {
-                kind: ProcessErrorType::PayloadNotUtf8,
-                source: Some(Box::new(source)),
-            })?;
 
-            tracing::trace!(%json, "Received JSON");
+            let header = match encoding {
+                Encoding::Etf => {
+                    tracing::trace!(bytes = ?buffer, "Received ETF");
 
-            let emitter = self.emitter.clone();
+                    etf::peek(buffer)
+                }
+                Encoding::Json => {
+                    let json = str::from_utf8_mut(buffer).map_err(|source| ProcessError {
+                        kind: ProcessErrorType::PayloadNotUtf8,
+                        source: Some(Box::new(source)),
+                    })?;
 
-            let (op, seq, event_type) =
-                if let Some(deserializer) = GatewayEventDeserializer::from_json(json) {
-                    let (op, seq, event_type) = deserializer.into_parts();
+                    tracing::trace!(%json, "Received JSON");
 
-                    // Unfortunately lifetimes and mutability requirements
-                    // conflict here if we return an immutable reference to the
-                    // event type, so we're going to have to take ownership of
-                    // this if we don't want to do anything too dangerous. It
-                    // should be a good trade-off either way.
-                    (op, seq, event_type.map(ToOwned::to_owned))
-                } else {
-                    tracing::error!(
-                        json = ?self.compression.buffer_slice_mut(),
-                        shard_id = self.config.shard()[0],
-                        shard_total = self.config.shard()[1],
-                        seq = self.session.seq(),
-                        stage = ?self.session.stage(),
-                        "received payload without opcode",
-                    );
+                    GatewayEventDeserializer::from_json(json).map(|deserializer| {
+                        let (op, seq, event_type) = deserializer.into_parts();
 
-                    return Err(ProcessError {
-                        kind: ProcessErrorType::ParsingPayload,
-                        source: Some(Box::new(GatewayEventParsingError {
-                            kind: GatewayEventParsingErrorType::PayloadInvalid,
-                            source: None,
-                        })),
-                    });
-                };
+                        // Unfortunately lifetimes and mutability requirements
+                        // conflict here if we return an immutable reference
+                        // to the event type, so we're going to have to take
+                        // ownership of this if we don't want to do anything
+                        // too dangerous. It should be a good trade-off either
+                        // way.
+                        (op, seq, event_type.map(ToOwned::to_owned))
+                    })
+                }
+            };
+
+            let emitter = self.emitter.clone();
+
+            let (op, seq, event_type) = if let Some(header) = header {
+                header
+            } else {
+                tracing::error!(
+                    payload = ?self.compression.buffer_slice_mut(),
+                    shard_id = self.config.shard()[0],
+                    shard_total = self.config.shard()[1],
+                    seq = self.session.seq(),
+                    stage = ?self.session.stage(),
+                    "received payload without opcode",
+                );
+
+                return Err(ProcessError {
+                    kind: ProcessErrorType::ParsingPayload,
+                    source: Some(Box::new(GatewayEventParsingError {
+                        kind: GatewayEventParsingErrorType::PayloadInvalid,
+                        source: None,
+                    })),
+                });
+            };
+
+            let buffer = self.compression.buffer_slice_mut();
 
             // We can do a few little optimization tricks here. For the
             // "heartbeat ack" and "reconnect" opcodes we can construct
@@ -509,19 +616,20 @@ impl ShardProcessor {
                 } else if op == OpCode::Reconnect as u8 {
                     GatewayEvent::Reconnect
                 } else {
-                    json::parse_gateway_event(op, seq, event_type.as_deref(), buffer).map_err(
-                        |source| ProcessError {
+                    encoding
+                        .parse_gateway_event(op, seq, event_type.as_deref(), buffer)
+                        .map_err(|source| ProcessError {
                             kind: ProcessErrorType::ParsingPayload,
                             source: Some(Box::new(source)),
-                        },
-                    )?
+                        })?
                 };
 
                 self.process_gateway_event(&gateway_event).await?;
-                emitter.event(Event::from(gateway_event));
+                emitter.event(Event::from(gateway_event)).await;
 
                 if let Some(seq) = seq {
                     self.session.set_seq(seq);
+                    self.store_session().await;
                 }
 
                 return Ok(());
@@ -533,40 +641,58 @@ impl ShardProcessor {
             })?;
 
             if event_type.as_deref() == Some("RESUMED") {
-                self.process_resumed(seq);
+                self.process_resumed(seq).await;
 
                 if emitter.wants(EventTypeFlags::RESUMED) {
                     let gateway_event = GatewayEvent::Dispatch(seq, DispatchEvent::Resumed);
 
-                    emitter.event(Event::from(gateway_event));
+                    emitter.event(Event::from(gateway_event)).await;
                 }
 
                 return Ok(());
             } else if event_type.as_deref() == Some("READY") {
-                let ready = json::from_slice::<ReadyMinimal>(self.compression.buffer_slice_mut())
+                let ready = encoding
+                    .deserialize::<ReadyMinimal>(self.compression.buffer_slice_mut())
                     .map_err(|source| ProcessError {
-                    kind: ProcessErrorType::ParsingPayload,
-                    source: Some(Box::new(GatewayEventParsingError {
-                        kind: GatewayEventParsingErrorType::Deserializing,
-                        source: Some(Box::new(source)),
-                    })),
-                })?;
+                        kind: ProcessErrorType::ParsingPayload,
+                        source: Some(Box::new(GatewayEventParsingError {
+                            kind: GatewayEventParsingErrorType::Deserializing,
+                            source: Some(source),
+                        })),
+                    })?;
 
-                self.process_ready(&ready.d);
-                emitter.event(Event::Ready(Box::new(ready.d)));
+                self.process_ready(&ready.d).await;
+                emitter.event(Event::Ready(Box::new(ready.d))).await;
 
                 return Ok(());
             }
 
             self.session.set_seq(seq);
+            self.store_session().await;
 
             (op, seq, event_type)
         };
 
+        if event_type.as_deref() == Some("GUILD_MEMBERS_CHUNK") && self.member_requests.is_pending()
+        {
+            self.collect_member_chunk();
+        }
+
         let buffer = self.compression.buffer_slice_mut();
 
+        if let (Some(filter), Some(event_type)) =
+            (self.config.guild_filter(), event_type.as_deref())
+        {
+            if guild_filter::peek_guild_id(event_type, encoding, buffer)
+                .map_or(false, |guild_id| !filter.allows(guild_id))
+            {
+                return Ok(());
+            }
+        }
+
         self.emitter
-            .json(op, Some(seq), event_type.as_deref(), buffer)
+            .payload(op, Some(seq), event_type.as_deref(), encoding, buffer)
+            .await
             .map_err(|source| {
                 let (kind, source) = source.into_parts();
 
@@ -584,32 +710,57 @@ impl ShardProcessor {
             })
     }
 
-    fn process_ready(&mut self, ready: &Ready) {
+    /// Add the buffered member chunk to the guild members request it is a
+    /// response to, if any.
+    fn collect_member_chunk(&mut self) {
+        // Parsing may modify the buffer in place, which still has to be
+        // emitted.
+        let mut bytes = self.compression.buffer_slice_mut().to_vec();
+
+        match self
+            .config
+            .encoding()
+            .deserialize::<MemberChunkMinimal>(bytes.as_mut_slice())
+        {
+            Ok(chunk) => self.member_requests.chunk(chunk.d),
+            Err(source) => tracing::warn!("parsing member chunk failed: {source:?}"),
+        }
+    }
+
+    async fn process_ready(&mut self, ready: &Ready) {
         #[cfg(feature = "metrics")]
         metrics::counter!("GatewayEvent", 1, "GatewayEvent" => "Dispatch");
 
+        self.reconnect_attempt = 0;
         self.session.set_stage(Stage::Connected);
         self.session
             .set_id(ready.session_id.clone().into_boxed_str());
         self.session
             .set_resume_url(ready.resume_gateway_url.clone().into_boxed_str());
+        self.store_session().await;
 
-        self.emitter.event(Event::ShardConnected(Connected {
-            heartbeat_interval: self.session.heartbeat_interval(),
-            shard_id: self.config.shard()[0],
-        }));
+        self.emitter
+            .event(Event::ShardConnected(Connected {
+                heartbeat_interval: self.session.heartbeat_interval(),
+                shard_id: self.config.shard()[0],
+            }))
+            .await;
     }
 
-    fn process_resumed(&self, seq: u64) {
+    async fn process_resumed(&mut self, seq: u64) {
         #[cfg(feature = "metrics")]
         metrics::counter!("GatewayEvent", 1, "GatewayEvent" => "Dispatch");
 
+        self.reconnect_attempt = 0;
         self.session.set_seq(seq);
         self.session.set_stage(Stage::Connected);
-        self.emitter.event(Event::ShardConnected(Connected {
-            heartbeat_interval: self.session.heartbeat_interval(),
-            shard_id: self.config.shard()[0],
-        }));
+        self.store_session().await;
+        self.emitter
+            .event(Event::ShardConnected(Connected {
+                heartbeat_interval: self.session.heartbeat_interval(),
+                shard_id: self.config.shard()[0],
+            }))
+            .await;
         self.session.heartbeats.receive();
     }
 
@@ -618,7 +769,7 @@ impl ShardProcessor {
             GatewayEvent::Dispatch(_, _) => unreachable!("dispatch events separately handled"),
             GatewayEvent::Heartbeat(seq) => self.process_heartbeat(*seq).await,
             GatewayEvent::Hello(interval) => self.process_hello(*interval).await?,
-            GatewayEvent::HeartbeatAck => self.process_heartbeat_ack(),
+            GatewayEvent::HeartbeatAck => self.process_heartbeat_ack().await,
             GatewayEvent::InvalidateSession(resumable) => {
                 self.process_invalidate_session(*resumable).await
             }
@@ -628,11 +779,28 @@ impl ShardProcessor {
         Ok(())
     }
 
-    fn process_heartbeat_ack(&self) {
+    async fn process_heartbeat_ack(&mut self) {
         #[cfg(feature = "metrics")]
         metrics::counter!("GatewayEvent", 1, "GatewayEvent" => "HeartbeatAck");
 
-        self.session.heartbeats.receive();
+        let latency = match self.session.heartbeats.receive() {
+            Some(latency) => latency,
+            None => return,
+        };
+
+        #[cfg(feature = "metrics")]
+        metrics::histogram!(
+            "Shard-Heartbeat-Latency",
+            latency.as_secs_f64(),
+            "shard" => self.config.shard()[0].to_string(),
+        );
+
+        if self.config.zombie_policy().latency_exceeded(latency) {
+            tracing::warn!(?latency, "heartbeat acknowledged too late");
+
+            self.disconnect_zombie(DisconnectCause::HeartbeatLatencyExceeded)
+                .await;
+        }
     }
 
     async fn process_heartbeat(&mut self, seq: u64) {
@@ -640,15 +808,16 @@ impl ShardProcessor {
         metrics::counter!("GatewayEvent", 1, "GatewayEvent" => "Heartbeat");
 
         if seq > self.session.seq() + 1 {
-            self.resume().await;
+            self.resume("SequenceSkipped").await;
         }
 
         if let Err(source) = self.session.heartbeat() {
             tracing::warn!("error sending heartbeat; reconnecting: {source}");
 
-            self.emit_disconnected(None, None);
+            self.emit_disconnected(DisconnectCause::Errored, None, None)
+                .await;
 
-            self.reconnect().await;
+            self.reconnect("Heartbeat").await;
         }
     }
 
@@ -672,7 +841,8 @@ impl ShardProcessor {
 
             if interval > 0 {
                 self.session.set_heartbeat_interval(interval);
-                self.session.start_heartbeater();
+                self.session
+                    .start_heartbeater(self.config.zombie_policy().missed_acks);
             }
 
             self.send(payload).await.map_err(|source| ProcessError {
@@ -684,7 +854,8 @@ impl ShardProcessor {
 
             if interval > 0 {
                 self.session.set_heartbeat_interval(interval);
-                self.session.start_heartbeater();
+                self.session
+                    .start_heartbeater(self.config.zombie_policy().missed_acks);
             }
 
             self.identify().await.map_err(|source| ProcessError {
@@ -697,7 +868,8 @@ impl ShardProcessor {
     }
 
     async fn process_invalidate_session(&mut self, resumable: bool) {
-        self.emit_disconnected(None, None);
+        self.emit_disconnected(DisconnectCause::InvalidSession, None, None)
+            .await;
 
         if resumable {
             #[cfg(feature = "metrics")]
@@ -705,14 +877,15 @@ impl ShardProcessor {
 
             tracing::debug!("got request to resume the session");
 
-            self.resume().await;
+            self.resume("InvalidSession").await;
         } else {
             #[cfg(feature = "metrics")]
             metrics::counter!("GatewayEvent", 1, "GatewayEvent" => "InvalidateSessionFalse");
 
             tracing::debug!("got request to invalidate the session and reconnect");
 
-            self.reconnect().await;
+            self.remove_stored_session().await;
+            self.reconnect("InvalidSession").await;
         }
     }
 
@@ -722,18 +895,21 @@ impl ShardProcessor {
 
         tracing::debug!("got request to reconnect");
 
-        let frame = CloseFrame {
-            code: CloseCode::Restart,
-            reason: Cow::Borrowed("Reconnecting"),
-        };
+        // Close with the service restart code.
+        let frame = CloseFrame::from((1012, "Reconnecting"));
         self.session
             .close(Some(frame.clone()))
             .map_err(|source| ProcessError {
                 source: Some(Box::new(source)),
                 kind: ProcessErrorType::SendingClose,
             })?;
-        self.emit_disconnected(Some(frame.code.into()), Some(frame.reason.to_string()));
-        self.resume().await;
+        self.emit_disconnected(
+            DisconnectCause::ReconnectRequested,
+            Some(frame.code),
+            Some(frame.reason.to_string()),
+        )
+        .await;
+        self.resume("Reconnect").await;
 
         Ok(())
     }
@@ -743,9 +919,10 @@ impl ShardProcessor {
             tracing::warn!("sending message failed: {source:?}");
 
             if matches!(source.kind(), SessionSendErrorType::Sending { .. }) {
-                self.emit_disconnected(None, None);
+                self.emit_disconnected(DisconnectCause::Errored, None, None)
+                    .await;
 
-                self.reconnect().await;
+                self.reconnect("Sending").await;
             }
 
             return Err(source);
@@ -767,12 +944,35 @@ impl ShardProcessor {
         self.compression.clear();
 
         loop {
-            // Returns None when the socket forwarder has ended, meaning the
-            // connection was dropped.
-            let mut msg = self.rx.recv().await.ok_or(ReceivingEventError {
-                kind: ReceivingEventErrorType::EventStreamEnded,
-                source: None,
-            })?;
+            // Resolve the futures within their own scope so that the
+            // receivers are no longer borrowed when acting on the result.
+            let next = {
+                let rx = self.rx.recv();
+                let zombie = self.zombie.recv();
+                tokio::pin!(rx, zombie);
+
+                match future::select(rx, zombie).await {
+                    Either::Left((maybe_msg, _)) => Either::Left(maybe_msg),
+                    Either::Right((maybe_cause, _)) => Either::Right(maybe_cause),
+                }
+            };
+
+            let mut msg = match next {
+                // Returns None when the socket forwarder has ended, meaning
+                // the connection was dropped.
+                Either::Left(maybe_msg) => maybe_msg.ok_or(ReceivingEventError {
+                    kind: ReceivingEventErrorType::EventStreamEnded,
+                    source: None,
+                })?,
+                Either::Right(Some(cause)) => {
+                    self.disconnect_zombie(cause).await;
+
+                    continue;
+                }
+                // The session holds a sender, so the zombie channel never
+                // ends while the session is in use.
+                Either::Right(None) => continue,
+            };
 
             if self.handle_message(&mut msg).await? {
                 return Ok(());
@@ -804,14 +1004,12 @@ impl ShardProcessor {
 
                 if extended {
                     match self.compression.message_mut() {
-                        Ok(Some(bytes)) => self.emitter.bytes(bytes),
-                        Ok(None) => return Ok(false),
-                        Err(source) => {
-                            return Err(ReceivingEventError {
-                                kind: ReceivingEventErrorType::Decompressing,
-                                source: Some(Box::new(source)),
-                            })
+                        Ok(Some(bytes)) => {
+                            record_inbound(&self.config, bytes);
+                            self.emitter.bytes(bytes).await;
                         }
+                        Ok(None) => return Ok(false),
+                        Err(source) => return Err(source),
                     };
                 }
 
@@ -826,16 +1024,15 @@ impl ShardProcessor {
                 let extended = self.compression.extend_text(json.as_bytes());
 
                 if extended {
-                    self.emitter.bytes(json.as_bytes());
+                    record_inbound(&self.config, json.as_bytes());
+                    self.emitter.bytes(json.as_bytes()).await;
                 }
 
                 Ok(extended)
             }
             // Discord doesn't appear to send Text messages, so we can ignore
             // these.
-            // Message::Frame is send-only and therefore falls into the same group,
-            // see https://docs.rs/tungstenite/latest/tungstenite/enum.Message.html#variant.Frame
-            Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => Ok(false),
+            Message::Ping(_) | Message::Pong(_) => Ok(false),
         }
     }
 
@@ -845,80 +1042,84 @@ impl ShardProcessor {
     ) -> Result<(), ReceivingEventError> {
         tracing::info!("got close code: {close_frame:?}");
 
+        #[cfg(feature = "metrics")]
+        metrics::counter!(
+            "Shard-Close-Code",
+            1,
+            "shard" => self.config.shard()[0].to_string(),
+            "code" => close_frame.map_or_else(|| "None".to_owned(), |c| c.code.to_string()),
+        );
+
         self.emit_disconnected(
-            close_frame.map(|c| c.code.into()),
+            DisconnectCause::Closed,
+            close_frame.map(|c| c.code),
             close_frame.map(|c| c.reason.to_string()),
-        );
+        )
+        .await;
+
+        if let Some(code) = close_frame.and_then(|frame| CloseCode::try_from(frame.code).ok()) {
+            // Reconnecting with the same configuration would be closed with
+            // the same code, so stop instead of retrying.
+            if !code.can_reconnect() {
+                self.remove_stored_session().await;
+                self.emitter
+                    .event(Event::ShardFatal(Fatal {
+                        code,
+                        shard_id: self.config.shard()[0],
+                    }))
+                    .await;
+
+                return Err(ReceivingEventError {
+                    kind: self.fatal_close_kind(code),
+                    source: None,
+                });
+            }
 
-        if let Some(close_frame) = close_frame {
-            match close_frame.code {
-                CloseCode::Library(4004) => {
-                    return Err(ReceivingEventError {
-                        kind: ReceivingEventErrorType::AuthorizationInvalid {
-                            shard_id: self.config.shard()[0],
-                            token: self.config.token().to_owned(),
-                        },
-                        source: None,
-                    });
-                }
-                CloseCode::Library(4010) => {
-                    return Err(ReceivingEventError {
-                        kind: ReceivingEventErrorType::InvalidShard {
-                            shard_count: self.config.shard()[1],
-                            shard_id: self.config.shard()[0],
-                        },
-                        source: None,
-                    });
-                }
-                CloseCode::Library(4011) => {
-                    return Err(ReceivingEventError {
-                        kind: ReceivingEventErrorType::ShardingRequired,
-                        source: None,
-                    });
-                }
-                CloseCode::Library(4012) => {
-                    return Err(ReceivingEventError {
-                        kind: ReceivingEventErrorType::InvalidApiVersion,
-                        source: None,
-                    });
-                }
-                CloseCode::Library(4013) => {
-                    return Err(ReceivingEventError {
-                        kind: ReceivingEventErrorType::IntentsInvalid {
-                            intents: self.config.intents(),
-                            shard_id: self.config.shard()[0],
-                        },
-                        source: None,
-                    });
-                }
-                CloseCode::Library(4014) => {
-                    return Err(ReceivingEventError {
-                        kind: ReceivingEventErrorType::IntentsDisallowed {
-                            intents: self.config.intents(),
-                            shard_id: self.config.shard()[0],
-                        },
-                        source: None,
-                    });
-                }
-                _ => {}
+            if !code.can_resume() {
+                self.remove_stored_session().await;
+                self.reconnect("Close").await;
+
+                return Ok(());
             }
         }
 
-        self.resume().await;
+        self.resume("Close").await;
 
         Ok(())
     }
 
+    /// Type of error for a connection closed with a fatal close code.
+    fn fatal_close_kind(&self, code: CloseCode) -> ReceivingEventErrorType {
+        let [shard_id, shard_count] = self.config.shard();
+
+        match code {
+            CloseCode::AuthenticationFailed => ReceivingEventErrorType::AuthorizationInvalid {
+                shard_id,
+                token: self.config.token().to_owned(),
+            },
+            CloseCode::DisallowedIntents => ReceivingEventErrorType::IntentsDisallowed {
+                intents: self.config.intents(),
+                shard_id,
+            },
+            CloseCode::InvalidApiVersion => ReceivingEventErrorType::InvalidApiVersion,
+            CloseCode::InvalidIntents => ReceivingEventErrorType::IntentsInvalid {
+                intents: self.config.intents(),
+                shard_id,
+            },
+            CloseCode::InvalidShard => ReceivingEventErrorType::InvalidShard {
+                shard_count,
+                shard_id,
+            },
+            CloseCode::ShardingRequired => ReceivingEventErrorType::ShardingRequired,
+            code => ReceivingEventErrorType::ClosedFatally { code },
+        }
+    }
+
     async fn connect(
+        transport: &dyn GatewayTransport,
         url: &str,
         params: &str,
-        #[cfg(any(
-            feature = "native",
-            feature = "rustls-native-roots",
-            feature = "rustls-webpki-roots"
-        ))]
-        tls: Option<&TlsContainer>,
-    ) -> Result<ShardStream, ConnectingError> {
+    ) -> Result<Box<dyn GatewayConnection>, ConnectingError> {
         let mut url = Url::parse(url).map_err(|source| ConnectingError {
             kind: ConnectingErrorType::ParsingUrl {
                 url: url.to_owned(),
@@ -928,54 +1129,29 @@ impl ShardProcessor {
 
         url.set_query(Some(params));
 
-        // `max_frame_size` and `max_message_queue` limits are disabled because
-        // Discord is not a malicious actor.
-        //
-        // `accept_unmasked_frames` and `max_send_queue` are set to their
-        // defaults.
-        let config = WebSocketConfig {
-            accept_unmasked_frames: false,
-            max_frame_size: None,
-            max_message_size: None,
-            max_send_queue: None,
-        };
-
-        let (stream, _) = {
-            #[cfg(not(any(
-                feature = "native",
-                feature = "rustls-native-roots",
-                feature = "rustls-webpki-roots"
-            )))]
-            {
-                tokio_tungstenite::connect_async_with_config(url, Some(config))
-            }
-
-            #[cfg(any(
-                feature = "native",
-                feature = "rustls-native-roots",
-                feature = "rustls-webpki-roots"
-            ))]
-            {
-                tokio_tungstenite::connect_async_tls_with_config(
-                    url,
-                    Some(config),
-                    tls.map(TlsContainer::connector),
-                )
-            }
-        }
-        .await
-        .map_err(|source| ConnectingError {
-            kind: ConnectingErrorType::Establishing,
-            source: Some(Box::new(source)),
-        })?;
+        let connection =
+            transport
+                .connect(url.as_str())
+                .await
+                .map_err(|source| ConnectingError {
+                    kind: ConnectingErrorType::Establishing,
+                    source: Some(source),
+                })?;
 
         tracing::debug!("Shook hands with remote");
 
-        Ok(stream)
+        Ok(connection)
     }
 
     /// Identifies with the gateway to create a new session.
     async fn identify(&mut self) -> Result<(), SessionSendError> {
+        #[cfg(feature = "metrics")]
+        metrics::counter!(
+            "Shard-Identify",
+            1,
+            "shard" => self.config.shard()[0].to_string(),
+        );
+
         self.session.set_stage(Stage::Identifying);
 
         let properties = self
@@ -985,33 +1161,67 @@ impl ShardProcessor {
             .unwrap_or_else(default_identify_properties);
 
         let identify = Identify::new(IdentifyInfo {
-            compress: false,
+            compress: self.config.compression().is_payload(),
             large_threshold: self.config.large_threshold(),
             intents: self.config.intents(),
             properties,
             shard: Some(self.config.shard()),
-            presence: self.config.presence().cloned(),
+            presence: self.presence.get(),
             token: self.config.token().to_owned(),
         });
-        self.emitter.event(Event::ShardIdentifying(Identifying {
-            shard_id: self.config.shard()[0],
-            shard_total: self.config.shard()[1],
-        }));
+        self.emitter
+            .event(Event::ShardIdentifying(Identifying {
+                shard_id: self.config.shard()[0],
+                shard_total: self.config.shard()[1],
+            }))
+            .await;
 
         self.send(identify).await
     }
 
     /// Perform a full reconnect to the gateway, instantiating a new session.
-    async fn reconnect(&mut self) {
-        tracing::info!("reconnection started");
+    ///
+    /// The reason is the cause of the reconnect, used for logs and metrics.
+    async fn reconnect(&mut self, reason: &'static str) {
+        tracing::info!(reason, "reconnection started");
+
+        #[cfg(feature = "metrics")]
+        metrics::counter!(
+            "Shard-Reconnect",
+            1,
+            "shard" => self.config.shard()[0].to_string(),
+            "reason" => reason,
+        );
 
-        let mut wait = Duration::from_secs(1);
+        let shard_id = self.config.shard()[0];
 
         loop {
+            let attempt = self.reconnect_attempt;
+
+            let wait = if let Some(wait) = self.config.reconnect_strategy().delay(shard_id, attempt)
+            {
+                wait
+            } else {
+                tracing::warn!(shard_id, attempt, "gave up reconnecting; stopping shard");
+
+                self.session.stop_heartbeater();
+                self.session.set_stage(Stage::Disconnected);
+                self.stopped = true;
+                self.emit_disconnected(DisconnectCause::ReconnectGaveUp, None, None)
+                    .await;
+
+                return;
+            };
+
+            // Attempts only reset once the session is ready or resumed, so
+            // connections closed right after being established still count.
+            self.reconnect_attempt = attempt.saturating_add(1);
+
             tracing::debug!(
-                shard_id = self.config.shard()[0],
+                shard_id,
                 shard_total = self.config.shard()[1],
-                wait_in_seconds = wait.as_secs(),
+                attempt,
+                wait_in_seconds = wait.as_secs_f64(),
                 "waiting before attempting a reconnect",
             );
 
@@ -1020,19 +1230,16 @@ impl ShardProcessor {
             // Await allowance when doing a full reconnect.
             self.config.queue.request(self.config.shard()).await;
 
-            self.emitter.event(Event::ShardReconnecting(Reconnecting {
-                shard_id: self.config.shard()[0],
-            }));
+            self.emitter
+                .event(Event::ShardReconnecting(Reconnecting {
+                    shard_id: self.config.shard()[0],
+                }))
+                .await;
 
-            let stream = match Self::connect(
+            let connection = match Self::connect(
+                self.transport.as_ref(),
                 &self.gateway_endpoint,
                 &self.gateway_params,
-                #[cfg(any(
-                    feature = "native",
-                    feature = "rustls-native-roots",
-                    feature = "rustls-webpki-roots"
-                ))]
-                self.config.tls.as_ref(),
             )
             .await
             {
@@ -1040,15 +1247,11 @@ impl ShardProcessor {
                 Err(source) => {
                     tracing::warn!("reconnecting failed: {source:?}");
 
-                    if wait < Duration::from_secs(128) {
-                        wait *= 2;
-                    }
-
                     continue;
                 }
             };
 
-            self.set_session(stream, Stage::Connected);
+            self.set_session(connection, Stage::Connected);
 
             break;
         }
@@ -1057,16 +1260,28 @@ impl ShardProcessor {
         url.push('?');
         url.push_str(&self.gateway_params);
 
-        self.emitter.event(Event::ShardConnecting(Connecting {
-            gateway: url,
-            shard_id: self.config.shard()[0],
-        }));
+        self.emitter
+            .event(Event::ShardConnecting(Connecting {
+                gateway: url,
+                shard_id: self.config.shard()[0],
+            }))
+            .await;
     }
 
     /// Resume a session if possible, defaulting to instantiating a new
     /// connection.
-    async fn resume(&mut self) {
-        tracing::debug!("resuming shard {:?}", self.config.shard());
+    ///
+    /// The reason is the cause of the resume, used for logs and metrics.
+    async fn resume(&mut self, reason: &'static str) {
+        tracing::debug!(reason, "resuming shard {:?}", self.config.shard());
+
+        #[cfg(feature = "metrics")]
+        metrics::counter!(
+            "Shard-Resume",
+            1,
+            "shard" => self.config.shard()[0].to_string(),
+            "reason" => reason,
+        );
 
         self.session.set_stage(Stage::Resuming);
         self.session.stop_heartbeater();
@@ -1078,7 +1293,7 @@ impl ShardProcessor {
         } else {
             tracing::info!("session id unavailable, reconnecting");
 
-            self.reconnect().await;
+            self.reconnect("SessionUnavailable").await;
             return;
         };
 
@@ -1092,35 +1307,27 @@ impl ShardProcessor {
                 "failed to resume session: {source:?}",
             );
 
-            self.reconnect().await;
+            self.reconnect("ResumeFailed").await;
         }
     }
 
     /// Attempt to resume a session.
     async fn try_resume(&mut self) -> Result<(), ConnectingError> {
-        self.emitter.event(Event::ShardResuming(Resuming {
-            seq: self.session.seq(),
-            shard_id: self.config.shard()[0],
-        }));
+        self.emitter
+            .event(Event::ShardResuming(Resuming {
+                seq: self.session.seq(),
+                shard_id: self.config.shard()[0],
+            }))
+            .await;
 
         let url = self
             .session
             .resume_url()
             .unwrap_or_else(|| self.gateway_endpoint.clone());
 
-        let stream = Self::connect(
-            &url,
-            &self.gateway_params,
-            #[cfg(any(
-                feature = "native",
-                feature = "rustls-native-roots",
-                feature = "rustls-webpki-roots"
-            ))]
-            self.config.tls.as_ref(),
-        )
-        .await?;
+        let connection = Self::connect(self.transport.as_ref(), &url, &self.gateway_params).await?;
 
-        self.set_session(stream, Stage::Resuming);
+        self.set_session(connection, Stage::Resuming);
 
         Ok(())
     }
@@ -1129,13 +1336,27 @@ impl ShardProcessor {
     ///
     /// Set the session details and create and run a new socket forwarder for a
     /// new websocket connection.
-    fn set_session(&mut self, stream: ShardStream, stage: Stage) {
-        let (forwarder, rx, tx) = SocketForwarder::new(stream);
+    fn set_session(&mut self, connection: Box<dyn GatewayConnection>, stage: Stage) {
+        let (forwarder, rx, tx) = SocketForwarder::new(
+            connection,
+            self.config.shard()[0],
+            self.config.encoding(),
+            self.config.recorder.clone(),
+        );
 
         tokio::spawn(forwarder.run());
 
+        let (zombie_tx, zombie) = mpsc::unbounded_channel();
+
         self.rx = rx;
-        self.session = Arc::new(Session::new(tx, self.config.ratelimit_payloads));
+        self.zombie = zombie;
+        self.session = Arc::new(Session::new(
+            tx,
+            self.config.shard()[0],
+            self.config.ratelimit_payloads,
+            self.config.encoding(),
+            zombie_tx,
+        ));
 
         if let Err(source) = self.wtx.send(Arc::clone(&self.session)) {
             tracing::error!("failed to broadcast new session: {source:?}");
@@ -1145,12 +1366,92 @@ impl ShardProcessor {
         self.compression.reset();
     }
 
-    fn emit_disconnected(&self, code: Option<u16>, reason: Option<String>) {
-        self.emitter.event(Event::ShardDisconnected(Disconnected {
-            code,
-            reason,
-            shard_id: self.config.shard()[0],
-        }));
+    /// Store the session's resume details in the configured session store.
+    async fn store_session(&self) {
+        let session_store = match &self.config.session_store {
+            Some(session_store) => session_store,
+            None => return,
+        };
+
+        if let Some(session_id) = self.session.id() {
+            let resume_session = ResumeSession {
+                resume_url: self.session.resume_url().map(String::from),
+                session_id: session_id.into_string(),
+                sequence: self.session.seq(),
+            };
+
+            session_store
+                .store(self.config.shard()[0], resume_session)
+                .await;
+        }
+    }
+
+    /// Remove the session's resume details from the configured session store
+    /// once it can no longer be resumed.
+    async fn remove_stored_session(&self) {
+        if let Some(session_store) = &self.config.session_store {
+            session_store.remove(self.config.shard()[0]).await;
+        }
+    }
+
+    /// Disconnect a zombie connection, resuming the session or reconnecting
+    /// with a new one depending on the zombie policy.
+    async fn disconnect_zombie(&mut self, cause: DisconnectCause) {
+        let action = self.config.zombie_policy().action;
+
+        tracing::warn!(
+            shard_id = self.config.shard()[0],
+            ?action,
+            ?cause,
+            "connection is a zombie",
+        );
+
+        // Closing with a normal close code invalidates the session, so only
+        // do so when a new session will be identified.
+        let code = if action == ZombieAction::Reconnect {
+            1000
+        } else {
+            4000
+        };
+        let _res = self
+            .session
+            .close(Some(CloseFrame::from((code, "zombie connection"))));
+
+        self.emit_disconnected(cause, None, None).await;
+
+        if action == ZombieAction::Reconnect {
+            self.reconnect("Zombie").await;
+        } else {
+            self.resume("Zombie").await;
+        }
+    }
+
+    async fn emit_disconnected(
+        &self,
+        cause: DisconnectCause,
+        code: Option<u16>,
+        reason: Option<String>,
+    ) {
+        self.emitter
+            .event(Event::ShardDisconnected(Disconnected {
+                cause,
+                code,
+                reason,
+                shard_id: self.config.shard()[0],
+            }))
+            .await;
+    }
+}
+
+/// Record a received payload if the shard has a recorder.
+fn record_inbound(config: &Config, bytes: &[u8]) {
+    if let Some(recorder) = config.recorder() {
+        recorder.record(Record::new(
+            Direction::Inbound,
+            config.shard()[0],
+            config.encoding(),
+            bytes,
+        ));
     }
 }
 
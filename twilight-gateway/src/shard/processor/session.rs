@@ -1,5 +1,10 @@
 use super::{
-    super::{json, stage::Stage},
+    super::{
+        priority::CommandQueue,
+        raw_message::{CloseFrame, Message},
+        stage::Stage,
+        Encoding,
+    },
     heartbeat::{Heartbeater, Heartbeats},
 };
 use leaky_bucket_lite::LeakyBucket;
@@ -20,8 +25,7 @@ use tokio::{
     },
     task::JoinHandle,
 };
-use tokio_tungstenite::tungstenite::{protocol::CloseFrame, Message as TungsteniteMessage};
-use twilight_model::gateway::payload::outgoing::Heartbeat;
+use twilight_model::gateway::{event::shard::DisconnectCause, payload::outgoing::Heartbeat};
 
 // Interval of how often the ratelimit bucket resets, in milliseconds.
 const RESET_DURATION_MILLISECONDS: u64 = 60_000;
@@ -42,7 +46,7 @@ impl SessionSendError {
 impl Display for SessionSendError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         match &self.kind {
-            SessionSendErrorType::Serializing => f.write_str("failed to serialize payload as json"),
+            SessionSendErrorType::Serializing => f.write_str("failed to serialize payload"),
             SessionSendErrorType::Sending => f.write_str("failed to send message over websocket"),
         }
     }
@@ -65,29 +69,42 @@ pub enum SessionSendErrorType {
 
 #[derive(Debug)]
 pub struct Session {
+    pub encoding: Encoding,
     pub heartbeater_handle: MutexSync<Option<JoinHandle<()>>>,
     pub heartbeats: Arc<Heartbeats>,
     pub heartbeat_interval: AtomicU64,
     pub id: MutexSync<Option<Box<str>>>,
     pub resume_url: MutexSync<Option<Box<str>>>,
     pub seq: Arc<AtomicU64>,
+    pub shard_id: u64,
     pub stage: AtomicU8,
-    pub tx: UnboundedSender<TungsteniteMessage>,
-    pub ratelimit: OnceCell<Option<LeakyBucket>>,
+    pub tx: UnboundedSender<Message>,
+    pub ratelimit: OnceCell<Option<CommandQueue>>,
+    /// Sender notifying the processor that the connection is a zombie.
+    pub zombie: UnboundedSender<DisconnectCause>,
 }
 
 impl Session {
-    pub fn new(tx: UnboundedSender<TungsteniteMessage>, ratelimit_payloads: bool) -> Self {
+    pub fn new(
+        tx: UnboundedSender<Message>,
+        shard_id: u64,
+        ratelimit_payloads: bool,
+        encoding: Encoding,
+        zombie: UnboundedSender<DisconnectCause>,
+    ) -> Self {
         let session = Self {
+            encoding,
             heartbeater_handle: MutexSync::new(None),
             heartbeats: Arc::new(Heartbeats::default()),
             heartbeat_interval: AtomicU64::new(0),
             id: MutexSync::new(None),
             resume_url: MutexSync::new(None),
             seq: Arc::new(AtomicU64::new(0)),
+            shard_id,
             stage: AtomicU8::new(Stage::default() as u8),
             tx,
             ratelimit: OnceCell::new(),
+            zombie,
         };
 
         if !ratelimit_payloads {
@@ -108,13 +125,16 @@ impl Session {
     /// receiving channel has hung up. This will only happen when the shard has
     /// either not started or has already shutdown.
     pub fn send(&self, payload: impl Serialize) -> Result<(), SessionSendError> {
-        let bytes = json::to_vec(&payload).map_err(|source| SessionSendError {
-            kind: SessionSendErrorType::Serializing,
-            source: Some(Box::new(source)),
-        })?;
+        let bytes = self
+            .encoding
+            .to_vec(&payload)
+            .map_err(|source| SessionSendError {
+                kind: SessionSendErrorType::Serializing,
+                source: Some(source),
+            })?;
 
         self.tx
-            .send(TungsteniteMessage::Binary(bytes))
+            .send(Message::Binary(bytes))
             .map_err(|source| SessionSendError {
                 kind: SessionSendErrorType::Sending,
                 source: Some(Box::new(source)),
@@ -126,8 +146,8 @@ impl Session {
     pub fn close(
         &self,
         close_frame: Option<CloseFrame<'static>>,
-    ) -> Result<(), SendError<TungsteniteMessage>> {
-        self.tx.send(TungsteniteMessage::Close(close_frame))
+    ) -> Result<(), SendError<Message>> {
+        self.tx.send(Message::Close(close_frame))
     }
 
     fn disable_ratelimiter(&self) {
@@ -153,14 +173,14 @@ impl Session {
         // ratelimiting is enabled, or None if it was disabled.
         // If it was already disabled or previously enabled, setting the inner
         // value will fail and therefore errors should be ignored.
-        let _result = self.ratelimit.set(Some(
+        let _result = self.ratelimit.set(Some(CommandQueue::new(
             LeakyBucket::builder()
                 .max(commands_allotted)
                 .tokens(commands_allotted)
                 .refill_interval(REFILL_INTERVAL)
                 .refill_amount(commands_allotted)
                 .build(),
-        ));
+        )));
     }
 
     /// Returns the current sequence.
@@ -178,9 +198,30 @@ impl Session {
         Stage::try_from(self.stage.load(Ordering::Relaxed)).unwrap_or_default()
     }
 
-    /// Sets the stage.
+    /// Sets the stage, recording the transition if the stage changed.
     pub fn set_stage(&self, stage: Stage) {
-        self.stage.store(stage as u8, Ordering::Release);
+        let previous =
+            Stage::try_from(self.stage.swap(stage as u8, Ordering::AcqRel)).unwrap_or_default();
+
+        if previous == stage {
+            return;
+        }
+
+        tracing::debug!(
+            shard_id = self.shard_id,
+            from = %previous,
+            to = %stage,
+            "shard stage changed",
+        );
+
+        #[cfg(feature = "metrics")]
+        metrics::counter!(
+            "Shard-Stage-Transition",
+            1,
+            "shard" => self.shard_id.to_string(),
+            "from" => previous.to_string(),
+            "to" => stage.to_string(),
+        );
     }
 
     pub fn heartbeat(&self) -> Result<(), SessionSendError> {
@@ -217,12 +258,23 @@ impl Session {
         }
     }
 
-    pub fn start_heartbeater(&self) {
+    /// Start the heartbeater, which deems the connection a zombie after the
+    /// given number of heartbeats in a row went unacknowledged.
+    pub fn start_heartbeater(&self, missed_acks: u32) {
         let interval = self.heartbeat_interval();
         let seq = Arc::clone(&self.seq);
         let heartbeats = Arc::clone(&self.heartbeats);
 
-        let heartbeater = Heartbeater::new(heartbeats, interval, seq, self.tx.clone()).run();
+        let heartbeater = Heartbeater::new(
+            self.encoding,
+            heartbeats,
+            interval,
+            missed_acks,
+            seq,
+            self.tx.clone(),
+            self.zombie.clone(),
+        )
+        .run();
         let handle = tokio::spawn(heartbeater);
 
         if let Some(old) = self
@@ -1,19 +1,24 @@
-use super::super::ShardStream;
-use futures_util::{
-    future::{self, Either},
-    sink::SinkExt,
-    stream::StreamExt,
+use super::super::{
+    encoding::Encoding,
+    raw_message::Message,
+    recorder::{Direction, Record, Recorder},
+    transport::GatewayConnection,
 };
-use std::time::Duration;
+use futures_util::future::{self, Either};
+use std::{sync::Arc, time::Duration};
 use tokio::{
     sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
     time::timeout,
 };
-use tokio_tungstenite::tungstenite::Message;
 
 pub struct SocketForwarder {
+    connection: Box<dyn GatewayConnection>,
+    /// Encoding of sent payloads, as recorded.
+    encoding: Encoding,
+    /// Recorder of sent payloads.
+    recorder: Option<Arc<dyn Recorder>>,
     rx: UnboundedReceiver<Message>,
-    pub stream: ShardStream,
+    shard_id: u64,
     tx: UnboundedSender<Message>,
 }
 
@@ -21,15 +26,21 @@ impl SocketForwarder {
     const TIMEOUT: Duration = Duration::from_secs(90);
 
     pub fn new(
-        stream: ShardStream,
+        connection: Box<dyn GatewayConnection>,
+        shard_id: u64,
+        encoding: Encoding,
+        recorder: Option<Arc<dyn Recorder>>,
     ) -> (Self, UnboundedReceiver<Message>, UnboundedSender<Message>) {
         let (to_user, from_forwarder) = mpsc::unbounded_channel();
         let (to_forwarder, from_user) = mpsc::unbounded_channel();
 
         (
             Self {
+                connection,
+                encoding,
+                recorder,
                 rx: from_user,
-                stream,
+                shard_id,
                 tx: to_user,
             },
             from_forwarder,
@@ -41,18 +52,32 @@ impl SocketForwarder {
         tracing::debug!("starting driving loop");
 
         loop {
-            tokio::pin! {
+            // Resolve the futures within their own scope so that the
+            // connection is no longer borrowed when acting on the result.
+            let next = {
                 let rx = self.rx.recv();
-                let tx = self.stream.next();
-            }
+                let tx = self.connection.receive();
+                tokio::pin!(rx);
+
+                match timeout(Self::TIMEOUT, future::select(rx, tx)).await {
+                    Ok(Either::Left((maybe_msg, _))) => Ok(Either::Left(maybe_msg)),
+                    Ok(Either::Right((try_msg, _))) => Ok(Either::Right(try_msg)),
+                    Err(source) => Err(source),
+                }
+            };
 
-            match timeout(Self::TIMEOUT, future::select(rx, tx)).await {
+            match next {
                 // `rx` future finished first.
-                Ok(Either::Left((maybe_msg, _))) => {
+                Ok(Either::Left(maybe_msg)) => {
                     if let Some(msg) = maybe_msg {
-                        tracing::trace!("sending message: {msg}");
+                        tracing::trace!("sending message: {msg:?}");
+
+                        #[cfg(feature = "metrics")]
+                        self.payload_metrics("outbound", &msg);
 
-                        if let Err(source) = self.stream.send(msg).await {
+                        self.record(&msg);
+
+                        if let Err(source) = self.connection.send(msg).await {
                             tracing::warn!("sending failed: {source}");
 
                             break;
@@ -60,14 +85,17 @@ impl SocketForwarder {
                     } else {
                         tracing::debug!("rx stream ended, closing socket");
 
-                        let _res = self.stream.close(None).await;
+                        let _res = self.connection.close(None).await;
 
                         break;
                     }
                 }
                 // `tx` future finished first.
-                Ok(Either::Right((try_msg, _))) => match try_msg {
+                Ok(Either::Right(try_msg)) => match try_msg {
                     Some(Ok(msg)) => {
+                        #[cfg(feature = "metrics")]
+                        self.payload_metrics("inbound", &msg);
+
                         if self.tx.send(msg).is_err() {
                             break;
                         }
@@ -94,4 +122,43 @@ impl SocketForwarder {
 
         tracing::debug!("Leaving loop");
     }
+
+    /// Log metrics about the size of a sent or received payload.
+    #[cfg(feature = "metrics")]
+    #[allow(clippy::cast_possible_truncation)]
+    fn payload_metrics(&self, direction: &'static str, msg: &Message) {
+        let len = match msg {
+            Message::Binary(bytes) => bytes.len(),
+            Message::Text(text) => text.len(),
+            Message::Close(_) | Message::Ping(_) | Message::Pong(_) => return,
+        };
+
+        metrics::counter!(
+            "Shard-Payload-Bytes",
+            len as u64,
+            "shard" => self.shard_id.to_string(),
+            "direction" => direction,
+        );
+    }
+
+    /// Record a sent message if it is a payload.
+    fn record(&self, msg: &Message) {
+        let recorder = match &self.recorder {
+            Some(recorder) => recorder,
+            None => return,
+        };
+
+        let bytes = match msg {
+            Message::Binary(bytes) => bytes.as_slice(),
+            Message::Text(text) => text.as_bytes(),
+            Message::Close(_) | Message::Ping(_) | Message::Pong(_) => return,
+        };
+
+        recorder.record(Record::new(
+            Direction::Outbound,
+            self.shard_id,
+            self.encoding,
+            bytes,
+        ));
+    }
 }
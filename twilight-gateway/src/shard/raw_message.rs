@@ -7,10 +7,6 @@
 //! websocket library.
 
 use std::borrow::Cow;
-use tokio_tungstenite::tungstenite::{
-    protocol::{frame::coding::CloseCode, CloseFrame as TungsteniteCloseFrame},
-    Message as TungsteniteMessage,
-};
 
 /// Information about a close message, if any.
 ///
@@ -66,23 +62,6 @@ pub enum Message {
     Text(String),
 }
 
-impl Message {
-    pub(super) fn into_tungstenite(self) -> TungsteniteMessage {
-        match self {
-            Self::Binary(bytes) => TungsteniteMessage::Binary(bytes),
-            Self::Close(close) => {
-                TungsteniteMessage::Close(close.map(|close| TungsteniteCloseFrame {
-                    code: CloseCode::from(close.code),
-                    reason: close.reason,
-                }))
-            }
-            Self::Ping(bytes) => TungsteniteMessage::Ping(bytes),
-            Self::Pong(bytes) => TungsteniteMessage::Pong(bytes),
-            Self::Text(string) => TungsteniteMessage::Text(string),
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::{CloseFrame, Message};
@@ -0,0 +1,224 @@
+//! Strategies deciding how long shards wait before reconnecting, and when
+//! they give up.
+//!
+//! Shards configured with a [`ReconnectStrategy`] via
+//! [`ShardBuilder::reconnect_strategy`] ask it for a delay before each attempt
+//! to reconnect to the gateway. When the strategy gives up the shard stops,
+//! emitting a disconnect with the [`DisconnectCause::ReconnectGaveUp`] cause.
+//!
+//! [`ExponentialBackoff`] is the default strategy. Enabling its jitter is
+//! recommended when running many shards, so that they don't reconnect in
+//! lockstep after a gateway outage.
+//!
+//! [`DisconnectCause::ReconnectGaveUp`]: twilight_model::gateway::event::shard::DisconnectCause::ReconnectGaveUp
+//! [`ShardBuilder::reconnect_strategy`]: super::ShardBuilder::reconnect_strategy
+
+use std::{
+    collections::hash_map::RandomState,
+    fmt::{Debug, Formatter, Result as FmtResult},
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+
+/// Strategy deciding how long a shard waits before attempting to reconnect.
+pub trait ReconnectStrategy: Debug + Send + Sync {
+    /// Delay before a shard's reconnect attempt, or `None` to give up
+    /// reconnecting and stop the shard.
+    ///
+    /// Attempts start at 0 and reset once the session is ready or resumed,
+    /// so connections closed before then count as failed attempts.
+    fn delay(&self, shard_id: u64, attempt: u32) -> Option<Duration>;
+}
+
+/// Callback of an [`ExponentialBackoff`] called with the ID of a shard that
+/// gave up reconnecting.
+type GiveUpCallback = Arc<dyn Fn(u64) + Send + Sync>;
+
+/// Reconnect strategy doubling the delay after every attempt, up to a maximum.
+///
+/// By default the first delay is 1 second and delays are capped at 128
+/// seconds, without jitter or a limit on attempts.
+///
+/// # Examples
+///
+/// Randomize up to half of each delay and give up after 10 attempts:
+///
+/// ```
+/// use std::time::Duration;
+/// use twilight_gateway::shard::reconnect::ExponentialBackoff;
+///
+/// let strategy = ExponentialBackoff::new(Duration::from_secs(1), Duration::from_secs(60))
+///     .jitter(0.5)
+///     .max_attempts(10)
+///     .on_give_up(|shard_id| eprintln!("shard {shard_id} gave up reconnecting"));
+/// ```
+#[derive(Clone)]
+#[must_use = "has no effect if not given to a shard builder"]
+pub struct ExponentialBackoff {
+    /// Delay before the first attempt.
+    base: Duration,
+    /// Fraction of each delay that is randomized.
+    jitter: f64,
+    /// Maximum delay.
+    max: Duration,
+    /// Maximum number of attempts before giving up.
+    max_attempts: Option<u32>,
+    /// Callback called when giving up.
+    on_give_up: Option<GiveUpCallback>,
+}
+
+impl ExponentialBackoff {
+    /// Create a new strategy with the delay before the first attempt and the
+    /// maximum delay.
+    pub const fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            jitter: 0.0,
+            max,
+            max_attempts: None,
+            on_give_up: None,
+        }
+    }
+
+    /// Set the fraction of each delay that is randomized, between 0 and 1.
+    ///
+    /// With a jitter of 0.5 a delay of 8 seconds is randomized to between 4
+    /// and 8 seconds. Values are clamped between 0 and 1.
+    ///
+    /// Defaults to 0, meaning delays aren't randomized.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+
+        self
+    }
+
+    /// Set the maximum number of attempts before giving up.
+    ///
+    /// Defaults to no maximum.
+    pub const fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+
+        self
+    }
+
+    /// Set a callback called with the ID of a shard that gave up
+    /// reconnecting after the [maximum number of attempts].
+    ///
+    /// [maximum number of attempts]: Self::max_attempts
+    pub fn on_give_up(mut self, on_give_up: impl Fn(u64) + Send + Sync + 'static) -> Self {
+        self.on_give_up = Some(Arc::new(on_give_up));
+
+        self
+    }
+}
+
+impl Debug for ExponentialBackoff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("ExponentialBackoff")
+            .field("base", &self.base)
+            .field("jitter", &self.jitter)
+            .field("max", &self.max)
+            .field("max_attempts", &self.max_attempts)
+            .field("on_give_up", &self.on_give_up.is_some())
+            .finish()
+    }
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1), Duration::from_secs(128))
+    }
+}
+
+impl ReconnectStrategy for ExponentialBackoff {
+    fn delay(&self, shard_id: u64, attempt: u32) -> Option<Duration> {
+        if self.max_attempts.map_or(false, |max| attempt >= max) {
+            if let Some(on_give_up) = &self.on_give_up {
+                on_give_up(shard_id);
+            }
+
+            return None;
+        }
+
+        let delay = 2_u32
+            .checked_pow(attempt)
+            .and_then(|factor| self.base.checked_mul(factor))
+            .map_or(self.max, |delay| delay.min(self.max));
+
+        if self.jitter == 0.0 {
+            return Some(delay);
+        }
+
+        Some(delay.mul_f64(1.0 - self.jitter * random()))
+    }
+}
+
+/// Random number between 0 and 1.
+///
+/// Seeded by the standard library's randomly seeded hasher, which is plenty
+/// for spreading out reconnects.
+#[allow(clippy::cast_precision_loss)]
+fn random() -> f64 {
+    let value = RandomState::new().build_hasher().finish();
+
+    (value >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExponentialBackoff, ReconnectStrategy};
+    use static_assertions::{assert_impl_all, assert_obj_safe};
+    use std::{
+        fmt::Debug,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    assert_impl_all!(
+        ExponentialBackoff: Clone,
+        Debug,
+        Default,
+        ReconnectStrategy,
+        Send,
+        Sync
+    );
+    assert_obj_safe!(ReconnectStrategy);
+
+    #[test]
+    fn exponential_backoff() {
+        let strategy = ExponentialBackoff::default();
+        assert_eq!(Some(Duration::from_secs(1)), strategy.delay(0, 0));
+        assert_eq!(Some(Duration::from_secs(8)), strategy.delay(0, 3));
+        assert_eq!(Some(Duration::from_secs(128)), strategy.delay(0, 7));
+        assert_eq!(Some(Duration::from_secs(128)), strategy.delay(0, 40));
+    }
+
+    #[test]
+    fn jitter() {
+        let strategy = ExponentialBackoff::default().jitter(0.5);
+
+        for _ in 0..100 {
+            let delay = strategy.delay(0, 3).unwrap();
+            assert!(delay >= Duration::from_secs(4) && delay <= Duration::from_secs(8));
+        }
+    }
+
+    #[test]
+    fn give_up() {
+        let gave_up = Arc::new(AtomicU64::new(0));
+        let strategy = ExponentialBackoff::default().max_attempts(2).on_give_up({
+            let gave_up = Arc::clone(&gave_up);
+
+            move |shard_id| gave_up.store(shard_id, Ordering::Relaxed)
+        });
+
+        assert!(strategy.delay(7, 1).is_some());
+        assert_eq!(0, gave_up.load(Ordering::Relaxed));
+        assert!(strategy.delay(7, 2).is_none());
+        assert_eq!(7, gave_up.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,938 @@
+//! Recording of the payloads sent and received by shards, and replaying of
+//! recordings to reproduce the events they produced.
+//!
+//! Shards configured with a [`Recorder`] via [`ShardBuilder::recorder`]
+//! record every payload they receive from and send to the gateway. Received
+//! payloads are recorded once decompressed, while sent payloads are recorded
+//! as they were sent. Payloads are recorded in the shard's [encoding], which
+//! is recorded along with them.
+//!
+//! [`FileRecorder`] is an implementation appending records to a compact binary
+//! file. A recording is read back via a [`RecordReader`], and a [`Replayer`]
+//! feeds the received payloads through the same event type filtering and
+//! deserialization as a shard, producing the same events deterministically.
+//! This allows reproducing bugs in caches or parsers with production traffic.
+//!
+//! # File format
+//!
+//! Files begin with the bytes `twgr` followed by a format version byte of 2.
+//! Each record then consists of, with integers in little endian:
+//!
+//! - the direction, 0 for received and 1 for sent payloads, as a `u8`;
+//! - the encoding of the payload, 0 for JSON and 1 for ETF, as a `u8`;
+//! - the shard ID as a `u64`;
+//! - the timestamp, in milliseconds since the Unix epoch, as a `u64`;
+//! - the length of the payload as a `u32`, followed by the payload itself.
+//!
+//! [`ShardBuilder::recorder`]: super::ShardBuilder::recorder
+//! [encoding]: super::ShardBuilder::encoding
+
+use super::{
+    channel::Receiver,
+    emitter::{EmitJsonErrorType, Emitter},
+    etf, Encoding, LazyEvent,
+};
+use crate::{Event, EventTypeFlags};
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    str, thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    oneshot,
+};
+use twilight_model::gateway::event::GatewayEventDeserializer;
+
+/// Bytes the files of recordings begin with, followed by the format version.
+const MAGIC: &[u8; 4] = b"twgr";
+
+/// Version of the file format.
+const VERSION: u8 = 2;
+
+/// Length of a record's header preceding its payload.
+const RECORD_HEADER_LEN: usize = 1 + 1 + 8 + 8 + 4;
+
+/// Direction of a recorded payload.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Direction {
+    /// Payload was received from the gateway.
+    Inbound,
+    /// Payload was sent to the gateway.
+    Outbound,
+}
+
+/// Payload sent or received by a shard.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Record<'a> {
+    /// Bytes of the payload.
+    pub bytes: Cow<'a, [u8]>,
+    /// Direction of the payload.
+    pub direction: Direction,
+    /// Encoding of the payload.
+    pub encoding: Encoding,
+    /// ID of the shard that sent or received the payload.
+    pub shard_id: u64,
+    /// Time the payload was sent or received, in milliseconds since the Unix
+    /// epoch.
+    pub timestamp: u64,
+}
+
+impl<'a> Record<'a> {
+    /// Create a new record of a payload sent or received now.
+    pub fn new(
+        direction: Direction,
+        shard_id: u64,
+        encoding: Encoding,
+        bytes: impl Into<Cow<'a, [u8]>>,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| {
+                u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+            });
+
+        Self {
+            bytes: bytes.into(),
+            direction,
+            encoding,
+            shard_id,
+            timestamp,
+        }
+    }
+
+    /// Convert the record into one owning its payload.
+    pub fn into_owned(self) -> Record<'static> {
+        Record {
+            bytes: Cow::Owned(self.bytes.into_owned()),
+            direction: self.direction,
+            encoding: self.encoding,
+            shard_id: self.shard_id,
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+/// Recorder of the payloads sent and received by shards.
+pub trait Recorder: Debug + Send + Sync {
+    /// Record a payload.
+    ///
+    /// Called for every payload, from the tasks sending and receiving them.
+    /// Implementations should be cheap and must not block for long.
+    fn record(&self, record: Record<'_>);
+}
+
+/// Reading or writing a file of recorded payloads failed.
+#[derive(Debug)]
+pub struct RecordFileError {
+    kind: RecordFileErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl RecordFileError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &RecordFileErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(self) -> (RecordFileErrorType, Option<Box<dyn Error + Send + Sync>>) {
+        (self.kind, self.source)
+    }
+}
+
+impl Display for RecordFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            RecordFileErrorType::DirectionInvalid { direction } => {
+                f.write_str("record has an invalid direction of ")?;
+
+                Display::fmt(direction, f)
+            }
+            RecordFileErrorType::EncodingInvalid { encoding } => {
+                f.write_str("record has an invalid encoding of ")?;
+
+                Display::fmt(encoding, f)
+            }
+            RecordFileErrorType::HeaderInvalid => {
+                f.write_str("file is not a recording of a supported version")
+            }
+            RecordFileErrorType::Opening => f.write_str("failed to open the file"),
+            RecordFileErrorType::Reading => f.write_str("failed to read the file"),
+            RecordFileErrorType::RecordIncomplete => {
+                f.write_str("file ended in the middle of a record")
+            }
+        }
+    }
+}
+
+impl Error for RecordFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// Type of [`RecordFileError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RecordFileErrorType {
+    /// Record has a direction other than 0 or 1.
+    DirectionInvalid {
+        /// Direction of the record.
+        direction: u8,
+    },
+    /// Record has an encoding other than 0 or 1.
+    EncodingInvalid {
+        /// Encoding of the record.
+        encoding: u8,
+    },
+    /// File doesn't begin with the header of a supported version of the
+    /// format.
+    HeaderInvalid,
+    /// Opening or creating the file failed.
+    Opening,
+    /// Reading the file failed.
+    Reading,
+    /// File ended in the middle of a record, such as if the process crashed
+    /// while writing it.
+    RecordIncomplete,
+}
+
+/// [`Recorder`] appending records to a file.
+///
+/// Records are sent to a dedicated thread writing them to the file through a
+/// buffer, so recording never blocks the shards on the file system. The
+/// buffer is flushed whenever no more records are queued. Failing writes are
+/// logged.
+///
+/// Records still queued when the recorder is dropped are written in the
+/// background; use [`flush`] to wait until they have been written.
+///
+/// # Examples
+///
+/// Record the payloads of a cluster's shards:
+///
+/// ```no_run
+/// use std::{env, sync::Arc};
+/// use twilight_gateway::{shard::recorder::FileRecorder, Cluster, Intents};
+///
+/// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let token = env::var("DISCORD_TOKEN")?;
+/// let recorder = FileRecorder::open("payloads.twgr")?;
+///
+/// let (cluster, _events) = Cluster::builder(token, Intents::GUILDS)
+///     .recorder(Arc::new(recorder))
+///     .build()
+///     .await?;
+/// cluster.up().await;
+/// # Ok(()) }
+/// ```
+///
+/// [`flush`]: Self::flush
+#[derive(Debug)]
+pub struct FileRecorder {
+    path: PathBuf,
+    /// Sender of messages to the thread writing the file.
+    writer: UnboundedSender<WriterMessage>,
+}
+
+impl FileRecorder {
+    /// Open a file to append records to, creating it if it doesn't exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RecordFileErrorType::HeaderInvalid`] error type if the
+    /// file exists but isn't a recording of a supported version.
+    ///
+    /// Returns a [`RecordFileErrorType::Opening`] error type if the file
+    /// couldn't be opened or created, or if the thread writing it couldn't be
+    /// spawned.
+    ///
+    /// Returns a [`RecordFileErrorType::Reading`] error type if the header of
+    /// an existing file couldn't be read.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RecordFileError> {
+        let path = path.as_ref().to_owned();
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .read(true)
+            .open(&path)
+            .map_err(|source| RecordFileError {
+                kind: RecordFileErrorType::Opening,
+                source: Some(Box::new(source)),
+            })?;
+
+        let len = file
+            .seek(SeekFrom::End(0))
+            .map_err(|source| RecordFileError {
+                kind: RecordFileErrorType::Reading,
+                source: Some(Box::new(source)),
+            })?;
+
+        if len == 0 {
+            let mut header = MAGIC.to_vec();
+            header.push(VERSION);
+
+            file.write_all(&header).map_err(|source| RecordFileError {
+                kind: RecordFileErrorType::Opening,
+                source: Some(Box::new(source)),
+            })?;
+        } else {
+            file.seek(SeekFrom::Start(0))
+                .map_err(|source| RecordFileError {
+                    kind: RecordFileErrorType::Reading,
+                    source: Some(Box::new(source)),
+                })?;
+            read_header(&mut file)?;
+        }
+
+        let (writer, rx) = mpsc::unbounded_channel();
+
+        thread::Builder::new()
+            .name("twilight-recorder".to_owned())
+            .spawn({
+                let path = path.clone();
+
+                move || write_records(file, &path, rx)
+            })
+            .map_err(|source| RecordFileError {
+                kind: RecordFileErrorType::Opening,
+                source: Some(Box::new(source)),
+            })?;
+
+        Ok(Self { path, writer })
+    }
+
+    /// Immutable reference to the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Wait until the records recorded so far have been written to the file.
+    pub async fn flush(&self) {
+        let (tx, rx) = oneshot::channel();
+
+        if self.writer.send(WriterMessage::Flush(tx)).is_ok() {
+            // The writer only exits once the recorder is dropped.
+            let _res = rx.await;
+        }
+    }
+}
+
+impl Recorder for FileRecorder {
+    fn record(&self, record: Record<'_>) {
+        // The writer only exits once the recorder is dropped.
+        let _res = self.writer.send(WriterMessage::Record(encode(&record)));
+    }
+}
+
+/// Message to the thread writing the file of a [`FileRecorder`].
+#[derive(Debug)]
+enum WriterMessage {
+    /// Flush the buffer, notifying the sender once done.
+    Flush(oneshot::Sender<()>),
+    /// Write an encoded record.
+    Record(Vec<u8>),
+}
+
+/// Write records to a file until the recorder is dropped, flushing the buffer
+/// whenever no more records are queued.
+fn write_records(file: File, path: &Path, mut rx: UnboundedReceiver<WriterMessage>) {
+    let mut file = BufWriter::new(file);
+
+    while let Some(message) = rx.blocking_recv() {
+        let mut message = Some(message);
+
+        while let Some(current) = message.take() {
+            match current {
+                WriterMessage::Flush(tx) => {
+                    if let Err(source) = file.flush() {
+                        tracing::warn!(?path, "failed to write records: {source}");
+                    }
+
+                    let _res = tx.send(());
+                }
+                WriterMessage::Record(bytes) => {
+                    if let Err(source) = file.write_all(&bytes) {
+                        tracing::warn!(?path, "failed to write record: {source}");
+                    }
+                }
+            }
+
+            message = rx.try_recv().ok();
+        }
+
+        if let Err(source) = file.flush() {
+            tracing::warn!(?path, "failed to write records: {source}");
+        }
+    }
+}
+
+/// Reader of the records of a recording.
+///
+/// Iterates over the records in the order they were recorded. Iteration ends
+/// after the first error.
+#[derive(Debug)]
+pub struct RecordReader<R> {
+    done: bool,
+    reader: R,
+}
+
+impl RecordReader<BufReader<File>> {
+    /// Open a file of records for reading.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RecordFileErrorType::Opening`] error type if the file
+    /// couldn't be opened.
+    ///
+    /// Returns the errors of [`new`].
+    ///
+    /// [`new`]: Self::new
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RecordFileError> {
+        let file = File::open(path).map_err(|source| RecordFileError {
+            kind: RecordFileErrorType::Opening,
+            source: Some(Box::new(source)),
+        })?;
+
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read> RecordReader<R> {
+    /// Create a new reader of records, reading the header of the recording.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RecordFileErrorType::HeaderInvalid`] error type if the
+    /// reader isn't a recording of a supported version.
+    ///
+    /// Returns a [`RecordFileErrorType::Reading`] error type if the header
+    /// couldn't be read.
+    pub fn new(mut reader: R) -> Result<Self, RecordFileError> {
+        read_header(&mut reader)?;
+
+        Ok(Self {
+            done: false,
+            reader,
+        })
+    }
+
+    /// Read the next record, if the reader hasn't ended.
+    fn read(&mut self) -> Result<Option<Record<'static>>, RecordFileError> {
+        let mut header = [0; RECORD_HEADER_LEN];
+
+        if !read_exact_or_end(&mut self.reader, &mut header)? {
+            return Ok(None);
+        }
+
+        let direction = match header[0] {
+            0 => Direction::Inbound,
+            1 => Direction::Outbound,
+            direction => {
+                return Err(RecordFileError {
+                    kind: RecordFileErrorType::DirectionInvalid { direction },
+                    source: None,
+                })
+            }
+        };
+        let encoding = match header[1] {
+            0 => Encoding::Json,
+            1 => Encoding::Etf,
+            encoding => {
+                return Err(RecordFileError {
+                    kind: RecordFileErrorType::EncodingInvalid { encoding },
+                    source: None,
+                })
+            }
+        };
+        let shard_id = u64::from_le_bytes(header[2..10].try_into().unwrap_or_default());
+        let timestamp = u64::from_le_bytes(header[10..18].try_into().unwrap_or_default());
+        let len = u32::from_le_bytes(header[18..22].try_into().unwrap_or_default());
+
+        let mut bytes = vec![0; len as usize];
+
+        if !read_exact_or_end(&mut self.reader, &mut bytes)? {
+            return Err(RecordFileError {
+                kind: RecordFileErrorType::RecordIncomplete,
+                source: None,
+            });
+        }
+
+        Ok(Some(Record {
+            bytes: Cow::Owned(bytes),
+            direction,
+            encoding,
+            shard_id,
+            timestamp,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for RecordReader<R> {
+    type Item = Result<Record<'static>, RecordFileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.read().transpose();
+        self.done = !matches!(result, Some(Ok(_)));
+
+        result
+    }
+}
+
+/// Replaying a recording failed.
+#[derive(Debug)]
+pub struct ReplayError {
+    kind: ReplayErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl ReplayError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &ReplayErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(self) -> (ReplayErrorType, Option<Box<dyn Error + Send + Sync>>) {
+        (self.kind, self.source)
+    }
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            ReplayErrorType::EventTypeUnknown { event_type, op } => {
+                f.write_str("provided event type (")?;
+                Debug::fmt(event_type, f)?;
+                f.write_str(")/op (")?;
+                Display::fmt(op, f)?;
+
+                f.write_str(") pair is unknown")
+            }
+            ReplayErrorType::ParsingPayload => f.write_str("failed to parse a recorded payload"),
+            ReplayErrorType::Reading => f.write_str("failed to read a record"),
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// Type of [`ReplayError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ReplayErrorType {
+    /// Recorded event type and/or opcode combination doesn't match a known
+    /// event type flag.
+    EventTypeUnknown {
+        /// Recorded dispatch event type.
+        event_type: Option<String>,
+        /// Recorded opcode.
+        op: u8,
+    },
+    /// Recorded payload isn't a valid gateway event.
+    ParsingPayload,
+    /// Reading a record failed.
+    ///
+    /// The source is a [`RecordFileError`].
+    Reading,
+}
+
+/// Replayer of the payloads received in a recording.
+///
+/// Received payloads are emitted with the same event type filtering and
+/// deserialization as a shard, so a recording is always replayed into the
+/// same events. Sent payloads are skipped. Events emitted by shards without
+/// a payload, such as [`Event::ShardConnected`], are not replayed.
+///
+/// # Examples
+///
+/// Replay the events of shard 0 in a recording:
+///
+/// ```no_run
+/// use twilight_gateway::{
+///     shard::recorder::{RecordReader, Replayer},
+///     EventTypeFlags,
+/// };
+///
+/// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let records = RecordReader::open("payloads.twgr")?;
+/// let mut replayer = Replayer::new(records, EventTypeFlags::all()).shard_id(0);
+///
+/// while let Some(event) = replayer.next_event().await? {
+///     println!("replayed event: {:?}", event.kind());
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct Replayer<R> {
+    emitter: Emitter,
+    records: RecordReader<R>,
+    rx: Receiver,
+    shard_id: Option<u64>,
+}
+
+impl<R: Read> Replayer<R> {
+    /// Create a new replayer of records, emitting the event types a shard
+    /// configured with them would.
+    pub fn new(records: RecordReader<R>, event_types: EventTypeFlags) -> Self {
+        let (emitter, rx) = Emitter::new(event_types);
+
+        Self {
+            emitter,
+            records,
+            rx,
+            shard_id: None,
+        }
+    }
+
+    /// Set the ID of the shard whose payloads to replay.
+    ///
+    /// Defaults to replaying the payloads of all shards in the order they
+    /// were recorded.
+    #[must_use = "has no effect if not used"]
+    pub const fn shard_id(mut self, shard_id: u64) -> Self {
+        self.shard_id = Some(shard_id);
+
+        self
+    }
+
+    /// Replay the next event, returning `None` once the recording has ended.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ReplayErrorType::EventTypeUnknown`] error type if a
+    /// recorded payload has an unknown event type.
+    ///
+    /// Returns a [`ReplayErrorType::ParsingPayload`] error type if a recorded
+    /// payload isn't a valid gateway event.
+    ///
+    /// Returns a [`ReplayErrorType::Reading`] error type if reading a record
+    /// failed.
+    pub async fn next_event(&mut self) -> Result<Option<Event>, ReplayError> {
+        loop {
+            if let Some(LazyEvent::Event(event)) = self.rx.try_recv() {
+                return Ok(Some(event));
+            }
+
+            let record = match self.records.next() {
+                Some(Ok(record)) => record,
+                Some(Err(source)) => {
+                    return Err(ReplayError {
+                        kind: ReplayErrorType::Reading,
+                        source: Some(Box::new(source)),
+                    })
+                }
+                None => return Ok(None),
+            };
+
+            let skipped = record.direction != Direction::Inbound
+                || self.shard_id.map_or(false, |id| id != record.shard_id);
+
+            if !skipped {
+                self.replay(record.encoding, record.bytes.into_owned())
+                    .await?;
+            }
+        }
+    }
+
+    /// Emit the events of a received payload.
+    async fn replay(&self, encoding: Encoding, mut bytes: Vec<u8>) -> Result<(), ReplayError> {
+        self.emitter.bytes(&bytes).await;
+
+        let header = match encoding {
+            Encoding::Etf => etf::peek(&bytes),
+            Encoding::Json => str::from_utf8(&bytes)
+                .ok()
+                .and_then(GatewayEventDeserializer::from_json)
+                .map(|deserializer| {
+                    let (op, seq, event_type) = deserializer.into_parts();
+
+                    (op, seq, event_type.map(ToOwned::to_owned))
+                }),
+        };
+        let (op, seq, event_type) = header.ok_or(ReplayError {
+            kind: ReplayErrorType::ParsingPayload,
+            source: None,
+        })?;
+
+        self.emitter
+            .payload(op, seq, event_type.as_deref(), encoding, &mut bytes)
+            .await
+            .map_err(|source| {
+                let (kind, source) = source.into_parts();
+
+                let kind = match kind {
+                    EmitJsonErrorType::EventTypeUnknown { event_type, op } => {
+                        ReplayErrorType::EventTypeUnknown { event_type, op }
+                    }
+                    EmitJsonErrorType::Parsing => ReplayErrorType::ParsingPayload,
+                };
+
+                ReplayError { kind, source }
+            })
+    }
+}
+
+/// Encode a record as it is stored in a file.
+fn encode(record: &Record<'_>) -> Vec<u8> {
+    // Payloads are far smaller than 4 GiB.
+    #[allow(clippy::cast_possible_truncation)]
+    let len = record.bytes.len() as u32;
+
+    let mut bytes = Vec::with_capacity(RECORD_HEADER_LEN + record.bytes.len());
+    bytes.push(match record.direction {
+        Direction::Inbound => 0,
+        Direction::Outbound => 1,
+    });
+    bytes.push(match record.encoding {
+        Encoding::Json => 0,
+        Encoding::Etf => 1,
+    });
+    bytes.extend_from_slice(&record.shard_id.to_le_bytes());
+    bytes.extend_from_slice(&record.timestamp.to_le_bytes());
+    bytes.extend_from_slice(&len.to_le_bytes());
+    bytes.extend_from_slice(&record.bytes);
+
+    bytes
+}
+
+/// Read and validate the header of a recording.
+fn read_header(reader: &mut impl Read) -> Result<(), RecordFileError> {
+    let mut header = [0; MAGIC.len() + 1];
+
+    if !read_exact_or_end(reader, &mut header)?
+        || &header[..MAGIC.len()] != MAGIC
+        || header[MAGIC.len()] != VERSION
+    {
+        return Err(RecordFileError {
+            kind: RecordFileErrorType::HeaderInvalid,
+            source: None,
+        });
+    }
+
+    Ok(())
+}
+
+/// Fill a buffer from a reader, returning whether it was filled or the reader
+/// ended first.
+fn read_exact_or_end(reader: &mut impl Read, buf: &mut [u8]) -> Result<bool, RecordFileError> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(source) if source.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(source) => Err(RecordFileError {
+            kind: RecordFileErrorType::Reading,
+            source: Some(Box::new(source)),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Direction, FileRecorder, Record, RecordFileError, RecordFileErrorType, RecordReader,
+        Recorder, ReplayError, ReplayErrorType, Replayer,
+    };
+    use crate::{
+        shard::{etf, Encoding},
+        Event, EventTypeFlags,
+    };
+    use serde_json::Value;
+    use static_assertions::{assert_fields, assert_impl_all, assert_obj_safe};
+    use std::{env, error::Error, fmt::Debug, fs, io::Cursor, process};
+
+    assert_impl_all!(Direction: Clone, Copy, Debug, Eq, Send, Sync);
+    assert_impl_all!(FileRecorder: Debug, Recorder, Send, Sync);
+    assert_impl_all!(Record<'static>: Clone, Debug, Eq, Send, Sync);
+    assert_impl_all!(RecordFileError: Error, Send, Sync);
+    assert_impl_all!(RecordFileErrorType: Debug, Send, Sync);
+    assert_fields!(RecordFileErrorType::DirectionInvalid: direction);
+    assert_fields!(RecordFileErrorType::EncodingInvalid: encoding);
+    assert_impl_all!(RecordReader<Cursor<Vec<u8>>>: Debug, Iterator, Send, Sync);
+    assert_impl_all!(ReplayError: Error, Send, Sync);
+    assert_impl_all!(ReplayErrorType: Debug, Send, Sync);
+    assert_fields!(ReplayErrorType::EventTypeUnknown: event_type, op);
+    assert_impl_all!(Replayer<Cursor<Vec<u8>>>: Debug, Send, Sync);
+    assert_obj_safe!(Recorder);
+
+    const HEARTBEAT: &[u8] = br#"{"op":1,"d":1}"#;
+    const TYPING_START: &[u8] = br#"{"op":0,"s":2,"t":"TYPING_START","d":{"channel_id":"2","timestamp":1000,"user_id":"3"}}"#;
+
+    fn recording(records: &[Record<'_>]) -> Vec<u8> {
+        let mut bytes = b"twgr\x02".to_vec();
+
+        for record in records {
+            bytes.extend(super::encode(record));
+        }
+
+        bytes
+    }
+
+    #[tokio::test]
+    async fn file_round_trip() {
+        let path = env::temp_dir().join(format!("twilight-recording-{}.twgr", process::id()));
+        let _res = fs::remove_file(&path);
+
+        let recorder = FileRecorder::open(&path).unwrap();
+        recorder.record(Record::new(
+            Direction::Outbound,
+            1,
+            Encoding::Json,
+            HEARTBEAT,
+        ));
+        recorder.flush().await;
+        drop(recorder);
+
+        // Reopening appends to the recording.
+        let recorder = FileRecorder::open(&path).unwrap();
+        recorder.record(Record::new(
+            Direction::Inbound,
+            2,
+            Encoding::Json,
+            TYPING_START,
+        ));
+        recorder.flush().await;
+
+        let records = RecordReader::open(&path)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(2, records.len());
+        assert_eq!(Direction::Outbound, records[0].direction);
+        assert_eq!(1, records[0].shard_id);
+        assert_eq!(HEARTBEAT, records[0].bytes.as_ref());
+        assert_eq!(Direction::Inbound, records[1].direction);
+        assert_eq!(2, records[1].shard_id);
+        assert_eq!(Encoding::Json, records[1].encoding);
+        assert!(records[1].timestamp >= records[0].timestamp);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reader_errors() {
+        assert!(matches!(
+            RecordReader::new(Cursor::new(b"json".to_vec()))
+                .unwrap_err()
+                .kind(),
+            RecordFileErrorType::HeaderInvalid
+        ));
+
+        let mut bytes = recording(&[Record::new(
+            Direction::Inbound,
+            0,
+            Encoding::Json,
+            TYPING_START,
+        )]);
+        bytes[5 + 1] = 2;
+        let mut reader = RecordReader::new(Cursor::new(bytes.clone())).unwrap();
+        assert!(matches!(
+            reader.next().unwrap().unwrap_err().kind(),
+            RecordFileErrorType::EncodingInvalid { encoding: 2 }
+        ));
+
+        bytes[5 + 1] = 0;
+        bytes.truncate(bytes.len() - 1);
+        let mut reader = RecordReader::new(Cursor::new(bytes)).unwrap();
+        assert!(matches!(
+            reader.next().unwrap().unwrap_err().kind(),
+            RecordFileErrorType::RecordIncomplete
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    #[tokio::test]
+    async fn replay() {
+        let bytes = recording(&[
+            Record::new(Direction::Outbound, 0, Encoding::Json, HEARTBEAT),
+            Record::new(Direction::Inbound, 0, Encoding::Json, TYPING_START),
+            Record::new(Direction::Inbound, 1, Encoding::Json, TYPING_START),
+        ]);
+
+        let records = RecordReader::new(Cursor::new(bytes.clone())).unwrap();
+        let mut replayer = Replayer::new(records, EventTypeFlags::all());
+        let mut kinds = Vec::new();
+
+        while let Some(event) = replayer.next_event().await.unwrap() {
+            kinds.push(event.kind());
+        }
+
+        assert_eq!(4, kinds.len());
+        assert!(matches!(kinds[1], crate::EventType::TypingStart));
+
+        // Filtered by shard and event type.
+        let records = RecordReader::new(Cursor::new(bytes)).unwrap();
+        let mut replayer = Replayer::new(records, EventTypeFlags::TYPING_START).shard_id(1);
+
+        assert!(matches!(
+            replayer.next_event().await.unwrap(),
+            Some(Event::TypingStart(_))
+        ));
+        assert!(replayer.next_event().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn replay_etf() {
+        let payload = serde_json::from_slice::<Value>(TYPING_START).unwrap();
+        let typing_start = etf::to_vec(&payload).unwrap();
+        let bytes = recording(&[
+            Record::new(
+                Direction::Inbound,
+                0,
+                Encoding::Etf,
+                typing_start.as_slice(),
+            ),
+            Record::new(Direction::Inbound, 0, Encoding::Json, TYPING_START),
+        ]);
+
+        let mut records = RecordReader::new(Cursor::new(bytes.clone())).unwrap();
+        let record = records.next().unwrap().unwrap();
+        assert_eq!(Encoding::Etf, record.encoding);
+        assert_eq!(typing_start, record.bytes.as_ref());
+
+        let records = RecordReader::new(Cursor::new(bytes)).unwrap();
+        let mut replayer = Replayer::new(records, EventTypeFlags::TYPING_START);
+
+        for _ in 0..2 {
+            assert!(matches!(
+                replayer.next_event().await.unwrap(),
+                Some(Event::TypingStart(_))
+            ));
+        }
+        assert!(replayer.next_event().await.unwrap().is_none());
+    }
+}
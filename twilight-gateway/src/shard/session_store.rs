@@ -0,0 +1,523 @@
+//! Persistence of gateway sessions so that they can be resumed after a
+//! restart.
+//!
+//! Shards configured with a [`SessionStore`] via
+//! [`ShardBuilder::session_store`] store the details needed to resume their
+//! session whenever the session ID, resume URL, or sequence changes, and load
+//! them when starting. This allows a process that crashed, and so didn't shut
+//! down via [`Cluster::down_resumable`], to resume its sessions instead of
+//! re-identifying every shard.
+//!
+//! [`FileSessionStore`] is an implementation storing sessions in a JSON file.
+//!
+//! [`Cluster::down_resumable`]: crate::cluster::Cluster::down_resumable
+//! [`ShardBuilder::session_store`]: super::ShardBuilder::session_store
+
+use super::ResumeSession;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    fs,
+    future::Future,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// Storage of the resume details of shards' sessions.
+///
+/// Stored sessions are keyed by shard ID.
+pub trait SessionStore: Debug + Send + Sync {
+    /// Load the stored session of a shard, if there is one.
+    fn load(
+        &self,
+        shard_id: u64,
+    ) -> Pin<Box<dyn Future<Output = Option<ResumeSession>> + Send + '_>>;
+
+    /// Store the session of a shard, replacing any previously stored session.
+    ///
+    /// Called whenever the session's ID, resume URL, or sequence changes,
+    /// which happens for every dispatch event. Implementations should be
+    /// cheap, such as by batching writes.
+    fn store(
+        &self,
+        shard_id: u64,
+        session: ResumeSession,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + '_>>;
+
+    /// Remove the stored session of a shard once it can no longer be resumed.
+    fn remove(&self, shard_id: u64) -> Pin<Box<dyn Future<Output = ()> + Send + '_>>;
+}
+
+/// Reading or writing the file of a [`FileSessionStore`] failed.
+#[derive(Debug)]
+pub struct FileSessionStoreError {
+    kind: FileSessionStoreErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl FileSessionStoreError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &FileSessionStoreErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(
+        self,
+    ) -> (
+        FileSessionStoreErrorType,
+        Option<Box<dyn Error + Send + Sync>>,
+    ) {
+        (self.kind, self.source)
+    }
+}
+
+impl Display for FileSessionStoreError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            FileSessionStoreErrorType::Deserializing => {
+                f.write_str("failed to deserialize stored sessions")
+            }
+            FileSessionStoreErrorType::Reading => f.write_str("failed to read session file"),
+            FileSessionStoreErrorType::Serializing => f.write_str("failed to serialize sessions"),
+            FileSessionStoreErrorType::Writing => f.write_str("failed to write session file"),
+        }
+    }
+}
+
+impl Error for FileSessionStoreError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// Type of [`FileSessionStoreError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FileSessionStoreErrorType {
+    /// Contents of the file aren't valid stored sessions.
+    Deserializing,
+    /// Reading the file failed.
+    Reading,
+    /// Serializing the sessions failed.
+    Serializing,
+    /// Writing the file failed.
+    Writing,
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// Whether the sessions changed since they were last serialized.
+    dirty: AtomicBool,
+    flush_interval: Duration,
+    /// Lock held while writing the file, so that flushes don't write the
+    /// temporary file concurrently.
+    flush_lock: Mutex<()>,
+    /// Whether a flush is scheduled or being written.
+    flush_pending: AtomicBool,
+    path: PathBuf,
+    sessions: Mutex<HashMap<u64, ResumeSession>>,
+}
+
+impl Inner {
+    fn flush(&self) -> Result<(), FileSessionStoreError> {
+        let _flush_lock = self.flush_lock.lock().expect("flush lock poisoned");
+
+        let bytes = {
+            let sessions = self.sessions.lock().expect("sessions poisoned");
+            self.dirty.store(false, Ordering::Release);
+
+            serde_json::to_vec(&*sessions).map_err(|source| FileSessionStoreError {
+                kind: FileSessionStoreErrorType::Serializing,
+                source: Some(Box::new(source)),
+            })?
+        };
+
+        // Write to a temporary file first so that a crash while writing
+        // doesn't leave behind a truncated file.
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+
+        fs::write(&temporary, bytes)
+            .and_then(|()| fs::rename(&temporary, &self.path))
+            .map_err(|source| FileSessionStoreError {
+                kind: FileSessionStoreErrorType::Writing,
+                source: Some(Box::new(source)),
+            })
+    }
+
+    /// Record that the sessions changed and schedule a flush of them.
+    ///
+    /// Must be called while holding the lock on the sessions.
+    fn changed(self: &Arc<Self>) {
+        self.dirty.store(true, Ordering::Release);
+        self.schedule_flush();
+    }
+
+    /// Schedule a flush after the flush interval, unless one is pending.
+    fn schedule_flush(self: &Arc<Self>) {
+        if self.flush_pending.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        // Only hold a weak reference while waiting, so that a dropped store
+        // writes its pending sessions immediately.
+        let weak = Arc::downgrade(self);
+        let flush_interval = self.flush_interval;
+
+        tokio::spawn(async move {
+            tokio::time::sleep(flush_interval).await;
+
+            let inner = match weak.upgrade() {
+                Some(inner) => inner,
+                None => return,
+            };
+
+            let result = tokio::task::spawn_blocking({
+                let inner = Arc::clone(&inner);
+
+                move || inner.flush()
+            })
+            .await;
+
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(source)) => tracing::warn!("failed to store sessions: {source}"),
+                Err(source) => tracing::warn!("failed to store sessions: {source}"),
+            }
+
+            // Sessions changed while writing weren't written, as scheduling
+            // their flush was skipped while this one was pending.
+            inner.flush_pending.store(false, Ordering::Release);
+
+            if inner.dirty.load(Ordering::Acquire) {
+                inner.schedule_flush();
+            }
+        });
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if !*self.dirty.get_mut() {
+            return;
+        }
+
+        if let Err(source) = self.flush() {
+            tracing::warn!("failed to store sessions: {source}");
+        }
+    }
+}
+
+/// [`SessionStore`] keeping sessions in a JSON file.
+///
+/// Sessions are kept in memory and written to the file in batches, at most
+/// once per [flush interval], and when the last clone of the store is dropped.
+/// The file is written to a temporary file and then renamed, so that it is
+/// never left partially written.
+///
+/// # Examples
+///
+/// Resume the sessions of a cluster that was not shut down cleanly:
+///
+/// ```no_run
+/// use std::{env, sync::Arc};
+/// use twilight_gateway::{shard::session_store::FileSessionStore, Cluster, Intents};
+///
+/// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let token = env::var("DISCORD_TOKEN")?;
+/// let store = FileSessionStore::open("sessions.json")?;
+///
+/// let (cluster, _events) = Cluster::builder(token, Intents::GUILDS)
+///     .session_store(Arc::new(store))
+///     .build()
+///     .await?;
+/// cluster.up().await;
+/// # Ok(()) }
+/// ```
+///
+/// [flush interval]: Self::flush_interval
+#[derive(Clone, Debug)]
+pub struct FileSessionStore {
+    inner: Arc<Inner>,
+}
+
+impl FileSessionStore {
+    /// Default interval between writes of the file.
+    pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Open a store backed by a file, loading the sessions already stored in
+    /// it.
+    ///
+    /// The file is created when sessions are first written if it doesn't
+    /// exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`FileSessionStoreErrorType::Deserializing`] error type if
+    /// the file doesn't contain valid stored sessions.
+    ///
+    /// Returns a [`FileSessionStoreErrorType::Reading`] error type if the file
+    /// exists but couldn't be read.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, FileSessionStoreError> {
+        let path = path.as_ref().to_owned();
+
+        let sessions = match fs::read(&path) {
+            Ok(bytes) => {
+                serde_json::from_slice(&bytes).map_err(|source| FileSessionStoreError {
+                    kind: FileSessionStoreErrorType::Deserializing,
+                    source: Some(Box::new(source)),
+                })?
+            }
+            Err(source) if source.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(source) => {
+                return Err(FileSessionStoreError {
+                    kind: FileSessionStoreErrorType::Reading,
+                    source: Some(Box::new(source)),
+                })
+            }
+        };
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                dirty: AtomicBool::new(false),
+                flush_interval: Self::FLUSH_INTERVAL,
+                flush_lock: Mutex::new(()),
+                flush_pending: AtomicBool::new(false),
+                path,
+                sessions: Mutex::new(sessions),
+            }),
+        })
+    }
+
+    /// Set the interval between writes of the file.
+    ///
+    /// Defaults to [`FLUSH_INTERVAL`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the store has been cloned.
+    ///
+    /// [`FLUSH_INTERVAL`]: Self::FLUSH_INTERVAL
+    #[must_use = "has no effect if not used"]
+    #[track_caller]
+    pub fn flush_interval(mut self, flush_interval: Duration) -> Self {
+        Arc::get_mut(&mut self.inner)
+            .expect("store has been cloned")
+            .flush_interval = flush_interval;
+
+        self
+    }
+
+    /// Write the stored sessions to the file immediately.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`FileSessionStoreErrorType::Serializing`] error type if the
+    /// sessions couldn't be serialized.
+    ///
+    /// Returns a [`FileSessionStoreErrorType::Writing`] error type if the file
+    /// couldn't be written.
+    pub fn flush(&self) -> Result<(), FileSessionStoreError> {
+        self.inner.flush()
+    }
+
+    /// Immutable reference to the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    /// Copy of the stored sessions, keyed by shard ID.
+    ///
+    /// Can be provided to [`ClusterBuilder::resume_sessions`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if a thread panicked while holding the lock on the sessions.
+    ///
+    /// [`ClusterBuilder::resume_sessions`]: crate::cluster::ClusterBuilder::resume_sessions
+    pub fn sessions(&self) -> HashMap<u64, ResumeSession> {
+        self.inner
+            .sessions
+            .lock()
+            .expect("sessions poisoned")
+            .clone()
+    }
+}
+
+impl SessionStore for FileSessionStore {
+    fn load(
+        &self,
+        shard_id: u64,
+    ) -> Pin<Box<dyn Future<Output = Option<ResumeSession>> + Send + '_>> {
+        let session = self
+            .inner
+            .sessions
+            .lock()
+            .expect("sessions poisoned")
+            .get(&shard_id)
+            .cloned();
+
+        Box::pin(async move { session })
+    }
+
+    fn store(
+        &self,
+        shard_id: u64,
+        session: ResumeSession,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        let mut sessions = self.inner.sessions.lock().expect("sessions poisoned");
+        sessions.insert(shard_id, session);
+        self.inner.changed();
+        drop(sessions);
+
+        Box::pin(async {})
+    }
+
+    fn remove(&self, shard_id: u64) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        let mut sessions = self.inner.sessions.lock().expect("sessions poisoned");
+
+        if sessions.remove(&shard_id).is_some() {
+            self.inner.changed();
+        }
+
+        drop(sessions);
+
+        Box::pin(async {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileSessionStore, FileSessionStoreError, FileSessionStoreErrorType, SessionStore};
+    use crate::shard::ResumeSession;
+    use static_assertions::{assert_impl_all, assert_obj_safe};
+    use std::{env, error::Error, fmt::Debug, fs, process, thread, time::Duration};
+
+    assert_impl_all!(FileSessionStore: Clone, Debug, SessionStore, Send, Sync);
+    assert_impl_all!(FileSessionStoreError: Error, Send, Sync);
+    assert_impl_all!(FileSessionStoreErrorType: Debug, Send, Sync);
+    assert_obj_safe!(SessionStore);
+
+    fn session(sequence: u64) -> ResumeSession {
+        ResumeSession {
+            resume_url: Some("wss://gateway.discord.gg".to_owned()),
+            session_id: "id".to_owned(),
+            sequence,
+        }
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let path = env::temp_dir().join(format!("twilight-sessions-{}.json", process::id()));
+        let _res = fs::remove_file(&path);
+
+        let store = FileSessionStore::open(&path)
+            .unwrap()
+            .flush_interval(Duration::from_millis(10));
+        assert!(store.load(0).await.is_none());
+
+        store.store(0, session(1)).await;
+        store.store(0, session(2)).await;
+        store.store(1, session(3)).await;
+        store.remove(1).await;
+        assert_eq!(Some(2), store.load(0).await.map(|session| session.sequence));
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let reopened = FileSessionStore::open(&path).unwrap();
+        let sessions = reopened.sessions();
+        assert_eq!(1, sessions.len());
+        assert_eq!(2, sessions[&0].sequence);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn drop_writes_pending() {
+        let path = env::temp_dir().join(format!("twilight-dropped-{}.json", process::id()));
+        let _res = fs::remove_file(&path);
+
+        let store = FileSessionStore::open(&path)
+            .unwrap()
+            .flush_interval(Duration::from_secs(3600));
+        store.store(0, session(1)).await;
+        drop(store);
+
+        let reopened = FileSessionStore::open(&path).unwrap();
+        assert_eq!(1, reopened.sessions()[&0].sequence);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn concurrent_flushes() {
+        let path = env::temp_dir().join(format!("twilight-concurrent-{}.json", process::id()));
+        let store = FileSessionStore::open(&path)
+            .unwrap()
+            .flush_interval(Duration::ZERO);
+
+        let threads = (0..4)
+            .map(|shard_id| {
+                let store = store.clone();
+
+                thread::spawn(move || {
+                    for sequence in 0..50 {
+                        store
+                            .inner
+                            .sessions
+                            .lock()
+                            .unwrap()
+                            .insert(shard_id, session(sequence));
+                        store.flush().unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for sequence in 0..50 {
+            store.store(4, session(sequence)).await;
+            tokio::task::yield_now().await;
+        }
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        store.flush().unwrap();
+        assert_eq!(5, FileSessionStore::open(&path).unwrap().sessions().len());
+
+        drop(store);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_file() {
+        let path = env::temp_dir().join(format!("twilight-invalid-{}.json", process::id()));
+        fs::write(&path, "not json").unwrap();
+
+        assert!(matches!(
+            FileSessionStore::open(&path).unwrap_err().kind(),
+            FileSessionStoreErrorType::Deserializing
+        ));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,266 @@
+//! Transports used by shards to connect to the gateway.
+//!
+//! By default shards connect to the gateway over a websocket via
+//! [`TungsteniteTransport`]. Another transport, such as a proxy, an in-process
+//! mock, or a replay of recorded traffic, can be used by implementing
+//! [`GatewayTransport`] and providing it to [`ShardBuilder::transport`].
+//!
+//! [`ShardBuilder::transport`]: super::ShardBuilder::transport
+
+use super::raw_message::{CloseFrame, Message};
+use futures_util::{sink::SinkExt, stream::StreamExt};
+use std::{error::Error, fmt::Debug, future::Future, pin::Pin};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    tungstenite::{
+        protocol::{
+            frame::coding::CloseCode, CloseFrame as TungsteniteCloseFrame, WebSocketConfig,
+        },
+        Message as TungsteniteMessage,
+    },
+    MaybeTlsStream, WebSocketStream,
+};
+
+#[cfg(any(
+    feature = "native",
+    feature = "rustls-native-roots",
+    feature = "rustls-webpki-roots"
+))]
+use super::tls::TlsContainer;
+
+/// Error returned by a [`GatewayTransport`] or [`GatewayConnection`].
+pub type TransportError = Box<dyn Error + Send + Sync>;
+
+/// Future returned by [`GatewayTransport`] and [`GatewayConnection`] methods.
+pub type TransportFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Factory of connections to the gateway.
+///
+/// A transport is shared between all of the connections a shard makes over
+/// its lifetime, and between all of the shards of a [`Cluster`].
+///
+/// [`Cluster`]: crate::cluster::Cluster
+pub trait GatewayTransport: Debug + Send + Sync {
+    /// Connect to the gateway at a URL, including its query parameters.
+    fn connect<'a>(
+        &'a self,
+        url: &'a str,
+    ) -> TransportFuture<'a, Result<Box<dyn GatewayConnection>, TransportError>>;
+}
+
+/// Connection to the gateway created by a [`GatewayTransport`].
+pub trait GatewayConnection: Send {
+    /// Send a message to the gateway.
+    fn send(&mut self, message: Message) -> TransportFuture<'_, Result<(), TransportError>>;
+
+    /// Receive the next message from the gateway.
+    ///
+    /// Resolves to `None` once the connection has ended.
+    ///
+    /// The returned future must be cancellation safe: if it is dropped before
+    /// completing then no message may be lost.
+    fn receive(&mut self) -> TransportFuture<'_, Option<Result<Message, TransportError>>>;
+
+    /// Close the connection, optionally with a close code and reason.
+    fn close(
+        &mut self,
+        frame: Option<CloseFrame<'static>>,
+    ) -> TransportFuture<'_, Result<(), TransportError>>;
+}
+
+/// Default transport, connecting to the gateway over a websocket.
+#[derive(Clone, Debug, Default)]
+pub struct TungsteniteTransport {
+    #[cfg(any(
+        feature = "native",
+        feature = "rustls-native-roots",
+        feature = "rustls-webpki-roots"
+    ))]
+    tls: Option<TlsContainer>,
+}
+
+impl TungsteniteTransport {
+    /// Create a new websocket transport.
+    pub const fn new() -> Self {
+        Self {
+            #[cfg(any(
+                feature = "native",
+                feature = "rustls-native-roots",
+                feature = "rustls-webpki-roots"
+            ))]
+            tls: None,
+        }
+    }
+
+    /// Create a new websocket transport using an existing TLS connector.
+    #[cfg(any(
+        feature = "native",
+        feature = "rustls-native-roots",
+        feature = "rustls-webpki-roots"
+    ))]
+    pub(crate) const fn with_tls(tls: Option<TlsContainer>) -> Self {
+        Self { tls }
+    }
+}
+
+impl GatewayTransport for TungsteniteTransport {
+    fn connect<'a>(
+        &'a self,
+        url: &'a str,
+    ) -> TransportFuture<'a, Result<Box<dyn GatewayConnection>, TransportError>> {
+        Box::pin(async move {
+            // `max_frame_size` and `max_message_queue` limits are disabled
+            // because Discord is not a malicious actor.
+            //
+            // `accept_unmasked_frames` and `max_send_queue` are set to their
+            // defaults.
+            let config = WebSocketConfig {
+                accept_unmasked_frames: false,
+                max_frame_size: None,
+                max_message_size: None,
+                max_send_queue: None,
+            };
+
+            let (stream, _) = {
+                #[cfg(not(any(
+                    feature = "native",
+                    feature = "rustls-native-roots",
+                    feature = "rustls-webpki-roots"
+                )))]
+                {
+                    tokio_tungstenite::connect_async_with_config(url, Some(config))
+                }
+
+                #[cfg(any(
+                    feature = "native",
+                    feature = "rustls-native-roots",
+                    feature = "rustls-webpki-roots"
+                ))]
+                {
+                    tokio_tungstenite::connect_async_tls_with_config(
+                        url,
+                        Some(config),
+                        self.tls.as_ref().map(TlsContainer::connector),
+                    )
+                }
+            }
+            .await?;
+
+            Ok(Box::new(TungsteniteConnection { stream }) as Box<dyn GatewayConnection>)
+        })
+    }
+}
+
+/// Websocket connection created by [`TungsteniteTransport`].
+struct TungsteniteConnection {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl GatewayConnection for TungsteniteConnection {
+    fn send(&mut self, message: Message) -> TransportFuture<'_, Result<(), TransportError>> {
+        Box::pin(async move {
+            self.stream.send(into_tungstenite(message)).await?;
+
+            Ok(())
+        })
+    }
+
+    fn receive(&mut self) -> TransportFuture<'_, Option<Result<Message, TransportError>>> {
+        Box::pin(async move {
+            loop {
+                let message = match self.stream.next().await? {
+                    Ok(message) => message,
+                    Err(source) => return Some(Err(Box::new(source) as TransportError)),
+                };
+
+                if let Some(message) = from_tungstenite(message) {
+                    return Some(Ok(message));
+                }
+            }
+        })
+    }
+
+    fn close(
+        &mut self,
+        frame: Option<CloseFrame<'static>>,
+    ) -> TransportFuture<'_, Result<(), TransportError>> {
+        Box::pin(async move {
+            self.stream.close(frame.map(into_tungstenite_close)).await?;
+
+            Ok(())
+        })
+    }
+}
+
+/// Convert a websocket message received from tungstenite.
+///
+/// Returns `None` for raw frames, which tungstenite never emits when reading.
+fn from_tungstenite(message: TungsteniteMessage) -> Option<Message> {
+    Some(match message {
+        TungsteniteMessage::Binary(bytes) => Message::Binary(bytes),
+        TungsteniteMessage::Close(close) => Message::Close(close.map(|close| CloseFrame {
+            code: close.code.into(),
+            reason: close.reason,
+        })),
+        TungsteniteMessage::Ping(bytes) => Message::Ping(bytes),
+        TungsteniteMessage::Pong(bytes) => Message::Pong(bytes),
+        TungsteniteMessage::Text(string) => Message::Text(string),
+        TungsteniteMessage::Frame(_) => return None,
+    })
+}
+
+/// Convert a websocket message into a message sendable by tungstenite.
+fn into_tungstenite(message: Message) -> TungsteniteMessage {
+    match message {
+        Message::Binary(bytes) => TungsteniteMessage::Binary(bytes),
+        Message::Close(close) => TungsteniteMessage::Close(close.map(into_tungstenite_close)),
+        Message::Ping(bytes) => TungsteniteMessage::Ping(bytes),
+        Message::Pong(bytes) => TungsteniteMessage::Pong(bytes),
+        Message::Text(string) => TungsteniteMessage::Text(string),
+    }
+}
+
+fn into_tungstenite_close(close: CloseFrame<'static>) -> TungsteniteCloseFrame<'static> {
+    TungsteniteCloseFrame {
+        code: CloseCode::from(close.code),
+        reason: close.reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        from_tungstenite, into_tungstenite, CloseFrame, GatewayConnection, GatewayTransport,
+        Message, TungsteniteTransport,
+    };
+    use static_assertions::{assert_impl_all, assert_obj_safe};
+    use std::fmt::Debug;
+    use tokio_tungstenite::tungstenite::Message as TungsteniteMessage;
+
+    assert_impl_all!(TungsteniteTransport: Clone, Debug, Default, GatewayTransport, Send, Sync);
+    assert_obj_safe!(GatewayConnection, GatewayTransport);
+
+    #[test]
+    fn message_round_trip() {
+        let messages = [
+            Message::Binary(Vec::from([1, 2, 3])),
+            Message::Close(Some(CloseFrame::from((4000, "reason")))),
+            Message::Close(None),
+            Message::Ping(Vec::from([1])),
+            Message::Pong(Vec::from([2])),
+            Message::Text("text".to_owned()),
+        ];
+
+        for message in messages {
+            assert_eq!(
+                Some(message.clone()),
+                from_tungstenite(into_tungstenite(message))
+            );
+        }
+
+        assert!(from_tungstenite(TungsteniteMessage::Frame(
+            tokio_tungstenite::tungstenite::protocol::frame::Frame::ping(Vec::new())
+        ))
+        .is_none());
+    }
+}
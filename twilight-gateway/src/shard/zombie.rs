@@ -0,0 +1,157 @@
+//! Detection of zombie connections, which are connections that are open but
+//! no longer acknowledge heartbeats.
+//!
+//! A [`ZombiePolicy`] decides when a connection is deemed a zombie and which
+//! [`ZombieAction`] to take when it is. The shard then emits a
+//! [`ShardDisconnected`] event with a [`HeartbeatAckMissed`] or
+//! [`HeartbeatLatencyExceeded`] cause before taking the action.
+//!
+//! [`HeartbeatAckMissed`]: twilight_model::gateway::event::shard::DisconnectCause::HeartbeatAckMissed
+//! [`HeartbeatLatencyExceeded`]: twilight_model::gateway::event::shard::DisconnectCause::HeartbeatLatencyExceeded
+//! [`ShardDisconnected`]: twilight_model::gateway::event::Event::ShardDisconnected
+
+use std::time::Duration;
+
+/// Action to take when a connection is deemed a zombie.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum ZombieAction {
+    /// Close the connection and identify a new session.
+    Reconnect,
+    /// Close the connection and resume the session.
+    #[default]
+    Resume,
+}
+
+/// Policy for detecting and handling zombie connections.
+///
+/// By default a connection is deemed a zombie after 2 heartbeats in a row
+/// were not acknowledged, latency is not limited, and the session is resumed.
+///
+/// # Examples
+///
+/// Reconnect with a new session after 3 missed acknowledgements or when an
+/// acknowledgement takes longer than 5 seconds:
+///
+/// ```
+/// use std::time::Duration;
+/// use twilight_gateway::shard::{ZombieAction, ZombiePolicy};
+///
+/// let policy = ZombiePolicy::new()
+///     .action(ZombieAction::Reconnect)
+///     .max_latency(Duration::from_secs(5))
+///     .missed_acks(3);
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[must_use = "has no effect if not given to a shard builder"]
+pub struct ZombiePolicy {
+    /// Action to take when the connection is deemed a zombie.
+    pub(crate) action: ZombieAction,
+    /// Maximum tolerated heartbeat acknowledgement latency.
+    pub(crate) max_latency: Option<Duration>,
+    /// Number of heartbeats in a row that may go unacknowledged.
+    pub(crate) missed_acks: u32,
+}
+
+impl ZombiePolicy {
+    /// Default number of heartbeats in a row that may go unacknowledged.
+    const MISSED_ACKS: u32 = 2;
+
+    /// Create a new policy with the default settings.
+    pub const fn new() -> Self {
+        Self {
+            action: ZombieAction::Resume,
+            max_latency: None,
+            missed_acks: Self::MISSED_ACKS,
+        }
+    }
+
+    /// Set the action to take when the connection is deemed a zombie.
+    ///
+    /// Defaults to [`ZombieAction::Resume`].
+    pub const fn action(mut self, action: ZombieAction) -> Self {
+        self.action = action;
+
+        self
+    }
+
+    /// Set the maximum tolerated latency of a heartbeat acknowledgement.
+    ///
+    /// The connection is deemed a zombie when an acknowledgement is received
+    /// later than this.
+    ///
+    /// Defaults to no maximum.
+    pub const fn max_latency(mut self, max_latency: Duration) -> Self {
+        self.max_latency = Some(max_latency);
+
+        self
+    }
+
+    /// Set the number of heartbeats in a row that may go unacknowledged
+    /// before the connection is deemed a zombie.
+    ///
+    /// A value of 0 is treated as 1. Defaults to 2.
+    pub const fn missed_acks(mut self, missed_acks: u32) -> Self {
+        self.missed_acks = if missed_acks == 0 { 1 } else { missed_acks };
+
+        self
+    }
+
+    /// Whether a heartbeat acknowledgement latency exceeds the maximum.
+    pub(crate) fn latency_exceeded(&self, latency: Duration) -> bool {
+        self.max_latency.map_or(false, |max| latency > max)
+    }
+}
+
+impl Default for ZombiePolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ZombieAction, ZombiePolicy};
+    use static_assertions::assert_impl_all;
+    use std::{fmt::Debug, hash::Hash, time::Duration};
+
+    assert_impl_all!(
+        ZombieAction: Clone,
+        Copy,
+        Debug,
+        Default,
+        Eq,
+        Hash,
+        PartialEq,
+        Send,
+        Sync
+    );
+    assert_impl_all!(
+        ZombiePolicy: Clone,
+        Copy,
+        Debug,
+        Default,
+        Eq,
+        Hash,
+        PartialEq,
+        Send,
+        Sync
+    );
+
+    #[test]
+    fn policy() {
+        let policy = ZombiePolicy::default();
+        assert_eq!(ZombieAction::Resume, policy.action);
+        assert_eq!(2, policy.missed_acks);
+        assert!(!policy.latency_exceeded(Duration::from_secs(60)));
+
+        let policy = ZombiePolicy::new()
+            .action(ZombieAction::Reconnect)
+            .max_latency(Duration::from_secs(1))
+            .missed_acks(0);
+        assert_eq!(ZombieAction::Reconnect, policy.action);
+        assert_eq!(1, policy.missed_acks);
+        assert!(!policy.latency_exceeded(Duration::from_secs(1)));
+        assert!(policy.latency_exceeded(Duration::from_millis(1001)));
+    }
+}
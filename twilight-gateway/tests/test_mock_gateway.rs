@@ -0,0 +1,964 @@
+#![cfg(feature = "test-support")]
+
+use futures::{future, stream::StreamExt};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    error::Error,
+    fs,
+    future::Future,
+    pin::Pin,
+    process,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{sync::mpsc, time};
+use twilight_gateway::{
+    cluster::{self, Cluster, ClusterShardErrorType, ShardScheme},
+    mock::{MockConnection, MockGateway},
+    queue::Queue,
+    shard::{
+        reconnect::ExponentialBackoff,
+        recorder::{Direction, FileRecorder, RecordReader, Replayer},
+        session_store::SessionStore,
+        Compression, Encoding, Events, GuildFilter, ResumeSession, Shard, ZombiePolicy,
+    },
+    CloseCode, Event, EventTypeFlags, Intents,
+};
+use twilight_model::{
+    gateway::{
+        event::shard::DisconnectCause,
+        payload::outgoing::{
+            identify::IdentifyInfo, request_guild_members::RequestGuildMembersInfo,
+            update_presence::UpdatePresencePayload, RequestGuildMembers,
+        },
+        presence::{Activity, ActivityType, MinimalActivity, Status},
+        OpCode,
+    },
+    id::Id,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Queue allowing shards to identify immediately.
+#[derive(Debug)]
+struct NoopQueue;
+
+impl Queue for NoopQueue {
+    fn request<'a>(&'a self, _: [u64; 2]) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(async {})
+    }
+}
+
+/// Session store keeping sessions in memory.
+#[derive(Debug, Default)]
+struct MemorySessionStore(Mutex<HashMap<u64, ResumeSession>>);
+
+impl MemorySessionStore {
+    fn sessions(&self) -> HashMap<u64, ResumeSession> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn load(
+        &self,
+        shard_id: u64,
+    ) -> Pin<Box<dyn Future<Output = Option<ResumeSession>> + Send + '_>> {
+        let session = self.0.lock().unwrap().get(&shard_id).cloned();
+
+        Box::pin(async move { session })
+    }
+
+    fn store(
+        &self,
+        shard_id: u64,
+        session: ResumeSession,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        self.0.lock().unwrap().insert(shard_id, session);
+
+        Box::pin(async {})
+    }
+
+    fn remove(&self, shard_id: u64) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        self.0.lock().unwrap().remove(&shard_id);
+
+        Box::pin(async {})
+    }
+}
+
+async fn shard(gateway: &MockGateway) -> Result<(Shard, Events), Box<dyn Error>> {
+    let (shard, events) = Shard::builder("token".to_owned(), Intents::empty())
+        .gateway_url(gateway.url().to_owned())
+        .queue(Arc::new(NoopQueue))
+        .build();
+    shard.start().await?;
+
+    Ok((shard, events))
+}
+
+/// Wait for the next event matching a predicate.
+async fn wait_for(events: &mut Events, predicate: impl Fn(&Event) -> bool) -> Event {
+    let future = async {
+        while let Some(event) = events.next().await {
+            if predicate(&event) {
+                return event;
+            }
+        }
+
+        panic!("event stream ended");
+    };
+
+    time::timeout(TIMEOUT, future).await.expect("timed out")
+}
+
+async fn next_connection(gateway: &mut MockGateway) -> Result<MockConnection, Box<dyn Error>> {
+    Ok(time::timeout(TIMEOUT, gateway.next_connection()).await??)
+}
+
+#[tokio::test]
+async fn test_mock_identify_and_dispatch() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let (_shard, mut events) = shard(&gateway).await?;
+
+    let mut connection = next_connection(&mut gateway).await?;
+    #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+    assert!(connection.is_compressed());
+    assert!(connection.query().contains("encoding=json"));
+
+    let identify = connection.handshake().await?;
+    assert_eq!(OpCode::Identify, identify.op());
+
+    assert!(matches!(
+        wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await,
+        Event::Ready(ready) if ready.session_id == MockConnection::SESSION_ID
+    ));
+
+    connection
+        .dispatch(
+            "TYPING_START",
+            serde_json::json!({
+                "channel_id": "2",
+                "timestamp": 1_000,
+                "user_id": "3",
+            }),
+        )
+        .await?;
+    wait_for(&mut events, |event| matches!(event, Event::TypingStart(_))).await;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_compression() -> Result<(), Box<dyn Error>> {
+    // Compression, and whether payloads are compressed individually or as a
+    // stream.
+    let modes = [
+        (Compression::Disabled, false, false),
+        #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+        (Compression::Payload, true, false),
+        #[cfg(any(feature = "zlib-stock", feature = "zlib-simd"))]
+        (Compression::Stream, false, true),
+    ];
+
+    for (compression, payload, stream) in modes {
+        let mut gateway = MockGateway::bind().await?;
+        let (shard, mut events) = Shard::builder("token".to_owned(), Intents::empty())
+            .compression(compression)
+            .gateway_url(gateway.url().to_owned())
+            .queue(Arc::new(NoopQueue))
+            .build();
+        shard.start().await?;
+
+        let mut connection = next_connection(&mut gateway).await?;
+        assert_eq!(stream, connection.query().contains("compress=zlib-stream"));
+
+        let identify = connection.handshake().await?;
+        assert_eq!(payload, identify.deserialize::<IdentifyInfo>()?.compress);
+        assert_eq!(payload || stream, connection.is_compressed());
+        wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+
+        connection
+            .dispatch(
+                "TYPING_START",
+                serde_json::json!({
+                    "channel_id": "2",
+                    "timestamp": 1_000,
+                    "user_id": "3",
+                }),
+            )
+            .await?;
+        wait_for(&mut events, |event| matches!(event, Event::TypingStart(_))).await;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_etf_encoding() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let (shard, mut events) = Shard::builder("token".to_owned(), Intents::empty())
+        .encoding(Encoding::Etf)
+        .gateway_url(gateway.url().to_owned())
+        .queue(Arc::new(NoopQueue))
+        .build();
+    shard.start().await?;
+
+    let mut connection = next_connection(&mut gateway).await?;
+    assert!(connection.query().contains("encoding=etf"));
+    assert_eq!(Encoding::Etf, connection.encoding());
+
+    let identify = connection.handshake().await?;
+    assert_eq!("Bot token", identify.deserialize::<IdentifyInfo>()?.token);
+    wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+
+    // Snowflakes are sent as integers.
+    connection
+        .dispatch(
+            "TYPING_START",
+            serde_json::json!({
+                "channel_id": 2,
+                "timestamp": 1_000,
+                "user_id": 300_000_000_000_000_000_u64,
+            }),
+        )
+        .await?;
+    assert!(matches!(
+        wait_for(&mut events, |event| matches!(event, Event::TypingStart(_))).await,
+        Event::TypingStart(typing)
+            if typing.user_id == Id::new(300_000_000_000_000_000)
+    ));
+
+    shard
+        .command(&RequestGuildMembers::builder(Id::new(1)).query("tw", None))
+        .await?;
+    let request = connection.receive().await?;
+    assert_eq!(OpCode::RequestGuildMembers, request.op());
+    assert_eq!(
+        Id::new(1),
+        request.deserialize::<RequestGuildMembersInfo>()?.guild_id
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_guild_filter() -> Result<(), Box<dyn Error>> {
+    // Snowflakes are serialized as strings in JSON but as integers in ETF.
+    for encoding in [Encoding::Json, Encoding::Etf] {
+        let mut gateway = MockGateway::bind().await?;
+        let (shard, mut events) = Shard::builder("token".to_owned(), Intents::empty())
+            .encoding(encoding)
+            .gateway_url(gateway.url().to_owned())
+            .guild_filter(GuildFilter::allow([Id::new(1)]))
+            .queue(Arc::new(NoopQueue))
+            .build();
+        shard.start().await?;
+
+        let mut connection = next_connection(&mut gateway).await?;
+        connection.handshake().await?;
+        wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+
+        for guild_id in [2_u64, 1] {
+            let guild_id = match encoding {
+                Encoding::Etf => serde_json::json!(guild_id),
+                _ => serde_json::json!(guild_id.to_string()),
+            };
+
+            connection
+                .dispatch(
+                    "TYPING_START",
+                    serde_json::json!({
+                        "channel_id": "2",
+                        "guild_id": guild_id,
+                        "timestamp": 1_000,
+                        "user_id": "3",
+                    }),
+                )
+                .await?;
+        }
+
+        assert!(matches!(
+            wait_for(&mut events, |event| matches!(event, Event::TypingStart(_))).await,
+            Event::TypingStart(typing) if typing.guild_id == Some(Id::new(1))
+        ));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_request_guild_members() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let (shard, mut events) = shard(&gateway).await?;
+
+    let mut connection = next_connection(&mut gateway).await?;
+    connection.handshake().await?;
+    wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+
+    let request =
+        RequestGuildMembers::builder(Id::new(1)).user_ids(Vec::from([Id::new(2), Id::new(3)]))?;
+    let (members, sent) = future::join(shard.request_guild_members(request, TIMEOUT), async {
+        let request = connection.receive().await?;
+        let nonce = request.deserialize::<RequestGuildMembersInfo>()?.nonce;
+
+        for (chunk_index, user_id) in [(1, "3"), (0, "2")] {
+            connection
+                .dispatch(
+                    "GUILD_MEMBERS_CHUNK",
+                    serde_json::json!({
+                        "chunk_count": 2,
+                        "chunk_index": chunk_index,
+                        "guild_id": "1",
+                        "members": [],
+                        "nonce": nonce,
+                        "not_found": [user_id],
+                    }),
+                )
+                .await?;
+        }
+
+        Ok::<_, Box<dyn Error>>(())
+    })
+    .await;
+    sent?;
+
+    let members = members?;
+    assert_eq!(Id::new(1), members.guild_id);
+    assert_eq!(Vec::from([Id::new(3), Id::new(2)]), members.not_found);
+
+    // Member chunks are still emitted.
+    wait_for(&mut events, |event| matches!(event, Event::MemberChunk(_))).await;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_record_and_replay() -> Result<(), Box<dyn Error>> {
+    let path = env::temp_dir().join(format!("twilight-mock-{}.twgr", process::id()));
+    let _res = fs::remove_file(&path);
+
+    let mut gateway = MockGateway::bind().await?;
+    let recorder = Arc::new(FileRecorder::open(&path)?);
+    let (shard, mut events) = Shard::builder("token".to_owned(), Intents::empty())
+        .gateway_url(gateway.url().to_owned())
+        .queue(Arc::new(NoopQueue))
+        .recorder(Arc::clone(&recorder) as _)
+        .build();
+    shard.start().await?;
+
+    let mut connection = next_connection(&mut gateway).await?;
+    connection.handshake().await?;
+    wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+
+    connection
+        .dispatch(
+            "TYPING_START",
+            serde_json::json!({
+                "channel_id": "2",
+                "timestamp": 1_000,
+                "user_id": "3",
+            }),
+        )
+        .await?;
+    wait_for(&mut events, |event| matches!(event, Event::TypingStart(_))).await;
+    drop(shard);
+    recorder.flush().await;
+
+    let records = RecordReader::open(&path)?.collect::<Result<Vec<_>, _>>()?;
+    assert!(records
+        .iter()
+        .any(|record| record.direction == Direction::Outbound));
+    assert!(records.iter().all(|record| record.shard_id == 0));
+
+    let records = RecordReader::open(&path)?;
+    let mut replayer = Replayer::new(
+        records,
+        EventTypeFlags::READY | EventTypeFlags::TYPING_START,
+    );
+    assert!(matches!(
+        replayer.next_event().await?,
+        Some(Event::Ready(_))
+    ));
+    assert!(matches!(
+        replayer.next_event().await?,
+        Some(Event::TypingStart(_))
+    ));
+    assert!(replayer.next_event().await?.is_none());
+
+    fs::remove_file(&path)?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_reconnect_resumes() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let (_shard, mut events) = shard(&gateway).await?;
+
+    let mut connection = next_connection(&mut gateway).await?;
+    connection.handshake().await?;
+    wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+
+    connection.reconnect().await?;
+
+    let mut connection = next_connection(&mut gateway).await?;
+    let resume = connection.handshake().await?;
+    assert_eq!(OpCode::Resume, resume.op());
+    wait_for(&mut events, |event| matches!(event, Event::Resumed)).await;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_zombie_connection_resumes() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let (shard, mut events) = Shard::builder("token".to_owned(), Intents::empty())
+        .gateway_url(gateway.url().to_owned())
+        .queue(Arc::new(NoopQueue))
+        .zombie_policy(ZombiePolicy::new().missed_acks(1))
+        .build();
+    shard.start().await?;
+
+    // Heartbeat quickly and never acknowledge the heartbeats, which are only
+    // acknowledged when receiving.
+    let mut connection = next_connection(&mut gateway).await?;
+    connection.ack_heartbeats(false);
+    connection.hello(50).await?;
+    let identify = connection.receive().await?;
+    assert_eq!(OpCode::Identify, identify.op());
+    connection
+        .ready(MockConnection::SESSION_ID, Some([0, 1]))
+        .await?;
+    wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+
+    let disconnected = wait_for(&mut events, |event| {
+        matches!(event, Event::ShardDisconnected(_))
+    })
+    .await;
+    assert!(matches!(
+        disconnected,
+        Event::ShardDisconnected(disconnected)
+            if disconnected.cause == DisconnectCause::HeartbeatAckMissed
+    ));
+
+    let mut connection = next_connection(&mut gateway).await?;
+    let resume = connection.handshake().await?;
+    assert_eq!(OpCode::Resume, resume.op());
+    wait_for(&mut events, |event| matches!(event, Event::Resumed)).await;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_invalid_session_reidentifies() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let (_shard, mut events) = shard(&gateway).await?;
+
+    let mut connection = next_connection(&mut gateway).await?;
+    connection.handshake().await?;
+    wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+
+    connection.invalid_session(false).await?;
+
+    let mut connection = next_connection(&mut gateway).await?;
+    let identify = connection.handshake().await?;
+    assert_eq!(OpCode::Identify, identify.op());
+    wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_presence_reidentifies() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let (shard, mut events) = shard(&gateway).await?;
+    assert_eq!(None, shard.presence());
+
+    let mut connection = next_connection(&mut gateway).await?;
+    connection.handshake().await?;
+    wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+
+    let presence = presence("twilight")?;
+    shard.set_presence(presence.clone()).await?;
+    let update = time::timeout(TIMEOUT, connection.receive()).await??;
+    assert_eq!(OpCode::PresenceUpdate, update.op());
+    assert_eq!(presence, update.deserialize::<UpdatePresencePayload>()?);
+    assert_eq!(Some(presence.clone()), shard.presence());
+
+    connection.invalid_session(false).await?;
+
+    // The new session identifies with the presence set before.
+    let mut connection = next_connection(&mut gateway).await?;
+    let identify = connection.handshake().await?;
+    assert_eq!(
+        Some(presence),
+        identify.deserialize::<IdentifyInfo>()?.presence
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_close_code() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let store = Arc::new(MemorySessionStore::default());
+    let (shard, mut events) = Shard::builder("token".to_owned(), Intents::empty())
+        .gateway_url(gateway.url().to_owned())
+        .queue(Arc::new(NoopQueue))
+        .session_store(Arc::clone(&store) as _)
+        .build();
+    shard.start().await?;
+
+    let mut connection = next_connection(&mut gateway).await?;
+    connection.handshake().await?;
+    wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+    assert!(store.sessions().contains_key(&0));
+
+    connection.close(4004, "Authentication failed.").await?;
+
+    assert!(matches!(
+        wait_for(&mut events, |event| matches!(event, Event::ShardDisconnected(_))).await,
+        Event::ShardDisconnected(disconnected) if disconnected.code == Some(4004)
+    ));
+    assert!(matches!(
+        wait_for(&mut events, |event| matches!(event, Event::ShardFatal(_))).await,
+        Event::ShardFatal(fatal) if fatal.code == CloseCode::AuthenticationFailed
+    ));
+    assert!(store.sessions().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_close_code_reidentifies() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let store = Arc::new(MemorySessionStore::default());
+    let (shard, mut events) = Shard::builder("token".to_owned(), Intents::empty())
+        .gateway_url(gateway.url().to_owned())
+        .queue(Arc::new(NoopQueue))
+        .session_store(Arc::clone(&store) as _)
+        .build();
+    shard.start().await?;
+
+    let mut connection = next_connection(&mut gateway).await?;
+    connection.handshake().await?;
+    wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+    assert!(store.sessions().contains_key(&0));
+
+    // Sessions timing out can't be resumed.
+    connection.close(4009, "Session timed out.").await?;
+
+    let mut connection = next_connection(&mut gateway).await?;
+    assert!(store.sessions().is_empty());
+    let identify = connection.handshake().await?;
+    assert_eq!(OpCode::Identify, identify.op());
+    wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_reconnect_gives_up() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let strategy = ExponentialBackoff::default()
+        .max_attempts(0)
+        .on_give_up(move |shard_id| {
+            let _ = tx.send(shard_id);
+        });
+    let (shard, mut events) = Shard::builder("token".to_owned(), Intents::empty())
+        .gateway_url(gateway.url().to_owned())
+        .queue(Arc::new(NoopQueue))
+        .reconnect_strategy(Arc::new(strategy))
+        .build();
+    shard.start().await?;
+
+    let mut connection = next_connection(&mut gateway).await?;
+    connection.handshake().await?;
+    wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+
+    connection.close(4009, "Session timed out.").await?;
+
+    assert_eq!(Some(0), time::timeout(TIMEOUT, rx.recv()).await?);
+    wait_for(&mut events, |event| {
+        matches!(
+            event,
+            Event::ShardDisconnected(disconnected)
+                if disconnected.cause == DisconnectCause::ReconnectGaveUp
+        )
+    })
+    .await;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_reconnect_counts_closed_connections() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let delay = Duration::from_millis(10);
+    let (shard, mut events) = Shard::builder("token".to_owned(), Intents::empty())
+        .gateway_url(gateway.url().to_owned())
+        .queue(Arc::new(NoopQueue))
+        .reconnect_strategy(Arc::new(
+            ExponentialBackoff::new(delay, delay).max_attempts(2),
+        ))
+        .build();
+    shard.start().await?;
+
+    let mut connection = next_connection(&mut gateway).await?;
+    connection.handshake().await?;
+    wait_for(&mut events, |event| matches!(event, Event::Ready(_))).await;
+
+    // Connections closed before the session is ready don't reset the attempts.
+    for _ in 0..2 {
+        connection.close(4009, "Session timed out.").await?;
+        connection = next_connection(&mut gateway).await?;
+    }
+
+    connection.close(4009, "Session timed out.").await?;
+
+    wait_for(&mut events, |event| {
+        matches!(
+            event,
+            Event::ShardDisconnected(disconnected)
+                if disconnected.cause == DisconnectCause::ReconnectGaveUp
+        )
+    })
+    .await;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_cluster_reshard() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let (cluster, mut events) = Cluster::builder("token".to_owned(), Intents::empty())
+        .gateway_url(gateway.url().to_owned())
+        .queue(Arc::new(NoopQueue))
+        .shard_scheme(ShardScheme::try_from((0..=0, 1))?)
+        .build()
+        .await?;
+    let cluster = Arc::new(cluster);
+    cluster.up().await;
+
+    let mut old = next_connection(&mut gateway).await?;
+    old.handshake().await?;
+    wait_for_cluster(&mut events, |_, event| matches!(event, Event::Ready(_))).await;
+
+    let connections = async {
+        let mut connections = Vec::new();
+
+        for _ in 0..2 {
+            let mut connection = next_connection(&mut gateway).await?;
+            let identify = connection.handshake().await?;
+            let shard = identify.deserialize::<IdentifyInfo>()?.shard;
+            connections.push((shard, connection));
+        }
+
+        Ok::<_, Box<dyn Error>>(connections)
+    };
+    let reshard = cluster.reshard(ShardScheme::try_from((0..=1, 2))?);
+    let (reshard, connections) = time::timeout(TIMEOUT, future::join(reshard, connections)).await?;
+    let (_, mut connection) = connections?
+        .into_iter()
+        .find(|(shard, _)| *shard == Some([1, 2]))
+        .expect("shard 1 identified");
+
+    cluster.apply_reshard(reshard?);
+    assert_eq!(2, cluster.shards().len());
+    assert_eq!(2, cluster.shard_scheme().total());
+
+    // The old shard closes its connection once the new generation is applied.
+    time::timeout(TIMEOUT, async { while old.receive().await.is_ok() {} }).await?;
+
+    // Events of the new shards received while resharding are buffered.
+    let mut ready = HashSet::new();
+
+    while ready.len() < 2 {
+        let (shard_id, _) =
+            wait_for_cluster(&mut events, |_, event| matches!(event, Event::Ready(_))).await;
+        ready.insert(shard_id);
+    }
+
+    connection
+        .dispatch(
+            "TYPING_START",
+            serde_json::json!({
+                "channel_id": "2",
+                "timestamp": 1_000,
+                "user_id": "3",
+            }),
+        )
+        .await?;
+    let (shard_id, _) = wait_for_cluster(&mut events, |_, event| {
+        matches!(event, Event::TypingStart(_))
+    })
+    .await;
+    assert_eq!(1, shard_id);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_cluster_reshard_unavailable_guild() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let (cluster, _events) = Cluster::builder("token".to_owned(), Intents::GUILDS)
+        .gateway_url(gateway.url().to_owned())
+        .queue(Arc::new(NoopQueue))
+        .reshard_guild_timeout(Duration::from_millis(100))
+        .reshard_timeout(TIMEOUT)
+        .shard_scheme(ShardScheme::try_from((0..=0, 1))?)
+        .build()
+        .await?;
+
+    // The new shard's only guild stays unavailable without a GUILD_DELETE.
+    let connection = async {
+        let mut connection = next_connection(&mut gateway).await?;
+        connection.hello(MockConnection::HEARTBEAT_INTERVAL).await?;
+        connection.receive().await?;
+        connection
+            .dispatch(
+                "READY",
+                serde_json::json!({
+                    "application": {"flags": 0, "id": "1"},
+                    "guilds": [{"id": "2", "unavailable": true}],
+                    "resume_gateway_url": gateway.url(),
+                    "session_id": MockConnection::SESSION_ID,
+                    "shard": [0, 1],
+                    "user": {
+                        "avatar": null,
+                        "bot": true,
+                        "discriminator": "0001",
+                        "id": "1",
+                        "mfa_enabled": false,
+                        "username": "mock",
+                    },
+                    "v": 10,
+                }),
+            )
+            .await?;
+
+        Ok::<_, Box<dyn Error>>(connection)
+    };
+    let (reshard, connection) = time::timeout(
+        TIMEOUT,
+        future::join(
+            cluster.reshard(ShardScheme::try_from((0..=0, 1))?),
+            connection,
+        ),
+    )
+    .await?;
+    let _connection = connection?;
+
+    cluster.apply_reshard(reshard?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_cluster_reshard_timeout() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let (cluster, _events) = Cluster::builder("token".to_owned(), Intents::empty())
+        .gateway_url(gateway.url().to_owned())
+        .queue(Arc::new(NoopQueue))
+        .reshard_timeout(Duration::from_millis(100))
+        .shard_scheme(ShardScheme::try_from((0..=0, 1))?)
+        .build()
+        .await?;
+
+    // The new shard connects, but never receives a hello.
+    let (reshard, connection) = future::join(
+        cluster.reshard(ShardScheme::try_from((0..=0, 1))?),
+        next_connection(&mut gateway),
+    )
+    .await;
+    let _connection = connection?;
+
+    assert!(matches!(
+        reshard.unwrap_err().kind(),
+        cluster::ClusterReshardErrorType::TimedOut
+    ));
+    assert_eq!(1, cluster.shard_scheme().total());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_cluster_add_remove_restart() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let (cluster, mut events) = Cluster::builder("token".to_owned(), Intents::empty())
+        .gateway_url(gateway.url().to_owned())
+        .queue(Arc::new(NoopQueue))
+        .shard_scheme(ShardScheme::try_from((0..=0, 2))?)
+        .build()
+        .await?;
+    cluster.up().await;
+
+    let mut first = next_connection(&mut gateway).await?;
+    first.handshake().await?;
+    wait_for_cluster(&mut events, |_, event| matches!(event, Event::Ready(_))).await;
+
+    // Shards can be managed through a cluster shared across tasks.
+    let cluster = Arc::new(cluster);
+    let add = tokio::spawn({
+        let cluster = Arc::clone(&cluster);
+
+        async move { cluster.add_shard(1, None).await }
+    });
+    add.await??;
+    let mut second = next_connection(&mut gateway).await?;
+    second.handshake().await?;
+    wait_for_cluster(&mut events, |id, event| {
+        id == 1 && matches!(event, Event::Ready(_))
+    })
+    .await;
+
+    assert!(matches!(
+        cluster.add_shard(1, None).await.unwrap_err().kind(),
+        ClusterShardErrorType::ShardExists { id: 1 }
+    ));
+    assert!(matches!(
+        cluster.add_shard(2, None).await.unwrap_err().kind(),
+        ClusterShardErrorType::IdTooLarge { id: 2, total: 2 }
+    ));
+
+    cluster.restart_shard(1).await?;
+
+    // Shards started with a session to resume reconnect before resuming it.
+    let resume = loop {
+        let mut connection = next_connection(&mut gateway).await?;
+
+        if let Ok(payload) = connection.handshake().await {
+            break payload;
+        }
+    };
+    assert_eq!(OpCode::Resume, resume.op());
+    wait_for_cluster(&mut events, |id, event| {
+        id == 1 && matches!(event, Event::Resumed)
+    })
+    .await;
+
+    let resume_session = cluster.remove_shard(1)?;
+    assert!(matches!(
+        resume_session,
+        Some(session) if session.session_id == MockConnection::SESSION_ID
+    ));
+    assert!(matches!(
+        cluster.remove_shard(1).unwrap_err().kind(),
+        ClusterShardErrorType::ShardNonexistent { id: 1 }
+    ));
+    assert_eq!(1, cluster.shards().len());
+
+    // Guilds are routed to shard `(guild_id >> 22) % total`.
+    assert!(cluster.shard_for_guild(Id::new(1)).is_ok());
+    assert!(matches!(
+        cluster
+            .shard_for_guild(Id::new(1 << 22))
+            .unwrap_err()
+            .kind(),
+        ClusterShardErrorType::GuildNotHosted { id: 1, .. }
+    ));
+    let request = RequestGuildMembers::builder(Id::new(1 << 22)).query("", None);
+    assert!(matches!(
+        cluster
+            .command_for_guild(Id::new(1 << 22), &request)
+            .await
+            .unwrap_err()
+            .kind(),
+        cluster::ClusterCommandErrorType::GuildNotHosted { id: 1, .. }
+    ));
+
+    first
+        .dispatch(
+            "TYPING_START",
+            serde_json::json!({
+                "channel_id": "2",
+                "timestamp": 1_000,
+                "user_id": "3",
+            }),
+        )
+        .await?;
+    let (shard_id, _) = wait_for_cluster(&mut events, |_, event| {
+        matches!(event, Event::TypingStart(_))
+    })
+    .await;
+    assert_eq!(0, shard_id);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_cluster_set_presence() -> Result<(), Box<dyn Error>> {
+    let mut gateway = MockGateway::bind().await?;
+    let (cluster, mut events) = Cluster::builder("token".to_owned(), Intents::empty())
+        .gateway_url(gateway.url().to_owned())
+        .queue(Arc::new(NoopQueue))
+        .shard_scheme(ShardScheme::try_from((0..=1, 2))?)
+        .build()
+        .await?;
+    cluster.up().await;
+
+    let mut connections = Vec::new();
+
+    for _ in 0..2 {
+        let mut connection = next_connection(&mut gateway).await?;
+        connection.handshake().await?;
+        connections.push(connection);
+    }
+
+    for _ in 0..2 {
+        wait_for_cluster(&mut events, |_, event| matches!(event, Event::Ready(_))).await;
+    }
+
+    let presence = presence("twilight")?;
+    cluster.set_presence(&presence).await?;
+
+    for connection in &mut connections {
+        let update = time::timeout(TIMEOUT, connection.receive()).await??;
+        assert_eq!(presence, update.deserialize::<UpdatePresencePayload>()?);
+    }
+
+    // Restarted shards keep their presence.
+    cluster.restart_shard(1).await?;
+    assert_eq!(
+        Some(presence),
+        cluster.shard(1).and_then(|shard| shard.presence())
+    );
+
+    Ok(())
+}
+
+/// Presence playing a game.
+fn presence(name: &str) -> Result<UpdatePresencePayload, Box<dyn Error>> {
+    let activity = Activity::from(MinimalActivity {
+        kind: ActivityType::Playing,
+        name: name.to_owned(),
+        url: None,
+    });
+
+    Ok(UpdatePresencePayload::new(
+        vec![activity],
+        false,
+        None,
+        Status::Online,
+    )?)
+}
+
+/// Wait for the next event of a cluster matching a predicate.
+async fn wait_for_cluster(
+    events: &mut cluster::Events,
+    predicate: impl Fn(u64, &Event) -> bool,
+) -> (u64, Event) {
+    let future = async {
+        while let Some((shard_id, event)) = events.next().await {
+            if predicate(shard_id, &event) {
+                return (shard_id, event);
+            }
+        }
+
+        panic!("event stream ended");
+    };
+
+    time::timeout(TIMEOUT, future).await.expect("timed out")
+}
@@ -39,6 +39,40 @@ pub enum CloseCode {
     DisallowedIntents = 4014,
 }
 
+impl CloseCode {
+    /// Whether a shard may reconnect after its connection was closed with the
+    /// close code.
+    ///
+    /// Close codes that can't be reconnected after are fatal, such as
+    /// [`AuthenticationFailed`] or [`DisallowedIntents`]: reconnecting with
+    /// the same configuration would be closed with the same code.
+    ///
+    /// [`AuthenticationFailed`]: Self::AuthenticationFailed
+    /// [`DisallowedIntents`]: Self::DisallowedIntents
+    pub const fn can_reconnect(self) -> bool {
+        !matches!(
+            self,
+            Self::AuthenticationFailed
+                | Self::InvalidShard
+                | Self::ShardingRequired
+                | Self::InvalidApiVersion
+                | Self::InvalidIntents
+                | Self::DisallowedIntents
+        )
+    }
+
+    /// Whether a shard may resume its session after its connection was closed
+    /// with the close code.
+    ///
+    /// A new session must be identified otherwise, if the shard
+    /// [can reconnect].
+    ///
+    /// [can reconnect]: Self::can_reconnect
+    pub const fn can_resume(self) -> bool {
+        self.can_reconnect() && !matches!(self, Self::InvalidSequence | Self::SessionTimedOut)
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct CloseCodeConversionError {
     code: u16,
@@ -158,4 +192,28 @@ mod tests {
         );
         assert!(CloseCode::try_from(5000).is_err());
     }
+
+    #[test]
+    fn classification() {
+        assert!(CloseCode::UnknownError.can_reconnect());
+        assert!(CloseCode::UnknownError.can_resume());
+        assert!(CloseCode::RateLimited.can_resume());
+
+        assert!(CloseCode::InvalidSequence.can_reconnect());
+        assert!(!CloseCode::InvalidSequence.can_resume());
+        assert!(CloseCode::SessionTimedOut.can_reconnect());
+        assert!(!CloseCode::SessionTimedOut.can_resume());
+
+        for fatal in [
+            CloseCode::AuthenticationFailed,
+            CloseCode::InvalidShard,
+            CloseCode::ShardingRequired,
+            CloseCode::InvalidApiVersion,
+            CloseCode::InvalidIntents,
+            CloseCode::DisallowedIntents,
+        ] {
+            assert!(!fatal.can_reconnect());
+            assert!(!fatal.can_resume());
+        }
+    }
 }
@@ -75,6 +75,7 @@ pub enum EventType {
     ShardConnected,
     ShardConnecting,
     ShardDisconnected,
+    ShardFatal,
     ShardIdentifying,
     ShardPayload,
     ShardReconnecting,
@@ -171,6 +172,7 @@ impl EventType {
             | Self::ShardConnected
             | Self::ShardConnecting
             | Self::ShardDisconnected
+            | Self::ShardFatal
             | Self::ShardIdentifying
             | Self::ShardPayload
             | Self::ShardReconnecting
@@ -368,6 +370,7 @@ mod tests {
         assert_variant(EventType::ShardConnected, "SHARD_CONNECTED");
         assert_variant(EventType::ShardConnecting, "SHARD_CONNECTING");
         assert_variant(EventType::ShardDisconnected, "SHARD_DISCONNECTED");
+        assert_variant(EventType::ShardFatal, "SHARD_FATAL");
         assert_variant(EventType::ShardIdentifying, "SHARD_IDENTIFYING");
         assert_variant(EventType::ShardPayload, "SHARD_PAYLOAD");
         assert_variant(EventType::ShardReconnecting, "SHARD_RECONNECTING");
@@ -145,6 +145,9 @@ pub enum Event {
     ShardConnecting(Connecting),
     /// A shard is now in a disconnected stage after the connection was closed.
     ShardDisconnected(Disconnected),
+    /// A shard stopped after its connection was closed with a fatal close
+    /// code.
+    ShardFatal(Fatal),
     /// A shard is now in a identifying stage after starting a new session.
     ShardIdentifying(Identifying),
     /// A shard is now in a reconnecting stage after a disconnect or session was
@@ -267,6 +270,7 @@ impl Event {
             | Event::ShardConnected(_)
             | Event::ShardConnecting(_)
             | Event::ShardDisconnected(_)
+            | Event::ShardFatal(_)
             | Event::ShardIdentifying(_)
             | Event::ShardPayload(_)
             | Event::ShardReconnecting(_)
@@ -335,6 +339,7 @@ impl Event {
             Self::ShardConnected(_) => EventType::ShardConnected,
             Self::ShardConnecting(_) => EventType::ShardConnecting,
             Self::ShardDisconnected(_) => EventType::ShardDisconnected,
+            Self::ShardFatal(_) => EventType::ShardFatal,
             Self::ShardIdentifying(_) => EventType::ShardIdentifying,
             Self::ShardReconnecting(_) => EventType::ShardReconnecting,
             Self::ShardPayload(_) => EventType::ShardPayload,
@@ -452,6 +457,7 @@ impl From<ShardEvent> for Event {
             ShardEvent::Connected(v) => Self::ShardConnected(v),
             ShardEvent::Connecting(v) => Self::ShardConnecting(v),
             ShardEvent::Disconnected(v) => Self::ShardDisconnected(v),
+            ShardEvent::Fatal(v) => Self::ShardFatal(v),
             ShardEvent::Identifying(v) => Self::ShardIdentifying(v),
             ShardEvent::Payload(v) => Self::ShardPayload(v),
             ShardEvent::Reconnecting(v) => Self::ShardReconnecting(v),
@@ -1,4 +1,4 @@
-use super::{Event, EventConversionError};
+use super::{super::CloseCode, Event, EventConversionError};
 use serde::{Deserialize, Serialize};
 
 /// Indicator that a shard is now fully connected.
@@ -19,10 +19,44 @@ pub struct Connecting {
     pub shard_id: u64,
 }
 
+/// Cause of a shard disconnecting.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[non_exhaustive]
+#[serde(rename_all = "snake_case")]
+pub enum DisconnectCause {
+    /// The gateway closed the connection.
+    Closed,
+    /// Sending or receiving over the connection failed.
+    Errored,
+    /// Too many heartbeats in a row were not acknowledged, so the connection
+    /// was deemed a zombie.
+    HeartbeatAckMissed,
+    /// A heartbeat was acknowledged later than the maximum tolerated latency,
+    /// so the connection was deemed a zombie.
+    HeartbeatLatencyExceeded,
+    /// The gateway invalidated the session.
+    InvalidSession,
+    /// The reconnect strategy gave up reconnecting, so the shard stopped and
+    /// won't reconnect.
+    ReconnectGaveUp,
+    /// The gateway requested a reconnect.
+    ReconnectRequested,
+    /// The cause is unknown, such as for disconnects serialized before the
+    /// cause was recorded.
+    #[default]
+    Unknown,
+}
+
 /// Indicator that a shard is now disconnected and may soon be reconnecting if
 /// not explicitly shutdown.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Disconnected {
+    /// Why the shard disconnected.
+    ///
+    /// Defaults to [`DisconnectCause::Unknown`] when deserializing a
+    /// disconnect without a cause.
+    #[serde(default)]
+    pub cause: DisconnectCause,
     /// The code for the disconnect if not initiated by the host, if any.
     pub code: Option<u16>,
     /// The reason for the disconnect if not initiated by the host, if any.
@@ -31,6 +65,19 @@ pub struct Disconnected {
     pub shard_id: u64,
 }
 
+/// Indicator that a shard's connection was closed with a fatal close code, so
+/// the shard stopped and won't reconnect.
+///
+/// Reconnecting with the same configuration would be closed with the same
+/// code; refer to [`CloseCode::can_reconnect`].
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Fatal {
+    /// The close code the connection was closed with.
+    pub code: CloseCode,
+    /// The ID of the shard that stopped.
+    pub shard_id: u64,
+}
+
 /// Indicator that a shard is now identifying with the gateway to create a new
 /// session.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -76,6 +123,9 @@ pub enum ShardEvent {
     Connecting(Connecting),
     /// A shard is now in a Disconnected stage after the connection was closed.
     Disconnected(Disconnected),
+    /// A shard stopped after its connection was closed with a fatal close
+    /// code.
+    Fatal(Fatal),
     /// A shard is now in a Identifying stage after starting a new session.
     Identifying(Identifying),
     /// A payload of bytes came in through the shard's connection.
@@ -95,6 +145,7 @@ impl TryFrom<Event> for ShardEvent {
             Event::ShardConnected(v) => Self::Connected(v),
             Event::ShardConnecting(v) => Self::Connecting(v),
             Event::ShardDisconnected(v) => Self::Disconnected(v),
+            Event::ShardFatal(v) => Self::Fatal(v),
             Event::ShardIdentifying(v) => Self::Identifying(v),
             Event::ShardPayload(v) => Self::Payload(v),
             Event::ShardReconnecting(v) => Self::Reconnecting(v),
@@ -108,8 +159,8 @@ impl TryFrom<Event> for ShardEvent {
 #[cfg(test)]
 mod tests {
     use super::{
-        Connected, Connecting, Disconnected, Event, Identifying, Payload, Reconnecting, Resuming,
-        ShardEvent,
+        super::super::CloseCode, Connected, Connecting, DisconnectCause, Disconnected, Event,
+        Fatal, Identifying, Payload, Reconnecting, Resuming, ShardEvent,
     };
     use serde_test::Token;
 
@@ -159,9 +210,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn disconnect_cause() {
+        let causes = [
+            (DisconnectCause::Closed, "closed"),
+            (DisconnectCause::Errored, "errored"),
+            (DisconnectCause::HeartbeatAckMissed, "heartbeat_ack_missed"),
+            (
+                DisconnectCause::HeartbeatLatencyExceeded,
+                "heartbeat_latency_exceeded",
+            ),
+            (DisconnectCause::InvalidSession, "invalid_session"),
+            (DisconnectCause::ReconnectGaveUp, "reconnect_gave_up"),
+            (DisconnectCause::ReconnectRequested, "reconnect_requested"),
+            (DisconnectCause::Unknown, "unknown"),
+        ];
+
+        for (cause, variant) in causes {
+            serde_test::assert_tokens(
+                &cause,
+                &[Token::UnitVariant {
+                    name: "DisconnectCause",
+                    variant,
+                }],
+            );
+        }
+    }
+
     #[test]
     fn disconnected() {
         let value = Disconnected {
+            cause: DisconnectCause::Closed,
             code: Some(4_000),
             reason: Some("the reason".to_owned()),
             shard_id: 4,
@@ -172,7 +251,12 @@ mod tests {
             &[
                 Token::Struct {
                     name: "Disconnected",
-                    len: 3,
+                    len: 4,
+                },
+                Token::Str("cause"),
+                Token::UnitVariant {
+                    name: "DisconnectCause",
+                    variant: "closed",
                 },
                 Token::Str("code"),
                 Token::Some,
@@ -187,6 +271,58 @@ mod tests {
         );
     }
 
+    /// Disconnects serialized before the cause was recorded deserialize with
+    /// an unknown cause.
+    #[test]
+    fn disconnected_without_cause() {
+        let value = Disconnected {
+            cause: DisconnectCause::Unknown,
+            code: None,
+            reason: None,
+            shard_id: 4,
+        };
+
+        serde_test::assert_de_tokens(
+            &value,
+            &[
+                Token::Struct {
+                    name: "Disconnected",
+                    len: 3,
+                },
+                Token::Str("code"),
+                Token::None,
+                Token::Str("reason"),
+                Token::None,
+                Token::Str("shard_id"),
+                Token::U64(4),
+                Token::StructEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn fatal() {
+        let value = Fatal {
+            code: CloseCode::DisallowedIntents,
+            shard_id: 4,
+        };
+
+        serde_test::assert_tokens(
+            &value,
+            &[
+                Token::Struct {
+                    name: "Fatal",
+                    len: 2,
+                },
+                Token::Str("code"),
+                Token::U16(4014),
+                Token::Str("shard_id"),
+                Token::U64(4),
+                Token::StructEnd,
+            ],
+        );
+    }
+
     #[test]
     fn identifying() {
         let value = Identifying {
@@ -293,6 +429,7 @@ mod tests {
         ));
 
         let disconnected = Event::ShardDisconnected(Disconnected {
+            cause: DisconnectCause::Closed,
             code: Some(4_000),
             reason: None,
             shard_id: 4,
@@ -302,6 +439,12 @@ mod tests {
             ShardEvent::Disconnected(_)
         ));
 
+        let fatal = Event::ShardFatal(Fatal {
+            code: CloseCode::DisallowedIntents,
+            shard_id: 4,
+        });
+        assert!(matches!(fatal.try_into().unwrap(), ShardEvent::Fatal(_)));
+
         let identifying = Event::ShardIdentifying(Identifying {
             shard_id: 4,
             shard_total: 7,
@@ -0,0 +1,806 @@
+//! Connection to a voice server, consisting of a voice gateway websocket and a
+//! UDP socket to send voice data over.
+
+use crate::{
+    crypto::EncryptionMode,
+    event::{VoiceEvent, VoiceEvents},
+    info::ConnectionInfo,
+    payload::{
+        self, ClientDisconnect, Heartbeat, Hello, Identify, MinimalPayload, Ready, Resume,
+        SelectProtocol, SelectProtocolData, SessionDescription, Speaking, SpeakingFlags, PROTOCOL,
+    },
+    rtp::{VoiceReceiver, VoiceSender},
+    udp,
+};
+use futures_util::{SinkExt, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    net::{self, TcpStream, UdpSocket},
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::{self, Instant},
+};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use twilight_model::voice::{CloseCode, OpCode};
+
+/// Websocket connection to the voice gateway.
+type WebSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Maximum time to connect to the voice server, or to resume a session.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Interval between IP discovery requests until one is answered.
+const DISCOVERY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Maximum heartbeat interval accepted from the voice server, in
+/// milliseconds.
+const MAX_HEARTBEAT_INTERVAL: f64 = 60.0 * 60.0 * 1000.0;
+
+/// Number of attempts to resume a session before giving up.
+const RESUME_ATTEMPTS: u32 = 5;
+
+/// Delay between two attempts to resume a session.
+const RESUME_DELAY: Duration = Duration::from_secs(1);
+
+/// Close codes after which a session can't be resumed.
+const FATAL_CLOSE_CODES: [CloseCode; 10] = [
+    CloseCode::UnknownOpcode,
+    CloseCode::DecodeError,
+    CloseCode::NotAuthenticated,
+    CloseCode::AuthenticationFailed,
+    CloseCode::AlreadyAuthenticated,
+    CloseCode::SessionNoLongerValid,
+    CloseCode::ServerNotFound,
+    CloseCode::UnknownProtocol,
+    CloseCode::Disconnected,
+    CloseCode::UnknownEncryptionMode,
+];
+
+/// Connecting to or communicating with a voice server failed.
+#[derive(Debug)]
+pub struct VoiceConnectionError {
+    kind: VoiceConnectionErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl VoiceConnectionError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &VoiceConnectionErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(
+        self,
+    ) -> (
+        VoiceConnectionErrorType,
+        Option<Box<dyn Error + Send + Sync>>,
+    ) {
+        (self.kind, self.source)
+    }
+
+    /// Create a new error of a type without a source.
+    pub(crate) const fn new(kind: VoiceConnectionErrorType) -> Self {
+        Self { kind, source: None }
+    }
+
+    /// Create a new error of a type with a source.
+    pub(crate) fn with_source(
+        kind: VoiceConnectionErrorType,
+        source: impl Into<Box<dyn Error + Send + Sync>>,
+    ) -> Self {
+        Self {
+            kind,
+            source: Some(source.into()),
+        }
+    }
+}
+
+impl Display for VoiceConnectionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            VoiceConnectionErrorType::Closed { code: Some(code) } => {
+                f.write_str("connection was closed with code ")?;
+
+                Display::fmt(code, f)
+            }
+            VoiceConnectionErrorType::Closed { code: None } => f.write_str("connection was closed"),
+            VoiceConnectionErrorType::Connecting => {
+                f.write_str("failed to connect to the voice server")
+            }
+            VoiceConnectionErrorType::Deserializing => f.write_str("failed to deserialize payload"),
+            VoiceConnectionErrorType::Discovering => {
+                f.write_str("failed to discover the external address")
+            }
+            VoiceConnectionErrorType::Sending => f.write_str("failed to send message"),
+            VoiceConnectionErrorType::Serializing => f.write_str("failed to serialize payload"),
+            VoiceConnectionErrorType::TimedOut => {
+                f.write_str("voice server didn't complete the handshake in time")
+            }
+            VoiceConnectionErrorType::UnsupportedEncryption { modes } => {
+                f.write_str("voice server doesn't support any implemented encryption mode, only ")?;
+
+                f.write_str(&modes.join(", "))
+            }
+        }
+    }
+}
+
+impl Error for VoiceConnectionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// Type of [`VoiceConnectionError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum VoiceConnectionErrorType {
+    /// Connection has been closed.
+    Closed {
+        /// Close code sent by the voice server, if any.
+        code: Option<u16>,
+    },
+    /// Connecting to the voice gateway or binding the UDP socket failed.
+    Connecting,
+    /// Deserializing a received payload failed.
+    Deserializing,
+    /// Discovering the external address over the UDP socket failed.
+    Discovering,
+    /// Sending a message to the voice server failed.
+    Sending,
+    /// Serializing a payload to send failed.
+    Serializing,
+    /// Voice server didn't complete the handshake in time.
+    TimedOut,
+    /// Voice server doesn't support any of the encryption modes implemented.
+    UnsupportedEncryption {
+        /// Encryption modes the voice server supports.
+        modes: Vec<String>,
+    },
+}
+
+/// Command sent to the task running a connection.
+#[derive(Debug)]
+enum Command {
+    /// Send a speaking payload.
+    Speaking(SpeakingFlags),
+}
+
+/// Connection to a voice server.
+///
+/// Connecting identifies with the voice gateway, discovers the external
+/// address of the UDP socket, and negotiates the encryption of voice data.
+/// The websocket is then run in a background task sending heartbeats,
+/// emitting [`VoiceEvents`], and resuming the session when the connection
+/// drops. The connection is closed when this is dropped.
+///
+/// # Examples
+///
+/// Connect to a voice server and start speaking:
+///
+/// ```no_run
+/// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let info: twilight_voice::ConnectionInfo = unimplemented!();
+/// use twilight_voice::{payload::SpeakingFlags, VoiceConnection};
+///
+/// let (connection, _events) = VoiceConnection::connect(info).await?;
+/// connection.speaking(SpeakingFlags::MICROPHONE)?;
+///
+/// println!("sending voice data as SSRC {}", connection.ssrc());
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct VoiceConnection {
+    /// Sender of commands to the task running the connection.
+    commands: UnboundedSender<Command>,
+    /// External address of the UDP socket.
+    external_address: SocketAddr,
+    /// Information used to connect.
+    info: ConnectionInfo,
+    /// Negotiated encryption mode.
+    mode: EncryptionMode,
+    /// Key to encrypt voice data with.
+    secret_key: [u8; 32],
+    /// UDP socket connected to the voice server.
+    socket: Arc<UdpSocket>,
+    /// Synchronization source identifying the user's voice data.
+    ssrc: u32,
+}
+
+impl VoiceConnection {
+    /// Create a new builder to configure and connect to a voice server.
+    pub const fn builder(info: ConnectionInfo) -> VoiceConnectionBuilder {
+        VoiceConnectionBuilder::new(info)
+    }
+
+    /// Connect to a voice server with the default configuration.
+    ///
+    /// # Errors
+    ///
+    /// Refer to [`VoiceConnectionBuilder::connect`].
+    pub async fn connect(
+        info: ConnectionInfo,
+    ) -> Result<(Self, VoiceEvents), VoiceConnectionError> {
+        Self::builder(info).connect().await
+    }
+
+    /// External address of the UDP socket, as seen by the voice server.
+    pub const fn external_address(&self) -> SocketAddr {
+        self.external_address
+    }
+
+    /// Immutable reference to the information used to connect.
+    pub const fn info(&self) -> &ConnectionInfo {
+        &self.info
+    }
+
+    /// Negotiated encryption mode.
+    pub const fn mode(&self) -> EncryptionMode {
+        self.mode
+    }
+
+    /// Create a receiver of the voice data of other users.
+    ///
+    /// The receiver maps users to their voice data from the [events] given to
+    /// [`VoiceReceiver::process`].
+    ///
+    /// [events]: VoiceEvents
+    pub fn receiver(&self) -> VoiceReceiver {
+        VoiceReceiver::new(Arc::clone(&self.socket), self.mode, &self.secret_key)
+    }
+
+    /// Create a sender of the user's voice data.
+    ///
+    /// The user must be [speaking] for the voice server to forward the sent
+    /// data.
+    ///
+    /// [speaking]: Self::speaking
+    pub fn sender(&self) -> VoiceSender {
+        VoiceSender::new(
+            Arc::clone(&self.socket),
+            self.ssrc,
+            self.mode,
+            &self.secret_key,
+        )
+    }
+
+    /// Key of the session to encrypt voice data with.
+    pub const fn secret_key(&self) -> &[u8; 32] {
+        &self.secret_key
+    }
+
+    /// Immutable reference to the UDP socket connected to the voice server.
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Synchronization source identifying the user's voice data.
+    pub const fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// Set the ways in which the user is speaking.
+    ///
+    /// This must be sent at least once before sending voice data.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of type [`Closed`] if the connection has been
+    /// closed.
+    ///
+    /// [`Closed`]: VoiceConnectionErrorType::Closed
+    pub fn speaking(&self, speaking: SpeakingFlags) -> Result<(), VoiceConnectionError> {
+        self.commands
+            .send(Command::Speaking(speaking))
+            .map_err(|_| VoiceConnectionError::new(VoiceConnectionErrorType::Closed { code: None }))
+    }
+}
+
+/// Builder to configure and connect to a voice server.
+#[derive(Debug)]
+#[must_use = "has no effect if not connected"]
+pub struct VoiceConnectionBuilder {
+    /// Information used to connect.
+    info: ConnectionInfo,
+    /// URL of the voice gateway, overriding the one of the information.
+    url: Option<String>,
+}
+
+impl VoiceConnectionBuilder {
+    /// Create a new builder from the information to connect with.
+    pub const fn new(info: ConnectionInfo) -> Self {
+        Self { info, url: None }
+    }
+
+    /// Connect to the voice server.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of type [`Connecting`] if connecting to the voice
+    /// gateway or binding the UDP socket failed.
+    ///
+    /// Returns an error of type [`UnsupportedEncryption`] if the voice server
+    /// doesn't support any of the [encryption modes].
+    ///
+    /// Returns an error of type [`TimedOut`] if the voice server didn't
+    /// complete the handshake within 10 seconds.
+    ///
+    /// [`Connecting`]: VoiceConnectionErrorType::Connecting
+    /// [`TimedOut`]: VoiceConnectionErrorType::TimedOut
+    /// [`UnsupportedEncryption`]: VoiceConnectionErrorType::UnsupportedEncryption
+    /// [encryption modes]: EncryptionMode
+    pub async fn connect(self) -> Result<(VoiceConnection, VoiceEvents), VoiceConnectionError> {
+        let url = self.url.unwrap_or_else(|| self.info.url());
+
+        time::timeout(HANDSHAKE_TIMEOUT, handshake(url, self.info))
+            .await
+            .map_err(|_| VoiceConnectionError::new(VoiceConnectionErrorType::TimedOut))?
+    }
+
+    /// Set the URL of the voice gateway, overriding the one derived from the
+    /// endpoint of the [`ConnectionInfo`].
+    ///
+    /// This is useful for connecting to a mock voice server.
+    pub fn url(mut self, url: String) -> Self {
+        self.url = Some(url);
+
+        self
+    }
+}
+
+/// Connect to the voice gateway, identify, and negotiate the UDP connection.
+async fn handshake(
+    url: String,
+    info: ConnectionInfo,
+) -> Result<(VoiceConnection, VoiceEvents), VoiceConnectionError> {
+    tracing::debug!(%url, guild_id = %info.guild_id, "connecting to voice server");
+    let mut ws = connect(&url).await?;
+
+    let identify = Identify {
+        server_id: info.guild_id,
+        session_id: info.session_id.clone(),
+        token: info.token.clone(),
+        user_id: info.user_id,
+    };
+    send(&mut ws, OpCode::Identify, &identify).await?;
+
+    let mut hello = None;
+    let mut ready = None::<Ready>;
+
+    // The hello may be received before or after the ready.
+    let (hello, ready) = loop {
+        if let (Some(hello), Some(ready)) = (hello, ready.as_ref()) {
+            break (hello, ready.clone());
+        }
+
+        let (op, json) = receive(&mut ws).await?;
+
+        if op == OpCode::Hello as u8 {
+            hello = Some(deserialize::<Hello>(&json)?);
+        } else if op == OpCode::Ready as u8 {
+            ready = Some(deserialize::<Ready>(&json)?);
+        }
+    };
+
+    let mode = match EncryptionMode::select(&ready.modes) {
+        Some(mode) => mode,
+        None => {
+            return Err(VoiceConnectionError::new(
+                VoiceConnectionErrorType::UnsupportedEncryption { modes: ready.modes },
+            ))
+        }
+    };
+
+    let socket = bind(&ready).await?;
+    let external_address = discover(&socket, ready.ssrc).await?;
+
+    let select_protocol = SelectProtocol {
+        data: SelectProtocolData {
+            address: external_address.ip().to_string(),
+            mode: mode.name().to_owned(),
+            port: external_address.port(),
+        },
+        protocol: PROTOCOL.to_owned(),
+    };
+    send(&mut ws, OpCode::SelectProtocol, &select_protocol).await?;
+
+    let description = loop {
+        let (op, json) = receive(&mut ws).await?;
+
+        if op == OpCode::SessionDescription as u8 {
+            break deserialize::<SessionDescription>(&json)?;
+        }
+    };
+
+    let mode = EncryptionMode::from_name(&description.mode).ok_or_else(|| {
+        VoiceConnectionError::new(VoiceConnectionErrorType::UnsupportedEncryption {
+            modes: vec![description.mode.clone()],
+        })
+    })?;
+
+    tracing::debug!(ssrc = ready.ssrc, %external_address, ?mode, "voice session started");
+
+    let (commands_tx, commands_rx) = mpsc::unbounded_channel();
+    let (events_tx, events_rx) = mpsc::unbounded_channel();
+
+    let runner = Runner {
+        commands: commands_rx,
+        events: events_tx,
+        heartbeat_interval: heartbeat_interval(hello),
+        info: info.clone(),
+        ssrc: ready.ssrc,
+        url,
+        ws,
+    };
+    tokio::spawn(runner.run());
+
+    let connection = VoiceConnection {
+        commands: commands_tx,
+        external_address,
+        info,
+        mode,
+        secret_key: description.secret_key,
+        socket: Arc::new(socket),
+        ssrc: ready.ssrc,
+    };
+
+    Ok((connection, VoiceEvents::new(events_rx)))
+}
+
+/// Bind a UDP socket connected to the voice server.
+async fn bind(ready: &Ready) -> Result<UdpSocket, VoiceConnectionError> {
+    let address = net::lookup_host((ready.ip.as_str(), ready.port))
+        .await
+        .map_err(|source| {
+            VoiceConnectionError::with_source(VoiceConnectionErrorType::Connecting, source)
+        })?
+        .next()
+        .ok_or_else(|| VoiceConnectionError::new(VoiceConnectionErrorType::Connecting))?;
+
+    let local = if address.is_ipv4() {
+        SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+    } else {
+        SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))
+    };
+
+    let socket = UdpSocket::bind(local).await.map_err(|source| {
+        VoiceConnectionError::with_source(VoiceConnectionErrorType::Connecting, source)
+    })?;
+
+    socket.connect(address).await.map_err(|source| {
+        VoiceConnectionError::with_source(VoiceConnectionErrorType::Connecting, source)
+    })?;
+
+    Ok(socket)
+}
+
+/// Discover the external address of a UDP socket.
+///
+/// Requests are resent until one is answered, as UDP packets may be lost.
+async fn discover(socket: &UdpSocket, ssrc: u32) -> Result<SocketAddr, VoiceConnectionError> {
+    let request = udp::request(ssrc);
+    let mut buf = [0; udp::DISCOVERY_LEN];
+
+    loop {
+        socket.send(&request).await.map_err(|source| {
+            VoiceConnectionError::with_source(VoiceConnectionErrorType::Discovering, source)
+        })?;
+
+        let deadline = Instant::now() + DISCOVERY_INTERVAL;
+
+        while let Ok(received) = time::timeout_at(deadline, socket.recv(&mut buf)).await {
+            let len = received.map_err(|source| {
+                VoiceConnectionError::with_source(VoiceConnectionErrorType::Discovering, source)
+            })?;
+
+            if let Some(address) = udp::parse_response(&buf[..len]) {
+                return Ok(address);
+            }
+        }
+    }
+}
+
+/// Connect to the voice gateway.
+async fn connect(url: &str) -> Result<WebSocket, VoiceConnectionError> {
+    let (ws, _) = tokio_tungstenite::connect_async(url)
+        .await
+        .map_err(|source| {
+            VoiceConnectionError::with_source(VoiceConnectionErrorType::Connecting, source)
+        })?;
+
+    Ok(ws)
+}
+
+/// Send a payload to the voice gateway.
+async fn send(
+    ws: &mut WebSocket,
+    op: OpCode,
+    data: impl Serialize,
+) -> Result<(), VoiceConnectionError> {
+    let json = payload::to_string(op, data).map_err(|source| {
+        VoiceConnectionError::with_source(VoiceConnectionErrorType::Serializing, source)
+    })?;
+
+    ws.send(Message::Text(json)).await.map_err(|source| {
+        VoiceConnectionError::with_source(VoiceConnectionErrorType::Sending, source)
+    })
+}
+
+/// Receive the next payload from the voice gateway, returning its opcode and
+/// JSON.
+async fn receive(ws: &mut WebSocket) -> Result<(u8, String), VoiceConnectionError> {
+    loop {
+        let message = match ws.next().await {
+            Some(Ok(message)) => message,
+            Some(Err(source)) => {
+                return Err(VoiceConnectionError::with_source(
+                    VoiceConnectionErrorType::Closed { code: None },
+                    source,
+                ))
+            }
+            None => {
+                return Err(VoiceConnectionError::new(
+                    VoiceConnectionErrorType::Closed { code: None },
+                ))
+            }
+        };
+
+        match message {
+            Message::Close(frame) => {
+                return Err(VoiceConnectionError::new(
+                    VoiceConnectionErrorType::Closed {
+                        code: frame.map(|frame| frame.code.into()),
+                    },
+                ))
+            }
+            Message::Text(json) => {
+                let op = serde_json::from_str::<MinimalPayload>(&json)
+                    .map_err(|source| {
+                        VoiceConnectionError::with_source(
+                            VoiceConnectionErrorType::Deserializing,
+                            source,
+                        )
+                    })?
+                    .op;
+
+                return Ok((op, json));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Deserialize the data of a received payload.
+fn deserialize<T: DeserializeOwned>(json: &str) -> Result<T, VoiceConnectionError> {
+    payload::data(json).map_err(|source| {
+        VoiceConnectionError::with_source(VoiceConnectionErrorType::Deserializing, source)
+    })
+}
+
+/// Interval between heartbeats requested by a hello.
+#[allow(clippy::manual_clamp)]
+fn heartbeat_interval(hello: Hello) -> Duration {
+    // Bounded to avoid panicking on nonsensical intervals. Unlike `clamp`,
+    // `max` and `min` also bound NaN.
+    let millis = hello
+        .heartbeat_interval
+        .max(1.0)
+        .min(MAX_HEARTBEAT_INTERVAL);
+
+    Duration::from_secs_f64(millis / 1000.0)
+}
+
+/// Whether a session can be resumed after its connection was closed with a
+/// code.
+fn is_resumable(code: Option<u16>) -> bool {
+    code.map_or(true, |code| {
+        !FATAL_CLOSE_CODES.iter().any(|fatal| *fatal as u16 == code)
+    })
+}
+
+/// Task running the websocket of a connection once it has been established.
+struct Runner {
+    /// Receiver of commands from the connection.
+    commands: UnboundedReceiver<Command>,
+    /// Sender of events to the connection's event stream.
+    events: UnboundedSender<VoiceEvent>,
+    /// Interval between heartbeats.
+    heartbeat_interval: Duration,
+    /// Information used to connect.
+    info: ConnectionInfo,
+    /// Synchronization source identifying the user's voice data.
+    ssrc: u32,
+    /// URL of the voice gateway.
+    url: String,
+    /// Websocket connection to the voice gateway.
+    ws: WebSocket,
+}
+
+impl Runner {
+    /// Run the connection until it is dropped or can't be resumed.
+    async fn run(mut self) {
+        loop {
+            let code = match self.poll().await {
+                Ok(()) => return,
+                Err(error) => {
+                    tracing::debug!("voice connection closed: {error}");
+
+                    match error.kind {
+                        VoiceConnectionErrorType::Closed { code } => code,
+                        _ => None,
+                    }
+                }
+            };
+
+            if !is_resumable(code) || !self.resume().await {
+                let _res = self.events.send(VoiceEvent::Disconnected { code });
+
+                return;
+            }
+
+            let _res = self.events.send(VoiceEvent::Resumed);
+        }
+    }
+
+    /// Send heartbeats and commands and handle received payloads until the
+    /// connection is closed.
+    ///
+    /// Returns successfully if the connection was dropped.
+    async fn poll(&mut self) -> Result<(), VoiceConnectionError> {
+        let start = Instant::now() + self.heartbeat_interval;
+        let mut heartbeats = time::interval_at(start, self.heartbeat_interval);
+        let mut awaiting_ack = false;
+
+        loop {
+            tokio::select! {
+                _ = heartbeats.tick() => {
+                    if awaiting_ack {
+                        tracing::info!("voice connection didn't acknowledge heartbeat");
+
+                        return Err(VoiceConnectionError::new(
+                            VoiceConnectionErrorType::Closed { code: None },
+                        ));
+                    }
+
+                    send(&mut self.ws, OpCode::Heartbeat, Heartbeat(nonce())).await?;
+                    awaiting_ack = true;
+                }
+                command = self.commands.recv() => match command {
+                    Some(Command::Speaking(speaking)) => {
+                        let speaking = Speaking {
+                            delay: 0,
+                            speaking,
+                            ssrc: self.ssrc,
+                            user_id: None,
+                        };
+
+                        send(&mut self.ws, OpCode::Speaking, &speaking).await?;
+                    }
+                    None => {
+                        let _res = self.ws.close(None).await;
+
+                        return Ok(());
+                    }
+                },
+                payload = receive(&mut self.ws) => {
+                    let (op, json) = payload?;
+
+                    if op == OpCode::HeartbeatAck as u8 {
+                        awaiting_ack = false;
+                    } else if op == OpCode::Speaking as u8 {
+                        let speaking = deserialize::<Speaking>(&json)?;
+                        let _res = self.events.send(VoiceEvent::Speaking(speaking));
+                    } else if op == OpCode::ClientDisconnect as u8 {
+                        let disconnect = deserialize::<ClientDisconnect>(&json)?;
+                        let _res = self.events.send(VoiceEvent::ClientDisconnect(disconnect));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reconnect and resume the session, returning whether it was resumed.
+    async fn resume(&mut self) -> bool {
+        for attempt in 0..RESUME_ATTEMPTS {
+            if attempt > 0 {
+                time::sleep(RESUME_DELAY).await;
+            }
+
+            match time::timeout(HANDSHAKE_TIMEOUT, self.try_resume()).await {
+                Ok(Ok(())) => return true,
+                Ok(Err(source)) => {
+                    tracing::warn!(attempt, "resuming voice session failed: {source}");
+                }
+                Err(_) => tracing::warn!(attempt, "resuming voice session timed out"),
+            }
+        }
+
+        false
+    }
+
+    /// Reconnect and attempt to resume the session once.
+    async fn try_resume(&mut self) -> Result<(), VoiceConnectionError> {
+        let mut ws = connect(&self.url).await?;
+
+        let resume = Resume {
+            server_id: self.info.guild_id,
+            session_id: self.info.session_id.clone(),
+            token: self.info.token.clone(),
+        };
+        send(&mut ws, OpCode::Resume, &resume).await?;
+
+        loop {
+            let (op, json) = receive(&mut ws).await?;
+
+            if op == OpCode::Hello as u8 {
+                self.heartbeat_interval = heartbeat_interval(deserialize(&json)?);
+            } else if op == OpCode::Resumed as u8 {
+                break;
+            }
+        }
+
+        tracing::debug!(guild_id = %self.info.guild_id, "voice session resumed");
+        self.ws = ws;
+
+        Ok(())
+    }
+}
+
+/// Nonce of a heartbeat, the current UNIX timestamp in milliseconds.
+fn nonce() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| {
+            elapsed.as_millis().try_into().unwrap_or(u64::MAX)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        VoiceConnection, VoiceConnectionBuilder, VoiceConnectionError, VoiceConnectionErrorType,
+    };
+    use crate::payload::Hello;
+    use static_assertions::assert_impl_all;
+    use std::{error::Error, fmt::Debug, time::Duration};
+
+    assert_impl_all!(VoiceConnection: Debug, Send, Sync);
+    assert_impl_all!(VoiceConnectionBuilder: Debug, Send, Sync);
+    assert_impl_all!(VoiceConnectionError: Error, Send, Sync);
+    assert_impl_all!(VoiceConnectionErrorType: Debug, Send, Sync);
+
+    #[test]
+    fn heartbeat_interval() {
+        let interval = |heartbeat_interval| super::heartbeat_interval(Hello { heartbeat_interval });
+
+        assert_eq!(Duration::from_micros(13_750_250), interval(13_750.25));
+        assert_eq!(Duration::from_millis(1), interval(f64::NAN));
+        assert_eq!(Duration::from_secs(60 * 60), interval(f64::INFINITY));
+    }
+
+    #[test]
+    fn is_resumable() {
+        assert!(super::is_resumable(None));
+        assert!(super::is_resumable(Some(1006)));
+        assert!(super::is_resumable(Some(4015)));
+        assert!(!super::is_resumable(Some(4006)));
+        assert!(!super::is_resumable(Some(4014)));
+    }
+}
@@ -0,0 +1,267 @@
+//! Encryption of voice packets with the modes negotiated with the voice
+//! server.
+//!
+//! All modes encrypt the payload of RTP packets with [XSalsa20Poly1305], and
+//! differ in the nonce used:
+//!
+//! - [`EncryptionMode::Normal`] uses the RTP header as the nonce;
+//! - [`EncryptionMode::Suffix`] uses a random nonce appended to the packet;
+//! - [`EncryptionMode::Lite`] uses an incrementing 32-bit nonce appended to
+//!   the packet.
+//!
+//! [XSalsa20Poly1305]: https://en.wikipedia.org/wiki/Salsa20#XSalsa20_with_192-bit_nonce
+
+use crate::rtp::{VoicePacketError, VoicePacketErrorType};
+use crypto_secretbox::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Nonce, XSalsa20Poly1305,
+};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+
+/// Length of a nonce.
+const NONCE_LEN: usize = 24;
+
+/// Length of the nonce appended to packets in the lite mode.
+const LITE_NONCE_LEN: usize = 4;
+
+/// Length of the authentication tag prepended to encrypted payloads.
+pub const TAG_LEN: usize = 16;
+
+/// Encryption mode of voice packets.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum EncryptionMode {
+    /// Nonce is the RTP header, padded with zeroes.
+    Normal,
+    /// Nonce is an incrementing 32-bit integer appended to the packet, padded
+    /// with zeroes.
+    Lite,
+    /// Nonce is random and appended to the packet.
+    Suffix,
+}
+
+impl EncryptionMode {
+    /// Modes in order of preference when negotiating with the voice server.
+    ///
+    /// The lite mode is preferred as its nonce is the cheapest to generate
+    /// and send.
+    pub const PREFERENCE: [Self; 3] = [Self::Lite, Self::Suffix, Self::Normal];
+
+    /// Parse a mode from its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::PREFERENCE
+            .into_iter()
+            .find(|mode| mode.name() == name)
+    }
+
+    /// Name of the mode, as sent to and received from the voice server.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "xsalsa20_poly1305",
+            Self::Lite => "xsalsa20_poly1305_lite",
+            Self::Suffix => "xsalsa20_poly1305_suffix",
+        }
+    }
+
+    /// Select the most preferred mode out of the names of the modes supported
+    /// by the voice server.
+    pub fn select(names: &[String]) -> Option<Self> {
+        Self::PREFERENCE
+            .into_iter()
+            .find(|mode| names.iter().any(|name| name == mode.name()))
+    }
+
+    /// Length of the nonce appended to packets.
+    const fn suffix_len(self) -> usize {
+        match self {
+            Self::Normal => 0,
+            Self::Lite => LITE_NONCE_LEN,
+            Self::Suffix => NONCE_LEN,
+        }
+    }
+}
+
+/// Cipher encrypting and decrypting the payloads of voice packets.
+pub struct PacketCipher {
+    /// Cipher keyed with the session's secret key.
+    cipher: XSalsa20Poly1305,
+    /// Nonce of the next packet encrypted in the lite mode.
+    lite_nonce: u32,
+    /// Encryption mode.
+    mode: EncryptionMode,
+}
+
+impl PacketCipher {
+    /// Create a new cipher for a mode with the session's secret key.
+    pub fn new(mode: EncryptionMode, secret_key: &[u8; 32]) -> Self {
+        Self {
+            cipher: XSalsa20Poly1305::new(secret_key.into()),
+            lite_nonce: 0,
+            mode,
+        }
+    }
+
+    /// Encryption mode of the cipher.
+    pub const fn mode(&self) -> EncryptionMode {
+        self.mode
+    }
+
+    /// Encrypt a payload, returning a packet of the header followed by the
+    /// encrypted payload and the mode's nonce, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of type [`Encrypting`] if encrypting the payload
+    /// failed.
+    ///
+    /// [`Encrypting`]: VoicePacketErrorType::Encrypting
+    pub fn encrypt(&mut self, header: &[u8], payload: &[u8]) -> Result<Vec<u8>, VoicePacketError> {
+        let mut nonce = Nonce::default();
+
+        let suffix = match self.mode {
+            EncryptionMode::Normal => {
+                let len = header.len().min(NONCE_LEN);
+                nonce[..len].copy_from_slice(&header[..len]);
+
+                Vec::new()
+            }
+            EncryptionMode::Lite => {
+                let suffix = self.lite_nonce.to_be_bytes();
+                self.lite_nonce = self.lite_nonce.wrapping_add(1);
+                nonce[..LITE_NONCE_LEN].copy_from_slice(&suffix);
+
+                suffix.to_vec()
+            }
+            EncryptionMode::Suffix => {
+                nonce = XSalsa20Poly1305::generate_nonce(&mut OsRng);
+
+                nonce.to_vec()
+            }
+        };
+
+        let encrypted = self
+            .cipher
+            .encrypt(&nonce, payload)
+            .map_err(|_| VoicePacketError::new(VoicePacketErrorType::Encrypting))?;
+
+        let mut packet = Vec::with_capacity(header.len() + encrypted.len() + suffix.len());
+        packet.extend_from_slice(header);
+        packet.extend_from_slice(&encrypted);
+        packet.extend_from_slice(&suffix);
+
+        Ok(packet)
+    }
+
+    /// Decrypt the payload of a packet whose header is of a length.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of type [`Malformed`] if the packet is too short.
+    ///
+    /// Returns an error of type [`Decrypting`] if the payload could not be
+    /// decrypted with the session's secret key.
+    ///
+    /// [`Decrypting`]: VoicePacketErrorType::Decrypting
+    /// [`Malformed`]: VoicePacketErrorType::Malformed
+    pub fn decrypt(&self, header_len: usize, packet: &[u8]) -> Result<Vec<u8>, VoicePacketError> {
+        let suffix_len = self.mode.suffix_len();
+
+        if packet.len() < header_len + TAG_LEN + suffix_len {
+            return Err(VoicePacketError::new(VoicePacketErrorType::Malformed));
+        }
+
+        let (body, suffix) = packet.split_at(packet.len() - suffix_len);
+        let mut nonce = Nonce::default();
+
+        match self.mode {
+            EncryptionMode::Normal => {
+                let len = header_len.min(NONCE_LEN);
+                nonce[..len].copy_from_slice(&packet[..len]);
+            }
+            EncryptionMode::Lite | EncryptionMode::Suffix => {
+                nonce[..suffix_len].copy_from_slice(suffix);
+            }
+        }
+
+        self.cipher
+            .decrypt(&nonce, &body[header_len..])
+            .map_err(|_| VoicePacketError::new(VoicePacketErrorType::Decrypting))
+    }
+}
+
+impl Debug for PacketCipher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("PacketCipher")
+            .field("lite_nonce", &self.lite_nonce)
+            .field("mode", &self.mode)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EncryptionMode, PacketCipher, TAG_LEN};
+    use crate::rtp::VoicePacketErrorType;
+    use static_assertions::assert_impl_all;
+    use std::{fmt::Debug, hash::Hash};
+
+    assert_impl_all!(EncryptionMode: Clone, Copy, Debug, Eq, Hash, PartialEq, Send, Sync);
+    assert_impl_all!(PacketCipher: Debug, Send, Sync);
+
+    #[test]
+    fn select() {
+        let names = |names: &[&str]| names.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        assert_eq!(
+            Some(EncryptionMode::Lite),
+            EncryptionMode::select(&names(&[
+                "aead_aes256_gcm",
+                "xsalsa20_poly1305",
+                "xsalsa20_poly1305_lite",
+                "xsalsa20_poly1305_suffix",
+            ]))
+        );
+        assert_eq!(
+            Some(EncryptionMode::Normal),
+            EncryptionMode::select(&names(&["xsalsa20_poly1305"]))
+        );
+        assert_eq!(None, EncryptionMode::select(&names(&["aead_aes256_gcm"])));
+
+        for mode in EncryptionMode::PREFERENCE {
+            assert_eq!(Some(mode), EncryptionMode::from_name(mode.name()));
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let header = [0x80, 0x78, 0, 1, 0, 0, 3, 192, 0, 0, 0, 42];
+
+        for (mode, suffix_len) in [
+            (EncryptionMode::Normal, 0),
+            (EncryptionMode::Lite, 4),
+            (EncryptionMode::Suffix, 24),
+        ] {
+            let mut cipher = PacketCipher::new(mode, &[1; 32]);
+            let packet = cipher.encrypt(&header, b"opus").unwrap();
+            assert_eq!(header, packet[..12]);
+            assert_eq!(12 + TAG_LEN + 4 + suffix_len, packet.len());
+            assert_eq!(b"opus".to_vec(), cipher.decrypt(12, &packet).unwrap());
+
+            let other = PacketCipher::new(mode, &[2; 32]);
+            assert!(matches!(
+                other.decrypt(12, &packet).unwrap_err().kind(),
+                VoicePacketErrorType::Decrypting
+            ));
+            assert!(matches!(
+                other.decrypt(12, &packet[..20]).unwrap_err().kind(),
+                VoicePacketErrorType::Malformed
+            ));
+        }
+
+        // Lite nonces increment with every packet.
+        let mut cipher = PacketCipher::new(EncryptionMode::Lite, &[1; 32]);
+        cipher.encrypt(&header, b"opus").unwrap();
+        let packet = cipher.encrypt(&header, b"opus").unwrap();
+        assert_eq!([0, 0, 0, 1], packet[packet.len() - 4..]);
+    }
+}
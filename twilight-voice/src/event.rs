@@ -0,0 +1,67 @@
+//! Events emitted by a voice connection.
+
+use crate::payload::{ClientDisconnect, Speaking};
+use futures_util::stream::Stream;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// Event emitted by a [`VoiceConnection`].
+///
+/// [`VoiceConnection`]: crate::VoiceConnection
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum VoiceEvent {
+    /// Another user disconnected from the voice channel.
+    ClientDisconnect(ClientDisconnect),
+    /// Connection was closed and won't be resumed.
+    ///
+    /// This is the last event of the stream.
+    Disconnected {
+        /// Close code sent by the voice server, if any.
+        code: Option<u16>,
+    },
+    /// Connection was lost and the voice session has been resumed.
+    Resumed,
+    /// Speaking state of another user changed.
+    Speaking(Speaking),
+}
+
+/// Stream of events from a [`VoiceConnection`].
+///
+/// This implements [`futures::stream::Stream`]. The stream ends once the
+/// connection has been closed.
+///
+/// [`VoiceConnection`]: crate::VoiceConnection
+/// [`futures::stream::Stream`]: https://docs.rs/futures/*/futures/stream/trait.Stream.html
+#[derive(Debug)]
+pub struct VoiceEvents {
+    rx: UnboundedReceiver<VoiceEvent>,
+}
+
+impl VoiceEvents {
+    pub(crate) const fn new(rx: UnboundedReceiver<VoiceEvent>) -> Self {
+        Self { rx }
+    }
+}
+
+impl Stream for VoiceEvents {
+    type Item = VoiceEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VoiceEvent, VoiceEvents};
+    use futures_util::stream::Stream;
+    use static_assertions::assert_impl_all;
+    use std::fmt::Debug;
+
+    assert_impl_all!(VoiceEvent: Clone, Debug, Eq, PartialEq, Send, Sync);
+    assert_impl_all!(VoiceEvents: Debug, Send, Stream, Sync);
+}
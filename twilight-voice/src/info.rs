@@ -0,0 +1,238 @@
+//! Information needed to connect to a voice server.
+
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+use twilight_model::{
+    gateway::payload::incoming::{VoiceServerUpdate, VoiceStateUpdate},
+    id::{
+        marker::{GuildMarker, UserMarker},
+        Id,
+    },
+};
+
+/// Version of the voice gateway API.
+pub const API_VERSION: u8 = 4;
+
+/// Creating connection information failed.
+#[derive(Debug)]
+pub struct ConnectionInfoError {
+    kind: ConnectionInfoErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl ConnectionInfoError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &ConnectionInfoErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(
+        self,
+    ) -> (
+        ConnectionInfoErrorType,
+        Option<Box<dyn Error + Send + Sync>>,
+    ) {
+        (self.kind, self.source)
+    }
+}
+
+impl Display for ConnectionInfoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            ConnectionInfoErrorType::EndpointMissing => {
+                f.write_str("voice server update has no endpoint")
+            }
+            ConnectionInfoErrorType::GuildMismatch { server, state } => {
+                f.write_str("voice server update is for guild ")?;
+                Display::fmt(server, f)?;
+                f.write_str(" but voice state update is for ")?;
+
+                match state {
+                    Some(state) => {
+                        f.write_str("guild ")?;
+
+                        Display::fmt(state, f)
+                    }
+                    None => f.write_str("no guild"),
+                }
+            }
+        }
+    }
+}
+
+impl Error for ConnectionInfoError {}
+
+/// Type of [`ConnectionInfoError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConnectionInfoErrorType {
+    /// Voice server update has no endpoint, meaning the voice server has gone
+    /// away and a new one is being allocated.
+    EndpointMissing,
+    /// Voice server and voice state updates are for different guilds.
+    GuildMismatch {
+        /// ID of the guild of the voice server update.
+        server: Id<GuildMarker>,
+        /// ID of the guild of the voice state update, if any.
+        state: Option<Id<GuildMarker>>,
+    },
+}
+
+/// Information needed to connect to a voice server, received from the gateway
+/// after joining a voice channel.
+///
+/// # Examples
+///
+/// Create connection information from the voice updates received after
+/// joining a voice channel:
+///
+/// ```
+/// # use twilight_model::{gateway::payload::incoming::{VoiceServerUpdate, VoiceStateUpdate}, id::Id, voice::VoiceState};
+/// use twilight_voice::ConnectionInfo;
+///
+/// # let state = VoiceStateUpdate(VoiceState {
+/// #     channel_id: Some(Id::new(2)), deaf: false, guild_id: Some(Id::new(1)), member: None,
+/// #     mute: false, self_deaf: false, self_mute: false, self_stream: false, self_video: false,
+/// #     session_id: "session".to_owned(), suppress: false, user_id: Id::new(3),
+/// #     request_to_speak_timestamp: None,
+/// # });
+/// let server = VoiceServerUpdate {
+///     endpoint: Some("example.discord.media:443".to_owned()),
+///     guild_id: Id::new(1),
+///     token: "token".to_owned(),
+/// };
+///
+/// let info = ConnectionInfo::new(&server, &state)?;
+/// assert_eq!("wss://example.discord.media:443/?v=4", info.url());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ConnectionInfo {
+    /// Host of the voice server.
+    pub endpoint: String,
+    /// ID of the guild.
+    pub guild_id: Id<GuildMarker>,
+    /// ID of the user's gateway session.
+    pub session_id: String,
+    /// Token of the voice server.
+    pub token: String,
+    /// ID of the user.
+    pub user_id: Id<UserMarker>,
+}
+
+impl ConnectionInfo {
+    /// Create connection information from a voice server update and the
+    /// current user's voice state update.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of type [`EndpointMissing`] if the voice server
+    /// update has no endpoint.
+    ///
+    /// Returns an error of type [`GuildMismatch`] if the updates are for
+    /// different guilds.
+    ///
+    /// [`EndpointMissing`]: ConnectionInfoErrorType::EndpointMissing
+    /// [`GuildMismatch`]: ConnectionInfoErrorType::GuildMismatch
+    pub fn new(
+        server: &VoiceServerUpdate,
+        state: &VoiceStateUpdate,
+    ) -> Result<Self, ConnectionInfoError> {
+        if state.0.guild_id != Some(server.guild_id) {
+            return Err(ConnectionInfoError {
+                kind: ConnectionInfoErrorType::GuildMismatch {
+                    server: server.guild_id,
+                    state: state.0.guild_id,
+                },
+                source: None,
+            });
+        }
+
+        let endpoint = server.endpoint.as_ref().ok_or(ConnectionInfoError {
+            kind: ConnectionInfoErrorType::EndpointMissing,
+            source: None,
+        })?;
+
+        Ok(Self {
+            endpoint: endpoint.trim_start_matches("wss://").to_owned(),
+            guild_id: server.guild_id,
+            session_id: state.0.session_id.clone(),
+            token: server.token.clone(),
+            user_id: state.0.user_id,
+        })
+    }
+
+    /// URL of the voice gateway.
+    pub fn url(&self) -> String {
+        format!("wss://{}/?v={API_VERSION}", self.endpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConnectionInfo, ConnectionInfoError, ConnectionInfoErrorType};
+    use static_assertions::assert_impl_all;
+    use std::{error::Error, fmt::Debug, hash::Hash};
+    use twilight_model::{
+        gateway::payload::incoming::{VoiceServerUpdate, VoiceStateUpdate},
+        id::Id,
+        voice::VoiceState,
+    };
+
+    assert_impl_all!(ConnectionInfo: Clone, Debug, Eq, Hash, PartialEq, Send, Sync);
+    assert_impl_all!(ConnectionInfoError: Error, Send, Sync);
+
+    fn state(guild_id: u64) -> VoiceStateUpdate {
+        VoiceStateUpdate(VoiceState {
+            channel_id: Some(Id::new(2)),
+            deaf: false,
+            guild_id: Some(Id::new(guild_id)),
+            member: None,
+            mute: false,
+            self_deaf: false,
+            self_mute: false,
+            self_stream: false,
+            self_video: false,
+            session_id: "session".to_owned(),
+            suppress: false,
+            user_id: Id::new(3),
+            request_to_speak_timestamp: None,
+        })
+    }
+
+    #[test]
+    fn new() {
+        let mut server = VoiceServerUpdate {
+            endpoint: Some("wss://example.discord.media".to_owned()),
+            guild_id: Id::new(1),
+            token: "token".to_owned(),
+        };
+
+        let info = ConnectionInfo::new(&server, &state(1)).unwrap();
+        assert_eq!("example.discord.media", info.endpoint);
+        assert_eq!("session", info.session_id);
+        assert_eq!(Id::new(3), info.user_id);
+
+        assert!(matches!(
+            ConnectionInfo::new(&server, &state(4)).unwrap_err().kind(),
+            ConnectionInfoErrorType::GuildMismatch { .. }
+        ));
+
+        server.endpoint = None;
+        assert!(matches!(
+            ConnectionInfo::new(&server, &state(1)).unwrap_err().kind(),
+            ConnectionInfoErrorType::EndpointMissing
+        ));
+    }
+}
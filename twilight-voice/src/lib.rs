@@ -0,0 +1,41 @@
+#![deny(
+    clippy::all,
+    clippy::missing_const_for_fn,
+    clippy::pedantic,
+    future_incompatible,
+    missing_docs,
+    nonstandard_style,
+    rust_2018_idioms,
+    rustdoc::broken_intra_doc_links,
+    unsafe_code,
+    unused
+)]
+#![allow(
+    clippy::module_name_repetitions,
+    clippy::must_use_candidate,
+    clippy::unnecessary_wraps,
+    clippy::used_underscore_binding
+)]
+#![cfg_attr(docsrs, feature(doc_auto_cfg))]
+#![doc = include_str!("../README.md")]
+
+pub mod connection;
+pub mod crypto;
+pub mod event;
+pub mod info;
+pub mod manager;
+#[cfg(feature = "test-support")]
+pub mod mock;
+pub mod payload;
+pub mod rtp;
+
+mod udp;
+
+pub use self::{
+    connection::{VoiceConnection, VoiceConnectionBuilder},
+    crypto::EncryptionMode,
+    event::{VoiceEvent, VoiceEvents},
+    info::ConnectionInfo,
+    manager::VoiceConnectionManager,
+    rtp::{VoicePacket, VoiceReceiver, VoiceSender},
+};
@@ -0,0 +1,526 @@
+//! Manager of the voice sessions of the current user, bridging gateway events
+//! to the information needed to connect to voice servers.
+//!
+//! Joining a voice channel is done by sending an [`UpdateVoiceState`] command
+//! over the gateway, after which Discord sends the current user's voice state
+//! and the voice server of the guild as two separate events.
+//! [`VoiceConnectionManager`] tracks the joins of every guild, stitching the
+//! two events together into a [`ConnectionInfo`] once both have been received.
+//!
+//! The manager also keeps track of the voice sessions after they have been
+//! joined, notifying of voice server changes, such as when the guild's voice
+//! region changes, and of the current user being disconnected from the
+//! channel, such as when kicked by a moderator.
+
+use crate::info::ConnectionInfo;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    future::Future,
+    pin::Pin,
+    sync::{Mutex, MutexGuard},
+    task::{Context, Poll},
+};
+use tokio::sync::oneshot::{self, Receiver, Sender};
+use twilight_model::{
+    gateway::{
+        event::Event,
+        payload::{
+            incoming::{VoiceServerUpdate, VoiceStateUpdate},
+            outgoing::UpdateVoiceState,
+        },
+    },
+    id::{
+        marker::{ChannelMarker, GuildMarker, UserMarker},
+        Id,
+    },
+};
+
+/// Joining a voice channel failed.
+#[derive(Debug)]
+pub struct VoiceJoinError {
+    kind: VoiceJoinErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl VoiceJoinError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &VoiceJoinErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(self) -> (VoiceJoinErrorType, Option<Box<dyn Error + Send + Sync>>) {
+        (self.kind, self.source)
+    }
+
+    /// Create a new error of a type without a source.
+    const fn new(kind: VoiceJoinErrorType) -> Self {
+        Self { kind, source: None }
+    }
+}
+
+impl Display for VoiceJoinError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            VoiceJoinErrorType::Cancelled => {
+                f.write_str("join was cancelled by leaving or joining another channel")
+            }
+            VoiceJoinErrorType::Disconnected => {
+                f.write_str("current user was disconnected from the voice channel")
+            }
+        }
+    }
+}
+
+impl Error for VoiceJoinError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// Type of [`VoiceJoinError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum VoiceJoinErrorType {
+    /// Join was cancelled by leaving the guild's voice channel, joining
+    /// another channel of the guild, or dropping the manager.
+    Cancelled,
+    /// Current user was disconnected from the voice channel before the join
+    /// completed, such as when lacking permission to join it.
+    Disconnected,
+}
+
+/// Change to a voice session of the current user, returned by
+/// [`VoiceConnectionManager::process`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum VoiceSessionUpdate {
+    /// Joining a voice channel completed.
+    Joined(ConnectionInfo),
+    /// Voice server of a joined voice channel changed, such as when the
+    /// guild's voice region changed.
+    ///
+    /// The voice connection must be re-established with the new information.
+    Moved(ConnectionInfo),
+    /// Current user was disconnected from a voice channel without leaving it
+    /// through the manager, such as when kicked by a moderator.
+    Disconnected {
+        /// ID of the guild.
+        guild_id: Id<GuildMarker>,
+    },
+}
+
+/// Voice session of the current user in a guild.
+#[derive(Debug, Default)]
+struct Session {
+    /// ID of the channel being joined or that has been joined.
+    channel_id: Option<Id<ChannelMarker>>,
+    /// Information of the joined voice session, if the join has completed.
+    info: Option<ConnectionInfo>,
+    /// Latest voice server update with an endpoint.
+    server: Option<VoiceServerUpdate>,
+    /// Latest voice state update of the current user.
+    state: Option<VoiceStateUpdate>,
+    /// Senders of pending joins.
+    waiters: Vec<Sender<Result<ConnectionInfo, VoiceJoinErrorType>>>,
+}
+
+impl Session {
+    /// Complete the session once both voice updates have been received for
+    /// the joined channel, returning the resulting update.
+    fn update(&mut self) -> Option<VoiceSessionUpdate> {
+        let state = self.state.as_ref()?;
+
+        if state.0.channel_id != self.channel_id {
+            return None;
+        }
+
+        let info = ConnectionInfo::new(self.server.as_ref()?, state).ok()?;
+
+        for waiter in self.waiters.drain(..) {
+            let _res = waiter.send(Ok(info.clone()));
+        }
+
+        // Switching channels on the same voice server keeps the information.
+        let update = match &self.info {
+            Some(current) if *current == info => None,
+            Some(_) => Some(VoiceSessionUpdate::Moved(info.clone())),
+            None => Some(VoiceSessionUpdate::Joined(info.clone())),
+        };
+
+        self.info = Some(info);
+
+        update
+    }
+}
+
+/// Manager of the voice sessions of the current user across guilds.
+///
+/// Pass every gateway event to [`process`] to keep track of the sessions.
+/// Only the voice state and voice server update events are used, so the
+/// [`VOICE_STATE_UPDATE`] and [`VOICE_SERVER_UPDATE`] event types must be
+/// enabled.
+///
+/// # Examples
+///
+/// Join a voice channel and connect to its voice server:
+///
+/// ```no_run
+/// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use futures_util::StreamExt;
+/// use std::{env, sync::Arc};
+/// use twilight_gateway::{Intents, Shard};
+/// use twilight_model::id::Id;
+/// use twilight_voice::{manager::VoiceConnectionManager, VoiceConnection};
+///
+/// let token = env::var("DISCORD_TOKEN")?;
+/// let (shard, mut events) = Shard::new(token, Intents::GUILDS | Intents::GUILD_VOICE_STATES);
+/// shard.start().await?;
+///
+/// let manager = Arc::new(VoiceConnectionManager::new(Id::new(1)));
+///
+/// tokio::spawn({
+///     let manager = Arc::clone(&manager);
+///
+///     async move {
+///         while let Some(event) = events.next().await {
+///             manager.process(&event);
+///         }
+///     }
+/// });
+///
+/// let join = manager.join(Id::new(2), Id::new(3));
+/// shard.command(&join.command()).await?;
+///
+/// let (connection, _voice_events) = VoiceConnection::connect(join.await?).await?;
+/// # drop(connection);
+/// # Ok(()) }
+/// ```
+///
+/// [`VOICE_SERVER_UPDATE`]: twilight_model::gateway::event::EventType::VoiceServerUpdate
+/// [`VOICE_STATE_UPDATE`]: twilight_model::gateway::event::EventType::VoiceStateUpdate
+/// [`process`]: Self::process
+#[derive(Debug)]
+pub struct VoiceConnectionManager {
+    /// Voice sessions by guild.
+    sessions: Mutex<HashMap<Id<GuildMarker>, Session>>,
+    /// ID of the current user.
+    user_id: Id<UserMarker>,
+}
+
+impl VoiceConnectionManager {
+    /// Create a new manager for the current user.
+    pub fn new(user_id: Id<UserMarker>) -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            user_id,
+        }
+    }
+
+    /// Information of the joined voice session of a guild, if any.
+    pub fn connection_info(&self, guild_id: Id<GuildMarker>) -> Option<ConnectionInfo> {
+        self.lock_sessions()
+            .get(&guild_id)
+            .and_then(|session| session.info.clone())
+    }
+
+    /// Start joining a voice channel of a guild.
+    ///
+    /// The returned join's [command] must be sent over the gateway shard of
+    /// the guild. The join resolves once both voice updates have been
+    /// processed, while pending joins of the guild are cancelled.
+    ///
+    /// [command]: VoiceJoin::command
+    pub fn join(&self, guild_id: Id<GuildMarker>, channel_id: Id<ChannelMarker>) -> VoiceJoin {
+        let (tx, rx) = oneshot::channel();
+        let mut sessions = self.lock_sessions();
+        let session = sessions.entry(guild_id).or_default();
+
+        // Dropping the senders cancels the pending joins.
+        session.waiters.clear();
+
+        // Joining another channel is followed by new voice updates, so the
+        // previous ones must not complete the join.
+        if session.channel_id != Some(channel_id) {
+            session.server = None;
+            session.state = None;
+        }
+
+        session.channel_id = Some(channel_id);
+        session.waiters.push(tx);
+
+        VoiceJoin {
+            channel_id,
+            guild_id,
+            rx,
+        }
+    }
+
+    /// Leave the voice channel of a guild, returning the command to send over
+    /// the gateway shard of the guild.
+    ///
+    /// Pending joins of the guild are cancelled.
+    pub fn leave(&self, guild_id: Id<GuildMarker>) -> UpdateVoiceState {
+        self.lock_sessions().remove(&guild_id);
+
+        UpdateVoiceState::new(guild_id, None, false, false)
+    }
+
+    /// Process a gateway event, returning the change to a voice session it
+    /// caused, if any.
+    pub fn process(&self, event: &Event) -> Option<VoiceSessionUpdate> {
+        match event {
+            Event::VoiceServerUpdate(update) => self.server_update(update),
+            Event::VoiceStateUpdate(update) if update.0.user_id == self.user_id => {
+                self.state_update(update)
+            }
+            _ => None,
+        }
+    }
+
+    /// Process a voice server update.
+    fn server_update(&self, update: &VoiceServerUpdate) -> Option<VoiceSessionUpdate> {
+        let mut sessions = self.lock_sessions();
+        let session = sessions.get_mut(&update.guild_id)?;
+
+        // An update without an endpoint means the voice server has gone away
+        // and another update follows once a new one has been allocated.
+        if update.endpoint.is_none() {
+            tracing::debug!(guild_id = %update.guild_id, "voice server deallocated");
+            session.server = None;
+
+            return None;
+        }
+
+        session.server = Some(update.clone());
+
+        session.update()
+    }
+
+    /// Process a voice state update of the current user.
+    fn state_update(&self, update: &VoiceStateUpdate) -> Option<VoiceSessionUpdate> {
+        let guild_id = update.0.guild_id?;
+        let mut sessions = self.lock_sessions();
+
+        if update.0.channel_id.is_none() {
+            let session = sessions.remove(&guild_id)?;
+            tracing::debug!(%guild_id, "disconnected from voice channel");
+
+            let disconnected = session.info.is_some();
+
+            for waiter in session.waiters {
+                let _res = waiter.send(Err(VoiceJoinErrorType::Disconnected));
+            }
+
+            return disconnected.then_some(VoiceSessionUpdate::Disconnected { guild_id });
+        }
+
+        let session = sessions.get_mut(&guild_id)?;
+
+        // The channel is changed when moved by a moderator.
+        if session.info.is_some() && session.waiters.is_empty() {
+            session.channel_id = update.0.channel_id;
+        }
+
+        session.state = Some(update.clone());
+
+        session.update()
+    }
+
+    /// Lock the voice sessions.
+    fn lock_sessions(&self) -> MutexGuard<'_, HashMap<Id<GuildMarker>, Session>> {
+        self.sessions.lock().expect("sessions poisoned")
+    }
+}
+
+/// Future resolving with the information to connect to a voice server once
+/// joining a voice channel completed.
+///
+/// Created by [`VoiceConnectionManager::join`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct VoiceJoin {
+    /// ID of the channel.
+    channel_id: Id<ChannelMarker>,
+    /// ID of the guild.
+    guild_id: Id<GuildMarker>,
+    /// Receiver of the connection information.
+    rx: Receiver<Result<ConnectionInfo, VoiceJoinErrorType>>,
+}
+
+impl VoiceJoin {
+    /// ID of the channel being joined.
+    pub const fn channel_id(&self) -> Id<ChannelMarker> {
+        self.channel_id
+    }
+
+    /// Command to send over the gateway shard of the guild to join the
+    /// channel.
+    ///
+    /// The current user isn't deafened or muted, which can be changed through
+    /// the command's data.
+    pub fn command(&self) -> UpdateVoiceState {
+        UpdateVoiceState::new(self.guild_id, self.channel_id, false, false)
+    }
+
+    /// ID of the guild of the channel being joined.
+    pub const fn guild_id(&self) -> Id<GuildMarker> {
+        self.guild_id
+    }
+}
+
+impl Future for VoiceJoin {
+    type Output = Result<ConnectionInfo, VoiceJoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx).poll(cx).map(|result| {
+            result
+                .unwrap_or(Err(VoiceJoinErrorType::Cancelled))
+                .map_err(VoiceJoinError::new)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        VoiceConnectionManager, VoiceJoin, VoiceJoinError, VoiceJoinErrorType, VoiceSessionUpdate,
+    };
+    use static_assertions::assert_impl_all;
+    use std::{error::Error, fmt::Debug, future::Future};
+    use twilight_model::{
+        gateway::{
+            event::Event,
+            payload::incoming::{VoiceServerUpdate, VoiceStateUpdate},
+        },
+        id::Id,
+        voice::VoiceState,
+    };
+
+    assert_impl_all!(VoiceConnectionManager: Debug, Send, Sync);
+    assert_impl_all!(VoiceJoin: Debug, Future, Send, Sync);
+    assert_impl_all!(VoiceJoinError: Error, Send, Sync);
+    assert_impl_all!(VoiceSessionUpdate: Clone, Debug, Eq, PartialEq, Send, Sync);
+
+    fn server(endpoint: Option<&str>) -> Event {
+        Event::VoiceServerUpdate(VoiceServerUpdate {
+            endpoint: endpoint.map(ToOwned::to_owned),
+            guild_id: Id::new(1),
+            token: "token".to_owned(),
+        })
+    }
+
+    fn state(user_id: u64, channel_id: Option<u64>) -> Event {
+        Event::VoiceStateUpdate(Box::new(VoiceStateUpdate(VoiceState {
+            channel_id: channel_id.map(Id::new),
+            deaf: false,
+            guild_id: Some(Id::new(1)),
+            member: None,
+            mute: false,
+            self_deaf: false,
+            self_mute: false,
+            self_stream: false,
+            self_video: false,
+            session_id: "session".to_owned(),
+            suppress: false,
+            user_id: Id::new(user_id),
+            request_to_speak_timestamp: None,
+        })))
+    }
+
+    #[tokio::test]
+    async fn join() {
+        let manager = VoiceConnectionManager::new(Id::new(3));
+        let join = manager.join(Id::new(1), Id::new(2));
+        assert_eq!(Some(Id::new(2)), join.command().d.channel_id);
+
+        assert_eq!(None, manager.process(&state(4, Some(2))));
+        assert_eq!(None, manager.process(&server(None)));
+        assert_eq!(None, manager.process(&state(3, Some(2))));
+
+        let update = manager.process(&server(Some("a.discord.media:443")));
+        let info = join.await.unwrap();
+        assert_eq!(Some(VoiceSessionUpdate::Joined(info.clone())), update);
+        assert_eq!("a.discord.media:443", info.endpoint);
+        assert_eq!("session", info.session_id);
+        assert_eq!(Id::new(3), info.user_id);
+        assert_eq!(Some(info), manager.connection_info(Id::new(1)));
+
+        // The voice server goes away before a new one is allocated.
+        assert_eq!(None, manager.process(&server(None)));
+        let update = manager.process(&server(Some("b.discord.media:443")));
+        assert!(matches!(
+            update,
+            Some(VoiceSessionUpdate::Moved(info)) if info.endpoint == "b.discord.media:443"
+        ));
+
+        // Being moved to another channel keeps the session.
+        assert_eq!(None, manager.process(&state(3, Some(5))));
+
+        assert_eq!(
+            Some(VoiceSessionUpdate::Disconnected {
+                guild_id: Id::new(1)
+            }),
+            manager.process(&state(3, None))
+        );
+        assert_eq!(None, manager.connection_info(Id::new(1)));
+    }
+
+    #[tokio::test]
+    async fn rejoin() {
+        let manager = VoiceConnectionManager::new(Id::new(3));
+        let join = manager.join(Id::new(1), Id::new(2));
+        manager.process(&state(3, Some(2)));
+        manager.process(&server(Some("a.discord.media:443")));
+        join.await.unwrap();
+
+        // The state update of the other channel arrives before its server
+        // update.
+        let join = manager.join(Id::new(1), Id::new(5));
+        assert_eq!(None, manager.process(&state(3, Some(5))));
+
+        let update = manager.process(&server(Some("b.discord.media:443")));
+        let info = join.await.unwrap();
+        assert_eq!("b.discord.media:443", info.endpoint);
+        assert_eq!(Some(VoiceSessionUpdate::Moved(info.clone())), update);
+        assert_eq!(Some(info), manager.connection_info(Id::new(1)));
+    }
+
+    #[tokio::test]
+    async fn join_fails() {
+        let manager = VoiceConnectionManager::new(Id::new(3));
+
+        let join = manager.join(Id::new(1), Id::new(2));
+        let other = manager.join(Id::new(1), Id::new(5));
+        assert!(matches!(
+            join.await.unwrap_err().kind(),
+            VoiceJoinErrorType::Cancelled
+        ));
+
+        assert_eq!(None, manager.process(&state(3, None)));
+        assert!(matches!(
+            other.await.unwrap_err().kind(),
+            VoiceJoinErrorType::Disconnected
+        ));
+
+        let join = manager.join(Id::new(1), Id::new(2));
+        assert_eq!(None, manager.leave(Id::new(1)).d.channel_id);
+        assert!(matches!(
+            join.await.unwrap_err().kind(),
+            VoiceJoinErrorType::Cancelled
+        ));
+        assert_eq!(None, manager.process(&state(3, Some(2))));
+    }
+}
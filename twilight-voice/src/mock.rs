@@ -0,0 +1,568 @@
+//! In-process mock of a Discord voice server for testing voice connections.
+//!
+//! [`MockVoiceServer`] is a local websocket server speaking the voice gateway
+//! protocol along with a UDP socket answering IP discovery requests. Each
+//! connection made to it is handed to the test as a [`MockVoiceConnection`],
+//! which is then scripted to complete the handshake, send payloads, or close
+//! the connection with a close code. Voice packets sent to the UDP socket are
+//! handed to the test as they are, and packets can be sent back to the last
+//! client that made an IP discovery request.
+//!
+//! Point a connection at the mock via [`VoiceConnectionBuilder::url`].
+//!
+//! Requires the `test-support` feature.
+//!
+//! # Examples
+//!
+//! Connect to the mock voice server:
+//!
+//! ```no_run
+//! use twilight_model::id::Id;
+//! use twilight_voice::{mock::MockVoiceServer, ConnectionInfo, VoiceConnection};
+//!
+//! # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut server = MockVoiceServer::bind().await?;
+//! let info = ConnectionInfo {
+//!     endpoint: "localhost".to_owned(),
+//!     guild_id: Id::new(1),
+//!     session_id: "session".to_owned(),
+//!     token: "token".to_owned(),
+//!     user_id: Id::new(2),
+//! };
+//!
+//! let connect = VoiceConnection::builder(info)
+//!     .url(server.url().to_owned())
+//!     .connect();
+//! let handshake = async {
+//!     let mut connection = server.next_connection().await?;
+//!
+//!     connection.handshake().await
+//! };
+//!
+//! let (connection, identify) = tokio::join!(connect, handshake);
+//! assert_eq!(MockVoiceServer::SECRET_KEY, *connection?.0.secret_key());
+//! # identify?;
+//! # Ok(()) }
+//! ```
+//!
+//! [`VoiceConnectionBuilder::url`]: crate::VoiceConnectionBuilder::url
+
+use crate::{
+    crypto::EncryptionMode,
+    payload::{
+        self, HeartbeatAck, Hello, MinimalPayload, Ready, SelectProtocol, SessionDescription,
+    },
+    udp,
+};
+use futures_util::{SinkExt, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    error::Error,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+};
+use tokio::{
+    net::{TcpListener, TcpStream, UdpSocket},
+    sync::{
+        mpsc::{self, UnboundedReceiver},
+        watch,
+    },
+    task::JoinHandle,
+};
+use tokio_tungstenite::{
+    tungstenite::{
+        protocol::{frame::coding::CloseCode, CloseFrame},
+        Message,
+    },
+    WebSocketStream,
+};
+use twilight_model::voice::OpCode;
+
+/// Operating the mock voice server failed.
+#[derive(Debug)]
+pub struct MockVoiceError {
+    kind: MockVoiceErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl MockVoiceError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &MockVoiceErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(self) -> (MockVoiceErrorType, Option<Box<dyn Error + Send + Sync>>) {
+        (self.kind, self.source)
+    }
+}
+
+impl Display for MockVoiceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            MockVoiceErrorType::Binding => f.write_str("failed to bind the mock voice server"),
+            MockVoiceErrorType::Closed => f.write_str("connection has been closed"),
+            MockVoiceErrorType::Deserializing => f.write_str("failed to deserialize payload"),
+            MockVoiceErrorType::Receiving => f.write_str("failed to receive message"),
+            MockVoiceErrorType::Sending => f.write_str("failed to send message"),
+            MockVoiceErrorType::Serializing => f.write_str("failed to serialize payload"),
+            MockVoiceErrorType::UnexpectedPayload { op } => {
+                f.write_str("received unexpected payload with opcode ")?;
+
+                Display::fmt(op, f)
+            }
+        }
+    }
+}
+
+impl Error for MockVoiceError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// Type of [`MockVoiceError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MockVoiceErrorType {
+    /// Binding the server to a local port failed.
+    Binding,
+    /// Connection or server has been closed.
+    Closed,
+    /// Deserializing an incoming payload failed.
+    Deserializing,
+    /// Receiving a message from the connection failed.
+    Receiving,
+    /// Sending a message to the connection failed.
+    Sending,
+    /// Serializing an outgoing payload failed.
+    Serializing,
+    /// Connection sent a payload that wasn't expected.
+    UnexpectedPayload {
+        /// Opcode of the payload.
+        op: u8,
+    },
+}
+
+/// Local voice server speaking the voice gateway protocol.
+///
+/// The server is stopped when dropped.
+#[derive(Debug)]
+pub struct MockVoiceServer {
+    client: watch::Receiver<Option<SocketAddr>>,
+    connections: UnboundedReceiver<MockVoiceConnection>,
+    handles: [JoinHandle<()>; 2],
+    packets: UnboundedReceiver<Vec<u8>>,
+    udp: Arc<UdpSocket>,
+    url: String,
+}
+
+impl MockVoiceServer {
+    /// Heartbeat interval sent by [`MockVoiceConnection::handshake`], in
+    /// milliseconds.
+    pub const HEARTBEAT_INTERVAL: f64 = 13_750.0;
+
+    /// Secret key sent by [`MockVoiceConnection::handshake`].
+    pub const SECRET_KEY: [u8; 32] = [7; 32];
+
+    /// Synchronization source sent by [`MockVoiceConnection::handshake`].
+    pub const SSRC: u32 = 42;
+
+    /// Bind a new mock voice server to free local ports.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockVoiceErrorType::Binding`] error type if binding to a
+    /// local port failed.
+    pub async fn bind() -> Result<Self, MockVoiceError> {
+        let binding = |source: std::io::Error| MockVoiceError {
+            kind: MockVoiceErrorType::Binding,
+            source: Some(Box::new(source)),
+        };
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .map_err(binding)?;
+        let url = format!("ws://{}", listener.local_addr().map_err(binding)?);
+
+        let udp = Arc::new(
+            UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))
+                .await
+                .map_err(binding)?,
+        );
+        let udp_address = udp.local_addr().map_err(binding)?;
+
+        let (tx, connections) = mpsc::unbounded_channel();
+
+        let accept = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let stream = match tokio_tungstenite::accept_async(stream).await {
+                    Ok(stream) => stream,
+                    Err(source) => {
+                        tracing::warn!("mock voice server handshake failed: {source}");
+
+                        continue;
+                    }
+                };
+
+                let connection = MockVoiceConnection {
+                    ack_heartbeats: true,
+                    stream,
+                    udp_address,
+                };
+
+                if tx.send(connection).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (client_tx, client) = watch::channel(None);
+        let (packets_tx, packets) = mpsc::unbounded_channel();
+        let socket = Arc::clone(&udp);
+
+        let receive = tokio::spawn(async move {
+            let mut buf = [0; 1500];
+
+            while let Ok((len, address)) = socket.recv_from(&mut buf).await {
+                if let Some(ssrc) = udp::parse_request(&buf[..len]) {
+                    client_tx.send_replace(Some(address));
+                    let _res = socket.send_to(&udp::response(ssrc, address), address).await;
+                } else if packets_tx.send(buf[..len].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            client,
+            connections,
+            handles: [accept, receive],
+            packets,
+            udp,
+            url,
+        })
+    }
+
+    /// URL of the mock voice server, to provide to
+    /// [`VoiceConnectionBuilder::url`].
+    ///
+    /// [`VoiceConnectionBuilder::url`]: crate::VoiceConnectionBuilder::url
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Wait for the next connection made to the mock voice server.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockVoiceErrorType::Closed`] error type if the server has
+    /// stopped accepting connections.
+    pub async fn next_connection(&mut self) -> Result<MockVoiceConnection, MockVoiceError> {
+        self.connections.recv().await.ok_or(MockVoiceError {
+            kind: MockVoiceErrorType::Closed,
+            source: None,
+        })
+    }
+
+    /// Wait for the next voice packet sent to the mock voice server.
+    ///
+    /// IP discovery requests are not included.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockVoiceErrorType::Closed`] error type if the server has
+    /// stopped receiving packets.
+    pub async fn next_packet(&mut self) -> Result<Vec<u8>, MockVoiceError> {
+        self.packets.recv().await.ok_or(MockVoiceError {
+            kind: MockVoiceErrorType::Closed,
+            source: None,
+        })
+    }
+
+    /// Send a voice packet to the last client that made an IP discovery
+    /// request.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockVoiceErrorType::Closed`] error type if no client has
+    /// made an IP discovery request.
+    ///
+    /// Returns a [`MockVoiceErrorType::Sending`] error type if sending the
+    /// packet failed.
+    pub async fn send_packet(&self, packet: &[u8]) -> Result<(), MockVoiceError> {
+        let address = self.client.borrow().ok_or(MockVoiceError {
+            kind: MockVoiceErrorType::Closed,
+            source: None,
+        })?;
+
+        self.udp
+            .send_to(packet, address)
+            .await
+            .map(drop)
+            .map_err(|source| MockVoiceError {
+                kind: MockVoiceErrorType::Sending,
+                source: Some(Box::new(source)),
+            })
+    }
+}
+
+impl Drop for MockVoiceServer {
+    fn drop(&mut self) {
+        for handle in &self.handles {
+            handle.abort();
+        }
+    }
+}
+
+/// Payload sent by a voice connection to the [`MockVoiceServer`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MockVoicePayload {
+    json: String,
+    op: u8,
+}
+
+impl MockVoicePayload {
+    /// JSON of the payload.
+    pub fn json(&self) -> &str {
+        &self.json
+    }
+
+    /// Opcode of the payload.
+    pub const fn op(&self) -> u8 {
+        self.op
+    }
+
+    /// Deserialize the data of the payload, its `d` field.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockVoiceErrorType::Deserializing`] error type if the data
+    /// could not be deserialized into the type.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, MockVoiceError> {
+        payload::data(&self.json).map_err(|source| MockVoiceError {
+            kind: MockVoiceErrorType::Deserializing,
+            source: Some(Box::new(source)),
+        })
+    }
+}
+
+/// Connection made to the [`MockVoiceServer`].
+///
+/// Heartbeats are acknowledged while [receiving] payloads. Refer to
+/// [`ack_heartbeats`] to disable this.
+///
+/// [`ack_heartbeats`]: Self::ack_heartbeats
+/// [receiving]: Self::receive
+#[derive(Debug)]
+pub struct MockVoiceConnection {
+    ack_heartbeats: bool,
+    stream: WebSocketStream<TcpStream>,
+    udp_address: SocketAddr,
+}
+
+impl MockVoiceConnection {
+    /// Set whether heartbeats are acknowledged while receiving payloads.
+    ///
+    /// Defaults to enabled.
+    pub fn ack_heartbeats(&mut self, ack_heartbeats: bool) {
+        self.ack_heartbeats = ack_heartbeats;
+    }
+
+    /// Complete the handshake of a new voice session, returning the identify
+    /// payload.
+    ///
+    /// Sends a hello, waits for the identify, sends a ready pointing to the
+    /// mock's UDP socket and offering every [encryption mode], waits for the
+    /// protocol selection, and then sends the session description with the
+    /// selected mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockVoiceErrorType::UnexpectedPayload`] error type if the
+    /// connection sent a payload other than those expected.
+    ///
+    /// [encryption mode]: EncryptionMode
+    pub async fn handshake(&mut self) -> Result<MockVoicePayload, MockVoiceError> {
+        self.hello().await?;
+        let identify = self.expect(OpCode::Identify).await?;
+
+        let ready = Ready {
+            ip: self.udp_address.ip().to_string(),
+            modes: EncryptionMode::PREFERENCE
+                .iter()
+                .map(|mode| mode.name().to_owned())
+                .collect(),
+            port: self.udp_address.port(),
+            ssrc: MockVoiceServer::SSRC,
+        };
+        self.send(OpCode::Ready, &ready).await?;
+
+        let select_protocol = self
+            .expect(OpCode::SelectProtocol)
+            .await?
+            .deserialize::<SelectProtocol>()?;
+
+        let description = SessionDescription {
+            mode: select_protocol.data.mode,
+            secret_key: MockVoiceServer::SECRET_KEY,
+        };
+        self.send(OpCode::SessionDescription, &description).await?;
+
+        Ok(identify)
+    }
+
+    /// Complete the handshake of a resumed voice session, returning the resume
+    /// payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockVoiceErrorType::UnexpectedPayload`] error type if the
+    /// connection sent a payload other than a resume.
+    pub async fn resume_handshake(&mut self) -> Result<MockVoicePayload, MockVoiceError> {
+        self.hello().await?;
+        let resume = self.expect(OpCode::Resume).await?;
+        self.send(OpCode::Resumed, ()).await?;
+
+        Ok(resume)
+    }
+
+    /// Receive the next payload, acknowledging heartbeats if enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockVoiceErrorType::Closed`] error type if the connection
+    /// has been closed.
+    pub async fn receive(&mut self) -> Result<MockVoicePayload, MockVoiceError> {
+        loop {
+            let message = self
+                .stream
+                .next()
+                .await
+                .ok_or(MockVoiceError {
+                    kind: MockVoiceErrorType::Closed,
+                    source: None,
+                })?
+                .map_err(|source| MockVoiceError {
+                    kind: MockVoiceErrorType::Receiving,
+                    source: Some(Box::new(source)),
+                })?;
+
+            let json = match message {
+                Message::Text(json) => json,
+                Message::Close(_) => {
+                    return Err(MockVoiceError {
+                        kind: MockVoiceErrorType::Closed,
+                        source: None,
+                    })
+                }
+                _ => continue,
+            };
+
+            let op = serde_json::from_str::<MinimalPayload>(&json)
+                .map_err(|source| MockVoiceError {
+                    kind: MockVoiceErrorType::Deserializing,
+                    source: Some(Box::new(source)),
+                })?
+                .op;
+            let payload = MockVoicePayload { json, op };
+
+            if op == OpCode::Heartbeat as u8 && self.ack_heartbeats {
+                let nonce = payload.deserialize::<u64>()?;
+                self.send(OpCode::HeartbeatAck, HeartbeatAck(nonce)).await?;
+
+                continue;
+            }
+
+            return Ok(payload);
+        }
+    }
+
+    /// Send a payload with an opcode and data.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockVoiceErrorType::Sending`] error type if sending the
+    /// payload failed.
+    pub async fn send(&mut self, op: OpCode, data: impl Serialize) -> Result<(), MockVoiceError> {
+        let json = payload::to_string(op, data).map_err(|source| MockVoiceError {
+            kind: MockVoiceErrorType::Serializing,
+            source: Some(Box::new(source)),
+        })?;
+
+        self.stream
+            .send(Message::Text(json))
+            .await
+            .map_err(|source| MockVoiceError {
+                kind: MockVoiceErrorType::Sending,
+                source: Some(Box::new(source)),
+            })
+    }
+
+    /// Close the connection with a close code.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MockVoiceErrorType::Sending`] error type if sending the
+    /// close frame failed.
+    pub async fn close(&mut self, code: u16) -> Result<(), MockVoiceError> {
+        let frame = CloseFrame {
+            code: CloseCode::from(code),
+            reason: "".into(),
+        };
+
+        self.stream
+            .close(Some(frame))
+            .await
+            .map_err(|source| MockVoiceError {
+                kind: MockVoiceErrorType::Sending,
+                source: Some(Box::new(source)),
+            })
+    }
+
+    /// Send a hello.
+    async fn hello(&mut self) -> Result<(), MockVoiceError> {
+        let hello = Hello {
+            heartbeat_interval: MockVoiceServer::HEARTBEAT_INTERVAL,
+        };
+
+        self.send(OpCode::Hello, hello).await
+    }
+
+    /// Receive the next payload, failing if it isn't of an opcode.
+    async fn expect(&mut self, op: OpCode) -> Result<MockVoicePayload, MockVoiceError> {
+        let payload = self.receive().await?;
+
+        if payload.op != op as u8 {
+            return Err(MockVoiceError {
+                kind: MockVoiceErrorType::UnexpectedPayload { op: payload.op },
+                source: None,
+            });
+        }
+
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MockVoiceConnection, MockVoiceError, MockVoicePayload, MockVoiceServer};
+    use static_assertions::assert_impl_all;
+    use std::{error::Error, fmt::Debug};
+
+    assert_impl_all!(MockVoiceConnection: Debug, Send, Sync);
+    assert_impl_all!(MockVoiceError: Error, Send, Sync);
+    assert_impl_all!(MockVoicePayload: Clone, Debug, Eq, PartialEq, Send, Sync);
+    assert_impl_all!(MockVoiceServer: Debug, Send, Sync);
+}
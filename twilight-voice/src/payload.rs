@@ -0,0 +1,233 @@
+//! Payloads sent and received over the voice gateway.
+//!
+//! Each payload is sent as the data of a JSON object along with its
+//! [`OpCode`], of the form `{"op": 0, "d": {...}}`.
+
+use bitflags::bitflags;
+use serde::{
+    de::{Deserializer, Error as DeError},
+    Deserialize, Serialize, Serializer,
+};
+use twilight_model::{
+    id::{
+        marker::{GuildMarker, UserMarker},
+        Id,
+    },
+    voice::OpCode,
+};
+
+/// Protocol used to send voice data.
+pub const PROTOCOL: &str = "udp";
+
+bitflags! {
+    /// Ways in which a user is speaking.
+    pub struct SpeakingFlags: u8 {
+        /// Transmitting voice audio.
+        const MICROPHONE = 1;
+        /// Transmitting context audio for video, without a speaking indicator.
+        const SOUNDSHARE = 1 << 1;
+        /// Priority speaker, lowering the audio of other speakers.
+        const PRIORITY = 1 << 2;
+    }
+}
+
+impl<'de> Deserialize<'de> for SpeakingFlags {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bits = u8::deserialize(deserializer)?;
+
+        Self::from_bits(bits)
+            .ok_or_else(|| DeError::custom(format!("unknown speaking flags {bits}")))
+    }
+}
+
+impl Serialize for SpeakingFlags {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.bits())
+    }
+}
+
+/// Another user disconnected from the voice channel.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ClientDisconnect {
+    /// ID of the user.
+    pub user_id: Id<UserMarker>,
+}
+
+/// Keep the connection alive.
+///
+/// Contains a nonce that the voice server echoes in its [`HeartbeatAck`].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Heartbeat(pub u64);
+
+/// Acknowledgement of a [`Heartbeat`], echoing its nonce.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct HeartbeatAck(pub u64);
+
+/// First payload received after connecting.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Hello {
+    /// Interval between heartbeats, in milliseconds.
+    pub heartbeat_interval: f64,
+}
+
+/// Start a new voice session.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Identify {
+    /// ID of the guild.
+    pub server_id: Id<GuildMarker>,
+    /// ID of the user's gateway session.
+    pub session_id: String,
+    /// Token of the voice server.
+    pub token: String,
+    /// ID of the user.
+    pub user_id: Id<UserMarker>,
+}
+
+/// Voice session has been started.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Ready {
+    /// IP address of the UDP voice server.
+    pub ip: String,
+    /// Encryption modes supported by the voice server.
+    pub modes: Vec<String>,
+    /// Port of the UDP voice server.
+    pub port: u16,
+    /// Synchronization source identifying the user's voice data.
+    pub ssrc: u32,
+}
+
+/// Resume a disconnected voice session.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Resume {
+    /// ID of the guild.
+    pub server_id: Id<GuildMarker>,
+    /// ID of the user's gateway session.
+    pub session_id: String,
+    /// Token of the voice server.
+    pub token: String,
+}
+
+/// Select the protocol and encryption mode to send voice data with.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct SelectProtocol {
+    /// Address and encryption mode.
+    pub data: SelectProtocolData,
+    /// Protocol, which is always [`PROTOCOL`].
+    pub protocol: String,
+}
+
+/// Address and encryption mode of a [`SelectProtocol`] payload.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct SelectProtocolData {
+    /// External IP address of the client, found through IP discovery.
+    pub address: String,
+    /// Encryption mode.
+    pub mode: String,
+    /// External port of the client, found through IP discovery.
+    pub port: u16,
+}
+
+/// Encryption details of the voice session.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct SessionDescription {
+    /// Selected encryption mode.
+    pub mode: String,
+    /// Key to encrypt voice data with.
+    pub secret_key: [u8; 32],
+}
+
+/// Speaking state of a user.
+///
+/// Sent to indicate the client's speaking state before sending voice data,
+/// and received when other users start speaking.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Speaking {
+    /// Delay, which is always 0 for bots.
+    #[serde(default)]
+    pub delay: u32,
+    /// Ways in which the user is speaking.
+    pub speaking: SpeakingFlags,
+    /// Synchronization source of the user's voice data.
+    pub ssrc: u32,
+    /// ID of the user, only present when received.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<Id<UserMarker>>,
+}
+
+/// Payload sent to the voice gateway.
+#[derive(Serialize)]
+pub(crate) struct OutgoingPayload<T> {
+    pub d: T,
+    pub op: OpCode,
+}
+
+/// Opcode of a received payload, which may not be known.
+#[derive(Deserialize)]
+pub(crate) struct MinimalPayload {
+    pub op: u8,
+}
+
+/// Data of a received payload.
+#[derive(Deserialize)]
+pub(crate) struct PayloadData<T> {
+    pub d: T,
+}
+
+/// Serialize a payload with its opcode.
+pub(crate) fn to_string<T: Serialize>(op: OpCode, d: T) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&OutgoingPayload { d, op })
+}
+
+/// Deserialize the data of a received payload.
+pub(crate) fn data<T: for<'de> Deserialize<'de>>(json: &str) -> Result<T, serde_json::Error> {
+    serde_json::from_str::<PayloadData<T>>(json).map(|payload| payload.d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ready, SessionDescription, Speaking, SpeakingFlags};
+    use static_assertions::assert_impl_all;
+    use std::{fmt::Debug, hash::Hash};
+    use twilight_model::{id::Id, voice::OpCode};
+
+    assert_impl_all!(SpeakingFlags: Copy, Debug, Eq, Hash, Send, Sync);
+    assert_impl_all!(Speaking: Clone, Debug, Eq, Hash, PartialEq, Send, Sync);
+
+    #[test]
+    fn speaking() {
+        let speaking = Speaking {
+            delay: 0,
+            speaking: SpeakingFlags::MICROPHONE | SpeakingFlags::PRIORITY,
+            ssrc: 1,
+            user_id: None,
+        };
+
+        assert_eq!(
+            r#"{"d":{"delay":0,"speaking":5,"ssrc":1},"op":5}"#,
+            super::to_string(OpCode::Speaking, &speaking).unwrap()
+        );
+
+        let json = r#"{"op":5,"d":{"speaking":1,"ssrc":2,"user_id":"3"}}"#;
+        let speaking = super::data::<Speaking>(json).unwrap();
+        assert_eq!(SpeakingFlags::MICROPHONE, speaking.speaking);
+        assert_eq!(Some(Id::new(3)), speaking.user_id);
+    }
+
+    #[test]
+    fn ready_and_session_description() {
+        let json = r#"{"op":2,"d":{"ssrc":1,"ip":"127.0.0.1","port":1234,"modes":["xsalsa20_poly1305"],"heartbeat_interval":1}}"#;
+        let ready = super::data::<Ready>(json).unwrap();
+        assert_eq!(1234, ready.port);
+        assert_eq!(vec!["xsalsa20_poly1305".to_owned()], ready.modes);
+
+        let key = (0..32).map(|byte| byte.to_string()).collect::<Vec<_>>();
+        let json = format!(
+            r#"{{"op":4,"d":{{"mode":"xsalsa20_poly1305","secret_key":[{}]}}}}"#,
+            key.join(",")
+        );
+        let description = super::data::<SessionDescription>(&json).unwrap();
+        assert_eq!(31, description.secret_key[31]);
+    }
+}
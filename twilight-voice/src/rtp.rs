@@ -0,0 +1,581 @@
+//! Sending and receiving Opus voice data framed in encrypted RTP packets.
+//!
+//! Each packet carries one Opus frame of 20 milliseconds of 48 kHz audio.
+//! [`VoiceSender`] frames and encrypts Opus packets, keeping track of the
+//! sequence numbers and timestamps of the sent packets. [`VoiceReceiver`]
+//! decrypts the packets of other users and demultiplexes them by their SSRC,
+//! which is mapped to a user ID from the speaking events of the connection.
+//!
+//! Opus frames must be encoded and paced by the caller: the sender sends a
+//! packet as soon as it is given one.
+
+use crate::{
+    crypto::{EncryptionMode, PacketCipher},
+    event::VoiceEvent,
+};
+use crypto_secretbox::aead::{rand_core::RngCore, OsRng};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    sync::Arc,
+};
+use tokio::net::UdpSocket;
+use twilight_model::id::{marker::UserMarker, Id};
+
+/// Length of a fixed RTP header.
+pub const HEADER_LEN: usize = 12;
+
+/// Opus frame of silence, sent when stopping to speak to avoid interpolation.
+pub const SILENCE_FRAME: [u8; 3] = [0xF8, 0xFF, 0xFE];
+
+/// Number of silence frames sent when stopping to speak.
+const SILENCE_FRAMES: usize = 5;
+
+/// Maximum length of a received packet.
+const MAX_PACKET_LEN: usize = 1460;
+
+/// First byte of an RTP header: version 2 without padding, extension, or
+/// contributing sources.
+const VERSION: u8 = 0x80;
+
+/// Mask of the version bits of an RTP header's first byte.
+const VERSION_MASK: u8 = 0xC0;
+
+/// Bit of an RTP header's first byte set if a header extension is present.
+const EXTENSION: u8 = 0x10;
+
+/// Mask of the contributing source count bits of an RTP header's first byte.
+const CSRC_COUNT_MASK: u8 = 0x0F;
+
+/// Payload type of Opus.
+const PAYLOAD_TYPE: u8 = 0x78;
+
+/// Sending or receiving a voice packet failed.
+#[derive(Debug)]
+pub struct VoicePacketError {
+    kind: VoicePacketErrorType,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl VoicePacketError {
+    /// Immutable reference to the type of error that occurred.
+    #[must_use = "retrieving the type has no effect if left unused"]
+    pub const fn kind(&self) -> &VoicePacketErrorType {
+        &self.kind
+    }
+
+    /// Consume the error, returning the source error if there is any.
+    #[must_use = "consuming the error and retrieving the source has no effect if left unused"]
+    pub fn into_source(self) -> Option<Box<dyn Error + Send + Sync>> {
+        self.source
+    }
+
+    /// Consume the error, returning the owned error type and the source error.
+    #[must_use = "consuming the error into its parts has no effect if left unused"]
+    pub fn into_parts(self) -> (VoicePacketErrorType, Option<Box<dyn Error + Send + Sync>>) {
+        (self.kind, self.source)
+    }
+
+    /// Create a new error of a type without a source.
+    pub(crate) const fn new(kind: VoicePacketErrorType) -> Self {
+        Self { kind, source: None }
+    }
+}
+
+impl Display for VoicePacketError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match &self.kind {
+            VoicePacketErrorType::Decrypting => f.write_str("failed to decrypt packet"),
+            VoicePacketErrorType::Encrypting => f.write_str("failed to encrypt packet"),
+            VoicePacketErrorType::Malformed => f.write_str("packet is malformed"),
+            VoicePacketErrorType::Receiving => f.write_str("failed to receive packet"),
+            VoicePacketErrorType::Sending => f.write_str("failed to send packet"),
+        }
+    }
+}
+
+impl Error for VoicePacketError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+/// Type of [`VoicePacketError`] that occurred.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum VoicePacketErrorType {
+    /// Packet could not be decrypted with the session's secret key.
+    Decrypting,
+    /// Encrypting a packet failed.
+    Encrypting,
+    /// Packet is not a valid RTP packet.
+    Malformed,
+    /// Receiving a packet from the UDP socket failed.
+    Receiving,
+    /// Sending a packet over the UDP socket failed.
+    Sending,
+}
+
+/// Fixed header of an RTP packet.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct RtpHeader {
+    /// Sequence number, incremented with every packet.
+    pub sequence: u16,
+    /// Synchronization source identifying the sender.
+    pub ssrc: u32,
+    /// Timestamp, incremented by the number of samples of every packet.
+    pub timestamp: u32,
+}
+
+impl RtpHeader {
+    /// Encode the header of an Opus packet.
+    pub fn encode(self) -> [u8; HEADER_LEN] {
+        let mut header = [0; HEADER_LEN];
+        header[0] = VERSION;
+        header[1] = PAYLOAD_TYPE;
+        header[2..4].copy_from_slice(&self.sequence.to_be_bytes());
+        header[4..8].copy_from_slice(&self.timestamp.to_be_bytes());
+        header[8..12].copy_from_slice(&self.ssrc.to_be_bytes());
+
+        header
+    }
+
+    /// Parse the header of a packet, returning it along with the length of
+    /// the header including its contributing sources.
+    ///
+    /// The header extension, if any, is part of the encrypted payload.
+    pub fn parse(packet: &[u8]) -> Option<(Self, usize)> {
+        if packet.len() < HEADER_LEN || packet[0] & VERSION_MASK != VERSION {
+            return None;
+        }
+
+        let len = HEADER_LEN + 4 * usize::from(packet[0] & CSRC_COUNT_MASK);
+
+        if packet.len() < len {
+            return None;
+        }
+
+        let header = Self {
+            sequence: u16::from_be_bytes([packet[2], packet[3]]),
+            ssrc: u32::from_be_bytes(packet[8..12].try_into().ok()?),
+            timestamp: u32::from_be_bytes(packet[4..8].try_into().ok()?),
+        };
+
+        Some((header, len))
+    }
+}
+
+/// Sender of Opus frames as encrypted RTP packets.
+///
+/// Created by [`VoiceConnection::sender`]. Only one sender should be used per
+/// connection, as each keeps track of its own sequence numbers.
+///
+/// [`VoiceConnection::sender`]: crate::VoiceConnection::sender
+#[derive(Debug)]
+pub struct VoiceSender {
+    /// Cipher encrypting the packets.
+    cipher: PacketCipher,
+    /// Sequence number of the next packet.
+    sequence: u16,
+    /// UDP socket connected to the voice server.
+    socket: Arc<UdpSocket>,
+    /// Synchronization source identifying the user's voice data.
+    ssrc: u32,
+    /// Timestamp of the next packet.
+    timestamp: u32,
+}
+
+impl VoiceSender {
+    /// Number of samples per channel in a 20 millisecond frame of 48 kHz
+    /// audio.
+    pub const SAMPLES_PER_FRAME: u32 = 960;
+
+    /// Create a new sender over a UDP socket connected to the voice server.
+    ///
+    /// The sequence number and timestamp start at random values.
+    pub fn new(
+        socket: Arc<UdpSocket>,
+        ssrc: u32,
+        mode: EncryptionMode,
+        secret_key: &[u8; 32],
+    ) -> Self {
+        let mut start = [0; 6];
+        OsRng.fill_bytes(&mut start);
+
+        Self {
+            cipher: PacketCipher::new(mode, secret_key),
+            sequence: u16::from_be_bytes([start[0], start[1]]),
+            socket,
+            ssrc,
+            timestamp: u32::from_be_bytes([start[2], start[3], start[4], start[5]]),
+        }
+    }
+
+    /// Sequence number of the next packet.
+    pub const fn sequence(&self) -> u16 {
+        self.sequence
+    }
+
+    /// Timestamp of the next packet.
+    pub const fn timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    /// Frame and encrypt an Opus frame of 20 milliseconds into a packet,
+    /// advancing the sequence number and timestamp.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of type [`Encrypting`] if encrypting the packet
+    /// failed.
+    ///
+    /// [`Encrypting`]: VoicePacketErrorType::Encrypting
+    pub fn packet(&mut self, opus: &[u8]) -> Result<Vec<u8>, VoicePacketError> {
+        let header = RtpHeader {
+            sequence: self.sequence,
+            ssrc: self.ssrc,
+            timestamp: self.timestamp,
+        };
+        let packet = self.cipher.encrypt(&header.encode(), opus)?;
+
+        self.sequence = self.sequence.wrapping_add(1);
+        self.timestamp = self.timestamp.wrapping_add(Self::SAMPLES_PER_FRAME);
+
+        Ok(packet)
+    }
+
+    /// Send an Opus frame of 20 milliseconds.
+    ///
+    /// The user must be [speaking] for the voice server to forward the
+    /// packet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of type [`Encrypting`] if encrypting the packet
+    /// failed.
+    ///
+    /// Returns an error of type [`Sending`] if sending the packet failed.
+    ///
+    /// [`Encrypting`]: VoicePacketErrorType::Encrypting
+    /// [`Sending`]: VoicePacketErrorType::Sending
+    /// [speaking]: crate::VoiceConnection::speaking
+    pub async fn send(&mut self, opus: &[u8]) -> Result<(), VoicePacketError> {
+        let packet = self.packet(opus)?;
+
+        self.socket
+            .send(&packet)
+            .await
+            .map_err(|source| VoicePacketError {
+                kind: VoicePacketErrorType::Sending,
+                source: Some(Box::new(source)),
+            })?;
+
+        Ok(())
+    }
+
+    /// Send the frames of silence to send when stopping to speak.
+    ///
+    /// # Errors
+    ///
+    /// Refer to [`send`].
+    ///
+    /// [`send`]: Self::send
+    pub async fn send_silence(&mut self) -> Result<(), VoicePacketError> {
+        for _ in 0..SILENCE_FRAMES {
+            self.send(&SILENCE_FRAME).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Opus frame received from another user.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct VoicePacket {
+    /// Decrypted Opus frame.
+    pub opus: Vec<u8>,
+    /// Sequence number of the packet.
+    pub sequence: u16,
+    /// Synchronization source of the sender.
+    pub ssrc: u32,
+    /// Timestamp of the packet.
+    pub timestamp: u32,
+    /// ID of the sender, if it is known from a speaking event.
+    pub user_id: Option<Id<UserMarker>>,
+}
+
+/// State of the stream of packets of one synchronization source.
+#[derive(Debug, Default)]
+struct Stream {
+    /// Sequence number of the latest packet.
+    sequence: Option<u16>,
+    /// ID of the user sending the stream, if known.
+    user_id: Option<Id<UserMarker>>,
+}
+
+/// Receiver of the encrypted RTP packets of other users.
+///
+/// Created by [`VoiceConnection::receiver`]. Give it the connection's
+/// [events] via [`process`] to map synchronization sources to users.
+///
+/// [`VoiceConnection::receiver`]: crate::VoiceConnection::receiver
+/// [`process`]: Self::process
+/// [events]: crate::VoiceEvents
+#[derive(Debug)]
+pub struct VoiceReceiver {
+    /// Cipher decrypting the packets.
+    cipher: PacketCipher,
+    /// UDP socket connected to the voice server.
+    socket: Arc<UdpSocket>,
+    /// Streams by synchronization source.
+    streams: HashMap<u32, Stream>,
+}
+
+impl VoiceReceiver {
+    /// Create a new receiver over a UDP socket connected to the voice server.
+    pub fn new(socket: Arc<UdpSocket>, mode: EncryptionMode, secret_key: &[u8; 32]) -> Self {
+        Self {
+            cipher: PacketCipher::new(mode, secret_key),
+            socket,
+            streams: HashMap::new(),
+        }
+    }
+
+    /// Process an event of the connection, mapping synchronization sources to
+    /// users as they start speaking and forgetting them as they disconnect.
+    pub fn process(&mut self, event: &VoiceEvent) {
+        match event {
+            VoiceEvent::Speaking(speaking) => {
+                if let Some(user_id) = speaking.user_id {
+                    self.streams.entry(speaking.ssrc).or_default().user_id = Some(user_id);
+                }
+            }
+            VoiceEvent::ClientDisconnect(disconnect) => {
+                self.streams
+                    .retain(|_, stream| stream.user_id != Some(disconnect.user_id));
+            }
+            _ => {}
+        }
+    }
+
+    /// ID of the user sending a synchronization source, if known.
+    pub fn user_id(&self, ssrc: u32) -> Option<Id<UserMarker>> {
+        self.streams.get(&ssrc).and_then(|stream| stream.user_id)
+    }
+
+    /// Decrypt a received packet.
+    ///
+    /// Returns `None` if the packet isn't an Opus packet, such as RTCP
+    /// packets, or if a newer packet of its synchronization source has
+    /// already been received.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of type [`Malformed`] if the packet isn't a valid RTP
+    /// packet.
+    ///
+    /// Returns an error of type [`Decrypting`] if the packet could not be
+    /// decrypted.
+    ///
+    /// [`Decrypting`]: VoicePacketErrorType::Decrypting
+    /// [`Malformed`]: VoicePacketErrorType::Malformed
+    pub fn packet(&mut self, packet: &[u8]) -> Result<Option<VoicePacket>, VoicePacketError> {
+        let (header, header_len) = RtpHeader::parse(packet)
+            .ok_or_else(|| VoicePacketError::new(VoicePacketErrorType::Malformed))?;
+
+        if packet[1] & 0x7F != PAYLOAD_TYPE {
+            return Ok(None);
+        }
+
+        let stream = self.streams.entry(header.ssrc).or_default();
+
+        // Sequence numbers wrap around, so packets are stale if they are
+        // behind the latest one by less than half of the range.
+        #[allow(clippy::cast_possible_wrap)]
+        if let Some(latest) = stream.sequence {
+            if header.sequence.wrapping_sub(latest) as i16 <= 0 {
+                return Ok(None);
+            }
+        }
+
+        let mut opus = self.cipher.decrypt(header_len, packet)?;
+
+        if packet[0] & EXTENSION != 0 {
+            let len = opus
+                .get(2..4)
+                .map(|len| 4 + 4 * usize::from(u16::from_be_bytes([len[0], len[1]])))
+                .filter(|len| *len <= opus.len())
+                .ok_or_else(|| VoicePacketError::new(VoicePacketErrorType::Malformed))?;
+
+            opus.drain(..len);
+        }
+
+        stream.sequence = Some(header.sequence);
+
+        Ok(Some(VoicePacket {
+            opus,
+            sequence: header.sequence,
+            ssrc: header.ssrc,
+            timestamp: header.timestamp,
+            user_id: stream.user_id,
+        }))
+    }
+
+    /// Receive the next Opus packet of another user.
+    ///
+    /// Packets that aren't Opus packets, are stale, or can't be decrypted are
+    /// skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of type [`Receiving`] if receiving from the UDP
+    /// socket failed.
+    ///
+    /// [`Receiving`]: VoicePacketErrorType::Receiving
+    pub async fn recv(&mut self) -> Result<VoicePacket, VoicePacketError> {
+        let mut buf = [0; MAX_PACKET_LEN];
+
+        loop {
+            let len = self
+                .socket
+                .recv(&mut buf)
+                .await
+                .map_err(|source| VoicePacketError {
+                    kind: VoicePacketErrorType::Receiving,
+                    source: Some(Box::new(source)),
+                })?;
+
+            match self.packet(&buf[..len]) {
+                Ok(Some(packet)) => return Ok(packet),
+                Ok(None) => {}
+                Err(source) => tracing::debug!("skipping voice packet: {source}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RtpHeader, VoicePacket, VoicePacketError, VoiceReceiver, VoiceSender, HEADER_LEN};
+    use crate::{
+        crypto::{EncryptionMode, PacketCipher},
+        event::VoiceEvent,
+        payload::{ClientDisconnect, Speaking, SpeakingFlags},
+    };
+    use static_assertions::assert_impl_all;
+    use std::{error::Error, fmt::Debug, hash::Hash, net::Ipv4Addr, sync::Arc};
+    use tokio::net::UdpSocket;
+    use twilight_model::id::Id;
+
+    assert_impl_all!(RtpHeader: Clone, Copy, Debug, Eq, Hash, PartialEq, Send, Sync);
+    assert_impl_all!(VoicePacket: Clone, Debug, Eq, PartialEq, Send, Sync);
+    assert_impl_all!(VoicePacketError: Error, Send, Sync);
+    assert_impl_all!(VoiceReceiver: Debug, Send, Sync);
+    assert_impl_all!(VoiceSender: Debug, Send, Sync);
+
+    const KEY: [u8; 32] = [3; 32];
+
+    async fn socket() -> Arc<UdpSocket> {
+        Arc::new(UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap())
+    }
+
+    #[test]
+    fn header() {
+        let header = RtpHeader {
+            sequence: 1,
+            ssrc: 2,
+            timestamp: 3,
+        };
+        let encoded = header.encode();
+        assert_eq!([0x80, 0x78, 0, 1, 0, 0, 0, 3, 0, 0, 0, 2], encoded);
+        assert_eq!(Some((header, HEADER_LEN)), RtpHeader::parse(&encoded));
+
+        // Contributing sources extend the header.
+        let mut packet = encoded.to_vec();
+        packet[0] |= 1;
+        assert_eq!(None, RtpHeader::parse(&packet));
+        packet.extend([0; 4]);
+        assert_eq!(Some((header, HEADER_LEN + 4)), RtpHeader::parse(&packet));
+    }
+
+    #[tokio::test]
+    async fn sender_advances() {
+        let mut sender = VoiceSender::new(socket().await, 42, EncryptionMode::Lite, &KEY);
+        let (sequence, timestamp) = (sender.sequence(), sender.timestamp());
+
+        let packet = sender.packet(b"opus").unwrap();
+        let (header, _) = RtpHeader::parse(&packet).unwrap();
+        assert_eq!(sequence, header.sequence);
+        assert_eq!(timestamp, header.timestamp);
+        assert_eq!(42, header.ssrc);
+
+        assert_eq!(sequence.wrapping_add(1), sender.sequence());
+        assert_eq!(
+            timestamp.wrapping_add(VoiceSender::SAMPLES_PER_FRAME),
+            sender.timestamp()
+        );
+    }
+
+    #[tokio::test]
+    async fn receiver_demultiplexes() {
+        let mut receiver = VoiceReceiver::new(socket().await, EncryptionMode::Suffix, &KEY);
+        let mut cipher = PacketCipher::new(EncryptionMode::Suffix, &KEY);
+        let mut packet = |ssrc, sequence| {
+            let header = RtpHeader {
+                sequence,
+                ssrc,
+                timestamp: 0,
+            };
+
+            cipher.encrypt(&header.encode(), b"opus").unwrap()
+        };
+
+        receiver.process(&VoiceEvent::Speaking(Speaking {
+            delay: 0,
+            speaking: SpeakingFlags::MICROPHONE,
+            ssrc: 7,
+            user_id: Some(Id::new(1)),
+        }));
+
+        let opus_packet = receiver.packet(&packet(7, u16::MAX)).unwrap().unwrap();
+        assert_eq!(b"opus".to_vec(), opus_packet.opus);
+        assert_eq!(Some(Id::new(1)), opus_packet.user_id);
+
+        // Sequence numbers are tracked per source and wrap around.
+        assert!(receiver.packet(&packet(8, 5)).unwrap().is_some());
+        assert!(receiver.packet(&packet(7, 0)).unwrap().is_some());
+        assert!(receiver.packet(&packet(7, u16::MAX)).unwrap().is_none());
+        assert_eq!(None, receiver.user_id(8));
+
+        receiver.process(&VoiceEvent::ClientDisconnect(ClientDisconnect {
+            user_id: Id::new(1),
+        }));
+        assert_eq!(None, receiver.user_id(7));
+    }
+
+    #[tokio::test]
+    async fn receiver_skips_extension_and_rtcp() {
+        let mut receiver = VoiceReceiver::new(socket().await, EncryptionMode::Normal, &KEY);
+        let mut cipher = PacketCipher::new(EncryptionMode::Normal, &KEY);
+
+        let mut header = RtpHeader {
+            sequence: 1,
+            ssrc: 7,
+            timestamp: 0,
+        }
+        .encode();
+        header[0] |= 0x10;
+        let payload = [0xBE, 0xDE, 0, 1, 1, 2, 3, 4, b'o', b'p', b'u', b's'];
+        let packet = cipher.encrypt(&header, &payload).unwrap();
+        let opus_packet = receiver.packet(&packet).unwrap().unwrap();
+        assert_eq!(b"opus".to_vec(), opus_packet.opus);
+
+        let mut rtcp = packet;
+        rtcp[1] = 200;
+        assert!(receiver.packet(&rtcp).unwrap().is_none());
+    }
+}
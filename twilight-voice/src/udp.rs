@@ -0,0 +1,98 @@
+//! IP discovery over the UDP voice connection.
+//!
+//! The client sends a request containing its SSRC to the voice server, which
+//! responds with the external address and port the request came from. These
+//! are then given to the voice gateway to receive voice data on.
+
+use std::net::{IpAddr, SocketAddr};
+
+/// Length of IP discovery packets.
+pub const DISCOVERY_LEN: usize = 74;
+
+/// Type of IP discovery requests.
+const REQUEST: u16 = 1;
+
+/// Type of IP discovery responses.
+const RESPONSE: u16 = 2;
+
+/// Length of the fields following the type and length of a packet.
+#[allow(clippy::cast_possible_truncation)]
+const BODY_LEN: u16 = DISCOVERY_LEN as u16 - 4;
+
+/// Length of the null-terminated address of a packet.
+const ADDRESS_LEN: usize = 64;
+
+/// Create an IP discovery packet of a type.
+fn packet(kind: u16, ssrc: u32, address: &str, port: u16) -> [u8; DISCOVERY_LEN] {
+    let mut packet = [0; DISCOVERY_LEN];
+    packet[..2].copy_from_slice(&kind.to_be_bytes());
+    packet[2..4].copy_from_slice(&BODY_LEN.to_be_bytes());
+    packet[4..8].copy_from_slice(&ssrc.to_be_bytes());
+
+    // The address is null-terminated, so one byte must remain zeroed.
+    let address = address.as_bytes();
+    let len = address.len().min(ADDRESS_LEN - 1);
+    packet[8..8 + len].copy_from_slice(&address[..len]);
+
+    packet[8 + ADDRESS_LEN..].copy_from_slice(&port.to_be_bytes());
+
+    packet
+}
+
+/// Create an IP discovery request.
+pub fn request(ssrc: u32) -> [u8; DISCOVERY_LEN] {
+    packet(REQUEST, ssrc, "", 0)
+}
+
+/// Create an IP discovery response containing the address a request came
+/// from.
+#[cfg(any(test, feature = "test-support"))]
+pub fn response(ssrc: u32, address: SocketAddr) -> [u8; DISCOVERY_LEN] {
+    packet(RESPONSE, ssrc, &address.ip().to_string(), address.port())
+}
+
+/// Whether a packet is an IP discovery request, returning its SSRC.
+#[cfg(any(test, feature = "test-support"))]
+pub fn parse_request(packet: &[u8]) -> Option<u32> {
+    if packet.len() != DISCOVERY_LEN || packet[..2] != REQUEST.to_be_bytes() {
+        return None;
+    }
+
+    Some(u32::from_be_bytes(packet[4..8].try_into().ok()?))
+}
+
+/// Parse the external address of an IP discovery response.
+pub fn parse_response(packet: &[u8]) -> Option<SocketAddr> {
+    if packet.len() != DISCOVERY_LEN || packet[..2] != RESPONSE.to_be_bytes() {
+        return None;
+    }
+
+    let address = &packet[8..8 + ADDRESS_LEN];
+    let end = address.iter().position(|byte| *byte == 0)?;
+    let ip = std::str::from_utf8(&address[..end])
+        .ok()?
+        .parse::<IpAddr>()
+        .ok()?;
+    let port = u16::from_be_bytes(packet[8 + ADDRESS_LEN..].try_into().ok()?);
+
+    Some(SocketAddr::new(ip, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    #[test]
+    fn discovery() {
+        let request = super::request(7);
+        assert_eq!(Some(7), super::parse_request(&request));
+        assert_eq!(None, super::parse_response(&request));
+
+        let address = SocketAddr::from((Ipv4Addr::new(203, 0, 113, 9), 50_000));
+        let response = super::response(7, address);
+        assert_eq!([0, 2, 0, 70, 0, 0, 0, 7], response[..8]);
+        assert_eq!(Some(address), super::parse_response(&response));
+        assert_eq!(None, super::parse_request(&response));
+        assert_eq!(None, super::parse_response(&response[..10]));
+    }
+}
@@ -0,0 +1,226 @@
+#![cfg(feature = "test-support")]
+
+use futures_util::{future, StreamExt};
+use std::{error::Error, time::Duration};
+use tokio::time;
+use twilight_model::{
+    id::Id,
+    voice::{CloseCode, OpCode},
+};
+use twilight_voice::{
+    crypto::PacketCipher,
+    mock::{MockVoiceConnection, MockVoiceServer},
+    payload::{ClientDisconnect, Hello, Identify, Resume, Speaking, SpeakingFlags},
+    rtp::{RtpHeader, HEADER_LEN},
+    ConnectionInfo, EncryptionMode, VoiceConnection, VoiceEvent, VoiceEvents,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn info() -> ConnectionInfo {
+    ConnectionInfo {
+        endpoint: "localhost".to_owned(),
+        guild_id: Id::new(1),
+        session_id: "session".to_owned(),
+        token: "token".to_owned(),
+        user_id: Id::new(2),
+    }
+}
+
+/// Connect to the mock voice server, completing the handshake.
+async fn connect(
+    server: &mut MockVoiceServer,
+) -> Result<(VoiceConnection, VoiceEvents, MockVoiceConnection), Box<dyn Error>> {
+    let connect = VoiceConnection::builder(info())
+        .url(server.url().to_owned())
+        .connect();
+    let handshake = async {
+        let mut connection = server.next_connection().await?;
+        let identify = connection.handshake().await?;
+
+        Ok::<_, Box<dyn Error>>((connection, identify))
+    };
+
+    let (connected, handshake) = time::timeout(TIMEOUT, future::join(connect, handshake)).await?;
+    let (voice, events) = connected?;
+    let (connection, identify) = handshake?;
+
+    let identify = identify.deserialize::<Identify>()?;
+    assert_eq!(Id::new(1), identify.server_id);
+    assert_eq!("session", identify.session_id);
+    assert_eq!(Id::new(2), identify.user_id);
+
+    Ok((voice, events, connection))
+}
+
+async fn next_event(events: &mut VoiceEvents) -> Result<VoiceEvent, Box<dyn Error>> {
+    Ok(time::timeout(TIMEOUT, events.next())
+        .await?
+        .expect("event stream ended"))
+}
+
+#[tokio::test]
+async fn test_mock_voice_handshake() -> Result<(), Box<dyn Error>> {
+    let mut server = MockVoiceServer::bind().await?;
+    let (voice, _events, _connection) = connect(&mut server).await?;
+
+    assert_eq!(MockVoiceServer::SSRC, voice.ssrc());
+    assert_eq!(MockVoiceServer::SECRET_KEY, *voice.secret_key());
+    assert_eq!(EncryptionMode::Lite, voice.mode());
+    assert_eq!(
+        voice.socket().local_addr()?.port(),
+        voice.external_address().port()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_voice_speaking_and_events() -> Result<(), Box<dyn Error>> {
+    let mut server = MockVoiceServer::bind().await?;
+    let (voice, mut events, mut connection) = connect(&mut server).await?;
+
+    voice.speaking(SpeakingFlags::MICROPHONE)?;
+    let payload = time::timeout(TIMEOUT, connection.receive()).await??;
+    assert_eq!(OpCode::Speaking as u8, payload.op());
+    let speaking = payload.deserialize::<Speaking>()?;
+    assert_eq!(SpeakingFlags::MICROPHONE, speaking.speaking);
+    assert_eq!(MockVoiceServer::SSRC, speaking.ssrc);
+
+    let other = Speaking {
+        delay: 0,
+        speaking: SpeakingFlags::MICROPHONE,
+        ssrc: 7,
+        user_id: Some(Id::new(3)),
+    };
+    connection.send(OpCode::Speaking, &other).await?;
+    assert_eq!(VoiceEvent::Speaking(other), next_event(&mut events).await?);
+
+    let disconnect = ClientDisconnect {
+        user_id: Id::new(3),
+    };
+    connection
+        .send(OpCode::ClientDisconnect, &disconnect)
+        .await?;
+    assert_eq!(
+        VoiceEvent::ClientDisconnect(disconnect),
+        next_event(&mut events).await?
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_voice_packets() -> Result<(), Box<dyn Error>> {
+    let mut server = MockVoiceServer::bind().await?;
+    let (voice, mut events, mut connection) = connect(&mut server).await?;
+    let mut cipher = PacketCipher::new(voice.mode(), &MockVoiceServer::SECRET_KEY);
+
+    let mut sender = voice.sender();
+    let sequence = sender.sequence();
+    sender.send(b"opus").await?;
+    let packet = time::timeout(TIMEOUT, server.next_packet()).await??;
+    let (header, header_len) = RtpHeader::parse(&packet).expect("valid header");
+    assert_eq!(sequence, header.sequence);
+    assert_eq!(MockVoiceServer::SSRC, header.ssrc);
+    assert_eq!(b"opus".to_vec(), cipher.decrypt(header_len, &packet)?);
+
+    let speaking = Speaking {
+        delay: 0,
+        speaking: SpeakingFlags::MICROPHONE,
+        ssrc: 7,
+        user_id: Some(Id::new(3)),
+    };
+    connection.send(OpCode::Speaking, &speaking).await?;
+
+    let mut receiver = voice.receiver();
+    receiver.process(&next_event(&mut events).await?);
+
+    let header = RtpHeader {
+        sequence: 1,
+        ssrc: 7,
+        timestamp: 960,
+    };
+    let packet = cipher.encrypt(&header.encode(), b"hello")?;
+    assert!(packet.len() > HEADER_LEN);
+    server.send_packet(&packet).await?;
+
+    let received = time::timeout(TIMEOUT, receiver.recv()).await??;
+    assert_eq!(b"hello".to_vec(), received.opus);
+    assert_eq!(7, received.ssrc);
+    assert_eq!(Some(Id::new(3)), received.user_id);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_voice_resumes() -> Result<(), Box<dyn Error>> {
+    let mut server = MockVoiceServer::bind().await?;
+    let (voice, mut events, mut connection) = connect(&mut server).await?;
+
+    connection
+        .close(CloseCode::VoiceServerCrashed as u16)
+        .await?;
+
+    let mut connection = time::timeout(TIMEOUT, server.next_connection()).await??;
+    let resume = connection.resume_handshake().await?;
+    let resume = resume.deserialize::<Resume>()?;
+    assert_eq!("session", resume.session_id);
+    assert_eq!(VoiceEvent::Resumed, next_event(&mut events).await?);
+
+    // Commands are sent over the new connection.
+    voice.speaking(SpeakingFlags::SOUNDSHARE)?;
+    let payload = time::timeout(TIMEOUT, connection.receive()).await??;
+    assert_eq!(OpCode::Speaking as u8, payload.op());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_voice_disconnected() -> Result<(), Box<dyn Error>> {
+    let mut server = MockVoiceServer::bind().await?;
+    let (voice, mut events, mut connection) = connect(&mut server).await?;
+
+    connection.close(CloseCode::Disconnected as u16).await?;
+
+    assert_eq!(
+        VoiceEvent::Disconnected { code: Some(4014) },
+        next_event(&mut events).await?
+    );
+    assert!(time::timeout(TIMEOUT, events.next()).await?.is_none());
+    assert!(voice.speaking(SpeakingFlags::MICROPHONE).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mock_voice_unsupported_encryption() -> Result<(), Box<dyn Error>> {
+    let mut server = MockVoiceServer::bind().await?;
+
+    let connect = VoiceConnection::builder(info())
+        .url(server.url().to_owned())
+        .connect();
+    let handshake = async {
+        let mut connection = server.next_connection().await?;
+        let hello = Hello {
+            heartbeat_interval: MockVoiceServer::HEARTBEAT_INTERVAL,
+        };
+        connection.send(OpCode::Hello, hello).await?;
+        connection.receive().await?;
+        connection
+            .send(
+                OpCode::Ready,
+                serde_json::json!({ "ip": "127.0.0.1", "modes": ["aead_aes256_gcm"], "port": 1, "ssrc": 1 }),
+            )
+            .await?;
+
+        Ok::<_, Box<dyn Error>>(connection)
+    };
+
+    let (connected, handshake) = time::timeout(TIMEOUT, future::join(connect, handshake)).await?;
+    let _connection = handshake?;
+    let error = connected.unwrap_err();
+    assert!(error.to_string().contains("aead_aes256_gcm"));
+
+    Ok(())
+}